@@ -0,0 +1,24 @@
+use near_sdk::assert_one_yocto;
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Burns `amount` tokens that the treasury (the owner account) bought back from the market.
+    /// The tokens are taken straight from the treasury's balance and removed from the total supply,
+    /// so there's no window where they sit in another account. Exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn buyback_and_burn(&mut self, amount: U128, memo: Option<String>) {
+        /*
+            FILL THIS IN
+        */
+    }
+
+    /// Returns the supply held outside of the treasury (the owner account) in a decimal string representation.
+    pub fn ft_circulating_supply(&self) -> U128 {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+}
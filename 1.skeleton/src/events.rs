@@ -0,0 +1,19 @@
+//! Standard for nep141 (Fungible Token) events.
+//!
+//! These events will be picked up by the NEAR indexer.
+//!
+//! <https://github.com/near/NEPs/blob/master/specs/Standards/FungibleToken/Event.md>
+//!
+//! This is an extension of the events format (nep-297):
+//! <https://github.com/near/NEPs/blob/master/specs/Standards/EventsFormat.md>
+//!
+//! The three events in this standard are [`FtMint`], [`FtTransfer`], and [`FtBurn`].
+//!
+//! These events can be logged by calling `.emit()` on them if a single event, or calling
+//! [`FtMint::emit_many`], [`FtTransfer::emit_many`],
+//! or [`FtBurn::emit_many`] respectively.
+
+//!
+//! The nep141 event types are defined once in the shared `ft-common` crate and re-exported here.
+
+pub use ft_common::events::{FtBurn, FtMint, FtTransfer};
@@ -0,0 +1,43 @@
+use near_sdk::json_types::U64;
+use near_sdk::require;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The structure returned by `verify_supply_invariant`. Pages are meant to be chained: pass `next_index`
+/// back in as `from_index` and keep adding `shares_sum` until `next_index` is `null`, then check the
+/// total with `assert_supply_invariant`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SupplyInvariantPage {
+    /// Index of the first account summed in this page
+    pub from_index: U64,
+    /// How many accounts were summed in this page
+    pub accounts_checked: U64,
+    /// Where the next page starts, or `None` if every account has been summed
+    pub next_index: Option<U64>,
+    /// Sum of the shares held by the accounts in this page
+    pub shares_sum: U128,
+    /// Sum of the balances held by the accounts in this page
+    pub balance_sum: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sums the shares and balances of up to `limit` registered accounts starting at `from_index`.
+    pub fn verify_supply_invariant(&self, from_index: Option<U64>, limit: Option<u64>) -> SupplyInvariantPage {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+
+    /// Final check for an audit done with `verify_supply_invariant`: panics unless the shares summed over
+    /// every page add up to the total shares, which means the balances add up to the total supply.
+    pub fn assert_supply_invariant(&self, shares_sum: U128) -> bool {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+}
@@ -1,18 +1,8 @@
-use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::json_types::Base64VecU8;
-use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{near_bindgen};
+use near_sdk::near_bindgen;
 
 use crate::*;
 
-#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct FungibleTokenMetadata {
-    /*
-        FILL THIS IN
-    */
-}
+pub use ft_common::metadata::FungibleTokenMetadata;
 
 pub trait FungibleTokenMetadataProvider {
     // View call for returning the contract metadata
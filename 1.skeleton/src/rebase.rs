@@ -0,0 +1,32 @@
+use near_sdk::{assert_one_yocto, log, require};
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Changes the total supply to `new_total_supply` and rescales every balance proportionally.
+    /// Balances are stored as shares, so this only updates the supply - no account is touched.
+    /// Only the owner can rebase the token and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn rebase(&mut self, new_total_supply: U128) {
+        /*
+            FILL THIS IN
+        */
+    }
+
+    /// Returns the number of shares held by the given account. If the account doesn't exist, returns `"0"`.
+    pub fn shares_of(&self, account_id: AccountId) -> U128 {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+
+    /// Returns the total number of shares across all accounts.
+    pub fn total_shares(&self) -> U128 {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+}
@@ -0,0 +1,87 @@
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, log, require, Gas, Promise};
+
+use crate::*;
+
+/// How long staged code has to wait before it can be deployed (24 hours in nanoseconds).
+/// This gives token holders time to review an upgrade before it goes live.
+pub const UPGRADE_TIMELOCK_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// GAS attached to the `migrate` call that runs right after the new code is deployed.
+const GAS_FOR_MIGRATE: Gas = Gas::from_tgas(50);
+
+/// Contract code that's been staged by the owner but not deployed yet.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct StagedCode {
+    /// The raw wasm to deploy
+    pub code: Vec<u8>,
+    /// When the code was staged (in nanoseconds)
+    pub staged_at: u64,
+}
+
+/// The structure returned by `get_staged_code`. The code itself is left out since it's large; clients
+/// can compare the hash against a build they trust.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StagedCodeInfo {
+    /// The sha256 hash of the staged code
+    pub code_hash: Base64VecU8,
+    /// When the code was staged (in nanoseconds)
+    pub staged_at: U64,
+    /// The earliest time the code can be deployed (in nanoseconds)
+    pub deployable_at: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Stages new contract code to be deployed once the timelock has passed. The code is read as raw
+    /// bytes from the input instead of JSON so that it doesn't need to be encoded. Only the owner (which
+    /// can be a DAO) can stage code, and staging again replaces whatever was staged before. Exactly 1
+    /// yoctoNEAR must be attached.
+    #[payable]
+    pub fn stage_code(&mut self) {
+        /*
+            FILL THIS IN
+        */
+    }
+
+    /// Removes the staged code without deploying it. Only the owner can discard staged code.
+    #[payable]
+    pub fn discard_staged_code(&mut self) {
+        /*
+            FILL THIS IN
+        */
+    }
+
+    /// Deploys the staged code to this account and calls `migrate` on it. The timelock must have passed
+    /// since the code was staged. Only the owner can commit an upgrade.
+    #[payable]
+    pub fn commit_staged_code(&mut self) -> Promise {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+
+    /// Returns information about the staged code, or `null` if nothing is staged.
+    pub fn get_staged_code(&self) -> Option<StagedCodeInfo> {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+
+    /// Called by the contract itself right after new code is deployed. Reads the state written by the
+    /// previous code and converts it to the current layout. When a new version changes the `Contract`
+    /// struct, this is where the old struct gets deserialized and mapped onto the new one.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        /*
+            FILL THIS IN
+        */
+        todo!(); //remove once code is filled in.
+    }
+}
@@ -3,10 +3,20 @@ use near_sdk::{require};
 use crate::*;
 
 impl Contract {
+    /// Internal method for force getting the balance of an account. If the account doesn't have a balance, panic with a custom message.
+    pub(crate) fn internal_unwrap_balance_of(&self, account_id: &AccountId) -> NearToken {
+        match self.accounts.get(account_id) {
+            Some(balance) => balance,
+            None => {
+                env::panic_str(format!("The account {} is not registered", &account_id).as_str())
+            }
+        }
+    }
+
     /// Internal method for depositing some amount of FTs into an account. 
     pub(crate) fn internal_deposit(&mut self, account_id: &AccountId, amount: NearToken) {
-        // Get the current balance of the account.
-        let balance = self.accounts.get(&account_id).unwrap_or(ZERO_TOKEN);
+        // Get the current balance of the account. If they're not registered, panic.
+        let balance = self.internal_unwrap_balance_of(account_id);
         
         // Add the amount to the balance and insert the new balance into the accounts map
         if let Some(new_balance) = balance.checked_add(amount) {
@@ -15,4 +25,73 @@ impl Contract {
             env::panic_str("Balance overflow");
         }
     }
-}
\ No newline at end of file
+
+    /// Internal method for registering an account with the contract. Accounts have to be registered before
+    /// they can hold a balance. For now only the owner is registered; the next step lets anyone register
+    /// by paying for their storage.
+    pub(crate) fn internal_register_account(&mut self, account_id: &AccountId) {
+        if self.accounts.insert(account_id, &ZERO_TOKEN).is_some() {
+            env::panic_str("The account is already registered");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::json_types::U128;
+
+    use super::*;
+
+    /// Initializes the contract with the whole supply owned by `owner()`
+    fn setup() -> Contract {
+        Context::new().set();
+        Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY))
+    }
+
+    #[test]
+    fn new_registers_the_owner_with_the_total_supply() {
+        let contract = setup();
+        assert_eq!(contract.internal_unwrap_balance_of(&owner()), NearToken::from_yoctonear(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    fn registered_accounts_start_with_nothing() {
+        let mut contract = setup();
+        contract.internal_register_account(&alice());
+        assert_eq!(contract.internal_unwrap_balance_of(&alice()), ZERO_TOKEN);
+    }
+
+    #[test]
+    fn deposit_adds_to_the_balance() {
+        let mut contract = setup();
+        contract.internal_register_account(&alice());
+
+        contract.internal_deposit(&alice(), NearToken::from_yoctonear(10));
+        contract.internal_deposit(&alice(), NearToken::from_yoctonear(5));
+
+        assert_eq!(contract.internal_unwrap_balance_of(&alice()), NearToken::from_yoctonear(15));
+    }
+
+    #[test]
+    #[should_panic(expected = "The account alice.near is not registered")]
+    fn deposit_into_unregistered_account_panics() {
+        let mut contract = setup();
+        contract.internal_deposit(&alice(), NearToken::from_yoctonear(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "The account is already registered")]
+    fn registering_twice_panics() {
+        let mut contract = setup();
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&alice());
+    }
+
+    #[test]
+    #[should_panic(expected = "Balance overflow")]
+    fn deposit_overflow_panics() {
+        let mut contract = setup();
+        contract.internal_deposit(&owner(), NearToken::from_yoctonear(u128::MAX));
+    }
+}
@@ -1,42 +1,8 @@
 use near_sdk::{env, log, AccountId, Promise};
-use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use near_sdk::serde::{Deserialize, Serialize};
 
 use crate::*;
 
-// The structure that will be returned for the methods:
-// * `storage_deposit`
-// * `storage_withdraw`
-// * `storage_balance_of`
-// The `total` and `available` values are string representations of unsigned
-// 128-bit integers showing the balance of a specific account in yoctoⓃ.
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct StorageBalance {
-    pub total: NearToken,
-    pub available: NearToken,
-}
-
-// The below structure will be returned for the method `storage_balance_bounds`.
-// Both `min` and `max` are string representations of unsigned 128-bit integers.
-//
-// `min` is the amount of tokens required to start using this contract at all
-// (eg to register with the contract). If a new contract user attaches `min`
-// NEAR to a `storage_deposit` call, subsequent calls to `storage_balance_of`
-// for this user must show their `total` equal to `min` and `available=0` .
-//
-// A contract may implement `max` equal to `min` if it only charges for initial
-// registration, and does not adjust per-user storage over time. A contract
-// which implements `max` must refund deposits that would increase a user's
-// storage balance beyond this amount.
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct StorageBalanceBounds {
-    pub min: NearToken,
-    pub max: Option<NearToken>,
-}
+pub use ft_common::storage::{StorageBalance, StorageBalanceBounds};
 
 pub trait StorageManagement {
     /************************************/
@@ -43,4 +43,66 @@ impl Contract {
         self.bytes_for_longest_account_id = env::storage_usage() - initial_storage_usage;
         self.accounts.remove(&tmp_account_id);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::json_types::U128;
+
+    use super::*;
+
+    /// Initializes the contract with the whole supply owned by `owner()`
+    fn setup() -> Contract {
+        Context::new().set();
+        Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY))
+    }
+
+    #[test]
+    fn new_registers_the_owner_with_the_total_supply() {
+        let contract = setup();
+        assert_eq!(contract.internal_unwrap_balance_of(&owner()), NearToken::from_yoctonear(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    fn new_measures_the_longest_account_id() {
+        let contract = setup();
+        // The account ID alone is 64 bytes, on top of the key prefix and the balance
+        assert!(contract.bytes_for_longest_account_id > 64);
+        // The temporary account is removed again
+        assert!(contract.accounts.get(&"a".repeat(64).parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn deposit_adds_to_the_balance() {
+        let mut contract = setup();
+        contract.internal_register_account(&alice());
+
+        contract.internal_deposit(&alice(), NearToken::from_yoctonear(10));
+        contract.internal_deposit(&alice(), NearToken::from_yoctonear(5));
+
+        assert_eq!(contract.internal_unwrap_balance_of(&alice()), NearToken::from_yoctonear(15));
+    }
+
+    #[test]
+    #[should_panic(expected = "The account alice.near is not registered")]
+    fn deposit_into_unregistered_account_panics() {
+        let mut contract = setup();
+        contract.internal_deposit(&alice(), NearToken::from_yoctonear(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "The account is already registered")]
+    fn registering_twice_panics() {
+        let mut contract = setup();
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&alice());
+    }
+
+    #[test]
+    #[should_panic(expected = "Balance overflow")]
+    fn deposit_overflow_panics() {
+        let mut contract = setup();
+        contract.internal_deposit(&owner(), NearToken::from_yoctonear(u128::MAX));
+    }
+}
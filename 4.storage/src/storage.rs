@@ -1,42 +1,8 @@
-use near_sdk::{env, log, AccountId, Promise};
-use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, env, log, require, AccountId, Promise};
 
 use crate::*;
 
-// The structure that will be returned for the methods:
-// * `storage_deposit`
-// * `storage_withdraw`
-// * `storage_balance_of`
-// The `total` and `available` values are string representations of unsigned
-// 128-bit integers showing the balance of a specific account in yoctoⓃ.
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct StorageBalance {
-    pub total: NearToken,
-    pub available: NearToken,
-}
-
-// The below structure will be returned for the method `storage_balance_bounds`.
-// Both `min` and `max` are string representations of unsigned 128-bit integers.
-//
-// `min` is the amount of tokens required to start using this contract at all
-// (eg to register with the contract). If a new contract user attaches `min`
-// NEAR to a `storage_deposit` call, subsequent calls to `storage_balance_of`
-// for this user must show their `total` equal to `min` and `available=0` .
-//
-// A contract may implement `max` equal to `min` if it only charges for initial
-// registration, and does not adjust per-user storage over time. A contract
-// which implements `max` must refund deposits that would increase a user's
-// storage balance beyond this amount.
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct StorageBalanceBounds {
-    pub min: NearToken,
-    pub max: Option<NearToken>,
-}
+pub use ft_common::storage::{StorageBalance, StorageBalanceBounds};
 
 pub trait StorageManagement {
     /************************************/
@@ -62,6 +28,20 @@ pub trait StorageManagement {
         registration_only: Option<bool>,
     ) -> StorageBalance;
 
+    // Withdraws `amount` of the predecessor's available storage balance, or all of it if `amount` is omitted.
+    // Requires exactly 1 yoctoⓃ attached. Panics if the predecessor isn't registered or `amount` is more than
+    // what's available.
+    //
+    // Returns the StorageBalance structure showing updated balances.
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance;
+
+    // Unregisters the predecessor account and refunds its storage deposit. Requires exactly 1 yoctoⓃ attached.
+    //
+    // If the account still holds tokens, panics unless `force=true`, in which case the tokens are burnt.
+    //
+    // Returns `true` if the account was unregistered and `false` if it wasn't registered.
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
     /****************/
     /* VIEW METHODS */
     /****************/
@@ -106,6 +86,7 @@ impl StorageManagement for Contract {
 
             // Register the account
             self.internal_register_account(&account_id);
+            StorageRegister { account_id: &account_id, amount: &min_balance }.emit();
             // Perform a refund
             let refund = amount.saturating_sub(min_balance);
             if refund.gt(&ZERO_TOKEN) {
@@ -117,6 +98,56 @@ impl StorageManagement for Contract {
         StorageBalance { total: self.storage_balance_bounds().min, available: ZERO_TOKEN }
     }
 
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_balance = self
+            .storage_balance_of(account_id.clone())
+            .unwrap_or_else(|| env::panic_str(format!("The account {} is not registered", &account_id).as_str()));
+
+        // The deposit is always exactly the minimum, so nothing is ever available and only 0 can be withdrawn
+        let amount = amount.unwrap_or(storage_balance.available);
+        require!(
+            amount <= storage_balance.available,
+            "The amount is greater than the available storage balance"
+        );
+        StorageWithdraw { account_id: &account_id, amount: &amount }.emit();
+
+        storage_balance
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let Some(balance) = self.accounts.get(&account_id) else {
+            log!("The account {} is not registered", account_id);
+            return false;
+        };
+
+        // Burn whatever is left in the account if `force` is set
+        if balance.gt(&ZERO_TOKEN) {
+            require!(
+                force.unwrap_or(false),
+                "Can't unregister the account with the positive balance without force"
+            );
+            self.total_supply = self.total_supply.saturating_sub(balance);
+            FtBurn { owner_id: &account_id, amount: &balance, memo: Some("Force unregister") }.emit();
+        }
+
+        // Remove the account and refund the storage deposit
+        self.accounts.remove(&account_id);
+        let refund = self.storage_balance_bounds().min;
+        Promise::new(account_id.clone()).transfer(refund);
+        StorageUnregister { account_id: &account_id, amount: &refund }.emit();
+
+        true
+    }
+
     fn storage_balance_bounds(&self) -> StorageBalanceBounds {
         // Calculate the required storage balance by taking the bytes for the longest account ID and multiplying by the current byte cost
         let required_storage_balance =
@@ -0,0 +1,140 @@
+use near_sdk::Promise;
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The memo on the `ft_transfer` event logged when an account migrates its balance.
+pub const MIGRATION_MEMO: &str = "account migration";
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Moves everything the caller has in the contract to `new_account_id`, which has to be registered already,
+    /// and unregisters the caller. Useful when rotating to a new account, which would otherwise take a transfer,
+    /// a vault withdrawal and an unregistration, and lose whatever isn't moved by hand. Exactly 1 yoctoNEAR must
+    /// be attached.
+    ///
+    /// What moves:
+    /// - the balance
+    /// - the vault position
+    /// - the deposit tagging opt-in
+    /// - the storage deposit, which is refunded to `new_account_id` since its registration is already paid for
+    ///
+    /// The caller's transfer history is dropped, the same as when it unregisters. The move is checked against the
+    /// transfer policy like any other transfer, and like any transfer of a whole balance it doesn't pay a fee.
+    #[payable]
+    pub fn migrate_account(&mut self, new_account_id: AccountId) -> NearToken {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let old_account_id = env::predecessor_account_id();
+        // The treasury has to stay registered, the owner methods depend on it
+        require_or(old_account_id != self.owner_id, FtError::OwnerCannotUnregister);
+        require_or(old_account_id != new_account_id, FtError::SelfTransfer);
+        // Migrating is a way of sending everything, so it's bound by the outbound allowlist too
+        self.assert_outbound_allowed(&old_account_id, &new_account_id);
+        let shares = self.internal_unwrap_shares_of(&old_account_id);
+        let amount = self.internal_shares_to_amount(shares);
+        // The contract's transfer policy applies too. Sending the whole balance never pays a fee, so it's ignored
+        self.transfer_policy().check_transfer(&old_account_id, &new_account_id, amount);
+        let new_shares = self
+            .accounts
+            .get(&new_account_id)
+            .unwrap_or_else(|| FtError::ReceiverNotRegistered(new_account_id.clone()).panic());
+        // Pending refunds would otherwise be credited to an account that no longer exists
+        self.assert_nothing_in_flight(&old_account_id);
+
+        // Move the balance
+        let new_shares = new_shares.checked_add(shares).unwrap_or_else(|| FtError::BalanceOverflow.panic());
+        self.accounts.insert(&new_account_id, &new_shares);
+        if shares > 0 {
+            FtTransfer {
+                old_owner_id: &old_account_id,
+                new_owner_id: &new_account_id,
+                amount: &amount,
+                memo: Some(MIGRATION_MEMO),
+            }
+            .emit();
+        }
+
+        // Move the vault position
+        if let Some(vault_shares) = self.vault_shares.remove(&old_account_id) {
+            let new_vault_shares = self.vault_shares.get(&new_account_id).unwrap_or(0) + vault_shares;
+            self.vault_shares.insert(&new_account_id, &new_vault_shares);
+        }
+
+        // Move the deposit tagging opt-in
+        if self.deposit_tag_receivers.remove(&old_account_id) {
+            self.deposit_tag_receivers.insert(&new_account_id);
+        }
+
+        // Remove the old account along with its history and allowlist, and hand its storage deposit over
+        self.accounts.remove(&old_account_id);
+        self.internal_clear_history(&old_account_id);
+        self.outbound_allowlists.remove(&old_account_id);
+        let refund = self.storage_balance_required;
+        Promise::new(new_account_id.clone()).transfer(refund);
+        StorageUnregister { account_id: &old_account_id, amount: &refund }.emit();
+        StorageMigrate { old_account_id: &old_account_id, new_account_id: &new_account_id, amount: &amount }.emit();
+
+        amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    /// Creates the contract with alice holding 100 tokens, 40 of them in the vault, and bob registered
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.vault_deposit(tokens(40));
+        contract.set_deposit_tagging(true);
+        contract
+    }
+
+    #[test]
+    fn moves_everything_to_the_new_account() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert_eq!(contract.migrate_account(bob()), tokens(60));
+
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(60));
+        assert_eq!(contract.vault_balance_of(bob()), tokens(40));
+        assert!(contract.is_deposit_tagging_enabled(bob()));
+        assert!(!contract.accounts.contains_key(&alice()));
+        assert!(!contract.is_deposit_tagging_enabled(alice()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RECEIVER_NOT_REGISTERED")]
+    fn the_new_account_has_to_be_registered() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.migrate_account(charlie());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TRANSFERS_PAUSED")]
+    fn migrating_is_paused_with_transfers() {
+        let mut contract = setup();
+        contract.transfer_policy = TransferPolicyConfig::Paused;
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.migrate_account(bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OWNER_CANNOT_UNREGISTER")]
+    fn the_owner_cannot_migrate() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.migrate_account(bob());
+    }
+}
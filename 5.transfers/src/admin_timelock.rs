@@ -0,0 +1,259 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::log;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::transfer_policy::assert_valid_transfer_policy;
+use crate::*;
+
+/// How long a proposed admin operation has to wait before it can be executed (24 hours in nanoseconds).
+/// Token holders can see it coming with `get_admin_ops` and act before it goes through.
+pub const ADMIN_TIMELOCK_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// The share of the initial supply that can be minted per mint window without going through the timelock, in
+/// basis points. A mint window lasts `ADMIN_TIMELOCK_NS`.
+pub const DEFAULT_MINT_THRESHOLD_BPS: u128 = 100;
+
+/// An admin operation that has to wait out the timelock.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", tag = "kind", rename_all = "snake_case")]
+pub enum AdminOp {
+    /// A batch of mints that would take the current mint window above the mint threshold
+    Mint { mints: Vec<(AccountId, NearToken)>, memo: Option<String> },
+    /// Raises the mint threshold. Lowering it doesn't need the timelock
+    SetMintThreshold { threshold: NearToken },
+    /// Sets a dust policy that sweeps more than the current one. Other changes don't need the timelock
+    SetDustPolicy { policy: DustPolicy },
+    /// Pauses transfers, whitelists them or raises the transfer fee. Loosening the policy doesn't need the timelock
+    SetTransferPolicy { policy: TransferPolicyConfig },
+    /// Allows an account to call `ft_mint_with_reference`. Removing a minter doesn't need the timelock
+    AddMinter { minter_id: AccountId },
+    /// Sets the account that mints bridged tokens. Removing the operator doesn't need the timelock
+    SetBridgeOperator { operator_id: AccountId },
+}
+
+/// An admin operation waiting in the queue.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct QueuedAdminOp {
+    pub op: AdminOp,
+    /// When the operation was proposed (in nanoseconds)
+    pub proposed_at: u64,
+}
+
+/// The structure returned by `get_admin_op` and `get_admin_ops`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AdminOpInfo {
+    pub id: U64,
+    pub op: AdminOp,
+    /// When the operation was proposed (in nanoseconds)
+    pub proposed_at: U64,
+    /// The earliest time the operation can be executed (in nanoseconds)
+    pub executable_at: U64,
+}
+
+impl AdminOpInfo {
+    fn new(id: u64, queued: QueuedAdminOp) -> Self {
+        Self {
+            id: U64(id),
+            op: queued.op,
+            proposed_at: U64(queued.proposed_at),
+            executable_at: U64(queued.proposed_at.saturating_add(ADMIN_TIMELOCK_NS)),
+        }
+    }
+}
+
+/// The total of a batch of mints. Saturates rather than overflows, since an overflowing batch fails anyway.
+pub(crate) fn mint_batch_total(mints: &[(AccountId, NearToken)]) -> NearToken {
+    mints.iter().fold(ZERO_TOKEN, |total, (_, amount)| total.saturating_add(*amount))
+}
+
+impl Contract {
+    /// Internal method for counting a mint that doesn't go through the timelock against the mint threshold.
+    /// Everything minted this way in the same window adds up, so a large mint can't be split into several
+    /// small ones. Each window lasts `ADMIN_TIMELOCK_NS`.
+    pub(crate) fn internal_record_untimelocked_mint(&mut self, amount: NearToken) {
+        let now = env::block_timestamp();
+        if now >= self.mint_window_start.saturating_add(ADMIN_TIMELOCK_NS) {
+            self.mint_window_start = now;
+            self.mint_window_minted = ZERO_TOKEN;
+        }
+        self.mint_window_minted = self.mint_window_minted.saturating_add(amount);
+        require_or(self.mint_window_minted <= self.mint_threshold, FtError::MintAboveThreshold);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Queues `op` to be executed once the timelock has passed, and returns its ID. Only the owner can propose
+    /// operations and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn propose_admin_op(&mut self, op: AdminOp) -> U64 {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+
+        let id = self.next_admin_op_id;
+        self.next_admin_op_id += 1;
+        self.admin_ops.insert(&id, &QueuedAdminOp { op, proposed_at: env::block_timestamp() });
+
+        log!("Proposed admin operation {}", id);
+        U64(id)
+    }
+
+    /// Removes a queued operation without executing it. Only the owner can cancel operations and exactly
+    /// 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn cancel_admin_op(&mut self, id: U64) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+
+        require_or(self.admin_ops.remove(&id.0).is_some(), FtError::AdminOpNotFound);
+        log!("Cancelled admin operation {}", id.0);
+    }
+
+    /// Executes a queued operation once the timelock has passed. Only the owner can execute operations and
+    /// exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn execute_admin_op(&mut self, id: U64) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+
+        let queued = self.admin_ops.get(&id.0).unwrap_or_else(|| FtError::AdminOpNotFound.panic());
+        require_or(
+            env::block_timestamp() >= queued.proposed_at.saturating_add(ADMIN_TIMELOCK_NS),
+            FtError::AdminOpTimelocked,
+        );
+        self.admin_ops.remove(&id.0);
+
+        log!("Executed admin operation {}", id.0);
+        match queued.op {
+            AdminOp::Mint { mints, memo } => self.internal_mint_batch(&mints, memo.as_deref()),
+            AdminOp::SetMintThreshold { threshold } => self.mint_threshold = threshold,
+            AdminOp::SetDustPolicy { policy } => {
+                require_or(self.is_valid_dust_policy(&policy), FtError::InvalidDustPolicy);
+                self.dust_policy = Some(policy);
+            }
+            AdminOp::SetTransferPolicy { policy } => {
+                assert_valid_transfer_policy(&policy);
+                self.transfer_policy = policy;
+            }
+            AdminOp::AddMinter { minter_id } => {
+                self.minters.insert(&minter_id);
+            }
+            AdminOp::SetBridgeOperator { operator_id } => self.bridge_operator = Some(operator_id),
+        }
+    }
+
+    /// Lowers the largest total that can be minted per window without going through the timelock. Raising it
+    /// has to be proposed with `propose_admin_op`. Only the owner can change it and exactly 1 yoctoNEAR must be
+    /// attached.
+    #[payable]
+    pub fn lower_mint_threshold(&mut self, threshold: NearToken) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        require_or(threshold <= self.mint_threshold, FtError::AdminOpTimelocked);
+        self.mint_threshold = threshold;
+    }
+
+    /// Returns the largest total that can be minted per window without going through the timelock.
+    pub fn get_mint_threshold(&self) -> NearToken {
+        self.mint_threshold
+    }
+
+    /// Returns the queued operation with the given ID, or `null` if there's none.
+    pub fn get_admin_op(&self, id: U64) -> Option<AdminOpInfo> {
+        self.admin_ops.get(&id.0).map(|queued| AdminOpInfo::new(id.0, queued))
+    }
+
+    /// Returns up to `limit` queued operations, oldest first.
+    pub fn get_admin_ops(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AdminOpInfo> {
+        // Where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        // Iterate through the queue, skipping to the start and taking the first "limit" operations (50 by default)
+        self.admin_ops
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(id, queued)| AdminOpInfo::new(id, queued))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    fn large_mint() -> AdminOp {
+        AdminOp::Mint { mints: vec![(alice(), tokens(TOTAL_SUPPLY))], memo: None }
+    }
+
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract
+    }
+
+    #[test]
+    fn large_mints_wait_out_the_timelock() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(large_mint());
+        assert_eq!(contract.get_admin_ops(None, None).len(), 1);
+
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(TOTAL_SUPPLY));
+        assert!(contract.get_admin_op(id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ADMIN_OP_TIMELOCKED")]
+    fn operations_cannot_run_early() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(large_mint());
+
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS - 1).set();
+        contract.execute_admin_op(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ADMIN_OP_NOT_FOUND")]
+    fn cancelled_operations_cannot_run() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(large_mint());
+        contract.cancel_admin_op(id);
+
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MINT_ABOVE_THRESHOLD")]
+    fn large_mints_cannot_skip_the_queue() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.ft_mint_batch(vec![(alice(), tokens(TOTAL_SUPPLY))], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ADMIN_OP_TIMELOCKED")]
+    fn the_threshold_cannot_be_raised_directly() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.lower_mint_threshold(tokens(TOTAL_SUPPLY));
+    }
+}
@@ -0,0 +1,222 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_or, FtError};
+use crate::*;
+
+/// How long it takes for a looser allowlist to apply (24 hours in nanoseconds). Tightening applies at once, so
+/// whoever gets hold of the account's key can't simply lift the restriction and drain the account.
+pub const OUTBOUND_ALLOWLIST_DELAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// The most receivers an outbound allowlist can have.
+pub const MAX_OUTBOUND_ALLOWLIST_LEN: usize = 10;
+
+/// A change to an outbound allowlist that's waiting out the delay.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingAllowlist {
+    /// The receivers that will be allowed, or `None` if the allowlist is being removed
+    pub receivers: Option<Vec<AccountId>>,
+    /// When the change applies (in nanoseconds)
+    pub effective_at: U64,
+}
+
+/// The receivers an account restricted itself to.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct OutboundAllowlist {
+    /// The receivers the account can send tokens to
+    pub receivers: Vec<AccountId>,
+    /// A looser allowlist waiting out the delay
+    pub pending: Option<PendingAllowlist>,
+}
+
+impl OutboundAllowlist {
+    /// The receivers allowed at `now`, or `None` if the account isn't restricted anymore.
+    fn receivers_at(&self, now: u64) -> Option<&Vec<AccountId>> {
+        match &self.pending {
+            Some(pending) if now >= pending.effective_at.0 => pending.receivers.as_ref(),
+            _ => Some(&self.receivers),
+        }
+    }
+}
+
+impl Contract {
+    /// Internal method for asserting that `sender_id` allows sending tokens to `receiver_id`. Accounts without an
+    /// outbound allowlist can send to anyone.
+    pub(crate) fn assert_outbound_allowed(&self, sender_id: &AccountId, receiver_id: &AccountId) {
+        if let Some(allowlist) = self.outbound_allowlists.get(sender_id) {
+            if let Some(receivers) = allowlist.receivers_at(env::block_timestamp()) {
+                require_or(receivers.contains(receiver_id), FtError::ReceiverNotAllowed(receiver_id.clone()));
+            }
+        }
+    }
+
+    /// Internal method for writing `allowlist` for the caller, or removing it if it's `None`. The attached deposit
+    /// has to cover any extra storage, and whatever is left of it is refunded along with the storage freed.
+    fn internal_write_outbound_allowlist(&mut self, account_id: &AccountId, allowlist: Option<OutboundAllowlist>) {
+        let initial_storage_usage = env::storage_usage();
+        match allowlist {
+            Some(allowlist) => self.outbound_allowlists.insert(account_id, &allowlist),
+            None => self.outbound_allowlists.remove(account_id),
+        };
+        self.internal_settle_storage(account_id, initial_storage_usage);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Restricts the caller so that its tokens can only be sent to `receivers`. An empty list freezes outgoing
+    /// transfers altogether. Unused tokens returned by `ft_transfer_call` receivers are always refunded.
+    ///
+    /// If the new list only removes receivers, it applies at once. Otherwise it applies after
+    /// `OUTBOUND_ALLOWLIST_DELAY_NS`, and the current list stays in force until then.
+    ///
+    /// The caller must be registered, and must attach at least 1 yoctoNEAR plus the storage cost of the list.
+    /// What isn't needed is refunded.
+    #[payable]
+    pub fn set_outbound_allowlist(&mut self, receivers: Vec<AccountId>) {
+        // Assert that the user attached at least 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_or(env::attached_deposit() >= NearToken::from_yoctonear(1), FtError::OneYoctoRequired);
+        let account_id = env::predecessor_account_id();
+        require_or(self.accounts.contains_key(&account_id), FtError::AccountNotRegistered(account_id.clone()));
+        let mut receivers = receivers;
+        receivers.sort();
+        receivers.dedup();
+        require_or(receivers.len() <= MAX_OUTBOUND_ALLOWLIST_LEN, FtError::AllowlistTooLong);
+
+        let now = env::block_timestamp();
+        let current = self.outbound_allowlists.get(&account_id);
+        let current_receivers = current.as_ref().and_then(|allowlist| allowlist.receivers_at(now));
+        let allowlist = match current_receivers {
+            // Adding receivers loosens the restriction, so it has to wait out the delay
+            Some(current_receivers) if !receivers.iter().all(|receiver| current_receivers.contains(receiver)) => {
+                OutboundAllowlist {
+                    receivers: current_receivers.clone(),
+                    pending: Some(PendingAllowlist {
+                        receivers: Some(receivers),
+                        effective_at: U64(now.saturating_add(OUTBOUND_ALLOWLIST_DELAY_NS)),
+                    }),
+                }
+            }
+            // Restricting an account that wasn't restricted, or only removing receivers, applies at once
+            _ => OutboundAllowlist { receivers, pending: None },
+        };
+        self.internal_write_outbound_allowlist(&account_id, Some(allowlist));
+    }
+
+    /// Lifts the caller's outbound allowlist after `OUTBOUND_ALLOWLIST_DELAY_NS`. The current list stays in force
+    /// until then. At least 1 yoctoNEAR must be attached, and the storage freed is refunded once the list is
+    /// removed.
+    #[payable]
+    pub fn clear_outbound_allowlist(&mut self) {
+        // Assert that the user attached at least 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_or(env::attached_deposit() >= NearToken::from_yoctonear(1), FtError::OneYoctoRequired);
+        let account_id = env::predecessor_account_id();
+        let now = env::block_timestamp();
+
+        let Some(current) = self.outbound_allowlists.get(&account_id) else {
+            return;
+        };
+        let allowlist = current.receivers_at(now).map(|receivers| OutboundAllowlist {
+            receivers: receivers.clone(),
+            pending: Some(PendingAllowlist {
+                receivers: None,
+                effective_at: U64(now.saturating_add(OUTBOUND_ALLOWLIST_DELAY_NS)),
+            }),
+        });
+        self.internal_write_outbound_allowlist(&account_id, allowlist);
+    }
+
+    /// Returns the outbound allowlist of `account_id` as it's stored, including any pending change, or `null`
+    /// if the account never set one.
+    pub fn get_outbound_allowlist(&self, account_id: AccountId) -> Option<OutboundAllowlist> {
+        self.outbound_allowlists.get(&account_id)
+    }
+
+    /// Returns whether `sender_id` can send tokens to `receiver_id` right now.
+    pub fn is_outbound_allowed(&self, sender_id: AccountId, receiver_id: AccountId) -> bool {
+        self.outbound_allowlists
+            .get(&sender_id)
+            .and_then(|allowlist| allowlist.receivers_at(env::block_timestamp()).cloned())
+            .map(|receivers| receivers.contains(&receiver_id))
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    /// Creates the contract with alice holding 100 tokens and only allowed to send to bob
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_register_account(&charlie());
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+
+        Context::new().predecessor(alice()).attached_deposit(NearToken::from_millinear(10)).set();
+        contract.set_outbound_allowlist(vec![bob()]);
+        contract
+    }
+
+    #[test]
+    fn allowed_receivers_can_be_paid() {
+        let mut contract = setup();
+        contract.internal_transfer(&alice(), &bob(), tokens(10), None);
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RECEIVER_NOT_ALLOWED")]
+    fn other_receivers_are_refused() {
+        let mut contract = setup();
+        contract.internal_transfer(&alice(), &charlie(), tokens(10), None);
+    }
+
+    #[test]
+    fn refunds_ignore_the_allowlist() {
+        let mut contract = setup();
+        contract.internal_refund(&alice(), &charlie(), tokens(10), None);
+        assert_eq!(contract.internal_balance_of(&charlie()), tokens(10));
+    }
+
+    #[test]
+    fn loosening_waits_out_the_delay() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).attached_deposit(NearToken::from_millinear(10)).set();
+        contract.set_outbound_allowlist(vec![bob(), charlie()]);
+        assert!(!contract.is_outbound_allowed(alice(), charlie()));
+
+        Context::new().block_timestamp(OUTBOUND_ALLOWLIST_DELAY_NS).set();
+        assert!(contract.is_outbound_allowed(alice(), charlie()));
+        contract.internal_transfer(&alice(), &charlie(), tokens(10), None);
+    }
+
+    #[test]
+    fn tightening_applies_at_once() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.set_outbound_allowlist(vec![]);
+        assert!(!contract.is_outbound_allowed(alice(), bob()));
+    }
+
+    #[test]
+    fn clearing_waits_out_the_delay() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.clear_outbound_allowlist();
+        assert!(!contract.is_outbound_allowed(alice(), charlie()));
+
+        Context::new().block_timestamp(OUTBOUND_ALLOWLIST_DELAY_NS).set();
+        assert!(contract.is_outbound_allowed(alice(), charlie()));
+    }
+}
@@ -0,0 +1,197 @@
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// How to round an amount that has more decimal places than can be shown or stored.
+#[derive(Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Drop the extra digits
+    Down,
+    /// Round up if any of the extra digits aren't 0
+    Up,
+    /// Round to the nearest value, with halves rounded up
+    HalfUp,
+    /// Refuse amounts that would need rounding
+    #[default]
+    Exact,
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Returns an account's balance as a decimal string using the metadata `decimals`, e.g. "1.5" rather than
+    /// "1500000000000000000000000" for a token with 24 decimals. If a `precision` is passed in, at most that
+    /// many decimal places are shown, rounded with `rounding` (down by default).
+    pub fn ft_balance_of_formatted(
+        &self,
+        account_id: AccountId,
+        precision: Option<u8>,
+        rounding: Option<RoundingMode>,
+    ) -> String {
+        let balance = self.internal_balance_of(&account_id).as_yoctonear();
+        format_amount(balance, self.decimals, precision, rounding.unwrap_or(RoundingMode::Down))
+            .unwrap_or_else(|e| env::panic_str(&e))
+    }
+
+    /// Converts a decimal string such as "1.5" into the amount in the token's smallest unit, using the metadata
+    /// `decimals`. Amounts with more decimal places than the token has are refused unless a `rounding` mode
+    /// other than `exact` is passed in.
+    pub fn parse_amount(&self, human: String, rounding: Option<RoundingMode>) -> U128 {
+        parse_amount(&human, self.decimals, rounding.unwrap_or_default())
+            .map(U128)
+            .unwrap_or_else(|e| env::panic_str(&e))
+    }
+}
+
+/// Returns 10^`exponent`, or an error if it doesn't fit in a u128.
+fn pow10(exponent: u32) -> Result<u128, String> {
+    10u128.checked_pow(exponent).ok_or_else(|| "Too many decimals".to_string())
+}
+
+/// Whether `quotient` should be bumped up by one given the `remainder` of dividing by `divisor`.
+fn rounds_up(remainder: u128, divisor: u128, rounding: RoundingMode) -> Result<bool, String> {
+    Ok(match rounding {
+        RoundingMode::Down => false,
+        RoundingMode::Up => remainder > 0,
+        RoundingMode::HalfUp => remainder >= divisor - remainder,
+        RoundingMode::Exact if remainder > 0 => return Err("The amount has too many decimal places".to_string()),
+        RoundingMode::Exact => false,
+    })
+}
+
+/// Formats `amount` (in the smallest unit) as a decimal string with `decimals` decimal places. Trailing zeros
+/// are left out. If a `precision` lower than `decimals` is given, the fraction is rounded to that many places.
+pub(crate) fn format_amount(
+    amount: u128,
+    decimals: u8,
+    precision: Option<u8>,
+    rounding: RoundingMode,
+) -> Result<String, String> {
+    let one = pow10(decimals as u32)?;
+    let mut whole = amount / one;
+    let mut fraction = amount % one;
+
+    // Round the fraction down to the requested number of places, carrying into the whole part if needed
+    let places = precision.map(|p| p.min(decimals)).unwrap_or(decimals);
+    let divisor = pow10((decimals - places) as u32)?;
+    let remainder = fraction % divisor;
+    fraction /= divisor;
+    if rounds_up(remainder, divisor, rounding)? {
+        fraction += 1;
+        if fraction == pow10(places as u32)? {
+            fraction = 0;
+            whole += 1;
+        }
+    }
+
+    if fraction == 0 {
+        return Ok(whole.to_string());
+    }
+    let fraction = format!("{:0width$}", fraction, width = places as usize);
+    Ok(format!("{}.{}", whole, fraction.trim_end_matches('0')))
+}
+
+/// Parses a decimal string into an amount in the smallest unit of a token with `decimals` decimal places.
+pub(crate) fn parse_amount(human: &str, decimals: u8, rounding: RoundingMode) -> Result<u128, String> {
+    let human = human.trim();
+    let (whole, fraction) = human.split_once('.').unwrap_or((human, ""));
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() && fraction.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(format!("Invalid amount: {:?}", human));
+    }
+
+    // Split the fraction into the digits the token can hold and the ones that have to be rounded away
+    let (kept, extra) = fraction.split_at(fraction.len().min(decimals as usize));
+    let overflow = || "The amount is too large".to_string();
+    let parse = |s: &str| if s.is_empty() { Ok(0) } else { s.parse::<u128>().map_err(|_| overflow()) };
+
+    let whole = parse(whole)?.checked_mul(pow10(decimals as u32)?).ok_or_else(overflow)?;
+    let kept = parse(kept)? * pow10((decimals as usize - kept.len()) as u32)?;
+    let mut amount = whole.checked_add(kept).ok_or_else(overflow)?;
+
+    if !extra.is_empty() {
+        // Only the first 38 extra digits can matter when deciding which way to round
+        let extra = &extra[..extra.len().min(38)];
+        let divisor = pow10(extra.len() as u32)?;
+        if rounds_up(parse(extra)?, divisor, rounding)? {
+            amount = amount.checked_add(1).ok_or_else(overflow)?;
+        }
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_shows_every_significant_decimal() {
+        assert_eq!(format_amount(1_500_000, 6, None, RoundingMode::Down).unwrap(), "1.5");
+        assert_eq!(format_amount(1_000_000, 6, None, RoundingMode::Down).unwrap(), "1");
+        assert_eq!(format_amount(1, 6, None, RoundingMode::Down).unwrap(), "0.000001");
+        assert_eq!(format_amount(0, 24, None, RoundingMode::Down).unwrap(), "0");
+        assert_eq!(format_amount(123, 0, None, RoundingMode::Down).unwrap(), "123");
+    }
+
+    #[test]
+    fn format_rounds_to_the_precision() {
+        assert_eq!(format_amount(1_234_567, 6, Some(2), RoundingMode::Down).unwrap(), "1.23");
+        assert_eq!(format_amount(1_234_567, 6, Some(2), RoundingMode::Up).unwrap(), "1.24");
+        assert_eq!(format_amount(1_235_000, 6, Some(2), RoundingMode::HalfUp).unwrap(), "1.24");
+        assert_eq!(format_amount(1_234_999, 6, Some(2), RoundingMode::HalfUp).unwrap(), "1.23");
+        assert!(format_amount(1_234_567, 6, Some(2), RoundingMode::Exact).is_err());
+        assert_eq!(format_amount(1_230_000, 6, Some(2), RoundingMode::Exact).unwrap(), "1.23");
+    }
+
+    #[test]
+    fn format_carries_into_the_whole_part() {
+        assert_eq!(format_amount(1_999_999, 6, Some(2), RoundingMode::Up).unwrap(), "2");
+        assert_eq!(format_amount(999_999, 6, Some(0), RoundingMode::HalfUp).unwrap(), "1");
+    }
+
+    #[test]
+    fn format_handles_the_largest_amount() {
+        assert_eq!(
+            format_amount(u128::MAX, 24, None, RoundingMode::Down).unwrap(),
+            "340282366920938.463463374607431768211455"
+        );
+    }
+
+    #[test]
+    fn parse_reads_decimal_strings() {
+        assert_eq!(parse_amount("1.5", 6, RoundingMode::Exact).unwrap(), 1_500_000);
+        assert_eq!(parse_amount("1", 6, RoundingMode::Exact).unwrap(), 1_000_000);
+        assert_eq!(parse_amount("0.000001", 6, RoundingMode::Exact).unwrap(), 1);
+        assert_eq!(parse_amount(".5", 6, RoundingMode::Exact).unwrap(), 500_000);
+        assert_eq!(parse_amount("2.", 6, RoundingMode::Exact).unwrap(), 2_000_000);
+        assert_eq!(parse_amount(" 3 ", 0, RoundingMode::Exact).unwrap(), 3);
+        assert_eq!(parse_amount("1", 24, RoundingMode::Exact).unwrap(), 10u128.pow(24));
+    }
+
+    #[test]
+    fn parse_rounds_extra_decimals() {
+        assert!(parse_amount("1.0000015", 6, RoundingMode::Exact).is_err());
+        assert_eq!(parse_amount("1.0000010", 6, RoundingMode::Exact).unwrap(), 1_000_001);
+        assert_eq!(parse_amount("1.0000015", 6, RoundingMode::Down).unwrap(), 1_000_001);
+        assert_eq!(parse_amount("1.0000011", 6, RoundingMode::Up).unwrap(), 1_000_002);
+        assert_eq!(parse_amount("1.0000015", 6, RoundingMode::HalfUp).unwrap(), 1_000_002);
+        assert_eq!(parse_amount("1.0000014", 6, RoundingMode::HalfUp).unwrap(), 1_000_001);
+    }
+
+    #[test]
+    fn parse_refuses_invalid_amounts() {
+        for human in ["", ".", "-1", "1.2.3", "1,5", "abc", "1e6", "+1"] {
+            assert!(parse_amount(human, 6, RoundingMode::Exact).is_err(), "{:?} should be refused", human);
+        }
+        assert!(parse_amount("340282366920938.463463374607431768211456", 24, RoundingMode::Exact).is_err());
+    }
+
+    #[test]
+    fn parse_and_format_round_trip() {
+        for amount in [0, 1, 10, 1_500_000, 123_456_789_000_000_000_000_000_000, u128::MAX] {
+            let human = format_amount(amount, 24, None, RoundingMode::Exact).unwrap();
+            assert_eq!(parse_amount(&human, 24, RoundingMode::Exact).unwrap(), amount);
+        }
+    }
+}
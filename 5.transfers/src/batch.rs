@@ -0,0 +1,178 @@
+use crate::admin_timelock::mint_batch_total;
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The most entries a batch can have. Every entry adds one item to the batch's event, and a single log can't
+/// be longer than 16 KiB, so this leaves room for long account IDs and a memo.
+pub const MAX_BATCH_SIZE: usize = 50;
+
+/// Ensures the batch has between 1 and `MAX_BATCH_SIZE` entries.
+pub(crate) fn assert_batch_size<T>(batch: &[T]) {
+    require_or(!batch.is_empty(), FtError::EmptyBatch);
+    require_or(batch.len() <= MAX_BATCH_SIZE, FtError::BatchTooLarge);
+}
+
+impl Contract {
+    /// Internal method for minting a batch and emitting a single `ft_mint` event that lists every mint.
+    pub(crate) fn internal_mint_batch(&mut self, mints: &[(AccountId, NearToken)], memo: Option<&str>) {
+        assert_batch_size(mints);
+
+        for (account_id, amount) in mints {
+            self.internal_mint(account_id, *amount);
+        }
+
+        // Emit one event covering the whole batch
+        let events: Vec<FtMint> = mints.iter().map(|(owner_id, amount)| FtMint { owner_id, amount, memo }).collect();
+        FtMint::emit_many(&events);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Mints tokens into several registered accounts at once, e.g. for an airdrop. A single `ft_mint` event
+    /// lists every mint. Only the owner can mint and exactly 1 yoctoNEAR must be attached.
+    ///
+    /// Batches that would take what was minted in the current window above the mint threshold have to be
+    /// proposed with `propose_admin_op` instead, so that holders see them coming.
+    #[payable]
+    pub fn ft_mint_batch(&mut self, mints: Vec<(AccountId, NearToken)>, memo: Option<String>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        // Only the owner can change the supply
+        self.assert_owner();
+        self.internal_record_untimelocked_mint(mint_batch_total(&mints));
+
+        self.internal_mint_batch(&mints, memo.as_deref());
+    }
+
+    /// Transfers tokens from the caller to several receivers at once. A single `ft_transfer` event lists every
+    /// transfer. Every receiver must be registered and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn ft_transfer_batch(&mut self, transfers: Vec<(AccountId, NearToken)>, memo: Option<String>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        assert_batch_size(&transfers);
+        self.assert_valid_memo(memo.as_deref());
+
+        // The sender is the user who called the method
+        let sender_id = env::predecessor_account_id();
+        for (receiver_id, amount) in &transfers {
+            self.internal_transfer_without_event(&sender_id, receiver_id, *amount);
+            self.internal_record_deposit(&sender_id, receiver_id, *amount, memo.as_deref());
+        }
+
+        // Emit one event covering the whole batch
+        let events: Vec<FtTransfer> = transfers
+            .iter()
+            .map(|(new_owner_id, amount)| FtTransfer {
+                old_owner_id: &sender_id,
+                new_owner_id,
+                amount,
+                memo: memo.as_deref(),
+            })
+            .collect();
+        FtTransfer::emit_many(&events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::test_utils::get_logs;
+
+    use super::*;
+    use crate::admin_timelock::ADMIN_TIMELOCK_NS;
+
+    /// Initializes the contract with the whole supply owned by `owner()` and registers alice and bob
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        Context::new().one_yocto().set();
+        contract
+    }
+
+    /// Returns the only log of the call, parsed as an event
+    fn single_event() -> near_sdk::serde_json::Value {
+        let logs = get_logs();
+        assert_eq!(logs.len(), 1, "Expected a single log, got {:?}", logs);
+        near_sdk::serde_json::from_str(logs[0].strip_prefix("EVENT_JSON:").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn mint_batch_emits_one_event() {
+        let mut contract = setup();
+
+        contract.ft_mint_batch(vec![(alice(), tokens(10)), (bob(), tokens(20))], Some("airdrop".to_string()));
+
+        assert_eq!(contract.internal_unwrap_balance_of(&alice()), tokens(10));
+        assert_eq!(contract.internal_unwrap_balance_of(&bob()), tokens(20));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY + 30));
+        assert_eq!(contract.total_shares, TOTAL_SUPPLY + 30);
+
+        let event = single_event();
+        assert_eq!(event["event"], "ft_mint");
+        assert_eq!(event["data"].as_array().unwrap().len(), 2);
+        assert_eq!(event["data"][1]["owner_id"], "bob.near");
+        assert_eq!(event["data"][1]["memo"], "airdrop");
+    }
+
+    #[test]
+    fn transfer_batch_emits_one_event() {
+        let mut contract = setup();
+
+        contract.ft_transfer_batch(vec![(alice(), tokens(10)), (bob(), tokens(20))], None);
+
+        assert_eq!(contract.internal_unwrap_balance_of(&owner()), tokens(TOTAL_SUPPLY - 30));
+        assert_eq!(contract.internal_unwrap_balance_of(&bob()), tokens(20));
+
+        let event = single_event();
+        assert_eq!(event["event"], "ft_transfer");
+        assert_eq!(event["data"][0]["old_owner_id"], "owner.near");
+        assert_eq!(event["data"][1]["new_owner_id"], "bob.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EMPTY_BATCH")]
+    fn empty_batches_are_refused() {
+        let mut contract = setup();
+        contract.ft_transfer_batch(vec![], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BATCH_TOO_LARGE")]
+    fn oversized_batches_are_refused() {
+        let mut contract = setup();
+        contract.ft_mint_batch(vec![(alice(), tokens(1)); MAX_BATCH_SIZE + 1], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MINT_ABOVE_THRESHOLD")]
+    fn mints_in_the_same_window_add_up() {
+        let mut contract = setup();
+        let threshold = contract.get_mint_threshold();
+        contract.ft_mint_batch(vec![(alice(), threshold)], None);
+        contract.ft_mint_batch(vec![(bob(), threshold)], None);
+    }
+
+    #[test]
+    fn the_threshold_resets_in_the_next_window() {
+        let mut contract = setup();
+        let threshold = contract.get_mint_threshold();
+        contract.ft_mint_batch(vec![(alice(), threshold)], None);
+
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.ft_mint_batch(vec![(bob(), threshold)], None);
+        assert_eq!(contract.internal_unwrap_balance_of(&bob()), threshold);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_can_mint() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.ft_mint_batch(vec![(alice(), tokens(1))], None);
+    }
+}
@@ -0,0 +1,252 @@
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{ext_contract, log, Gas, Promise, PromiseResult};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// GAS attached to the bridge operator's `finish_withdraw` call
+const GAS_FOR_FINISH_WITHDRAW: Gas = Gas::from_tgas(10);
+/// GAS attached to the callback that re-mints the tokens if `finish_withdraw` fails
+const GAS_FOR_RESOLVE_WITHDRAW: Gas = Gas::from_tgas(5);
+
+/// The `msg` expected by `ft_transfer_call` when the receiver is the bridge operator, e.g.
+/// `{"recipient": "0x8ba1f109551bd432803012645ac136ddd64dba72"}`. The operator locks the tokens and releases
+/// them to `recipient` on the other chain.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BridgeMsg {
+    pub recipient: String,
+}
+
+/// Returns whether `address` looks like an Ethereum (or Aurora) address: `0x` followed by 40 hex digits.
+pub(crate) fn is_eth_address(address: &str) -> bool {
+    match address.strip_prefix("0x") {
+        Some(hex) => hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+#[ext_contract(ext_bridge_operator)]
+pub trait BridgeOperator {
+    /// Called by the token after `sender_id` burnt `amount` to exit to `recipient` on the other chain. The
+    /// operator records the exit so that a proof of it can be relayed to the other side.
+    fn finish_withdraw(&mut self, sender_id: AccountId, amount: NearToken, recipient: String);
+}
+
+impl Contract {
+    /// Internal method for asserting that the predecessor is the bridge operator.
+    pub(crate) fn assert_bridge_operator(&self) {
+        let operator_id = self.bridge_operator.as_ref().unwrap_or_else(|| FtError::BridgeNotConfigured.panic());
+        require_or(&env::predecessor_account_id() == operator_id, FtError::NotBridgeOperator);
+    }
+
+    /// Internal method for checking the `msg` of an `ft_transfer_call`. Transfers to the bridge operator lock the
+    /// tokens for bridging, so their `msg` has to name a valid recipient or the tokens could be stuck.
+    pub(crate) fn assert_valid_bridge_msg(&self, receiver_id: &AccountId, msg: &str) {
+        if self.bridge_operator.as_ref() != Some(receiver_id) {
+            return;
+        }
+        let recipient = near_sdk::serde_json::from_str::<BridgeMsg>(msg)
+            .map(|bridge_msg| bridge_msg.recipient)
+            .unwrap_or_default();
+        require_or(is_eth_address(&recipient), FtError::InvalidBridgeRecipient);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Removes the account that mints bridged tokens on proofs and finishes withdrawals, which turns bridging off.
+    /// The operator can mint any amount, so setting one has to be proposed as `AdminOp::SetBridgeOperator` with
+    /// `propose_admin_op`. Only the owner can remove the operator and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn remove_bridge_operator(&mut self) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.bridge_operator = None;
+    }
+
+    /// Returns the bridge operator, or `null` if bridging is off.
+    pub fn get_bridge_operator(&self) -> Option<AccountId> {
+        self.bridge_operator.clone()
+    }
+
+    /// Returns whether `proof` was already used to mint tokens.
+    pub fn is_bridge_proof_used(&self, proof: String) -> bool {
+        self.bridge_proofs.contains(&env::sha256_array(proof.as_bytes()))
+    }
+
+    /// Mints `amount` tokens into a registered account for tokens locked on the other chain. `proof` identifies
+    /// the lock (e.g. the serialized lock event proof) and can only be used once. Only the bridge operator can
+    /// mint, after it verified the proof.
+    pub fn bridge_mint(&mut self, account_id: AccountId, amount: NearToken, proof: String) {
+        self.assert_bridge_operator();
+        // Only the hash is kept so that the storage doesn't depend on the size of the proof
+        require_or(self.bridge_proofs.insert(&env::sha256_array(proof.as_bytes())), FtError::BridgeProofUsed);
+
+        self.internal_mint(&account_id, amount);
+
+        // Emit an event showing that the FTs were minted
+        FtMint {
+            owner_id: &account_id,
+            amount: &amount,
+            memo: Some("Bridge mint"),
+        }
+        .emit();
+    }
+
+    /// Exits `amount` tokens to `recipient` on the other chain. The tokens are burnt from the caller and the bridge
+    /// operator's `finish_withdraw` is called so that it can release them on the other side. If that call fails,
+    /// the tokens are minted back. Exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn bridge_withdraw(&mut self, amount: NearToken, recipient: String) -> Promise {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        require_or(is_eth_address(&recipient), FtError::InvalidBridgeRecipient);
+        let operator_id = self.bridge_operator.clone().unwrap_or_else(|| FtError::BridgeNotConfigured.panic());
+
+        // The sender is the user who called the method. Exits go through the operator, so the sender's outbound
+        // allowlist has to allow it
+        let sender_id = env::predecessor_account_id();
+        self.assert_outbound_allowed(&sender_id, &operator_id);
+        self.internal_burn(&sender_id, amount);
+
+        // Emit an event showing that the FTs were burnt
+        FtBurn {
+            owner_id: &sender_id,
+            amount: &amount,
+            memo: Some("Bridge exit"),
+        }
+        .emit();
+        // The sender can't unregister until `bridge_resolve_withdraw` had the chance to mint the tokens back
+        self.internal_start_in_flight(&sender_id);
+
+        ext_bridge_operator::ext(operator_id)
+            .with_static_gas(GAS_FOR_FINISH_WITHDRAW)
+            .finish_withdraw(sender_id.clone(), amount, recipient)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_WITHDRAW)
+                    .bridge_resolve_withdraw(sender_id, amount),
+            )
+    }
+
+    // Finalize a `bridge_withdraw`. If the operator didn't accept the exit, the burnt tokens are minted back
+    // to the sender. Returns whether the exit went through.
+    #[private]
+    pub fn bridge_resolve_withdraw(&mut self, sender_id: AccountId, amount: NearToken) -> bool {
+        self.internal_finish_in_flight(&sender_id);
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        // The sender may have unregistered in the meantime, in which case there's nowhere to mint to
+        if self.accounts.get(&sender_id).is_none() {
+            log!("The account {} is not registered, the exit can't be reverted", sender_id);
+            return false;
+        }
+        self.internal_mint(&sender_id, amount);
+
+        // Emit an event showing that the FTs were minted back
+        FtMint {
+            owner_id: &sender_id,
+            amount: &amount,
+            memo: Some("Reverted bridge exit"),
+        }
+        .emit();
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+    use crate::admin_timelock::{AdminOp, ADMIN_TIMELOCK_NS};
+
+    fn bridge() -> AccountId {
+        "bridge.near".parse().unwrap()
+    }
+
+    const RECIPIENT: &str = "0x8ba1f109551bd432803012645ac136ddd64dba72";
+
+    /// Creates the contract with `bridge()` as the bridge operator and alice registered with 100 tokens
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(AdminOp::SetBridgeOperator { operator_id: bridge() });
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+        contract
+    }
+
+    #[test]
+    fn recognizes_eth_addresses() {
+        assert!(is_eth_address(RECIPIENT));
+        assert!(!is_eth_address("8ba1f109551bd432803012645ac136ddd64dba72"));
+        assert!(!is_eth_address("0x8ba1f109551bd432803012645ac136ddd64dba7"));
+        assert!(!is_eth_address("0x8ba1f109551bd432803012645ac136ddd64dbazz"));
+    }
+
+    #[test]
+    fn operator_mints_on_proofs() {
+        let mut contract = setup();
+        Context::new().predecessor(bridge()).set();
+        contract.bridge_mint(alice(), tokens(50), "proof-1".to_string());
+
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(150));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY + 50));
+        assert!(contract.is_bridge_proof_used("proof-1".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BRIDGE_PROOF_USED")]
+    fn proofs_can_only_be_used_once() {
+        let mut contract = setup();
+        Context::new().predecessor(bridge()).set();
+        contract.bridge_mint(alice(), tokens(50), "proof-1".to_string());
+        contract.bridge_mint(alice(), tokens(50), "proof-1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_BRIDGE_OPERATOR")]
+    fn only_the_operator_mints() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).set();
+        contract.bridge_mint(alice(), tokens(50), "proof-1".to_string());
+    }
+
+    #[test]
+    fn withdraw_burns_and_a_failed_exit_mints_back() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.bridge_withdraw(tokens(40), RECIPIENT.to_string());
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(60));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY - 40));
+
+        Context::new().cross_contract_caller(ft_testing::contract()).set_with_promise_result(PromiseResult::Failed);
+        assert!(!contract.bridge_resolve_withdraw(alice(), tokens(40)));
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(100));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_BRIDGE_RECIPIENT")]
+    fn transfers_to_the_operator_need_a_recipient() {
+        let contract = setup();
+        contract.assert_valid_bridge_msg(&bridge(), "lock");
+    }
+
+    #[test]
+    fn transfers_to_other_receivers_keep_any_msg() {
+        let contract = setup();
+        contract.assert_valid_bridge_msg(&bob(), "lock");
+        contract.assert_valid_bridge_msg(&bridge(), &format!(r#"{{"recipient": "{}"}}"#, RECIPIENT));
+    }
+}
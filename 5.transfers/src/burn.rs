@@ -0,0 +1,36 @@
+use near_sdk::assert_one_yocto;
+
+use crate::*;
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Burns `amount` tokens that the treasury (the owner account) bought back from the market.
+    /// The tokens are taken straight from the treasury's balance and removed from the total supply,
+    /// so there's no window where they sit in another account. Exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn buyback_and_burn(&mut self, amount: U128, memo: Option<String>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        // Only the treasury can burn its repurchased tokens
+        self.assert_owner();
+
+        let amount = NearToken::from_yoctonear(amount.0);
+        let owner_id = self.owner_id.clone();
+        self.internal_burn(&owner_id, amount);
+
+        // Emit an event showing that the FTs were burnt
+        FtBurn {
+            owner_id: &owner_id,
+            amount: &amount,
+            memo: Some(memo.as_deref().unwrap_or("Buyback and burn")),
+        }
+        .emit();
+    }
+
+    /// Returns the supply held outside of the treasury (the owner account) in a decimal string representation.
+    pub fn ft_circulating_supply(&self) -> U128 {
+        let treasury_balance = self.internal_balance_of(&self.owner_id);
+        U128(self.total_supply.saturating_sub(treasury_balance).as_yoctonear())
+    }
+}
@@ -0,0 +1,172 @@
+use near_sdk::{ext_contract, log, PromiseOrValue};
+
+use crate::errors::{require_one_yocto, FtError};
+use crate::ft_core::unused_amount;
+use crate::*;
+
+/// The memo of the `ft_mint` event for burnt tokens that `ft_resolve_burn` restored.
+pub const RESOLVE_BURN_MEMO: &str = "burn reverted via resolve";
+
+#[ext_contract(ext_burn_handler)]
+pub trait BurnHandler {
+    /// Called by the token after `sender_id` burnt `amount` with `ft_burn_call`, e.g. so that a bridge or a
+    /// redemption system can release what the tokens stood for. `msg` is passed through from `ft_burn_call`.
+    ///
+    /// Returns how many of the burnt tokens the handler didn't use, which are minted back to the sender, the same
+    /// way `ft_on_transfer` returns the tokens to refund. Panicking restores the whole `amount`.
+    fn ft_on_burn(&mut self, sender_id: AccountId, amount: NearToken, msg: String) -> PromiseOrValue<NearToken>;
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Sets the contract that `ft_burn_call` notifies, or turns `ft_burn_call` off with `null`. Only the owner
+    /// can set the handler and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_burn_handler(&mut self, handler_id: Option<AccountId>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.burn_handler = handler_id;
+    }
+
+    /// Returns the contract `ft_burn_call` notifies, or `null` if `ft_burn_call` is off.
+    pub fn get_burn_handler(&self) -> Option<AccountId> {
+        self.burn_handler.clone()
+    }
+
+    /// Burns `amount` tokens from the caller and calls `ft_on_burn` on the burn handler with `msg`. Whatever the
+    /// handler doesn't use is minted back in `ft_resolve_burn`, and the whole amount is if the handler fails, so
+    /// a burn only sticks once the handler accepted it. Exactly 1 yoctoNEAR must be attached.
+    ///
+    /// Returns a promise which will result in the amount of tokens that stayed burnt.
+    #[payable]
+    pub fn ft_burn_call(&mut self, amount: NearToken, memo: Option<String>, msg: String) -> PromiseOrValue<NearToken> {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        // Reject oversized or malformed payloads before any tokens are burnt
+        self.assert_valid_memo(memo.as_deref());
+        self.assert_valid_msg(&msg);
+        let handler_id = self.burn_handler.clone().unwrap_or_else(|| FtError::BurnHandlerNotSet.panic());
+
+        // The sender is the user who called the method. The burn is a way of sending the tokens to the handler,
+        // so the sender's outbound allowlist has to allow it
+        let sender_id = env::predecessor_account_id();
+        self.assert_outbound_allowed(&sender_id, &handler_id);
+        self.internal_burn(&sender_id, amount);
+
+        // Emit an event showing that the FTs were burnt
+        FtBurn {
+            owner_id: &sender_id,
+            amount: &amount,
+            memo: memo.as_deref(),
+        }
+        .emit();
+        // The sender can't unregister until `ft_resolve_burn` had the chance to mint the tokens back
+        self.internal_start_in_flight(&sender_id);
+
+        // The handler gets the same GAS as the receiver of an `ft_transfer_call`, and so does the callback
+        ext_burn_handler::ext(handler_id)
+            .with_static_gas(self.gas_config.ft_transfer_call)
+            .ft_on_burn(sender_id.clone(), amount, msg)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(self.gas_config.resolve_transfer)
+                    .ft_resolve_burn(sender_id, amount),
+            )
+            .into()
+    }
+
+    // Finalize an `ft_burn_call`. Mints back to the sender whatever the handler didn't use, following the same
+    // rules as `ft_resolve_transfer` for reading the handler's result. Returns the amount that stayed burnt.
+    #[private]
+    pub fn ft_resolve_burn(&mut self, sender_id: AccountId, amount: NearToken) -> NearToken {
+        // Whatever happens below, this burn is no longer in flight
+        self.internal_finish_in_flight(&sender_id);
+
+        let unused_amount = unused_amount(env::promise_result(0), amount);
+        if unused_amount == ZERO_TOKEN {
+            return amount;
+        }
+
+        // The sender may have unregistered in the meantime, in which case there's nowhere to mint to
+        if !self.accounts.contains_key(&sender_id) {
+            log!("The account {} is not registered, the burn can't be reverted", sender_id);
+            return amount;
+        }
+        self.internal_mint(&sender_id, unused_amount);
+
+        // Emit an event showing that the FTs were minted back
+        FtMint {
+            owner_id: &sender_id,
+            amount: &unused_amount,
+            memo: Some(RESOLVE_BURN_MEMO),
+        }
+        .emit();
+        amount.saturating_sub(unused_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::PromiseResult;
+
+    use super::*;
+
+    fn handler() -> AccountId {
+        "handler.near".parse().unwrap()
+    }
+
+    /// Creates the contract with `handler()` as the burn handler and alice holding 100 tokens, then has alice
+    /// burn 40 of them with `ft_burn_call`
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+
+        Context::new().one_yocto().set();
+        contract.set_burn_handler(Some(handler()));
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        let _ = contract.ft_burn_call(tokens(40), None, "redeem".to_string());
+        contract
+    }
+
+    #[test]
+    fn burns_stick_once_the_handler_uses_them() {
+        let mut contract = setup();
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(60));
+        assert_eq!(contract.ft_pending_transfers(alice()), 1);
+
+        Context::new()
+            .cross_contract_caller(ft_testing::contract())
+            .set_with_promise_result(PromiseResult::Successful(br#""10""#.to_vec()));
+        assert_eq!(contract.ft_resolve_burn(alice(), tokens(40)), tokens(30));
+
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(70));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY - 30));
+        assert_eq!(contract.ft_pending_transfers(alice()), 0);
+    }
+
+    #[test]
+    fn burns_are_reverted_if_the_handler_fails() {
+        let mut contract = setup();
+
+        Context::new().cross_contract_caller(ft_testing::contract()).set_with_promise_result(PromiseResult::Failed);
+        assert_eq!(contract.ft_resolve_burn(alice(), tokens(40)), ZERO_TOKEN);
+
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(100));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BURN_HANDLER_NOT_SET")]
+    fn burn_calls_need_a_handler() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        Context::new().one_yocto().set();
+        let _ = contract.ft_burn_call(tokens(40), None, String::new());
+    }
+}
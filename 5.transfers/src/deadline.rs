@@ -0,0 +1,65 @@
+use near_sdk::json_types::U64;
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+impl Contract {
+    /// Internal method for asserting that the block isn't past `deadline_timestamp`, in nanoseconds.
+    pub(crate) fn assert_before_deadline(&self, deadline_timestamp: U64) {
+        require_or(env::block_timestamp() <= deadline_timestamp.0, FtError::DeadlineExpired);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Same as `ft_transfer`, but panics if the transfer executes after `deadline_timestamp` (in nanoseconds
+    /// since the Unix epoch), so that a transaction that got delayed or queued can't go through at a stale price.
+    /// Exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn ft_transfer_with_deadline(
+        &mut self,
+        receiver_id: AccountId,
+        amount: NearToken,
+        deadline_timestamp: U64,
+        memo: Option<String>,
+    ) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_before_deadline(deadline_timestamp);
+        // Reject oversized or malformed payloads before any tokens move
+        self.assert_valid_memo(memo.as_deref());
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer(&sender_id, &receiver_id, amount, memo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract
+    }
+
+    #[test]
+    fn transfers_before_the_deadline() {
+        let mut contract = setup();
+        Context::new().one_yocto().block_timestamp(100).set();
+        contract.ft_transfer_with_deadline(alice(), tokens(10), U64(100), None);
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEADLINE_EXPIRED")]
+    fn rejects_transfers_after_the_deadline() {
+        let mut contract = setup();
+        Context::new().one_yocto().block_timestamp(101).set();
+        contract.ft_transfer_with_deadline(alice(), tokens(10), U64(100), None);
+    }
+}
@@ -0,0 +1,157 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::Value;
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// How many tagged deposits are kept. Once the log is full, each new deposit replaces the oldest one.
+pub const DEPOSIT_LOG_CAPACITY: u64 = 1024;
+
+/// A tagged deposit as it's kept in the deposit log.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct DepositRecord {
+    pub tag: String,
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: NearToken,
+    /// When the deposit was made (in nanoseconds)
+    pub timestamp: u64,
+}
+
+/// The structure returned by `get_deposits_by_tag`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TaggedDeposit {
+    /// Position of the deposit in the log. It keeps increasing as old deposits are dropped, so it can be
+    /// used to tell which deposits were already processed
+    pub sequence: U64,
+    pub tag: String,
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: NearToken,
+    /// When the deposit was made (in nanoseconds)
+    pub timestamp: U64,
+}
+
+/// Returns the `deposit_tag` of a structured memo such as `{"deposit_tag": "123456"}`, if there is one.
+pub(crate) fn deposit_tag(memo: &str) -> Option<String> {
+    match near_sdk::serde_json::from_str::<Value>(memo).ok()?.get("deposit_tag")? {
+        Value::String(tag) => Some(tag.clone()),
+        _ => None,
+    }
+}
+
+impl Contract {
+    /// Internal method for logging a transfer if the receiver opted in to deposit tags and the memo has one.
+    pub(crate) fn internal_record_deposit(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: NearToken,
+        memo: Option<&str>,
+    ) {
+        if !self.deposit_tag_receivers.contains(receiver_id) {
+            return;
+        }
+        if let Some(tag) = memo.and_then(deposit_tag) {
+            self.deposit_log.push(&DepositRecord {
+                tag,
+                sender_id: sender_id.clone(),
+                receiver_id: receiver_id.clone(),
+                amount,
+                timestamp: env::block_timestamp(),
+            });
+        }
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Turns deposit tagging on or off for the caller. While it's on, every transfer to the caller whose memo
+    /// has a `deposit_tag` (e.g. `{"deposit_tag": "123456"}`) is logged and can be looked up with
+    /// `get_deposits_by_tag`. Meant for exchanges that attribute deposits to an omnibus account by tag.
+    /// The caller must be registered and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_deposit_tagging(&mut self, enabled: bool) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let account_id = env::predecessor_account_id();
+        require_or(self.accounts.get(&account_id).is_some(), FtError::AccountNotRegistered(account_id.clone()));
+
+        if enabled {
+            self.deposit_tag_receivers.insert(&account_id);
+        } else {
+            self.deposit_tag_receivers.remove(&account_id);
+        }
+    }
+
+    /// Returns whether transfers to `account_id` have their deposit tags logged.
+    pub fn is_deposit_tagging_enabled(&self, account_id: AccountId) -> bool {
+        self.deposit_tag_receivers.contains(&account_id)
+    }
+
+    /// Returns the logged deposits with the given tag, oldest first. Only the last `DEPOSIT_LOG_CAPACITY`
+    /// tagged deposits across all receivers are kept, so clients should poll often enough not to miss any.
+    pub fn get_deposits_by_tag(&self, tag: String, from_index: Option<U128>, limit: Option<u32>) -> Vec<TaggedDeposit> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.deposit_log
+            .iter()
+            .filter(|(_, deposit)| deposit.tag == tag)
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(sequence, deposit)| TaggedDeposit {
+                sequence: U64(sequence),
+                tag: deposit.tag,
+                sender_id: deposit.sender_id,
+                receiver_id: deposit.receiver_id,
+                amount: deposit.amount,
+                timestamp: U64(deposit.timestamp),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    #[test]
+    fn reads_the_tag_from_structured_memos() {
+        assert_eq!(deposit_tag(r#"{"deposit_tag": "123456"}"#), Some("123456".to_string()));
+        assert_eq!(deposit_tag(r#"{"deposit_tag": 123456}"#), None);
+        assert_eq!(deposit_tag("123456"), None);
+        assert_eq!(deposit_tag("Refund"), None);
+    }
+
+    #[test]
+    fn only_opted_in_receivers_are_logged() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.set_deposit_tagging(true);
+
+        let memo = Some(r#"{"deposit_tag": "42"}"#.to_string());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(10), memo.clone());
+        contract.internal_transfer(&owner(), &bob(), NearToken::from_yoctonear(10), memo.clone());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(5), None);
+
+        let deposits = contract.get_deposits_by_tag("42".to_string(), None, None);
+        assert_eq!(deposits.len(), 1);
+        assert_eq!(deposits[0].receiver_id, alice());
+        assert_eq!(deposits[0].amount, NearToken::from_yoctonear(10));
+        assert!(contract.get_deposits_by_tag("43".to_string(), None, None).is_empty());
+    }
+}
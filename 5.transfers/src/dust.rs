@@ -0,0 +1,246 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Promise;
+
+use crate::batch::assert_batch_size;
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The memo on the events logged when dust is swept.
+pub const DUST_MEMO: &str = "dust sweep";
+
+/// The largest share of the total supply the minimum transfer amount (and so the dust threshold) can be set to, in
+/// basis points.
+pub const MAX_DUST_AMOUNT_BPS: u128 = 1;
+
+/// Where the balances swept by `sweep_dust` go.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum DustDestination {
+    /// The balances are burnt
+    Burn,
+    /// The balances are moved to the owner
+    Owner,
+}
+
+/// How the contract keeps the accounts map from filling up with balances too small to matter, e.g. after an
+/// airdrop.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct DustPolicy {
+    /// Transfers of less than this are rejected, unless they send the sender's whole balance
+    pub min_transfer_amount: NearToken,
+    /// Balances below this can be swept by the owner
+    pub dust_threshold: NearToken,
+    pub destination: DustDestination,
+}
+
+impl DustPolicy {
+    /// Whether sweeping under this policy can take more from holders than under `current`, either because it sweeps
+    /// larger balances or because it moves them to the owner instead of burning them. Such changes have to go through
+    /// the admin timelock.
+    pub fn raises(&self, current: Option<&DustPolicy>) -> bool {
+        let (threshold, to_owner) = current
+            .map(|current| (current.dust_threshold, current.destination == DustDestination::Owner))
+            .unwrap_or((ZERO_TOKEN, false));
+        self.dust_threshold > threshold || (self.destination == DustDestination::Owner && !to_owner)
+    }
+}
+
+impl Contract {
+    /// Internal method for checking that a dust policy can only sweep dust: the dust threshold can't be above the
+    /// minimum transfer amount, which can't be above `MAX_DUST_AMOUNT_BPS` of the total supply.
+    pub(crate) fn is_valid_dust_policy(&self, policy: &DustPolicy) -> bool {
+        let max_amount = self.total_supply.as_yoctonear() / 10_000 * MAX_DUST_AMOUNT_BPS;
+        policy.dust_threshold <= policy.min_transfer_amount && policy.min_transfer_amount.as_yoctonear() <= max_amount
+    }
+
+    /// Internal method for asserting that `amount` is at least the dust policy's minimum transfer. Sending the
+    /// whole balance is always allowed, so that accounts holding dust can still empty themselves.
+    pub(crate) fn assert_above_min_transfer(&self, sender_id: &AccountId, amount: NearToken) {
+        let Some(policy) = self.dust_policy else {
+            return;
+        };
+        require_or(
+            amount >= policy.min_transfer_amount || amount == self.internal_balance_of(sender_id),
+            FtError::AmountBelowMinimum,
+        );
+    }
+
+    /// Internal method for sweeping the balance of `account_id` if it's below `policy.dust_threshold`, and
+    /// unregistering it. Accounts that can't be unregistered right now (the owner, accounts with a vault position or
+    /// transfers in flight) are skipped. Returns the storage deposit to refund if the account was swept.
+    pub(crate) fn internal_sweep_dust(&mut self, account_id: &AccountId, policy: &DustPolicy) -> Option<NearToken> {
+        if account_id == &self.owner_id
+            || self.in_flight.contains_key(account_id)
+            || self.vault_shares.get(account_id).unwrap_or(0) > 0
+        {
+            return None;
+        }
+        let shares = self.accounts.get(account_id)?;
+        let balance = self.internal_shares_to_amount(shares);
+        if balance >= policy.dust_threshold {
+            return None;
+        }
+
+        // Move the shares rather than the amount so that rounding can't leave anything behind
+        if shares > 0 {
+            self.internal_withdraw_shares(account_id, shares);
+            match policy.destination {
+                DustDestination::Burn => {
+                    self.total_shares -= shares;
+                    self.total_supply = self.total_supply.saturating_sub(balance);
+                    self.stats.total_burned = self.stats.total_burned.saturating_add(balance);
+                    FtBurn { owner_id: account_id, amount: &balance, memo: Some(DUST_MEMO) }.emit();
+                }
+                DustDestination::Owner => {
+                    let owner_id = self.owner_id.clone();
+                    self.internal_deposit_shares(&owner_id, shares);
+                    FtTransfer {
+                        old_owner_id: account_id,
+                        new_owner_id: &owner_id,
+                        amount: &balance,
+                        memo: Some(DUST_MEMO),
+                    }
+                    .emit();
+                }
+            }
+        }
+
+        // The balance is now 0 so this can't burn anything
+        self.internal_storage_unregister(account_id, false)
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Sets the dust policy, or turns it off if it's `null`. Raising the dust threshold or switching the
+    /// destination to the owner has to be proposed with `propose_admin_op` instead. Only the owner can set it and
+    /// exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_dust_policy(&mut self, policy: Option<DustPolicy>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        if let Some(policy) = &policy {
+            require_or(self.is_valid_dust_policy(policy), FtError::InvalidDustPolicy);
+            require_or(!policy.raises(self.dust_policy.as_ref()), FtError::AdminOpTimelocked);
+        }
+        self.dust_policy = policy;
+    }
+
+    /// Returns the dust policy, or `null` if it's off.
+    pub fn get_dust_policy(&self) -> Option<DustPolicy> {
+        self.dust_policy
+    }
+
+    /// Sweeps the balance of each of `account_ids` that's below the dust threshold into the policy's destination
+    /// and unregisters the account, refunding its storage deposit to it. The owner, unregistered accounts and
+    /// accounts that can't be unregistered right now are skipped. Only the owner can sweep, exactly 1 yoctoNEAR
+    /// must be attached and the dust policy must be set.
+    ///
+    /// Returns the accounts that were swept.
+    #[payable]
+    pub fn sweep_dust(&mut self, account_ids: Vec<AccountId>) -> Vec<AccountId> {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        assert_batch_size(&account_ids);
+        let policy = self.dust_policy.unwrap_or_else(|| FtError::DustPolicyNotSet.panic());
+
+        let mut swept = vec![];
+        for account_id in account_ids {
+            if let Some(refund) = self.internal_sweep_dust(&account_id, &policy) {
+                Promise::new(account_id.clone()).transfer(refund);
+                swept.push(account_id);
+            }
+        }
+        swept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::test_utils::get_logs;
+
+    use super::*;
+    use crate::admin_timelock::{AdminOp, ADMIN_TIMELOCK_NS};
+
+    fn policy(amount: u128, destination: DustDestination) -> DustPolicy {
+        DustPolicy { min_transfer_amount: tokens(amount), dust_threshold: tokens(amount), destination }
+    }
+
+    /// Creates the contract with alice holding 5 tokens, bob holding 500 and a policy that sweeps balances below
+    /// 10 tokens into `destination`
+    fn setup(destination: DustDestination) -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_transfer(&owner(), &alice(), tokens(5), None);
+        contract.internal_transfer(&owner(), &bob(), tokens(500), None);
+
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(AdminOp::SetDustPolicy { policy: policy(10, destination) });
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+        contract
+    }
+
+    #[test]
+    fn sweeping_burns_dust_and_unregisters() {
+        let mut contract = setup(DustDestination::Burn);
+        Context::new().one_yocto().set();
+        assert_eq!(contract.sweep_dust(vec![alice(), bob(), charlie(), owner()]), vec![alice()]);
+
+        assert!(!contract.accounts.contains_key(&alice()));
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(500));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY - 5));
+        assert_eq!(get_logs().len(), 2);
+    }
+
+    #[test]
+    fn sweeping_can_consolidate_into_the_owner() {
+        let mut contract = setup(DustDestination::Owner);
+        Context::new().one_yocto().set();
+        contract.sweep_dust(vec![alice()]);
+
+        assert!(!contract.accounts.contains_key(&alice()));
+        assert_eq!(contract.internal_balance_of(&owner()), tokens(TOTAL_SUPPLY - 500));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_BELOW_MINIMUM")]
+    fn small_transfers_are_rejected() {
+        let mut contract = setup(DustDestination::Burn);
+        contract.internal_transfer(&bob(), &alice(), tokens(9), None);
+    }
+
+    #[test]
+    fn the_whole_balance_can_always_be_sent() {
+        let mut contract = setup(DustDestination::Burn);
+        contract.internal_transfer(&alice(), &bob(), tokens(5), None);
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(505));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ADMIN_OP_TIMELOCKED")]
+    fn the_threshold_cannot_be_raised_directly() {
+        let mut contract = setup(DustDestination::Burn);
+        Context::new().one_yocto().set();
+        contract.set_dust_policy(Some(policy(20, DustDestination::Burn)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_DUST_POLICY")]
+    fn the_threshold_is_capped() {
+        let mut contract = setup(DustDestination::Burn);
+        Context::new().one_yocto().set();
+        contract.set_dust_policy(Some(policy(TOTAL_SUPPLY / 10_000 * MAX_DUST_AMOUNT_BPS + 1, DustDestination::Burn)));
+    }
+}
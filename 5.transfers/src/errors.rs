@@ -0,0 +1,370 @@
+use std::fmt;
+
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::batch::MAX_BATCH_SIZE;
+use crate::dust::MAX_DUST_AMOUNT_BPS;
+use crate::treasury::MAX_BUCKET_NAME_LEN;
+use crate::allowlist::MAX_OUTBOUND_ALLOWLIST_LEN;
+use crate::metadata::{MAX_LOCALE_LEN, MAX_LOCALIZATIONS};
+use crate::minters::MAX_MINT_REFERENCE_LEN;
+use crate::split::{MAX_SPLIT_NAME_LEN, MAX_SPLIT_PRESETS, SPLIT_TOTAL_BPS};
+use crate::transfer_policy::MAX_TRANSFER_FEE_BPS;
+use crate::*;
+
+/// Every error the contract can panic with. Panic messages have the form `ERR_CODE:message`, e.g.
+/// `ERR_RECEIVER_NOT_REGISTERED:The account bob.near is not registered`, so that clients can branch on the
+/// code before the colon instead of matching free text. The codes are stable; the messages may change.
+#[derive(Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", tag = "code", content = "account_id")]
+pub enum FtError {
+    #[serde(rename = "ERR_ACCOUNT_NOT_REGISTERED")]
+    AccountNotRegistered(AccountId),
+    #[serde(rename = "ERR_RECEIVER_NOT_REGISTERED")]
+    ReceiverNotRegistered(AccountId),
+    #[serde(rename = "ERR_ALREADY_REGISTERED")]
+    AlreadyRegistered,
+    #[serde(rename = "ERR_INSUFFICIENT_BALANCE")]
+    InsufficientBalance,
+    #[serde(rename = "ERR_BALANCE_OVERFLOW")]
+    BalanceOverflow,
+    #[serde(rename = "ERR_TOTAL_SUPPLY_OVERFLOW")]
+    TotalSupplyOverflow,
+    #[serde(rename = "ERR_TOTAL_SHARES_OVERFLOW")]
+    TotalSharesOverflow,
+    #[serde(rename = "ERR_ZERO_AMOUNT")]
+    ZeroAmount,
+    #[serde(rename = "ERR_AMOUNT_BELOW_ONE_SHARE")]
+    AmountBelowOneShare,
+    #[serde(rename = "ERR_SELF_TRANSFER")]
+    SelfTransfer,
+    #[serde(rename = "ERR_ONE_YOCTO_REQUIRED")]
+    OneYoctoRequired,
+    #[serde(rename = "ERR_INSUFFICIENT_STORAGE_DEPOSIT")]
+    InsufficientStorageDeposit,
+    #[serde(rename = "ERR_NOT_OWNER")]
+    NotOwner,
+    #[serde(rename = "ERR_EMPTY_BATCH")]
+    EmptyBatch,
+    #[serde(rename = "ERR_BATCH_TOO_LARGE")]
+    BatchTooLarge,
+    #[serde(rename = "ERR_STORAGE_WITHDRAW_TOO_LARGE")]
+    StorageWithdrawTooLarge,
+    #[serde(rename = "ERR_UNREGISTER_POSITIVE_BALANCE")]
+    UnregisterPositiveBalance,
+    #[serde(rename = "ERR_OWNER_CANNOT_UNREGISTER")]
+    OwnerCannotUnregister,
+    #[serde(rename = "ERR_MEMO_TOO_LONG")]
+    MemoTooLong,
+    #[serde(rename = "ERR_MSG_TOO_LONG")]
+    MsgTooLong,
+    #[serde(rename = "ERR_MEMO_SCHEMA_MISMATCH")]
+    MemoSchemaMismatch,
+    #[serde(rename = "ERR_NOT_BRIDGE_OPERATOR")]
+    NotBridgeOperator,
+    #[serde(rename = "ERR_BRIDGE_NOT_CONFIGURED")]
+    BridgeNotConfigured,
+    #[serde(rename = "ERR_BRIDGE_PROOF_USED")]
+    BridgeProofUsed,
+    #[serde(rename = "ERR_INVALID_BRIDGE_RECIPIENT")]
+    InvalidBridgeRecipient,
+    #[serde(rename = "ERR_TRANSFERS_IN_FLIGHT")]
+    TransfersInFlight,
+    #[serde(rename = "ERR_GAS_OUT_OF_BOUNDS")]
+    GasOutOfBounds,
+    #[serde(rename = "ERR_NOT_RELAYER")]
+    NotRelayer,
+    #[serde(rename = "ERR_NO_RELAY_KEY")]
+    NoRelayKey,
+    #[serde(rename = "ERR_INVALID_RELAY_KEY")]
+    InvalidRelayKey,
+    #[serde(rename = "ERR_INVALID_RELAY_NONCE")]
+    InvalidRelayNonce,
+    #[serde(rename = "ERR_INVALID_RELAY_SIGNATURE")]
+    InvalidRelaySignature,
+    #[serde(rename = "ERR_NOT_TREASURER")]
+    NotTreasurer,
+    #[serde(rename = "ERR_INVALID_TREASURY_BUCKET")]
+    InvalidTreasuryBucket,
+    #[serde(rename = "ERR_INSUFFICIENT_TREASURY_BALANCE")]
+    InsufficientTreasuryBalance,
+    #[serde(rename = "ERR_VAULT_POSITION_OPEN")]
+    VaultPositionOpen,
+    #[serde(rename = "ERR_DEADLINE_EXPIRED")]
+    DeadlineExpired,
+    #[serde(rename = "ERR_MINT_ABOVE_THRESHOLD")]
+    MintAboveThreshold,
+    #[serde(rename = "ERR_ADMIN_OP_NOT_FOUND")]
+    AdminOpNotFound,
+    #[serde(rename = "ERR_ADMIN_OP_TIMELOCKED")]
+    AdminOpTimelocked,
+    #[serde(rename = "ERR_RECEIVER_NOT_ALLOWED")]
+    ReceiverNotAllowed(AccountId),
+    #[serde(rename = "ERR_ALLOWLIST_TOO_LONG")]
+    AllowlistTooLong,
+    #[serde(rename = "ERR_FAUCET_DISABLED")]
+    FaucetDisabled,
+    #[serde(rename = "ERR_FAUCET_CLAIM_TOO_SOON")]
+    FaucetClaimTooSoon,
+    #[serde(rename = "ERR_FAUCET_WINDOW_EXHAUSTED")]
+    FaucetWindowExhausted,
+    #[serde(rename = "ERR_AMOUNT_BELOW_MINIMUM")]
+    AmountBelowMinimum,
+    #[serde(rename = "ERR_DUST_POLICY_NOT_SET")]
+    DustPolicyNotSet,
+    #[serde(rename = "ERR_INVALID_DUST_POLICY")]
+    InvalidDustPolicy,
+    #[serde(rename = "ERR_BURN_HANDLER_NOT_SET")]
+    BurnHandlerNotSet,
+    #[serde(rename = "ERR_NOT_MINTER")]
+    NotMinter,
+    #[serde(rename = "ERR_INVALID_MINT_REFERENCE")]
+    InvalidMintReference,
+    #[serde(rename = "ERR_MINT_REFERENCE_USED")]
+    MintReferenceUsed,
+    #[serde(rename = "ERR_REDEMPTION_NOT_FOUND")]
+    RedemptionNotFound,
+    #[serde(rename = "ERR_DUPLICATE_SPLIT_RECEIVER")]
+    DuplicateSplitReceiver,
+    #[serde(rename = "ERR_INVALID_SPLIT_PRESET")]
+    InvalidSplitPreset,
+    #[serde(rename = "ERR_TOO_MANY_SPLIT_PRESETS")]
+    TooManySplitPresets,
+    #[serde(rename = "ERR_SPLIT_PRESET_NOT_FOUND")]
+    SplitPresetNotFound,
+    #[serde(rename = "ERR_INVALID_ICON")]
+    InvalidIcon,
+    #[serde(rename = "ERR_INVALID_LOCALE")]
+    InvalidLocale,
+    #[serde(rename = "ERR_TOO_MANY_LOCALIZATIONS")]
+    TooManyLocalizations,
+    #[serde(rename = "ERR_INVALID_REFERENCE")]
+    InvalidReference,
+    #[serde(rename = "ERR_TRANSFERS_PAUSED")]
+    TransfersPaused,
+    #[serde(rename = "ERR_NOT_WHITELISTED")]
+    NotWhitelisted(AccountId),
+    #[serde(rename = "ERR_TRANSFER_FEE_TOO_HIGH")]
+    TransferFeeTooHigh,
+}
+
+impl FtError {
+    /// The machine-readable code that starts the panic message
+    pub fn code(&self) -> &'static str {
+        match self {
+            FtError::AccountNotRegistered(_) => "ERR_ACCOUNT_NOT_REGISTERED",
+            FtError::ReceiverNotRegistered(_) => "ERR_RECEIVER_NOT_REGISTERED",
+            FtError::AlreadyRegistered => "ERR_ALREADY_REGISTERED",
+            FtError::InsufficientBalance => "ERR_INSUFFICIENT_BALANCE",
+            FtError::BalanceOverflow => "ERR_BALANCE_OVERFLOW",
+            FtError::TotalSupplyOverflow => "ERR_TOTAL_SUPPLY_OVERFLOW",
+            FtError::TotalSharesOverflow => "ERR_TOTAL_SHARES_OVERFLOW",
+            FtError::ZeroAmount => "ERR_ZERO_AMOUNT",
+            FtError::AmountBelowOneShare => "ERR_AMOUNT_BELOW_ONE_SHARE",
+            FtError::SelfTransfer => "ERR_SELF_TRANSFER",
+            FtError::OneYoctoRequired => "ERR_ONE_YOCTO_REQUIRED",
+            FtError::InsufficientStorageDeposit => "ERR_INSUFFICIENT_STORAGE_DEPOSIT",
+            FtError::NotOwner => "ERR_NOT_OWNER",
+            FtError::EmptyBatch => "ERR_EMPTY_BATCH",
+            FtError::BatchTooLarge => "ERR_BATCH_TOO_LARGE",
+            FtError::StorageWithdrawTooLarge => "ERR_STORAGE_WITHDRAW_TOO_LARGE",
+            FtError::UnregisterPositiveBalance => "ERR_UNREGISTER_POSITIVE_BALANCE",
+            FtError::OwnerCannotUnregister => "ERR_OWNER_CANNOT_UNREGISTER",
+            FtError::MemoTooLong => "ERR_MEMO_TOO_LONG",
+            FtError::MsgTooLong => "ERR_MSG_TOO_LONG",
+            FtError::MemoSchemaMismatch => "ERR_MEMO_SCHEMA_MISMATCH",
+            FtError::NotBridgeOperator => "ERR_NOT_BRIDGE_OPERATOR",
+            FtError::BridgeNotConfigured => "ERR_BRIDGE_NOT_CONFIGURED",
+            FtError::BridgeProofUsed => "ERR_BRIDGE_PROOF_USED",
+            FtError::InvalidBridgeRecipient => "ERR_INVALID_BRIDGE_RECIPIENT",
+            FtError::TransfersInFlight => "ERR_TRANSFERS_IN_FLIGHT",
+            FtError::GasOutOfBounds => "ERR_GAS_OUT_OF_BOUNDS",
+            FtError::NotRelayer => "ERR_NOT_RELAYER",
+            FtError::NoRelayKey => "ERR_NO_RELAY_KEY",
+            FtError::InvalidRelayKey => "ERR_INVALID_RELAY_KEY",
+            FtError::InvalidRelayNonce => "ERR_INVALID_RELAY_NONCE",
+            FtError::InvalidRelaySignature => "ERR_INVALID_RELAY_SIGNATURE",
+            FtError::NotTreasurer => "ERR_NOT_TREASURER",
+            FtError::InvalidTreasuryBucket => "ERR_INVALID_TREASURY_BUCKET",
+            FtError::InsufficientTreasuryBalance => "ERR_INSUFFICIENT_TREASURY_BALANCE",
+            FtError::VaultPositionOpen => "ERR_VAULT_POSITION_OPEN",
+            FtError::DeadlineExpired => "ERR_DEADLINE_EXPIRED",
+            FtError::MintAboveThreshold => "ERR_MINT_ABOVE_THRESHOLD",
+            FtError::AdminOpNotFound => "ERR_ADMIN_OP_NOT_FOUND",
+            FtError::AdminOpTimelocked => "ERR_ADMIN_OP_TIMELOCKED",
+            FtError::ReceiverNotAllowed(_) => "ERR_RECEIVER_NOT_ALLOWED",
+            FtError::AllowlistTooLong => "ERR_ALLOWLIST_TOO_LONG",
+            FtError::FaucetDisabled => "ERR_FAUCET_DISABLED",
+            FtError::FaucetClaimTooSoon => "ERR_FAUCET_CLAIM_TOO_SOON",
+            FtError::FaucetWindowExhausted => "ERR_FAUCET_WINDOW_EXHAUSTED",
+            FtError::AmountBelowMinimum => "ERR_AMOUNT_BELOW_MINIMUM",
+            FtError::DustPolicyNotSet => "ERR_DUST_POLICY_NOT_SET",
+            FtError::InvalidDustPolicy => "ERR_INVALID_DUST_POLICY",
+            FtError::BurnHandlerNotSet => "ERR_BURN_HANDLER_NOT_SET",
+            FtError::NotMinter => "ERR_NOT_MINTER",
+            FtError::InvalidMintReference => "ERR_INVALID_MINT_REFERENCE",
+            FtError::MintReferenceUsed => "ERR_MINT_REFERENCE_USED",
+            FtError::RedemptionNotFound => "ERR_REDEMPTION_NOT_FOUND",
+            FtError::DuplicateSplitReceiver => "ERR_DUPLICATE_SPLIT_RECEIVER",
+            FtError::InvalidSplitPreset => "ERR_INVALID_SPLIT_PRESET",
+            FtError::TooManySplitPresets => "ERR_TOO_MANY_SPLIT_PRESETS",
+            FtError::SplitPresetNotFound => "ERR_SPLIT_PRESET_NOT_FOUND",
+            FtError::InvalidIcon => "ERR_INVALID_ICON",
+            FtError::InvalidLocale => "ERR_INVALID_LOCALE",
+            FtError::TooManyLocalizations => "ERR_TOO_MANY_LOCALIZATIONS",
+            FtError::InvalidReference => "ERR_INVALID_REFERENCE",
+            FtError::TransfersPaused => "ERR_TRANSFERS_PAUSED",
+            FtError::NotWhitelisted(_) => "ERR_NOT_WHITELISTED",
+            FtError::TransferFeeTooHigh => "ERR_TRANSFER_FEE_TOO_HIGH",
+        }
+    }
+
+    /// The human-readable part of the panic message
+    pub fn message(&self) -> String {
+        match self {
+            FtError::AccountNotRegistered(account_id) | FtError::ReceiverNotRegistered(account_id) => {
+                format!("The account {} is not registered", account_id)
+            }
+            FtError::AlreadyRegistered => "The account is already registered".to_string(),
+            FtError::InsufficientBalance => "The account doesn't have enough balance".to_string(),
+            FtError::BalanceOverflow => "Balance overflow".to_string(),
+            FtError::TotalSupplyOverflow => "Total supply overflow".to_string(),
+            FtError::TotalSharesOverflow => "Total shares overflow".to_string(),
+            FtError::ZeroAmount => "The amount should be a positive number".to_string(),
+            FtError::AmountBelowOneShare => "The amount is worth less than one share".to_string(),
+            FtError::SelfTransfer => "Sender and receiver should be different".to_string(),
+            FtError::OneYoctoRequired => "Requires attached deposit of exactly 1 yoctoNEAR".to_string(),
+            FtError::InsufficientStorageDeposit => {
+                "The attached deposit is less than the minimum storage balance".to_string()
+            }
+            FtError::NotOwner => "Only the owner can call this method".to_string(),
+            FtError::EmptyBatch => "The batch should not be empty".to_string(),
+            FtError::BatchTooLarge => format!("The batch should have at most {} entries", MAX_BATCH_SIZE),
+            FtError::StorageWithdrawTooLarge => {
+                "The amount is greater than the available storage balance".to_string()
+            }
+            FtError::UnregisterPositiveBalance => {
+                "Can't unregister the account with the positive balance without force".to_string()
+            }
+            FtError::OwnerCannotUnregister => "The owner can't unregister".to_string(),
+            FtError::MemoTooLong => "The memo is longer than the memo limit".to_string(),
+            FtError::MsgTooLong => "The msg is longer than the msg limit".to_string(),
+            FtError::MemoSchemaMismatch => "The memo doesn't match the memo schema".to_string(),
+            FtError::NotBridgeOperator => "Only the bridge operator can call this method".to_string(),
+            FtError::BridgeNotConfigured => "No bridge operator is set".to_string(),
+            FtError::BridgeProofUsed => "The proof was already used to mint".to_string(),
+            FtError::InvalidBridgeRecipient => {
+                "The bridge recipient should be a 0x-prefixed 20-byte hex address".to_string()
+            }
+            FtError::TransfersInFlight => "The account has transfers waiting to be resolved".to_string(),
+            FtError::GasOutOfBounds => "The GAS is outside of the allowed bounds".to_string(),
+            FtError::NotRelayer => "Only an approved relayer can call this method".to_string(),
+            FtError::NoRelayKey => "The account hasn't registered a key to sign relayed transfers with".to_string(),
+            FtError::InvalidRelayKey => "Relayed transfers can only be signed with ed25519 keys".to_string(),
+            FtError::InvalidRelayNonce => "The relayed transfer's nonce isn't the account's next nonce".to_string(),
+            FtError::InvalidRelaySignature => {
+                "The relayed transfer isn't signed with the account's relay key".to_string()
+            }
+            FtError::NotTreasurer => "Only the owner or a treasurer can call this method".to_string(),
+            FtError::InvalidTreasuryBucket => format!(
+                "Treasury bucket names should be 1 to {} characters of a-z, 0-9, - and _",
+                MAX_BUCKET_NAME_LEN
+            ),
+            FtError::InsufficientTreasuryBalance => "The treasury bucket doesn't have enough balance".to_string(),
+            FtError::VaultPositionOpen => "The account still has tokens in the vault".to_string(),
+            FtError::DeadlineExpired => "The transfer's deadline has passed".to_string(),
+            FtError::MintAboveThreshold => {
+                "Mints above the mint threshold for the current window have to be proposed through the admin timelock"
+                    .to_string()
+            }
+            FtError::AdminOpNotFound => "No admin operation is queued with this ID".to_string(),
+            FtError::AdminOpTimelocked => "The operation has to go through the admin timelock".to_string(),
+            FtError::ReceiverNotAllowed(account_id) => {
+                format!("The account {} is not on the sender's outbound allowlist", account_id)
+            }
+            FtError::AllowlistTooLong => {
+                format!("The outbound allowlist should have at most {} receivers", MAX_OUTBOUND_ALLOWLIST_LEN)
+            }
+            FtError::FaucetDisabled => "The faucet is off".to_string(),
+            FtError::FaucetClaimTooSoon => "The account already claimed from the faucet in this window".to_string(),
+            FtError::FaucetWindowExhausted => "The faucet gave out everything it can in this window".to_string(),
+            FtError::AmountBelowMinimum => "The amount is below the minimum transfer amount".to_string(),
+            FtError::DustPolicyNotSet => "No dust policy is set".to_string(),
+            FtError::InvalidDustPolicy => format!(
+                "The dust threshold should be at most the minimum transfer amount, which should be at most {} basis points of the total supply",
+                MAX_DUST_AMOUNT_BPS
+            ),
+            FtError::BurnHandlerNotSet => "No burn handler is set".to_string(),
+            FtError::NotMinter => "Only a minter can call this method".to_string(),
+            FtError::InvalidMintReference => {
+                format!("The mint reference should be 1 to {} characters", MAX_MINT_REFERENCE_LEN)
+            }
+            FtError::MintReferenceUsed => "The mint reference was already used".to_string(),
+            FtError::RedemptionNotFound => "No redemption is pending with this ID".to_string(),
+            FtError::DuplicateSplitReceiver => "Each receiver can only appear once in a split".to_string(),
+            FtError::InvalidSplitPreset => format!(
+                "A split preset needs a name of 1 to {} characters, and 1 to {} distinct receivers whose shares add up to {} basis points",
+                MAX_SPLIT_NAME_LEN, MAX_BATCH_SIZE, SPLIT_TOTAL_BPS
+            ),
+            FtError::TooManySplitPresets => format!("An account can keep at most {} split presets", MAX_SPLIT_PRESETS),
+            FtError::SplitPresetNotFound => "No split preset has this name".to_string(),
+            FtError::InvalidIcon => "The icon should be a data URL within the icon size limit".to_string(),
+            FtError::InvalidLocale => format!("The locale should be a BCP 47 tag of at most {} characters", MAX_LOCALE_LEN),
+            FtError::TooManyLocalizations => format!("The metadata can have at most {} localizations", MAX_LOCALIZATIONS),
+            FtError::InvalidReference => {
+                "The reference and its sha256 reference_hash should be set together".to_string()
+            }
+            FtError::TransfersPaused => "Transfers are paused".to_string(),
+            FtError::NotWhitelisted(account_id) => format!("The account {} is not whitelisted for transfers", account_id),
+            FtError::TransferFeeTooHigh => {
+                format!("The transfer fee should be at most {} basis points", MAX_TRANSFER_FEE_BPS)
+            }
+        }
+    }
+
+    /// Panics with `ERR_CODE:message`
+    pub fn panic(self) -> ! {
+        env::panic_str(&self.to_string())
+    }
+}
+
+impl fmt::Display for FtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.code(), self.message())
+    }
+}
+
+/// Panics with `error` unless `condition` holds. The `require!` equivalent for [`FtError`].
+pub(crate) fn require_or(condition: bool, error: FtError) {
+    if !condition {
+        error.panic()
+    }
+}
+
+/// Panics with `ERR_ONE_YOCTO_REQUIRED` unless exactly 1 yoctoNEAR is attached.
+pub(crate) fn require_one_yocto() {
+    require_or(env::attached_deposit() == NearToken::from_yoctonear(1), FtError::OneYoctoRequired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_messages_start_with_the_code() {
+        let error = FtError::ReceiverNotRegistered("bob.near".parse().unwrap());
+        assert_eq!(error.to_string(), "ERR_RECEIVER_NOT_REGISTERED:The account bob.near is not registered");
+        assert_eq!(FtError::ZeroAmount.to_string(), "ERR_ZERO_AMOUNT:The amount should be a positive number");
+    }
+
+    #[test]
+    fn serialized_code_matches_the_panic_code() {
+        let error = FtError::AccountNotRegistered("bob.near".parse().unwrap());
+        let json = near_sdk::serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], error.code());
+        assert_eq!(json["account_id"], "bob.near");
+
+        let json = near_sdk::serde_json::to_value(FtError::NotOwner).unwrap();
+        assert_eq!(json["code"], FtError::NotOwner.code());
+    }
+}
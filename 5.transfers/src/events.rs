@@ -12,18 +12,35 @@
 //! These events can be logged by calling `.emit()` on them if a single event, or calling
 //! [`FtMint::emit_many`], [`FtTransfer::emit_many`],
 //! or [`FtBurn::emit_many`] respectively.
+//!
+//! The nep141 event types are defined once in the shared `ft-common` crate and re-exported here.
+//!
+//! On top of the standard, the contract logs custom `storage` events whenever the set of registered accounts
+//! changes, so that indexers can follow it without diffing state: [`StorageRegister`], [`StorageUnregister`],
+//! [`StorageWithdraw`] and [`StorageMigrate`]. They use the same nep-297 format, with `"standard": "storage"`.
+//!
+//! Transfers made by a relayer on behalf of a user are logged as an ordinary [`FtTransfer`] followed by a
+//! [`RelayedTransfer`] with `"standard": "relay"`, which names the relayer.
+//!
+//! Moves in and out of the treasury buckets are logged with `"standard": "treasury"`: [`TreasuryDeposit`],
+//! [`TreasuryMove`] and [`TreasuryTransfer`]. Tokens in the buckets count as held by the contract account, so
+//! deposits and transfers out are also logged as an [`FtTransfer`] to or from it.
 
 use near_sdk::AccountId;
 use near_sdk::serde::Serialize;
 
 use near_sdk::{env, NearToken};
 
+pub use ft_common::events::{FtBurn, FtMint, FtTransfer};
+
 #[derive(Serialize, Debug)]
 #[serde(tag = "standard")]
 #[must_use = "don't forget to `.emit()` this event"]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum NearEvent<'a> {
-    Nep141(Nep141Event<'a>),
+    Storage(StorageEvent<'a>),
+    Relay(RelayEvent<'a>),
+    Treasury(TreasuryEvent<'a>),
 }
 
 impl<'a> NearEvent<'a> {
@@ -45,76 +62,229 @@ impl<'a> NearEvent<'a> {
 }
 
 
-/// Data to log for an FT mint event. To log this event, call [`.emit()`](FtMint::emit).
+/// Data to log when an account registers with the contract. To log this event,
+/// call [`.emit()`](StorageRegister::emit).
 #[must_use]
 #[derive(Serialize, Debug, Clone)]
-pub struct FtMint<'a> {
-    pub owner_id: &'a AccountId,
+pub struct StorageRegister<'a> {
+    pub account_id: &'a AccountId,
+    /// The storage deposit kept for the account
     pub amount: &'a NearToken,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub memo: Option<&'a str>,
 }
 
-impl FtMint<'_> {
+impl StorageRegister<'_> {
     /// Logs the event to the host. This is required to ensure that the event is triggered
     /// and to consume the event.
     pub fn emit(self) {
         Self::emit_many(&[self])
     }
 
-    /// Emits an FT mint event, through [`env::log_str`](near_sdk::env::log_str),
-    /// where each [`FtMint`] represents the data of each mint.
-    pub fn emit_many(data: &[FtMint<'_>]) {
-        new_141_v1(Nep141EventKind::FtMint(data)).emit()
+    /// Emits a storage register event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`StorageRegister`] represents the data of each registration.
+    pub fn emit_many(data: &[StorageRegister<'_>]) {
+        new_storage_v1(StorageEventKind::StorageRegister(data)).emit()
     }
 }
 
-/// Data to log for an FT transfer event. To log this event,
-/// call [`.emit()`](FtTransfer::emit).
+/// Data to log when an account unregisters from the contract. To log this event,
+/// call [`.emit()`](StorageUnregister::emit).
 #[must_use]
 #[derive(Serialize, Debug, Clone)]
-pub struct FtTransfer<'a> {
-    pub old_owner_id: &'a AccountId,
-    pub new_owner_id: &'a AccountId,
+pub struct StorageUnregister<'a> {
+    pub account_id: &'a AccountId,
+    /// The storage deposit refunded to the account
     pub amount: &'a NearToken,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub memo: Option<&'a str>,
 }
 
-impl FtTransfer<'_> {
+impl StorageUnregister<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a storage unregister event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`StorageUnregister`] represents the data of each unregistration.
+    pub fn emit_many(data: &[StorageUnregister<'_>]) {
+        new_storage_v1(StorageEventKind::StorageUnregister(data)).emit()
+    }
+}
+
+/// Data to log when an account withdraws from its storage balance. To log this event,
+/// call [`.emit()`](StorageWithdraw::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageWithdraw<'a> {
+    pub account_id: &'a AccountId,
+    /// The amount withdrawn
+    pub amount: &'a NearToken,
+}
+
+impl StorageWithdraw<'_> {
     /// Logs the event to the host. This is required to ensure that the event is triggered
     /// and to consume the event.
     pub fn emit(self) {
         Self::emit_many(&[self])
     }
 
-    /// Emits an FT transfer event, through [`env::log_str`](near_sdk::env::log_str),
-    /// where each [`FtTransfer`] represents the data of each transfer.
-    pub fn emit_many(data: &[FtTransfer<'_>]) {
-        new_141_v1(Nep141EventKind::FtTransfer(data)).emit()
+    /// Emits a storage withdraw event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`StorageWithdraw`] represents the data of each withdrawal.
+    pub fn emit_many(data: &[StorageWithdraw<'_>]) {
+        new_storage_v1(StorageEventKind::StorageWithdraw(data)).emit()
+    }
+}
+
+/// Data to log when an account moves everything it has in the contract to another account. To log this event,
+/// call [`.emit()`](StorageMigrate::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageMigrate<'a> {
+    pub old_account_id: &'a AccountId,
+    pub new_account_id: &'a AccountId,
+    /// The balance moved to the new account
+    pub amount: &'a NearToken,
+}
+
+impl StorageMigrate<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_storage_v1(StorageEventKind::StorageMigrate(&[self])).emit()
     }
 }
 
 #[derive(Serialize, Debug)]
-pub(crate) struct Nep141Event<'a> {
+pub(crate) struct StorageEvent<'a> {
     version: &'static str,
     #[serde(flatten)]
-    event_kind: Nep141EventKind<'a>,
+    event_kind: StorageEventKind<'a>,
 }
 
 #[derive(Serialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
 #[allow(clippy::enum_variant_names)]
-enum Nep141EventKind<'a> {
-    FtMint(&'a [FtMint<'a>]),
-    FtTransfer(&'a [FtTransfer<'a>]),
+enum StorageEventKind<'a> {
+    StorageRegister(&'a [StorageRegister<'a>]),
+    StorageUnregister(&'a [StorageUnregister<'a>]),
+    StorageWithdraw(&'a [StorageWithdraw<'a>]),
+    StorageMigrate(&'a [StorageMigrate<'a>]),
+}
+
+fn new_storage_v1(event_kind: StorageEventKind) -> NearEvent {
+    NearEvent::Storage(StorageEvent { version: "1.0.0", event_kind })
+}
+
+/// Data to log when a relayer transfers tokens on behalf of the account that signed the transfer. To log
+/// this event, call [`.emit()`](RelayedTransfer::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct RelayedTransfer<'a> {
+    pub relayer_id: &'a AccountId,
+    pub signer_id: &'a AccountId,
+    pub receiver_id: &'a AccountId,
+    pub amount: &'a NearToken,
+}
+
+impl RelayedTransfer<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        NearEvent::Relay(RelayEvent { version: "1.0.0", event_kind: RelayEventKind::RelayedTransfer(&[self]) }).emit()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct RelayEvent<'a> {
+    version: &'static str,
+    #[serde(flatten)]
+    event_kind: RelayEventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+enum RelayEventKind<'a> {
+    RelayedTransfer(&'a [RelayedTransfer<'a>]),
+}
+
+/// Data to log when tokens are moved from an account into a treasury bucket. To log this event,
+/// call [`.emit()`](TreasuryDeposit::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct TreasuryDeposit<'a> {
+    pub bucket: &'a str,
+    pub sender_id: &'a AccountId,
+    pub amount: &'a NearToken,
+}
+
+impl TreasuryDeposit<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_treasury_v1(TreasuryEventKind::TreasuryDeposit(&[self])).emit()
+    }
 }
 
-fn new_141<'a>(version: &'static str, event_kind: Nep141EventKind<'a>) -> NearEvent<'a> {
-    NearEvent::Nep141(Nep141Event { version, event_kind })
+/// Data to log when tokens are moved from one treasury bucket to another. To log this event,
+/// call [`.emit()`](TreasuryMove::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct TreasuryMove<'a> {
+    pub from_bucket: &'a str,
+    pub to_bucket: &'a str,
+    pub amount: &'a NearToken,
+    /// The owner or treasurer that moved the tokens
+    pub treasurer_id: &'a AccountId,
+}
+
+impl TreasuryMove<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_treasury_v1(TreasuryEventKind::TreasuryMove(&[self])).emit()
+    }
 }
 
-fn new_141_v1(event_kind: Nep141EventKind) -> NearEvent {
-    new_141("1.0.0", event_kind)
-}
\ No newline at end of file
+/// Data to log when tokens are transferred out of a treasury bucket to an account. To log this event,
+/// call [`.emit()`](TreasuryTransfer::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct TreasuryTransfer<'a> {
+    pub bucket: &'a str,
+    pub receiver_id: &'a AccountId,
+    pub amount: &'a NearToken,
+    /// The owner or treasurer that made the transfer
+    pub treasurer_id: &'a AccountId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl TreasuryTransfer<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_treasury_v1(TreasuryEventKind::TreasuryTransfer(&[self])).emit()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct TreasuryEvent<'a> {
+    version: &'static str,
+    #[serde(flatten)]
+    event_kind: TreasuryEventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+enum TreasuryEventKind<'a> {
+    TreasuryDeposit(&'a [TreasuryDeposit<'a>]),
+    TreasuryMove(&'a [TreasuryMove<'a>]),
+    TreasuryTransfer(&'a [TreasuryTransfer<'a>]),
+}
+
+fn new_treasury_v1(event_kind: TreasuryEventKind) -> NearEvent {
+    NearEvent::Treasury(TreasuryEvent { version: "1.0.0", event_kind })
+}
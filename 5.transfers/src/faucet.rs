@@ -0,0 +1,211 @@
+use std::str::FromStr;
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The memo on the events logged for faucet claims.
+pub const FAUCET_MEMO: &str = "faucet";
+
+/// Where the faucet's tokens come from.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum FaucetSource {
+    /// Newly minted tokens
+    Mint,
+    /// The owner's balance
+    Owner,
+}
+
+/// How the faucet hands out tokens.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct FaucetConfig {
+    pub source: FaucetSource,
+    /// How much each claim gives
+    pub amount_per_claim: NearToken,
+    /// How long an account has to wait between claims, and how long each window of `window_cap` lasts
+    pub window_sec: u64,
+    /// How much the faucet can give out in total per window, across all accounts
+    pub window_cap: NearToken,
+}
+
+impl FaucetConfig {
+    fn window_ns(&self) -> u64 {
+        self.window_sec.saturating_mul(1_000_000_000)
+    }
+}
+
+impl Contract {
+    /// Internal method for measuring how many bytes the last claim of an account takes. It's covered by the
+    /// storage deposit.
+    pub(crate) fn measure_bytes_for_faucet_claim(&mut self) -> StorageUsage {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = AccountId::from_str(&"a".repeat(64)).unwrap();
+        self.faucet_claims.insert(&tmp_account_id, &u64::MAX);
+        let bytes = env::storage_usage() - initial_storage_usage;
+        self.faucet_claims.remove(&tmp_account_id);
+        bytes
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Turns the faucet on with the given config, or off if it's `null`. A faucet that mints can't give out more
+    /// per window than the owner can mint without the admin timelock. Only the owner can configure the faucet
+    /// and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_faucet_config(&mut self, config: Option<FaucetConfig>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        if let Some(config) = &config {
+            require_or(config.amount_per_claim.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+            if config.source == FaucetSource::Mint {
+                require_or(config.window_cap <= self.mint_threshold, FtError::MintAboveThreshold);
+            }
+        }
+        self.faucet = config;
+    }
+
+    /// Returns the faucet's config, or `null` if the faucet is off.
+    pub fn get_faucet_config(&self) -> Option<FaucetConfig> {
+        self.faucet
+    }
+
+    /// Gives the caller the faucet's `amount_per_claim`. Each account can claim once per window, and the faucet
+    /// stops giving out tokens for the rest of a window once it reaches the window's cap. The caller must be
+    /// registered.
+    pub fn faucet_claim(&mut self) -> NearToken {
+        let config = self.faucet.unwrap_or_else(|| FtError::FaucetDisabled.panic());
+        let account_id = env::predecessor_account_id();
+        require_or(self.accounts.contains_key(&account_id), FtError::AccountNotRegistered(account_id.clone()));
+        let now = env::block_timestamp();
+
+        // Limit each account to one claim per window
+        if let Some(last_claim) = self.faucet_claims.get(&account_id) {
+            require_or(now >= last_claim.saturating_add(config.window_ns()), FtError::FaucetClaimTooSoon);
+        }
+        self.faucet_claims.insert(&account_id, &now);
+
+        // Limit the total given out per window
+        if now >= self.faucet_window_start.saturating_add(config.window_ns()) {
+            self.faucet_window_start = now;
+            self.faucet_window_claimed = ZERO_TOKEN;
+        }
+        let amount = config.amount_per_claim;
+        self.faucet_window_claimed = self.faucet_window_claimed.saturating_add(amount);
+        require_or(self.faucet_window_claimed <= config.window_cap, FtError::FaucetWindowExhausted);
+
+        match config.source {
+            FaucetSource::Mint => {
+                self.internal_record_untimelocked_mint(amount);
+                self.internal_mint(&account_id, amount);
+                FtMint { owner_id: &account_id, amount: &amount, memo: Some(FAUCET_MEMO) }.emit();
+            }
+            FaucetSource::Owner => {
+                let owner_id = self.owner_id.clone();
+                self.internal_transfer(&owner_id, &account_id, amount, Some(FAUCET_MEMO.to_string()));
+            }
+        }
+
+        amount
+    }
+
+    /// Returns when `account_id` can claim from the faucet next (in nanoseconds), or `null` if it can claim now.
+    pub fn faucet_next_claim_at(&self, account_id: AccountId) -> Option<U64> {
+        let config = self.faucet?;
+        let next_claim = self.faucet_claims.get(&account_id)?.saturating_add(config.window_ns());
+        (next_claim > env::block_timestamp()).then_some(U64(next_claim))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    /// Creates the contract with alice, bob and charlie registered and a faucet minting 10 tokens per claim,
+    /// up to 20 per minute
+    fn setup(source: FaucetSource) -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_register_account(&charlie());
+
+        Context::new().one_yocto().set();
+        contract.set_faucet_config(Some(FaucetConfig {
+            source,
+            amount_per_claim: tokens(10),
+            window_sec: 60,
+            window_cap: tokens(20),
+        }));
+        contract
+    }
+
+    #[test]
+    fn claims_mint_or_transfer() {
+        let mut contract = setup(FaucetSource::Mint);
+        Context::new().predecessor(alice()).set();
+        assert_eq!(contract.faucet_claim(), tokens(10));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY + 10));
+
+        let mut contract = setup(FaucetSource::Owner);
+        Context::new().predecessor(alice()).set();
+        contract.faucet_claim();
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(10));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    fn accounts_claim_once_per_window() {
+        let mut contract = setup(FaucetSource::Mint);
+        Context::new().predecessor(alice()).set();
+        contract.faucet_claim();
+        assert_eq!(contract.faucet_next_claim_at(alice()), Some(U64(60 * SECOND)));
+
+        Context::new().predecessor(alice()).block_timestamp(60 * SECOND).set();
+        contract.faucet_claim();
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(20));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FAUCET_CLAIM_TOO_SOON")]
+    fn accounts_cannot_claim_twice_in_a_window() {
+        let mut contract = setup(FaucetSource::Mint);
+        Context::new().predecessor(alice()).set();
+        contract.faucet_claim();
+        contract.faucet_claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FAUCET_WINDOW_EXHAUSTED")]
+    fn the_window_cap_is_shared() {
+        let mut contract = setup(FaucetSource::Mint);
+        for account_id in [alice(), bob(), charlie()] {
+            Context::new().predecessor(account_id).set();
+            contract.faucet_claim();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FAUCET_DISABLED")]
+    fn the_faucet_can_be_turned_off() {
+        let mut contract = setup(FaucetSource::Mint);
+        Context::new().one_yocto().set();
+        contract.set_faucet_config(None);
+
+        Context::new().predecessor(alice()).set();
+        contract.faucet_claim();
+    }
+}
@@ -1,9 +1,29 @@
-use near_sdk::{Gas, ext_contract, PromiseOrValue, assert_one_yocto, PromiseResult};
+use near_sdk::{ext_contract, PromiseOrValue, PromiseResult};
 
+use crate::errors::{require_one_yocto, FtError};
 use crate::*;
 
-const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(5);
-const GAS_FOR_FT_TRANSFER_CALL: Gas = Gas::from_tgas(25).saturating_add(GAS_FOR_RESOLVE_TRANSFER);
+/// The memo of the `ft_transfer` event for tokens refunded by `ft_resolve_transfer`, so that indexers can tell
+/// them apart from ordinary transfers back to the sender.
+pub const RESOLVE_REFUND_MEMO: &str = "refund via resolve";
+
+/// Works out how many of the `amount` tokens sent with `ft_transfer_call` the receiver didn't use, from the
+/// result of its `ft_on_transfer` call. The receiver must return a decimal string no larger than `amount`.
+/// Anything else, such as a failed call, a negative or out of range number, or a value that isn't JSON,
+/// refunds the full `amount`. Larger values are capped at `amount` so that a malicious receiver can't take
+/// more than it was sent.
+pub(crate) fn unused_amount(result: PromiseResult, amount: NearToken) -> NearToken {
+    match result {
+        // If we can properly parse the value, the unused amount is equal to whatever is smaller - the unused amount or the original amount (to prevent malicious contracts)
+        PromiseResult::Successful(value) => match near_sdk::serde_json::from_slice::<NearToken>(&value) {
+            Ok(unused_amount) => std::cmp::min(amount, unused_amount),
+            // If we can't properly parse the value, the original amount is returned.
+            Err(_) => amount,
+        },
+        // If the promise wasn't successful, return the original amount.
+        PromiseResult::Failed => amount,
+    }
+}
 
 #[ext_contract(ext_ft_core)]
 pub trait FungibleTokenCore {
@@ -57,12 +77,15 @@ pub trait FungibleTokenCore {
     fn ft_balance_of(&self, account_id: AccountId) -> NearToken;
 }
 
+#[cfg(feature = "contract")]
 #[near_bindgen]
 impl FungibleTokenCore for Contract {
     #[payable]
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: NearToken, memo: Option<String>) {
         // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
-        assert_one_yocto();
+        require_one_yocto();
+        // Reject oversized or malformed payloads before any tokens move
+        self.assert_valid_memo(memo.as_deref());
         // The sender is the user who called the method
         let sender_id = env::predecessor_account_id();
         // Transfer the tokens
@@ -78,22 +101,29 @@ impl FungibleTokenCore for Contract {
         msg: String,
     ) -> PromiseOrValue<NearToken> {
         // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
-        assert_one_yocto();
+        require_one_yocto();
+        // Reject oversized or malformed payloads before any tokens move
+        self.assert_valid_memo(memo.as_deref());
+        self.assert_valid_msg(&msg);
+        // Transfers to the bridge operator lock the tokens for bridging and need a valid recipient
+        self.assert_valid_bridge_msg(&receiver_id, &msg);
         // The sender is the user who called the method
         let sender_id = env::predecessor_account_id();
         // Transfer the tokens
         self.internal_transfer(&sender_id, &receiver_id, amount, memo);
+        // The sender can't unregister until `ft_resolve_transfer` had the chance to refund them
+        self.internal_start_in_flight(&sender_id);
 
         // Initiating receiver's call and the callback
-        // Defaulting GAS weight to 1, no attached deposit, and static GAS equal to the GAS for ft transfer call.
+        // Defaulting GAS weight to 1, no attached deposit, and static GAS equal to the configured GAS for ft transfer call.
         ext_ft_receiver::ext(receiver_id.clone())
-            .with_static_gas(GAS_FOR_FT_TRANSFER_CALL)
+            .with_static_gas(self.gas_config.ft_transfer_call)
             .ft_on_transfer(sender_id.clone(), amount.into(), msg)
             // We then resolve the promise and call ft_resolve_transfer on our own contract
-            // Defaulting GAS weight to 1, no attached deposit, and static GAS equal to the GAS for resolve transfer
+            // Defaulting GAS weight to 1, no attached deposit, and static GAS equal to the configured GAS for resolve transfer
             .then(
                 Self::ext(env::current_account_id())
-                    .with_static_gas(GAS_FOR_RESOLVE_TRANSFER)
+                    .with_static_gas(self.gas_config.resolve_transfer)
                     .ft_resolve_transfer(&sender_id, receiver_id, amount),
             )
             .into()
@@ -106,7 +136,7 @@ impl FungibleTokenCore for Contract {
 
     fn ft_balance_of(&self, account_id: AccountId) -> NearToken {
         // Return the balance of the account
-        self.accounts.get(&account_id).unwrap_or(ZERO_TOKEN)
+        self.internal_balance_of(&account_id)
     }
 }
 
@@ -138,6 +168,7 @@ pub trait FungibleTokenReceiver {
     ) -> PromiseOrValue<NearToken>;
 }
 
+#[cfg(feature = "contract")]
 #[near_bindgen]
 impl Contract {
     // Finalize an `ft_transfer_call` chain of cross-contract calls.
@@ -174,42 +205,96 @@ impl Contract {
         receiver_id: AccountId,
         amount: NearToken,
     ) -> NearToken {
+        // Whatever happens below, this transfer is no longer in flight
+        self.internal_finish_in_flight(sender_id);
+
         // Get the unused amount from the `ft_on_transfer` call result.
-        let unused_amount = match env::promise_result(0) {
-            // If the promise was successful, get the return value
-            PromiseResult::Successful(value) => {
-                // If we can properly parse the value, the unused amount is equal to whatever is smaller - the unused amount or the original amount (to prevent malicious contracts)
-                if let Ok(unused_amount) = near_sdk::serde_json::from_slice::<NearToken>(&value) {
-                    std::cmp::min(amount, unused_amount)
-                // If we can't properly parse the value, the original amount is returned.
-                } else {
-                  amount
-                }
-            }
-            // If the promise wasn't successful, return the original amount.
-            PromiseResult::Failed => amount,
-        };
+        let unused_amount = unused_amount(env::promise_result(0), amount);
 
         // If there is some unused amount, we should refund the sender
         if unused_amount.gt(&ZERO_TOKEN) {
             // Get the receiver's balance. We can only refund the sender if the receiver has enough balance.
-            let receiver_balance = self.accounts.get(&receiver_id).unwrap_or(ZERO_TOKEN);
-            if receiver_balance.gt(&ZERO_TOKEN) {
-                // The amount to refund is the smaller of the unused amount and the receiver's balance as we can only refund up to what the receiver currently has.
-                let refund_amount = std::cmp::min(receiver_balance, unused_amount);
-                
-                // Refund the sender for the unused amount.
-                self.internal_transfer(&receiver_id, &sender_id, refund_amount, Some("Refund".to_string()));
+            let receiver_balance = self.internal_balance_of(&receiver_id);
+            // The amount to refund is the smaller of the unused amount and the receiver's balance as we can only refund up to what the receiver currently has.
+            let refund_amount = std::cmp::min(receiver_balance, unused_amount);
+            // Amounts worth less than one share can't be moved, so they stay with the receiver
+            if self.internal_amount_to_shares(refund_amount) > 0 {
+                if self.accounts.contains_key(sender_id) {
+                    // Refund the sender for the unused amount.
+                    self.internal_refund(&receiver_id, sender_id, refund_amount, Some(RESOLVE_REFUND_MEMO));
+                } else {
+                    // The sender unregistered while the transfer was in flight, so there's nowhere to refund to and the tokens are burnt.
+                    self.internal_burn(&receiver_id, refund_amount);
+                    FtBurn {
+                        owner_id: &receiver_id,
+                        amount: &refund_amount,
+                        memo: Some("Refund to an unregistered account"),
+                    }
+                    .emit();
+                }
                 
                 // Return what was actually used (the amount sent - refund)
                 let used_amount = amount
                     .checked_sub(refund_amount)
-                    .unwrap_or_else(|| env::panic_str("Total supply overflow"));
+                    .unwrap_or_else(|| FtError::InsufficientBalance.panic());
                 return used_amount;
             }
         }
 
-        // If the unused amount is 0, return the original amount.
+        // If nothing was refunded, return the original amount.
         amount
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::test_utils::get_logs;
+
+    use super::*;
+
+    fn returned(value: &str) -> PromiseResult {
+        PromiseResult::Successful(value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn unused_amounts_are_capped_at_the_amount_sent() {
+        assert_eq!(unused_amount(returned(r#""20""#), tokens(100)), tokens(20));
+        assert_eq!(unused_amount(returned(r#""0""#), tokens(100)), tokens(0));
+        assert_eq!(unused_amount(returned(r#""1000""#), tokens(100)), tokens(100));
+        assert_eq!(unused_amount(PromiseResult::Failed, tokens(100)), tokens(100));
+    }
+
+    #[test]
+    fn malformed_return_values_refund_everything() {
+        let malformed = [
+            r#""-20""#.to_string(),
+            format!(r#""{}0""#, u128::MAX),
+            format!(r#""{}""#, "9".repeat(1000)),
+            r#""20.5""#.to_string(),
+            r#""twenty""#.to_string(),
+            "twenty".to_string(),
+            "".to_string(),
+            "null".to_string(),
+            r#"{"amount": "20"}"#.to_string(),
+        ];
+        for value in malformed {
+            assert_eq!(unused_amount(returned(&value), tokens(100)), tokens(100), "{}", value);
+        }
+    }
+
+    #[test]
+    fn refunds_have_a_distinct_memo() {
+        let receiver_id: AccountId = "receiver.near".parse().unwrap();
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&receiver_id);
+        contract.internal_transfer(&owner(), &receiver_id, tokens(100), None);
+
+        Context::new().cross_contract_caller(ft_testing::contract()).set_with_promise_result(returned(r#""30""#));
+        assert_eq!(contract.ft_resolve_transfer(&owner(), receiver_id.clone(), tokens(100)), tokens(70));
+
+        assert_eq!(contract.internal_balance_of(&receiver_id), tokens(70));
+        assert!(get_logs().iter().any(|log| log.contains(RESOLVE_REFUND_MEMO)));
+    }
+}
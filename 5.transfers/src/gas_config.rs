@@ -0,0 +1,109 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Gas;
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The default GAS for resolving an `ft_transfer_call`.
+pub const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(5);
+/// The default GAS attached to the receiver's `ft_on_transfer` call.
+pub const GAS_FOR_FT_TRANSFER_CALL: Gas = Gas::from_tgas(25).saturating_add(GAS_FOR_RESOLVE_TRANSFER);
+
+/// The range `resolve_transfer` can be set to. The lower bound leaves room for a refund, its event and the
+/// transfer history.
+pub const MIN_GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(5);
+pub const MAX_GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(50);
+/// The range `ft_transfer_call` can be set to. The upper bound keeps a transfer call well within the 300 TGas
+/// a transaction can have.
+pub const MIN_GAS_FOR_FT_TRANSFER_CALL: Gas = Gas::from_tgas(10);
+pub const MAX_GAS_FOR_FT_TRANSFER_CALL: Gas = Gas::from_tgas(150);
+
+/// The static GAS attached to the calls `ft_transfer_call` makes. The owner can adjust it when the protocol's
+/// GAS costs change, without redeploying the contract.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct GasConfig {
+    /// GAS attached to the receiver's `ft_on_transfer` call
+    pub ft_transfer_call: Gas,
+    /// GAS attached to the `ft_resolve_transfer` callback
+    pub resolve_transfer: Gas,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self { ft_transfer_call: GAS_FOR_FT_TRANSFER_CALL, resolve_transfer: GAS_FOR_RESOLVE_TRANSFER }
+    }
+}
+
+impl GasConfig {
+    /// Whether every value is within its bounds.
+    pub fn is_within_bounds(&self) -> bool {
+        (MIN_GAS_FOR_FT_TRANSFER_CALL..=MAX_GAS_FOR_FT_TRANSFER_CALL).contains(&self.ft_transfer_call)
+            && (MIN_GAS_FOR_RESOLVE_TRANSFER..=MAX_GAS_FOR_RESOLVE_TRANSFER).contains(&self.resolve_transfer)
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Replaces the GAS attached to the calls `ft_transfer_call` makes. Each value must be within its bounds.
+    /// Only the owner can change it and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_gas_config(&mut self, gas_config: GasConfig) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        require_or(gas_config.is_within_bounds(), FtError::GasOutOfBounds);
+        self.gas_config = gas_config;
+    }
+
+    /// Returns the GAS attached to the calls `ft_transfer_call` makes.
+    pub fn get_gas_config(&self) -> GasConfig {
+        self.gas_config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    fn setup() -> Contract {
+        Context::new().set();
+        Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY))
+    }
+
+    #[test]
+    fn defaults_are_within_bounds() {
+        assert!(GasConfig::default().is_within_bounds());
+    }
+
+    #[test]
+    fn owner_can_adjust_the_gas() {
+        let mut contract = setup();
+        let gas_config = GasConfig { ft_transfer_call: Gas::from_tgas(40), resolve_transfer: Gas::from_tgas(10) };
+
+        Context::new().one_yocto().set();
+        contract.set_gas_config(gas_config);
+        assert_eq!(contract.get_gas_config(), gas_config);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_GAS_OUT_OF_BOUNDS")]
+    fn gas_has_to_be_within_bounds() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.set_gas_config(GasConfig { ft_transfer_call: Gas::from_tgas(40), resolve_transfer: Gas::from_tgas(1) });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_can_adjust_the_gas() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.set_gas_config(GasConfig::default());
+    }
+}
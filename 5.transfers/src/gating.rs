@@ -0,0 +1,46 @@
+use crate::*;
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Returns whether `account_id` holds at least `min_amount` tokens. Meant for token-gating services and for
+    /// other contracts that gate on the token in a cross-contract call, so they don't have to compare amounts
+    /// themselves. Unregistered accounts hold nothing. Tokens in the vault don't count.
+    pub fn is_holder_with_minimum(&self, account_id: AccountId, min_amount: NearToken) -> bool {
+        self.internal_balance_of(&account_id) >= min_amount
+    }
+
+    /// Checks several accounts against the same `min_amount` at once. Returns whether each account holds at
+    /// least `min_amount`, in the order the accounts were passed in.
+    pub fn are_holders_with_minimum(&self, account_ids: Vec<AccountId>, min_amount: NearToken) -> Vec<bool> {
+        account_ids
+            .iter()
+            .map(|account_id| self.internal_balance_of(account_id) >= min_amount)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    #[test]
+    fn checks_each_balance_against_the_minimum() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(100), None);
+        contract.internal_transfer(&owner(), &bob(), NearToken::from_yoctonear(99), None);
+
+        let min_amount = NearToken::from_yoctonear(100);
+        assert!(contract.is_holder_with_minimum(alice(), min_amount));
+        assert!(!contract.is_holder_with_minimum(bob(), min_amount));
+        assert_eq!(
+            contract.are_holders_with_minimum(vec![alice(), bob(), charlie()], min_amount),
+            vec![true, false, false]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::errors::{require_or, FtError};
+use crate::*;
+
+impl Contract {
+    /// Internal method for counting a call whose callback may still refund or mint to `account_id`, such as an
+    /// `ft_transfer_call` waiting on `ft_resolve_transfer`. Must be paired with `internal_finish_in_flight`.
+    pub(crate) fn internal_start_in_flight(&mut self, account_id: &AccountId) {
+        let pending = self.in_flight.get(account_id).unwrap_or(0);
+        self.in_flight.insert(account_id, &(pending + 1));
+    }
+
+    /// Internal method for uncounting a call once its callback ran. The entry is removed when it reaches 0 so
+    /// that accounts without pending callbacks take up no storage.
+    pub(crate) fn internal_finish_in_flight(&mut self, account_id: &AccountId) {
+        // Calls made before the counter existed were never counted
+        match self.in_flight.get(account_id).unwrap_or(0) {
+            0 | 1 => {
+                self.in_flight.remove(account_id);
+            }
+            pending => {
+                self.in_flight.insert(account_id, &(pending - 1));
+            }
+        }
+    }
+
+    /// Internal method for asserting that no callback can still refund or mint to `account_id`. Called before
+    /// anything that would leave those callbacks without an account to credit, like unregistering.
+    pub(crate) fn assert_nothing_in_flight(&self, account_id: &AccountId) {
+        require_or(!self.in_flight.contains_key(account_id), FtError::TransfersInFlight);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Returns how many of the account's `ft_transfer_call`s bridge exits and redemptions are still waiting to be
+    /// resolved.
+    pub fn ft_pending_transfers(&self, account_id: AccountId) -> u32 {
+        self.in_flight.get(&account_id).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::PromiseResult;
+
+    use super::*;
+    use crate::ft_core::FungibleTokenCore;
+    use crate::storage::StorageManagement;
+
+    fn receiver() -> AccountId {
+        "receiver.near".parse().unwrap()
+    }
+
+    /// Creates the contract with alice and a receiver contract registered, and alice holding 100 tokens
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&receiver());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(100), None);
+        contract
+    }
+
+    /// Has alice start an `ft_transfer_call` of 10 tokens to the receiver
+    fn transfer_call(contract: &mut Contract) {
+        Context::new().predecessor(alice()).one_yocto().set();
+        let _ = contract.ft_transfer_call(receiver(), NearToken::from_yoctonear(10), None, String::new());
+    }
+
+    #[test]
+    fn transfer_calls_are_counted_until_resolved() {
+        let mut contract = setup();
+        transfer_call(&mut contract);
+        transfer_call(&mut contract);
+        assert_eq!(contract.ft_pending_transfers(alice()), 2);
+
+        Context::new().cross_contract_caller(ft_testing::contract()).set_with_promise_result(PromiseResult::Failed);
+        contract.ft_resolve_transfer(&alice(), receiver(), NearToken::from_yoctonear(10));
+        assert_eq!(contract.ft_pending_transfers(alice()), 1);
+
+        contract.ft_resolve_transfer(&alice(), receiver(), NearToken::from_yoctonear(10));
+        assert_eq!(contract.ft_pending_transfers(alice()), 0);
+        assert!(!contract.in_flight.contains_key(&alice()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TRANSFERS_IN_FLIGHT")]
+    fn force_unregister_is_blocked_while_a_refund_is_pending() {
+        let mut contract = setup();
+        transfer_call(&mut contract);
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn unregister_works_once_resolved() {
+        let mut contract = setup();
+        transfer_call(&mut contract);
+
+        Context::new().cross_contract_caller(ft_testing::contract()).set_with_promise_result(PromiseResult::Failed);
+        contract.ft_resolve_transfer(&alice(), receiver(), NearToken::from_yoctonear(10));
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert!(contract.storage_unregister(Some(true)));
+    }
+}
@@ -1,62 +1,143 @@
 use std::str::FromStr;
-use near_sdk::{require};
+use uint::construct_uint;
 
+use crate::errors::{require_or, FtError};
 use crate::*;
 
+construct_uint! {
+    /// 256-bit unsigned integer used so that `amount * shares` can't overflow while converting between the two.
+    pub struct U256(4);
+}
+
+/// Computes `a * b / c` rounding down, using 256-bit intermediate math.
+pub(crate) fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    (U256::from(a) * U256::from(b) / U256::from(c)).as_u128()
+}
+
 impl Contract {
-    /// Internal method for force getting the balance of an account. If the account doesn't have a balance, panic with a custom message.
-    pub(crate) fn internal_unwrap_balance_of(&self, account_id: &AccountId) -> NearToken {
-        match self.accounts.get(account_id) {
-            Some(balance) => balance,
-            None => {
-                env::panic_str(format!("The account {} is not registered", &account_id).as_str())
-            }
+    /// Internal method for converting an amount of FTs into the number of shares it represents.
+    /// As long as the contract was never rebased, shares and amounts are 1:1.
+    pub fn internal_amount_to_shares(&self, amount: NearToken) -> u128 {
+        if self.total_supply.is_zero() || self.total_shares == 0 {
+            return amount.as_yoctonear();
         }
+        mul_div(amount.as_yoctonear(), self.total_shares, self.total_supply.as_yoctonear())
     }
 
-    /// Internal method for depositing some amount of FTs into an account. 
-    pub(crate) fn internal_deposit(&mut self, account_id: &AccountId, amount: NearToken) {
-        // Get the current balance of the account. If they're not registered, panic.
-        let balance = self.internal_unwrap_balance_of(account_id);
-        
-        // Add the amount to the balance and insert the new balance into the accounts map
-        if let Some(new_balance) = balance.checked_add(amount) {
-            self.accounts.insert(account_id, &new_balance);
-        } else {
-            env::panic_str("Balance overflow");
+    /// Internal method for converting a number of shares into the amount of FTs it's currently worth.
+    pub fn internal_shares_to_amount(&self, shares: u128) -> NearToken {
+        if self.total_shares == 0 {
+            return NearToken::from_yoctonear(shares);
         }
+        NearToken::from_yoctonear(mul_div(shares, self.total_supply.as_yoctonear(), self.total_shares))
     }
 
-    /// Internal method for withdrawing some amount of FTs from an account. 
-    pub(crate) fn internal_withdraw(&mut self, account_id: &AccountId, amount: NearToken) {
-        // Get the current balance of the account. If they're not registered, panic.
-        let balance = self.internal_unwrap_balance_of(account_id);
-        
-        // Decrease the amount from the balance and insert the new balance into the accounts map
-        if let Some(new_balance) = balance.checked_sub(amount) {
-            self.accounts.insert(account_id, &new_balance);
-        } else {
-            env::panic_str("The account doesn't have enough balance");
+    /// Internal method for force getting the shares of an account. If the account doesn't have any shares, panic with a custom message.
+    pub(crate) fn internal_unwrap_shares_of(&self, account_id: &AccountId) -> u128 {
+        match self.accounts.get(account_id) {
+            Some(shares) => shares,
+            None => FtError::AccountNotRegistered(account_id.clone()).panic(),
         }
     }
 
+    /// Internal method for force getting the balance of an account. If the account doesn't have a balance, panic with a custom message.
+    pub fn internal_unwrap_balance_of(&self, account_id: &AccountId) -> NearToken {
+        self.internal_shares_to_amount(self.internal_unwrap_shares_of(account_id))
+    }
+
+    /// Internal method for getting the balance of an account. Unregistered accounts have a balance of 0.
+    pub fn internal_balance_of(&self, account_id: &AccountId) -> NearToken {
+        self.accounts
+            .get(account_id)
+            .map(|shares| self.internal_shares_to_amount(shares))
+            .unwrap_or(ZERO_TOKEN)
+    }
+
+    /// Internal method for depositing some amount of FTs into an account.
+    pub fn internal_deposit(&mut self, account_id: &AccountId, amount: NearToken) {
+        self.internal_deposit_shares(account_id, self.internal_amount_to_shares(amount));
+    }
+
+    /// Internal method for withdrawing some amount of FTs from an account.
+    pub fn internal_withdraw(&mut self, account_id: &AccountId, amount: NearToken) {
+        self.internal_withdraw_shares(account_id, self.internal_amount_to_shares(amount));
+    }
+
+    /// Internal method for adding shares to an account with one read and one write.
+    pub(crate) fn internal_deposit_shares(&mut self, account_id: &AccountId, shares: u128) {
+        // Get the current shares of the account. If they're not registered, panic.
+        let new_shares = self
+            .internal_unwrap_shares_of(account_id)
+            .checked_add(shares)
+            .unwrap_or_else(|| FtError::BalanceOverflow.panic());
+        self.accounts.insert(account_id, &new_shares);
+    }
+
+    /// Internal method for taking shares from an account with one read and one write.
+    pub(crate) fn internal_withdraw_shares(&mut self, account_id: &AccountId, shares: u128) {
+        // Get the current shares of the account. If they're not registered, panic.
+        let new_shares = self
+            .internal_unwrap_shares_of(account_id)
+            .checked_sub(shares)
+            .unwrap_or_else(|| FtError::InsufficientBalance.panic());
+        self.accounts.insert(account_id, &new_shares);
+    }
+
+    /// Internal method for burning some amount of FTs from an account. This removes the tokens from the
+    /// account's balance as well as from the total supply.
+    pub fn internal_burn(&mut self, account_id: &AccountId, amount: NearToken) {
+        // Ensure the account can't burn 0 tokens
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+
+        // Work out the shares before the supply changes, then take them from the account
+        let shares = self.internal_amount_to_shares(amount);
+        self.internal_withdraw_shares(account_id, shares);
+
+        // Remove the burnt shares and tokens from the totals
+        self.total_shares = self
+            .total_shares
+            .checked_sub(shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+        self.total_supply = self
+            .total_supply
+            .checked_sub(amount)
+            .unwrap_or_else(|| FtError::TotalSupplyOverflow.panic());
+        self.stats.total_burned = self.stats.total_burned.saturating_add(amount);
+    }
+
+    /// Internal method for minting some amount of FTs into an account. This adds the tokens to the account's
+    /// balance as well as to the total supply. No event is emitted, that's up to the caller.
+    pub fn internal_mint(&mut self, account_id: &AccountId, amount: NearToken) {
+        // Ensure the account can't mint 0 tokens
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+
+        // Work out the shares before the supply changes, then give them to the account
+        let shares = self.internal_amount_to_shares(amount);
+        self.internal_deposit_shares(account_id, shares);
+
+        // Add the minted shares and tokens to the totals
+        self.total_shares = self
+            .total_shares
+            .checked_add(shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+        self.total_supply = self
+            .total_supply
+            .checked_add(amount)
+            .unwrap_or_else(|| FtError::TotalSupplyOverflow.panic());
+        self.stats.total_minted = self.stats.total_minted.saturating_add(amount);
+    }
+
     /// Internal method for performing a transfer of FTs from one account to another.
-    pub(crate) fn internal_transfer(
+    pub fn internal_transfer(
         &mut self,
         sender_id: &AccountId,
         receiver_id: &AccountId,
         amount: NearToken,
         memo: Option<String>,
     ) {
-        // Ensure the sender can't transfer to themselves
-        require!(sender_id != receiver_id, "Sender and receiver should be different");
-        // Ensure the sender can't transfer 0 tokens
-        require!(amount.gt(&ZERO_TOKEN), "The amount should be a positive number");
-        
-        // Withdraw from the sender and deposit into the receiver
-        self.internal_withdraw(sender_id, amount);
-        self.internal_deposit(receiver_id, amount);
-        
+        self.internal_transfer_without_event(sender_id, receiver_id, amount);
+        self.internal_record_deposit(sender_id, receiver_id, amount, memo.as_deref());
+
         // Emit a Transfer event
         FtTransfer {
             old_owner_id: sender_id,
@@ -67,20 +148,270 @@ impl Contract {
         .emit();
     }
 
+    /// Internal method for moving FTs from one account to another without emitting an event. Used by the batch
+    /// methods, which emit a single event for the whole batch. Any transfer fee gets its own event.
+    pub(crate) fn internal_transfer_without_event(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: NearToken,
+    ) {
+        // Ensure the sender allows sending to the receiver
+        self.assert_outbound_allowed(sender_id, receiver_id);
+        // Ensure the amount isn't too small to be worth a transfer
+        self.assert_above_min_transfer(sender_id, amount);
+        // Ensure the contract's transfer policy allows it, and find out whether there's a fee
+        let fee = self.transfer_policy().check_transfer(sender_id, receiver_id, amount);
+        // Sending the whole balance is always allowed, like under the dust policy, so it doesn't pay a fee
+        let fee = if amount == self.internal_balance_of(sender_id) { ZERO_TOKEN } else { fee };
+        self.internal_move_tokens(sender_id, receiver_id, amount);
+
+        // The fee is paid on top of the amount, so receivers always get what was sent. Fees worth less than one
+        // share can't be moved, so they're waived
+        if self.internal_amount_to_shares(fee) > 0 {
+            let owner_id = self.owner_id.clone();
+            self.internal_move_tokens(sender_id, &owner_id, fee);
+            FtTransfer {
+                old_owner_id: sender_id,
+                new_owner_id: &owner_id,
+                amount: &fee,
+                memo: Some(TRANSFER_FEE_MEMO),
+            }
+            .emit();
+        }
+    }
+
+    /// Internal method for giving back tokens that a receiver didn't use, e.g. in `ft_resolve_transfer`. The
+    /// receiver's outbound allowlist doesn't apply, since the tokens go back to where they came from.
+    pub fn internal_refund(
+        &mut self,
+        receiver_id: &AccountId,
+        sender_id: &AccountId,
+        amount: NearToken,
+        memo: Option<&str>,
+    ) {
+        self.internal_move_tokens(receiver_id, sender_id, amount);
+        FtTransfer {
+            old_owner_id: receiver_id,
+            new_owner_id: sender_id,
+            amount: &amount,
+            memo,
+        }
+        .emit();
+    }
+
+    /// Internal method for moving FTs from one account to another, without any restriction besides the balances.
+    fn internal_move_tokens(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: NearToken) {
+        // Ensure the sender can't transfer to themselves
+        require_or(sender_id != receiver_id, FtError::SelfTransfer);
+        // Ensure the sender can't transfer 0 tokens
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+        // Read each balance once. The receiver goes first so that the error names the receiver if it isn't registered
+        let receiver_shares = self
+            .accounts
+            .get(receiver_id)
+            .unwrap_or_else(|| FtError::ReceiverNotRegistered(receiver_id.clone()).panic());
+        let sender_shares = self.internal_unwrap_shares_of(sender_id);
+
+        // Convert the amount once and work out both new balances before writing either of them. An amount worth
+        // less than one share would move nothing while still being logged as a transfer
+        let shares = self.internal_amount_to_shares(amount);
+        require_or(shares > 0, FtError::AmountBelowOneShare);
+        let new_sender_shares = sender_shares
+            .checked_sub(shares)
+            .unwrap_or_else(|| FtError::InsufficientBalance.panic());
+        let new_receiver_shares = receiver_shares
+            .checked_add(shares)
+            .unwrap_or_else(|| FtError::BalanceOverflow.panic());
+        self.accounts.insert(sender_id, &new_sender_shares);
+        self.accounts.insert(receiver_id, &new_receiver_shares);
+
+        // Add the transfer to both accounts' history and count it
+        self.internal_record_transfer(sender_id, receiver_id, amount);
+        self.stats.transfers += 1;
+    }
+
     /// Internal method for registering an account with the contract.
-    pub(crate) fn internal_register_account(&mut self, account_id: &AccountId) {
-        if self.accounts.insert(account_id, &ZERO_TOKEN).is_some() {
-            env::panic_str("The account is already registered");
+    pub fn internal_register_account(&mut self, account_id: &AccountId) {
+        if self.accounts.insert(account_id, &0).is_some() {
+            FtError::AlreadyRegistered.panic();
         }
+        self.stats.holders_ever += 1;
+    }
+
+    /// Internal method for asserting that the predecessor is the owner of the contract.
+    pub(crate) fn assert_owner(&self) {
+        require_or(env::predecessor_account_id() == self.owner_id, FtError::NotOwner);
     }
 
     /// Internal method for measuring how many bytes it takes to insert the longest possible account ID into our map
     /// This will insert the account, measure the storage, and remove the account. It is called in the initialization function.
+    /// A full transfer history, a vault position and a faucet claim are included so that registering covers them too.
     pub(crate) fn measure_bytes_for_longest_account_id(&mut self) {
       let initial_storage_usage = env::storage_usage();
       let tmp_account_id = AccountId::from_str(&"a".repeat(64)).unwrap();
-      self.accounts.insert(&tmp_account_id, &ZERO_TOKEN);
+      self.accounts.insert(&tmp_account_id, &0);
       self.bytes_for_longest_account_id = env::storage_usage() - initial_storage_usage;
       self.accounts.remove(&tmp_account_id);
+      self.bytes_for_longest_account_id += self.measure_bytes_for_transfer_history();
+      self.bytes_for_longest_account_id += self.measure_bytes_for_vault_position();
+      self.bytes_for_longest_account_id += self.measure_bytes_for_faucet_claim();
   }
-}
\ No newline at end of file
+
+    /// Internal method for working out the storage deposit needed to register at the current storage price.
+    /// It is called in the initialization function and whenever the owner refreshes the storage balance bounds.
+    pub(crate) fn internal_refresh_storage_balance_required(&mut self) {
+        self.storage_balance_required =
+            env::storage_byte_cost().saturating_mul(self.bytes_for_longest_account_id.into());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::json_types::U128;
+
+    use super::*;
+
+    /// Initializes the contract with the whole supply owned by `owner()` and registers alice and bob
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract
+    }
+
+    #[test]
+    fn new_registers_the_owner_with_the_total_supply() {
+        let contract = setup();
+        assert_eq!(contract.internal_unwrap_balance_of(&owner()), tokens(TOTAL_SUPPLY));
+        assert_eq!(contract.total_shares, TOTAL_SUPPLY);
+    }
+
+    #[test]
+    fn shares_and_amounts_are_one_to_one_before_a_rebase() {
+        let contract = setup();
+        assert_eq!(contract.internal_amount_to_shares(tokens(123)), 123);
+        assert_eq!(contract.internal_shares_to_amount(123), tokens(123));
+    }
+
+    #[test]
+    fn mul_div_does_not_overflow() {
+        assert_eq!(mul_div(u128::MAX, 4, 8), u128::MAX / 2);
+    }
+
+    #[test]
+    fn unregistered_accounts_have_no_balance() {
+        let contract = setup();
+        assert_eq!(contract.internal_balance_of(&charlie()), ZERO_TOKEN);
+    }
+
+    #[test]
+    fn deposit_and_withdraw() {
+        let mut contract = setup();
+
+        contract.internal_deposit(&alice(), tokens(10));
+        contract.internal_withdraw(&alice(), tokens(4));
+
+        assert_eq!(contract.internal_unwrap_balance_of(&alice()), tokens(6));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_BALANCE:The account doesn't have enough balance")]
+    fn withdraw_more_than_the_balance_panics() {
+        let mut contract = setup();
+        contract.internal_deposit(&alice(), tokens(10));
+        contract.internal_withdraw(&alice(), tokens(11));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ACCOUNT_NOT_REGISTERED:The account charlie.near is not registered")]
+    fn deposit_into_unregistered_account_panics() {
+        let mut contract = setup();
+        contract.internal_deposit(&charlie(), tokens(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_REGISTERED:The account is already registered")]
+    fn registering_twice_panics() {
+        let mut contract = setup();
+        contract.internal_register_account(&alice());
+    }
+
+    #[test]
+    fn transfer_moves_the_balance() {
+        let mut contract = setup();
+
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+        contract.internal_transfer(&alice(), &bob(), tokens(40), Some("memo".to_string()));
+
+        assert_eq!(contract.internal_unwrap_balance_of(&owner()), tokens(TOTAL_SUPPLY - 100));
+        assert_eq!(contract.internal_unwrap_balance_of(&alice()), tokens(60));
+        assert_eq!(contract.internal_unwrap_balance_of(&bob()), tokens(40));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SELF_TRANSFER:Sender and receiver should be different")]
+    fn transfer_to_self_panics() {
+        let mut contract = setup();
+        contract.internal_transfer(&owner(), &owner(), tokens(1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ZERO_AMOUNT:The amount should be a positive number")]
+    fn transfer_of_nothing_panics() {
+        let mut contract = setup();
+        contract.internal_transfer(&owner(), &alice(), ZERO_TOKEN, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_BELOW_ONE_SHARE")]
+    fn transfer_worth_less_than_a_share_panics() {
+        let mut contract = setup();
+        // Every share is now worth 2 tokens
+        contract.total_supply = tokens(TOTAL_SUPPLY * 2);
+        contract.internal_transfer(&owner(), &alice(), tokens(1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RECEIVER_NOT_REGISTERED:The account charlie.near is not registered")]
+    fn transfer_to_unregistered_account_panics() {
+        let mut contract = setup();
+        contract.internal_transfer(&owner(), &charlie(), tokens(1), None);
+    }
+
+    #[test]
+    fn burn_reduces_the_balance_and_the_supply() {
+        let mut contract = setup();
+
+        contract.internal_burn(&owner(), tokens(100));
+
+        assert_eq!(contract.internal_unwrap_balance_of(&owner()), tokens(TOTAL_SUPPLY - 100));
+        assert_eq!(contract.total_supply, tokens(TOTAL_SUPPLY - 100));
+        assert_eq!(contract.total_shares, TOTAL_SUPPLY - 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_BALANCE:The account doesn't have enough balance")]
+    fn burn_more_than_the_balance_panics() {
+        let mut contract = setup();
+        contract.internal_burn(&alice(), tokens(1));
+    }
+
+    #[test]
+    fn assert_owner_accepts_the_owner() {
+        let contract = setup();
+        contract.assert_owner();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER:Only the owner can call this method")]
+    fn assert_owner_rejects_everyone_else() {
+        let contract = setup();
+        Context::new().predecessor(alice()).set();
+        contract.assert_owner();
+    }
+}
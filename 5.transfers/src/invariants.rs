@@ -0,0 +1,76 @@
+use near_sdk::json_types::U64;
+use near_sdk::require;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The structure returned by `verify_supply_invariant`. Pages are meant to be chained: pass `next_index`
+/// back in as `from_index` and keep adding `shares_sum` until `next_index` is `null`, then check the
+/// total with `assert_supply_invariant`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SupplyInvariantPage {
+    /// Index of the first account summed in this page
+    pub from_index: U64,
+    /// How many accounts were summed in this page
+    pub accounts_checked: U64,
+    /// Where the next page starts, or `None` if every account has been summed
+    pub next_index: Option<U64>,
+    /// Sum of the shares held by the accounts in this page
+    pub shares_sum: U128,
+    /// Sum of the balances held by the accounts in this page
+    pub balance_sum: U128,
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Sums the shares and balances of up to `limit` registered accounts starting at `from_index`.
+    pub fn verify_supply_invariant(&self, from_index: Option<U64>, limit: Option<u64>) -> SupplyInvariantPage {
+        // Where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = from_index.map(|i| i.0).unwrap_or(0);
+        // Take the first "limit" accounts. If we didn't specify a limit, use 100
+        let limit = limit.unwrap_or(100);
+
+        let mut shares_sum: u128 = 0;
+        let mut accounts_checked: u64 = 0;
+        for shares in self.accounts.values_as_vector().iter().skip(start as usize).take(limit as usize) {
+            shares_sum = shares_sum
+                .checked_add(shares)
+                .unwrap_or_else(|| env::panic_str("Shares overflow"));
+            accounts_checked += 1;
+        }
+
+        let end = start + accounts_checked;
+        SupplyInvariantPage {
+            from_index: U64(start),
+            accounts_checked: U64(accounts_checked),
+            next_index: if end < self.accounts.len() { Some(U64(end)) } else { None },
+            shares_sum: U128(shares_sum),
+            balance_sum: U128(self.internal_shares_to_amount(shares_sum).as_yoctonear()),
+        }
+    }
+
+    /// Final check for an audit done with `verify_supply_invariant`: panics unless the shares summed over
+    /// every page, plus the shares held by the treasury buckets and the vault, add up to the total shares, which
+    /// means the balances add up to the total supply.
+    pub fn assert_supply_invariant(&self, shares_sum: U128) -> bool {
+        let treasury_shares: u128 = self.treasury_buckets.values().sum();
+        require!(
+            treasury_shares == self.treasury_shares,
+            format!(
+                "Supply invariant violated: treasury buckets hold {} shares but the treasury total is {}",
+                treasury_shares, self.treasury_shares
+            )
+        );
+        require!(
+            shares_sum.0.checked_add(self.treasury_shares).and_then(|sum| sum.checked_add(self.vault.token_shares))
+                == Some(self.total_shares),
+            format!(
+                "Supply invariant violated: accounts hold {} shares, the treasury {} and the vault {} but the total is {}",
+                shares_sum.0, self.treasury_shares, self.vault.token_shares, self.total_shares
+            )
+        );
+        true
+    }
+}
@@ -1,50 +1,283 @@
+//! A NEP-141 fungible token.
+//!
+//! The crate has two layers. The library layer is the [`Contract`] state along with the `internal_*` methods
+//! that keep the balances, storage registrations and supply consistent and log the [`events`]. It has no
+//! `#[near_bindgen]` code, so other contracts can embed it and tests can drive it natively.
+//!
+//! The contract methods are a thin `#[near_bindgen]` facade over it. They're behind the `contract` feature,
+//! which is on by default. Turn it off to use the token logic as a library without exporting its methods:
+//!
+//! ```toml
+//! fungible-token = { path = "../5.transfers", default-features = false }
+//! ```
+//!
+//! The unit tests and `tests/invariants.rs` go through the facade, so they need the `contract` feature.
+
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap};
 use near_sdk::json_types::U128;
-use near_sdk::{env, near_bindgen, AccountId, BorshStorageKey, NearToken, PanicOnDefault, StorageUsage, NearSchema};
+use near_sdk::{env, near_bindgen, AccountId, BorshStorageKey, CryptoHash, NearToken, PanicOnDefault, StorageUsage, NearSchema};
 
 pub mod ft_core;
 pub mod events;
 pub mod metadata;
 pub mod storage;
 pub mod internal;
+pub mod rebase;
+pub mod invariants;
+pub mod burn;
+pub mod upgrade;
+pub mod amounts;
+pub mod errors;
+pub mod batch;
+pub mod memo;
+pub mod ring_buffer;
+pub mod deposit_tags;
+pub mod transfer_history;
+pub mod bridge;
+pub mod in_flight;
+pub mod gas_config;
+pub mod relayers;
+pub mod treasury;
+pub mod vault;
+pub mod deadline;
+pub mod account_migration;
+pub mod admin_timelock;
+pub mod allowlist;
+pub mod faucet;
+pub mod dust;
+pub mod gating;
+pub mod burn_call;
+pub mod minters;
+pub mod redemptions;
+pub mod split;
+pub mod stats;
+pub mod transfer_policy;
+pub mod storage_keys;
 
+use crate::errors::{require_or, FtError};
 use crate::metadata::*;
 use crate::events::*;
+use crate::upgrade::*;
+use crate::memo::MemoLimits;
+use crate::gas_config::GasConfig;
+use crate::ring_buffer::RingBuffer;
+use crate::deposit_tags::{DepositRecord, DEPOSIT_LOG_CAPACITY};
+use crate::transfer_history::TransferRecord;
+use crate::vault::Vault;
+use crate::admin_timelock::{QueuedAdminOp, DEFAULT_MINT_THRESHOLD_BPS};
+use crate::allowlist::OutboundAllowlist;
+use crate::faucet::FaucetConfig;
+use crate::dust::DustPolicy;
+use crate::minters::ReferencedMint;
+use crate::redemptions::Redemption;
+use crate::split::SplitPreset;
+use crate::stats::FtStats;
+use crate::transfer_policy::{TransferPolicyConfig, TRANSFER_FEE_MEMO};
+use crate::relayers::RelaySigner;
 
 /// The image URL for the default icon
+#[cfg(feature = "default-icon")]
 const DATA_IMAGE_SVG_GT_ICON: &str = "data:image/jpeg;base64,/9j/4AAQSkZJRgABAQAASABIAAD/2wCEABwcHBwcHDAcHDBEMDAwRFxEREREXHRcXFxcXHSMdHR0dHR0jIyMjIyMjIyoqKioqKjExMTExNzc3Nzc3Nzc3NwBIiQkODQ4YDQ0YOacgJzm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5ubm5v/CABEIAUsB8gMBIgACEQEDEQH/xAAaAAEBAQEBAQEAAAAAAAAAAAAAAQIDBAUG/9oACAEBAAAAAPAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQoAAAAIUAQAoTNzHTcgAAEoQKBAAFTnnrtnXRyACAABGhAAAOeeuunTWr53KAAAIsE2AEKgGNXXTpenfj58cgAQBKSmgAACVrGuu726Z4cuMCABKEpNgAAC76+Z13rvvn5ZzyCEKlJQGgALQCejyZ16+WfV18eJAEBAEo0BbCgDN6eaX6HDl6u/lxIEIAiBLGxY0AATo4S/Q1z308vXzYASAJEqDoBQACb7eSX2bnS8XnyBIBIQEdgAAAvo8eL6O/Ptq8vNAZBEghKjtQUiglDv5Ma7dbvXDGAiERECAdFVQoAHThi9fRno8hEEiEEAF0soUJRQ1iXt3uc+YhEiIAAHQAVLBYG2c9PRq8PMEkJZQAA6ipZSUiovPPXDfTt28XESTLUIpALFdxRFixZUXhntTt36eDjoSYtJCoAZ09AKIsssqMc2u3XPTtv53K0kiwypADOnoBUBZZM5zldb9W51fN7YkRmkQM0APQFsBUxztZkq69Tvw4bZlmc1JcrIAB6c6ttYK048bOhiyw68+vHruY1JJckJAAPTNVrV5S1vn5prPSyQ9XLi5+rE10wY7c4lyiZpYB6JutavGatvlxbL057w36O3z+d9GdSxnPVzsSX1eXkqwDtOtbt4TdPEvXM7c2ue+vq+fjr6OFWa5zpcZWTfu8nmliwHWdpnd6+adbnHmrqx2zjrnPp7+Hl29Xmpbnn1sxLHT1+LnElgOk7Sdo4usnLjuOnPtvdk138PLfRKtcemmJWevbySWSQG3bV6Seedda8eNJrPXp2XWe/i8+tpba471c50nXp5AmZBe021UxN2ebOtZ9/1vL8zvb6vF39k4fJ1Zq1x1uaxTp3xnz25mcxbsKFuOeXSfufn/R/OeH6uPDrr97p8L5P2uXyO31+Xx/rc/V4PofHGvZ0vh5CTnLdABcZS9f23571fK+v39n5/zX6/q/IfpOvt/NfVx9X8n+m8P0vm/T/P/Njp7ZL4JbLMYnZKoHCF6/tfl9Pj/a4/Q+N4+X2/V+O/W9u3wPL9P635j9D8n7XxPu/A+bma9XWY8+ahOE7pVA4SXW/23wfu/A+lx+h8bwPs+r8X+w4ef5/2H0/zH6H5P2vifd+B83PPp6tXfm52SacM5pKRaLc/uPF7vge7fs+D5+f2vV+L/V31fA+x5vr/AJf9F8j7XxPu/n/mZ5du3ezhCZ1rE5yVQOU336Xr9Dz+Pn9jl8btn19fk+v6nP4f0PVv5P0/F7vB9D5/n15fTvvvPCiWr5SoNLz6+mznz93o+XmYskuvVjyVszjGtbrjN99Yb16POF+aNNa755T0fRz5+Ens9Xz8cVyq+nfga0Yzhvr0xKbl1r2eIV8yu+d9efTjyn0vX5OOT1evx+flblV9N8V1TGM67X0eeddYsXfs8cK//8QAFwEBAQEBAAAAAAAAAAAAAAAAAAECA//aAAgBAhAAAADAAAAAFgAAAAFgoIpAFAIoAZS2gAIFSiSS6oCBYACXJopAFgADJaACoAEXKaoChchAFyaBQCAAlytKACJSUCTRQKSsiVKVmNFAsUygNJSSyqAUyixoi51AFlsUyBSE1CWUmhSQFJE0JQlUICmpmXckoiTRSApoc+koMzJS6gSmhz6SgzJFGgEmpUsoqGSVdwlwsztZFKhDOl//xAAXAQEBAQEAAAAAAAAAAAAAAAAAAQID/9oACAEDEAAAANAAAAAAAAAAAAAWAAIogspqpkIFEoALq2YgAAAFrVmIAACgLOkucywAFJRGrNkwQATQAK1ZiAANAANXOSLADQAWLrOSVKgNAFVZpjIlQDQDWkDWpOUEWCga1dSRdLHAJYKlG7akatS8szWZULKLpdSTV0ubnkuRCg1bw3uZxves2JNMkig6RxHbjvAOs1ZDOdA6JxL146yDvlRGIK6Jk3rGLuIsmguMUs6RbdRx101Oa5lXOrj/xAA8EAACAgEBBQQIBAUEAgMAAAAAAQIRAyEEBRASMRMgQVEiMDIzNEBxcmGBscEUFSNCUFJTodFDkWKA4f/aAAgBAQABPwD/AOi9ll/5puhTVjlb04piaZy2OLj/AJZuhsSsSOU5Ds2KDRG71G09GU185ZfzEnbErZVCEhLgkchOLTseqGvl7+da1Ix8eCFxiUqJwtMa0K0+Sv8AwUo+Il6PBEUMRFCY2NDH3n/jW9KFFLFb68E0RaE7ItIi0fQl0JSfQsfX/B18gmTdRGxkZakFaJ6MjKV6EMj8TqZFUiyvU0UUUUUV8rRXrXd0iulmV6UUzVdSJjXoGSLshFqRya2hIy+1oRher0Gqdd6ivVUUV69fIf3JkVzToy9SihLUxP0aJRTOSiK4TrtBxSjZL5K/kK+QfQwv0kZorrxi0nqQmktSTtWiMrI0NnJbcmQdwaJdfU33771l8b+Zi6kjJ0ofBK2R6UyKVUinFkX4jdiVqifLjVIbv1tl92/8B4k9V+Q0WhNITQpUcykQ6DO1km6Jycnb9ZfyNnMcxZZZZZZfr5PQZQkR6kUvElGtUReg3p3bL4ssvjZZfzdlllllllllllid6FajVCIVZSHqjoTdQL71l8LLL/xcOo3qSdoTEyMtBM6md1USyy2Wyyyyyyyyyyyyyyyyyyyyyyyy/WL1KH3JtroQnLmJO+CExNkdRGd3P1F/JWWWWX8o+LZJ2xaOxFWOLEiERLhkdyZfqF8mvlUPhaRKV9OMXRF2VY0RI8JP+q0iUWmLuP1t/PcyQ5N9C2PU6dyLoxyUtBxo6EOhknyRZC+e2PUcSuL+dr1K4yn4IcmJ3wa4NC7kZNO0Rz37SJTizHOLVWZpczpCpPXjLQjqteDSorxGLu0V8g+CRRRRRy92iikTklouFCdPjVo6dRrxQnfUorhZGHNG2xS5ZF3qZLshNNU+vCStEPIY+hB+BKPiL5Z91cPAfcXDJKlXmPrwRLQi7QxPwJITGvFEX5kMcX1MuLkf4DR2kkqR1ZFUiSOTTQUmtJcFoyR4EfaOo1XqWX6xsXXuLg+gxcVwySuZLqRHoNWjH5DHoLVDQmUupFk1z42vFH4DIK5cJasXFdSXQXQS1ESVruvhV6GPBGa66mSDhJxfgMXq5dRMXGOvDwJuiPFEnUWzxsYirXCOkhjIsaHoJ6EeiZB6EvaYzBHmdLxMuKWJq/EfXuLrwWloQuEuvdRFamLQ2jXKyvWSF1EOSRzmOT1Of0jmVE3ZERaRzGV+jXci7GjxFqhniJ2hoWglWNMh0JdWSMMuV8y8DNneWlVUPrwXBcGjo6E+Eu6iBBmR802+5XqWLqIcbFAhEcNRw0JaERDVigZetcEMToeqGR6EEndnJHyORHJE7JEuvKvAxdKJqpMkYx8VwXGXUXTi1xaERRfLFsY+FFFeoYuouCQtCxsn1IiL4SdtvuUJ+BIj0MXi+Cd9Dll1oUXV0KPpakI0zJCXM3RJNdSHTvLjPqR6cZdxCMsax2u7RQ0V3aGkcq4WWcxZY42KNcZOoj4IobUVbNj3Zl2tLLlfJjfTzYtzbElTUm/OzPuVJOWyzafk+hi5oSliyKpx6o04avQi1CNMc7baNh3dHa9nWeeSabb6M/kuL/dyf+0fyPA//JP/AINo3Phw4J5Y5JNxV+BB3BN9xC4z6i6CPHguKE9Uc6qnqiWKM/Z0Hs0+q1GmnTFwooaGuK4Wyy2Wy2WWWzmOY5jmJytcGhMTHDtMkMf+qSRFKKUY6JaI23bZbLkxQjFNZHT4b3xqG04syXtaMx4821ZXi2fSusvIjuTFX9XLNv8ADT/sybnnj9LZcrvykdtOV4cq5ckeqFj9F/Q2LeMdj2dYcmOTab6GzbRHacSzQTSfmZcixY5ZH0irNo3vhzYJ44wlclSNlwZ9rfZYdEurfgQ3Hir+pkk3+Gn/AGZtyzgnLZsjb8pf9iclJ48iqUeqNnwZ9sm4YdEusmR3Hir+pkm3+Ghl3K4py2bI78pE5zhGUJrlnHRmPc0skIz7Z+kk+n/6Zdz5oOKxZHJydPSkkLceLl9LJLm810P5Ztaz/wAOn6PXn/A/keHl95Lm8/Ay4cuy53gy6+Kfmu5FWyEElZQp8pnX9Rvz7lFDVjjRXGmUUUUUUUUUUUPjQjD8Vh+9frw31JQy4JPom3/yj+dbF/8AL/0bz2/DtcYLDdxd6o3RiWPY1KtZttm8M+TZ9lllxupJr9SD5oKT8Ub4xqGbFnjo5eiyMzJNckvozdPwMPz/AFNs+FyfaypQwRl5o3TiWPYovxnqzeOfJs+zPJjdO0iLuKb8Ub7x9nnhmiq5k0/yN1Y1j2KDXWWrNv2rLs+fBDG9Jy14b6hybUpL++Js/uMf2r9Dem1Zdlwxli0blRF3FPzRvLasuzLF2Trmlr9Bao3yl22GXjqu5jrm1PAcvISb6kscMi/EkuV15dyuDQ4jdF9+iiiiih9e4jF8Vh+9frw2zDjz7Zgx5VzRalofyzYf9pf8m99k2fZ4Y3hjytt2bt+BxfT9zfHwMvqv1MXu4/RG+emH7jkXgTg1F+VG6fgYfn+ptnwuT7WSleCK8kbu+CxfabRs+PacfZZbq70/ASpJLwN+q44kvNmy702nHCOzYsSm4qvGz+F2jbMmPNtaWPs3ajHV/nw377/H9v7mz+4x/av0N++4h937EPYj9Eb76YPuf7C6I3z73B+Y+CIunoRbfUTSJSQnJv0SV8zvr330GUX3LL4WWWWPhXGjD8Vh+9frw3tKUdo2dxbTt9PquG/fd4/qzdvwOL6fub4+Bl9V+pi93H6I310w/ccxKb5H9Gbp+Bh+f6m2fC5PtYvdfkbu+Cxfab3lKGxtwbT5l0MG7u1wwyvPkTkk+pvTZP4VY2sk5237Ts3Tghi2SM0vSnq2bw2qeB4sWPR5JJN/hfDfvv8AH9v7mz+4x/av0N++4h937EPYj9Eb7/8AB9z/AGI9Eb695h/PgyPWxMjJUWct9SLUGZmnktdx8EPoS4c7O0O0O0Oc7Q7Q5ztDnO0FO9BlmjEkUzmePJDL/pkmJqSUo6p6m17G9qyYp81dm7fDfvu8X1ZunIp7FBL+20byw5M+ySx4lcrWn5kFywin4JG98nNnw4V1XpMqLJxXI2vJm6fgYfn+ptfwuT7WRV46XkbqyrJsUF4x0ZvPDkz7I4YlzStOjZ4PHghjl1jFJm/cilPHhXWKbf5m7tdixV/pN47Nmz5sE8atRlr+GvDf3vsf2/ubP7jH9q/Q377iH3fsQ9iP0Rvvpg+5/sR6I317zD+Yn4GT2SEvARjq9RMbYk2TVTfcZQuDhZ2Z2J2J2LOxZ2LOxOxOxOxOxOxOyrUfCKb0RHH5ihFEoRlFpmybxy7GuyzLnxro11RLfWzV/TjKUvKjFvXPDm7fDKVu1XgvI27bltvJGONx5Xeps205thm5Y1zQl1iR33srXpxlF+VWZd946a2eDk/N6IUsk8jzZXc5HaeZPLcWl4o2Pekdl2eOGWOUmr1Rm3xDLiljWKS5k0Yk0kmbPk2jZMjyYFzRfWJHfez9MsZRa8Kszb7x01s8HKXm+hJzySlkyu5S6mwby/g49hmi3C9GvAz76g0o7NGTdq2/I/nkP9mRvDant2SM4QceVVqY98wx44weKT5Ukbw3gttxxhHHKPK71I78xqKXZS0Xmbft62zs+WDjyO9Rb8xpV2Ujbds/jZ43GDjy31F7SMlctITp2J2hOiLtWWhzolLmlfdoXc7ZnbHbHbHbHbHbHbMWVs7RnaM7RjjbshictERxKC0FHzHSHPwLIP0Uy7hRNVNoSJvShd6PUxaTTNsjy5W/Piu4yQiyyI148JQ8hScRTZGTo5n3F5GSKeJNf2v1NFHKzkZ2chY/MjBJEqG0cyIRc2orxFFQVIlJRVslksci+GJ3FEfZaMy9M6IkxcH3I+0I2upJNeHfZLjFNlHLpY+HZpiwxYoKL0K4oj7SIq8cl3/HguokqINuzG23qT06FvmS4SHw2Ra/kTMjdj7mHoR6Gb2iQxD7q6iM/u/z77Hwjq+K1VD4LoR6njwfGHtGPoyXV97/xAAgEQADAAMAAgIDAAAAAAAAAAAAAREQIEAwUAISITFg/9oACAECAQE/AP66l9ChdkxBd9EX0C716Bda1XVMv0MJ3XD8N534WLhei7Gj6jR8cMhCCRCaXmW7w8LRYhCEITK3W7w9kXH52uPsL5UTLilKUpSlGIhMra6PLFqxfrwXRaf/xAAjEQACAgICAgIDAQAAAAAAAAAAARARAiAxQBITITADIkFg/9oACAEDAQE/AP8AWpFFDXdUsfcTix99IY12lKnJ91Q3163UZdtFQoyXZoqaKirQ8WiutQiipuE/4OGhqumhC5GPTEoU5K1pX3KEMYpYtHGSlD+1S5sQxaMRkrXRUN/FlmOVMeaFkmZFmGVcnsPNJWe1GWaPYexSitKmiipqcuNFOX83xX6woejiyyyyy5y40U5b/j4Mvhy9H9NlnieJ4iwoo9ZlyLGx4pnrPWeB4CVGRZZcJ7Vohwhi0z5MJY4cVL4MdP/Z";
 
 /// The specific version of the standard we're using
-pub const FT_METADATA_SPEC: &str = "ft-1.0.0";
+pub use ft_common::FT_METADATA_SPEC;
 
 pub const ZERO_TOKEN: NearToken = NearToken::from_yoctonear(0);
 
-#[near_bindgen]
+#[cfg_attr(feature = "contract", near_bindgen)]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 #[borsh(crate = "near_sdk::borsh")]
 pub struct Contract {
-    /// Keep track of each account's balances
-    pub accounts: LookupMap<AccountId, NearToken>,
+    /// The account that's allowed to perform privileged operations such as rebasing
+    pub owner_id: AccountId,
+
+    /// Keep track of each account's shares of the total supply. This map is enumerable so that
+    /// the accounting can be audited on-chain.
+    pub accounts: UnorderedMap<AccountId, u128>,
 
     /// Total supply of all tokens.
     pub total_supply: NearToken,
 
+    /// Total number of shares held across all accounts. Balances are `shares * total_supply / total_shares`.
+    pub total_shares: u128,
+
     /// The bytes for the largest possible account ID that can be registered on the contract 
     pub bytes_for_longest_account_id: StorageUsage,
 
+    /// The storage deposit needed to register, worked out from the bytes above at init so that it isn't
+    /// recomputed on every call. The owner can refresh it if the storage price changes
+    pub storage_balance_required: NearToken,
+
     /// Metadata for the contract itself
     pub metadata: LazyOption<FungibleTokenMetadata>,
+
+    /// Copy of the metadata `decimals` so that amount conversions don't have to read the whole metadata
+    pub decimals: u8,
+
+    /// Contract code waiting for the upgrade timelock to pass
+    pub staged_code: LazyOption<StagedCode>,
+
+    /// Limits on the memos and msgs passed to transfers
+    pub memo_limits: MemoLimits,
+
+    /// Accounts that opted in to having tagged deposits logged
+    pub deposit_tag_receivers: LookupSet<AccountId>,
+
+    /// The most recent tagged deposits to the accounts above
+    pub deposit_log: RingBuffer<DepositRecord>,
+
+    /// The most recent transfers of each account
+    pub transfer_history: LookupMap<AccountId, RingBuffer<TransferRecord>>,
+
+    /// The account that mints bridged tokens on proofs and finishes withdrawals. Bridging is off while it's unset
+    pub bridge_operator: Option<AccountId>,
+
+    /// Hashes of the proofs that were already used to mint bridged tokens
+    pub bridge_proofs: LookupSet<CryptoHash>,
+
+    /// How many callbacks may still refund or mint to each account. Accounts with none aren't stored
+    pub in_flight: LookupMap<AccountId, u32>,
+
+    /// The GAS attached to the calls `ft_transfer_call` makes
+    pub gas_config: GasConfig,
+
+    /// Accounts allowed to submit the transfers other accounts signed off-chain
+    pub relayers: LookupSet<AccountId>,
+
+    /// Shares held by each named treasury bucket. Buckets with no shares aren't stored
+    pub treasury_buckets: UnorderedMap<String, u128>,
+
+    /// Total shares held across all treasury buckets, so that the supply invariant doesn't have to sum them
+    pub treasury_shares: u128,
+
+    /// Accounts that can move tokens between and out of the treasury buckets, besides the owner
+    pub treasurers: LookupSet<AccountId>,
+
+    /// The interest-bearing vault's totals
+    pub vault: Vault,
+
+    /// Vault shares held by each depositor. Accounts with none aren't stored
+    pub vault_shares: LookupMap<AccountId, u128>,
+
+    /// The largest total that can be minted per mint window without going through the admin timelock
+    pub mint_threshold: NearToken,
+
+    /// When the current mint window started (in nanoseconds)
+    pub mint_window_start: u64,
+
+    /// How much was minted without the admin timelock in the current mint window
+    pub mint_window_minted: NearToken,
+
+    /// Admin operations waiting out the timelock, by ID
+    pub admin_ops: UnorderedMap<u64, QueuedAdminOp>,
+
+    /// The ID the next proposed admin operation gets
+    pub next_admin_op_id: u64,
+
+    /// The receivers each account restricted its outgoing transfers to. Unrestricted accounts aren't stored
+    pub outbound_allowlists: LookupMap<AccountId, OutboundAllowlist>,
+
+    /// How the faucet hands out tokens. The faucet is off while it's unset
+    pub faucet: Option<FaucetConfig>,
+
+    /// When each account last claimed from the faucet (in nanoseconds)
+    pub faucet_claims: LookupMap<AccountId, u64>,
+
+    /// When the current faucet window started (in nanoseconds)
+    pub faucet_window_start: u64,
+
+    /// How much the faucet gave out in the current window
+    pub faucet_window_claimed: NearToken,
+
+    /// Which transfers are too small and which balances the owner can sweep. Both are off while it's unset
+    pub dust_policy: Option<DustPolicy>,
+
+    /// The contract `ft_burn_call` notifies of burns. `ft_burn_call` is off while it's unset
+    pub burn_handler: Option<AccountId>,
+
+    /// Accounts that can mint with `ft_mint_with_reference`
+    pub minters: LookupSet<AccountId>,
+
+    /// The mints made with `ft_mint_with_reference`, by the issuer's reference
+    pub mint_references: LookupMap<String, ReferencedMint>,
+
+    /// Redemptions waiting on a minter to pay them out or reject them, by ID
+    pub redemptions: UnorderedMap<u64, Redemption>,
+
+    /// The ID of the next redemption
+    pub next_redemption_id: u64,
+
+    /// The split presets each account saved with `set_split`
+    pub split_presets: LookupMap<AccountId, Vec<SplitPreset>>,
+
+    /// The largest icon the metadata can have, in bytes
+    pub max_icon_len: u32,
+
+    /// The name and symbol of the token in other languages, by locale
+    pub localizations: UnorderedMap<String, LocalizedMetadata>,
+
+    /// Lifetime transfer, mint, burn and registration counters
+    pub stats: FtStats,
+
+    /// The policy every transfer is checked against
+    pub transfer_policy: TransferPolicyConfig,
+
+    /// Accounts that can send and receive under the `Whitelist` transfer policy
+    pub transfer_whitelist: LookupSet<AccountId>,
+
+    /// Accounts whose transfers don't pay the `FeeOnTransfer` fee, e.g. markets and bridges
+    pub fee_exempt: LookupSet<AccountId>,
+
+    /// The key each account signs relayed transfers with, and the nonce its next one has to use
+    pub relay_signers: LookupMap<AccountId, RelaySigner>,
 }
 
-/// Helper structure for keys of the persistent collections.
+/// Helper structure for keys of the persistent collections. Each variant is serialized as its index, so new
+/// variants must only ever be added at the end or existing collections would be read from the wrong prefix.
+/// Collections that belong to a single account use an `...Inner { account_id_hash }` variant, built with the
+/// helpers in `storage_keys`.
 #[derive(BorshSerialize, BorshStorageKey)]
 #[borsh(crate = "near_sdk::borsh")]
 pub enum StorageKey {
     Accounts,
-    Metadata
+    Metadata,
+    StagedCode,
+    DepositTagReceivers,
+    DepositLog,
+    TransferHistory,
+    TransferHistoryInner { account_id_hash: CryptoHash },
+    BridgeProofs,
+    InFlight,
+    Relayers,
+    TreasuryBuckets,
+    Treasurers,
+    VaultShares,
+    AdminOps,
+    OutboundAllowlists,
+    FaucetClaims,
+    Minters,
+    MintReferences,
+    Redemptions,
+    SplitPresets,
+    Localizations,
+    TransferWhitelist,
+    FeeExempt,
+    RelaySigners,
+}
+
+/// The icon `new_default_meta` uses. Builds without the `default-icon` feature have none.
+#[cfg(all(feature = "contract", feature = "default-icon"))]
+fn default_icon() -> Option<String> {
+    Some(DATA_IMAGE_SVG_GT_ICON.to_string())
 }
 
+#[cfg(all(feature = "contract", not(feature = "default-icon")))]
+fn default_icon() -> Option<String> {
+    None
+}
+
+#[cfg(feature = "contract")]
 #[near_bindgen]
 impl Contract {
     /// Initializes the contract with the given total supply owned by the given `owner_id` with
@@ -59,7 +292,7 @@ impl Contract {
                 spec: FT_METADATA_SPEC.to_string(),
                 name: "Team Token FT Tutorial".to_string(),
                 symbol: "gtNEAR".to_string(),
-                icon: Some(DATA_IMAGE_SVG_GT_ICON.to_string()),
+                icon: default_icon(),
                 reference: None,
                 reference_hash: None,
                 decimals: 24,
@@ -75,32 +308,105 @@ impl Contract {
         total_supply: U128,
         metadata: FungibleTokenMetadata,
     ) -> Self {
-        let casted_total_supply = NearToken::from_yoctonear(total_supply.0);
+        Self::init(owner_id, NearToken::from_yoctonear(total_supply.0), metadata)
+    }
+}
+
+impl Contract {
+    /// Creates the token state with the whole `total_supply` owned by `owner_id`, registers the owner and logs
+    /// the initial mint. This is what `new` calls, and what contracts embedding the token should call instead.
+    pub fn init(owner_id: AccountId, total_supply: NearToken, metadata: FungibleTokenMetadata) -> Self {
+        require_or(is_valid_icon(metadata.icon.as_deref(), DEFAULT_MAX_ICON_LEN), FtError::InvalidIcon);
+        require_or(
+            is_valid_reference(metadata.reference.as_deref(), metadata.reference_hash.as_ref()),
+            FtError::InvalidReference,
+        );
+
         // Create a variable of type Self with all the fields initialized. 
         let mut this = Self {
+            // Set the owner of the contract
+            owner_id: owner_id.clone(),
             // Set the total supply
-            total_supply: casted_total_supply,
+            total_supply,
+            // Start with one share per token so that balances and shares are 1:1 until the first rebase
+            total_shares: total_supply.as_yoctonear(),
             // Set the bytes for the longest account ID to 0 temporarily until it's calculated later
             bytes_for_longest_account_id: 0,
+            storage_balance_required: ZERO_TOKEN,
             // Storage keys are simply the prefixes used for the collections. This helps avoid data collision
-            accounts: LookupMap::new(StorageKey::Accounts),
+            accounts: UnorderedMap::new(StorageKey::Accounts),
             metadata: LazyOption::new(
                 StorageKey::Metadata,
                 Some(&metadata),
             ),
+            decimals: metadata.decimals,
+            staged_code: LazyOption::new(StorageKey::StagedCode, None),
+            // Start with the default memo and msg limits and no memo schema
+            memo_limits: MemoLimits::default(),
+            deposit_tag_receivers: LookupSet::new(StorageKey::DepositTagReceivers),
+            deposit_log: RingBuffer::new(StorageKey::DepositLog, DEPOSIT_LOG_CAPACITY),
+            transfer_history: LookupMap::new(StorageKey::TransferHistory),
+            // Bridging stays off until the owner sets an operator
+            bridge_operator: None,
+            bridge_proofs: LookupSet::new(StorageKey::BridgeProofs),
+            in_flight: LookupMap::new(StorageKey::InFlight),
+            gas_config: GasConfig::default(),
+            relayers: LookupSet::new(StorageKey::Relayers),
+            treasury_buckets: UnorderedMap::new(StorageKey::TreasuryBuckets),
+            treasury_shares: 0,
+            treasurers: LookupSet::new(StorageKey::Treasurers),
+            vault: Vault::default(),
+            vault_shares: LookupMap::new(StorageKey::VaultShares),
+            mint_threshold: NearToken::from_yoctonear(total_supply.as_yoctonear() / 10_000 * DEFAULT_MINT_THRESHOLD_BPS),
+            mint_window_start: 0,
+            mint_window_minted: ZERO_TOKEN,
+            admin_ops: UnorderedMap::new(StorageKey::AdminOps),
+            next_admin_op_id: 0,
+            outbound_allowlists: LookupMap::new(StorageKey::OutboundAllowlists),
+            // The faucet stays off until the owner configures it
+            faucet: None,
+            faucet_claims: LookupMap::new(StorageKey::FaucetClaims),
+            faucet_window_start: 0,
+            faucet_window_claimed: ZERO_TOKEN,
+            // There's no minimum transfer and nothing can be swept until the owner sets a dust policy
+            dust_policy: None,
+            // `ft_burn_call` stays off until the owner sets a handler
+            burn_handler: None,
+            minters: LookupSet::new(StorageKey::Minters),
+            mint_references: LookupMap::new(StorageKey::MintReferences),
+            redemptions: UnorderedMap::new(StorageKey::Redemptions),
+            next_redemption_id: 0,
+            split_presets: LookupMap::new(StorageKey::SplitPresets),
+            max_icon_len: DEFAULT_MAX_ICON_LEN,
+            localizations: UnorderedMap::new(StorageKey::Localizations),
+            // The initial supply counts as minted
+            stats: FtStats { total_minted: total_supply, ..FtStats::default() },
+            // Transfers are open until the owner picks another policy
+            transfer_policy: TransferPolicyConfig::Open,
+            transfer_whitelist: LookupSet::new(StorageKey::TransferWhitelist),
+            fee_exempt: LookupSet::new(StorageKey::FeeExempt),
+            relay_signers: LookupMap::new(StorageKey::RelaySigners),
         };
 
         // Measure the bytes for the longest account ID and store it in the contract.
         this.measure_bytes_for_longest_account_id();
+        this.internal_refresh_storage_balance_required();
 
         // Register the owner's account and set their balance to the total supply.
         this.internal_register_account(&owner_id);
-        this.internal_deposit(&owner_id, casted_total_supply);
+        this.internal_deposit(&owner_id, total_supply);
+
+        // Emit an event showing that the owner was registered. Their storage is paid for by the contract
+        StorageRegister {
+            account_id: &owner_id,
+            amount: &ZERO_TOKEN,
+        }
+        .emit();
         
         // Emit an event showing that the FTs were minted
         FtMint {
             owner_id: &owner_id,
-            amount: &casted_total_supply,
+            amount: &total_supply,
             memo: Some("Initial token supply is minted"),
         }
         .emit();
@@ -0,0 +1,151 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{Map, Value};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The default limit on the length of a transfer memo, in bytes.
+pub const DEFAULT_MAX_MEMO_LEN: u32 = 256;
+/// The default limit on the length of an `ft_transfer_call` msg, in bytes.
+pub const DEFAULT_MAX_MSG_LEN: u32 = 8 * 1024;
+
+/// The JSON type a field of a structured memo must have.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum MemoFieldType {
+    String,
+    Number,
+    Boolean,
+}
+
+/// A small subset of JSON Schema for structured memos, e.g. `{"deposit_tag": "123456"}` for exchange deposits.
+/// When set, every memo that's passed in must be a JSON object with the `required` fields.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct MemoSchema {
+    /// The fields the memo must have and the type of each
+    pub required: Vec<(String, MemoFieldType)>,
+    /// Whether the memo can have fields other than the required ones
+    pub allow_additional: bool,
+}
+
+/// Limits on the memo and msg passed to `ft_transfer`, `ft_transfer_call` and `ft_transfer_batch`, checked
+/// before any tokens move so that oversized payloads never make it into receipts.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct MemoLimits {
+    /// The longest memo allowed, in bytes
+    pub max_memo_len: u32,
+    /// The longest `ft_transfer_call` msg allowed, in bytes
+    pub max_msg_len: u32,
+    /// The structure memos must have, if any
+    pub memo_schema: Option<MemoSchema>,
+}
+
+impl Default for MemoLimits {
+    fn default() -> Self {
+        Self { max_memo_len: DEFAULT_MAX_MEMO_LEN, max_msg_len: DEFAULT_MAX_MSG_LEN, memo_schema: None }
+    }
+}
+
+impl MemoSchema {
+    /// Whether `memo` is a JSON object matching the schema.
+    pub fn matches(&self, memo: &str) -> bool {
+        let Ok(Value::Object(fields)) = near_sdk::serde_json::from_str::<Value>(memo) else {
+            return false;
+        };
+        let has_required = self.required.iter().all(|(name, field_type)| {
+            fields.get(name).map(|value| field_type.matches(value)).unwrap_or(false)
+        });
+        has_required && (self.allow_additional || self.only_required(&fields))
+    }
+
+    fn only_required(&self, fields: &Map<String, Value>) -> bool {
+        fields.keys().all(|key| self.required.iter().any(|(name, _)| name == key))
+    }
+}
+
+impl MemoFieldType {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            MemoFieldType::String => value.is_string(),
+            MemoFieldType::Number => value.is_number(),
+            MemoFieldType::Boolean => value.is_boolean(),
+        }
+    }
+}
+
+impl Contract {
+    /// Internal method for checking a transfer memo against the memo limits.
+    pub(crate) fn assert_valid_memo(&self, memo: Option<&str>) {
+        let Some(memo) = memo else { return };
+        require_or(memo.len() <= self.memo_limits.max_memo_len as usize, FtError::MemoTooLong);
+        if let Some(schema) = &self.memo_limits.memo_schema {
+            require_or(schema.matches(memo), FtError::MemoSchemaMismatch);
+        }
+    }
+
+    /// Internal method for checking an `ft_transfer_call` msg against the memo limits.
+    pub(crate) fn assert_valid_msg(&self, msg: &str) {
+        require_or(msg.len() <= self.memo_limits.max_msg_len as usize, FtError::MsgTooLong);
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Replaces the memo and msg limits. Only the owner can change them and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_memo_limits(&mut self, limits: MemoLimits) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.memo_limits = limits;
+    }
+
+    /// Returns the limits memos and msgs are checked against.
+    pub fn get_memo_limits(&self) -> MemoLimits {
+        self.memo_limits.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deposit_tag_schema(allow_additional: bool) -> MemoSchema {
+        MemoSchema { required: vec![("deposit_tag".to_string(), MemoFieldType::String)], allow_additional }
+    }
+
+    #[test]
+    fn schema_accepts_matching_memos() {
+        let schema = deposit_tag_schema(false);
+        assert!(schema.matches(r#"{"deposit_tag": "123456"}"#));
+    }
+
+    #[test]
+    fn schema_rejects_memos_that_are_not_objects() {
+        let schema = deposit_tag_schema(true);
+        assert!(!schema.matches("123456"));
+        assert!(!schema.matches(r#""123456""#));
+        assert!(!schema.matches(r#"["123456"]"#));
+    }
+
+    #[test]
+    fn schema_checks_the_required_fields() {
+        let schema = deposit_tag_schema(true);
+        assert!(!schema.matches(r#"{}"#));
+        assert!(!schema.matches(r#"{"deposit_tag": 123456}"#));
+        assert!(schema.matches(r#"{"deposit_tag": "123456", "note": "hi"}"#));
+    }
+
+    #[test]
+    fn schema_can_refuse_additional_fields() {
+        let schema = deposit_tag_schema(false);
+        assert!(!schema.matches(r#"{"deposit_tag": "123456", "note": "hi"}"#));
+    }
+}
@@ -1,21 +1,66 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::Base64VecU8;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::near_bindgen;
+use near_sdk::{log, near_bindgen};
 
+use crate::errors::{require_one_yocto, require_or, FtError};
 use crate::*;
 
-#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize, NearSchema)]
+pub use ft_common::metadata::FungibleTokenMetadata;
+
+/// The default limit on the size of the icon, in bytes. Every `ft_metadata` call returns the whole icon, so it's
+/// worth keeping small.
+pub const DEFAULT_MAX_ICON_LEN: u32 = 8 * 1024;
+
+/// The most locales the metadata can be translated into.
+pub const MAX_LOCALIZATIONS: usize = 50;
+/// The longest locale tag allowed. BCP 47 recommends supporting tags of up to 35 characters.
+pub const MAX_LOCALE_LEN: usize = 35;
+
+/// The name and symbol of the token in one locale, set by the owner with `set_localization`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
 #[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
-pub struct FungibleTokenMetadata {
-    pub spec: String, // Should be ft-1.0.0 to indicate that a Fungible Token contract adheres to the current versions of this Metadata and the Fungible Token Core specs. This will allow consumers of the Fungible Token to know if they support the features of a given contract.
-    pub name: String, // The human-readable name of the token.
-    pub symbol: String, // The abbreviation, like wETH or AMPL.
-    pub icon: Option<String>, // Icon of the fungible token.
-    pub reference: Option<String>, // A link to a valid JSON file containing various keys offering supplementary details on the token 
-    pub reference_hash: Option<Base64VecU8>, // The base64-encoded sha256 hash of the JSON file contained in the reference field. This is to guard against off-chain tampering.
-    pub decimals: u8, // used in frontends to show the proper significant digits of a token. This concept is explained well in this OpenZeppelin post. https://docs.openzeppelin.com/contracts/3.x/erc20#a-note-on-decimals
+pub struct LocalizedMetadata {
+    pub name: String,
+    pub symbol: String,
+    /// A short description of the token for wallets to show
+    pub description: Option<String>,
+}
+
+/// The structure returned by `ft_metadata_localized`: the metadata with the name and symbol translated.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LocalizedTokenMetadata {
+    #[serde(flatten)]
+    pub metadata: FungibleTokenMetadata,
+    /// The locale the name and symbol are in, or `None` if there was no translation and they're the defaults
+    pub locale: Option<String>,
+    /// A short description of the token in `locale`, if the owner wrote one
+    pub description: Option<String>,
+}
+
+/// Returns whether `locale` looks like a BCP 47 tag, e.g. `es` or `pt-BR`: letters and digits separated by
+/// hyphens, starting with a letter.
+pub(crate) fn is_valid_locale(locale: &str) -> bool {
+    locale.len() <= MAX_LOCALE_LEN
+        && locale.split('-').all(|part| !part.is_empty() && part.bytes().all(|c| c.is_ascii_alphanumeric()))
+        && locale.bytes().next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false)
+}
+
+/// Returns whether the reference and its hash are set together, with the hash being a sha256 hash (32 bytes).
+pub fn is_valid_reference(reference: Option<&str>, reference_hash: Option<&Base64VecU8>) -> bool {
+    match (reference, reference_hash) {
+        (Some(reference), Some(reference_hash)) => !reference.is_empty() && reference_hash.0.len() == 32,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether `icon` is a data URL (e.g. `data:image/svg+xml,...`) of at most `max_len` bytes. No icon is
+/// always valid.
+pub fn is_valid_icon(icon: Option<&str>, max_len: u32) -> bool {
+    icon.map(|icon| icon.starts_with("data:") && icon.len() <= max_len as usize).unwrap_or(true)
 }
 
 pub trait FungibleTokenMetadataProvider {
@@ -23,9 +68,227 @@ pub trait FungibleTokenMetadataProvider {
     fn ft_metadata(&self) -> FungibleTokenMetadata;
 }
 
+#[cfg(feature = "contract")]
 #[near_bindgen]
 impl FungibleTokenMetadataProvider for Contract {
     fn ft_metadata(&self) -> FungibleTokenMetadata {
         self.metadata.get().unwrap()
     }
 }
+
+/// Everything a wallet needs to render a token page, returned by `ft_info`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FungibleTokenInfo {
+    pub metadata: FungibleTokenMetadata,
+    /// Total supply of the token
+    pub total_supply: U128,
+    /// Supply held outside of the treasury (the owner account)
+    pub circulating_supply: U128,
+    /// Number of accounts registered with the contract, including ones with a zero balance
+    pub holder_count: u64,
+    /// Whether the `account_id` passed in is registered. `None` if no account was passed in
+    pub is_registered: Option<bool>,
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Returns the metadata, supply and holder count in a single view call. Pass in an `account_id` to also
+    /// find out whether it's registered (view calls have no caller to check).
+    pub fn ft_info(&self, account_id: Option<AccountId>) -> FungibleTokenInfo {
+        FungibleTokenInfo {
+            metadata: self.ft_metadata(),
+            total_supply: U128(self.total_supply.as_yoctonear()),
+            circulating_supply: self.ft_circulating_supply(),
+            holder_count: self.accounts.len(),
+            is_registered: account_id.map(|account_id| self.accounts.get(&account_id).is_some()),
+        }
+    }
+
+    /// Replaces the icon in the metadata, e.g. with a compressed version, or removes it if `icon` is `null`. The
+    /// icon must be a data URL of at most `get_max_icon_len` bytes. Only the owner can change it and exactly 1
+    /// yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_icon(&mut self, icon: Option<String>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        require_or(is_valid_icon(icon.as_deref(), self.max_icon_len), FtError::InvalidIcon);
+
+        let mut metadata = self.metadata.get().unwrap();
+        metadata.icon = icon;
+        self.metadata.set(&metadata);
+    }
+
+    /// Changes the largest icon the metadata can have, in bytes. If the current icon is larger than the new
+    /// limit, it's removed so that it can be replaced with a smaller one. Only the owner can change the limit and
+    /// exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_max_icon_len(&mut self, max_icon_len: u32) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.max_icon_len = max_icon_len;
+
+        let mut metadata = self.metadata.get().unwrap();
+        if !is_valid_icon(metadata.icon.as_deref(), max_icon_len) {
+            log!("Removed the icon as it's over the new limit of {} bytes", max_icon_len);
+            metadata.icon = None;
+            self.metadata.set(&metadata);
+        }
+    }
+
+    /// Replaces the reference JSON in the metadata along with its hash, or removes both if they're `null`. They
+    /// can only be set together, and `reference_hash` must be the base64-encoded sha256 hash of the JSON, so the
+    /// two can't drift apart. Only the owner can change them and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_reference(&mut self, reference: Option<String>, reference_hash: Option<Base64VecU8>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        require_or(is_valid_reference(reference.as_deref(), reference_hash.as_ref()), FtError::InvalidReference);
+
+        let mut metadata = self.metadata.get().unwrap();
+        metadata.reference = reference;
+        metadata.reference_hash = reference_hash;
+        self.metadata.set(&metadata);
+    }
+
+    /// Returns whether `bytes` hash to the `reference_hash` in the metadata, so that clients can check the JSON
+    /// they fetched from the reference is the one the owner published. Always false if there's no reference.
+    pub fn verify_reference(&self, bytes: Base64VecU8) -> bool {
+        self.ft_metadata()
+            .reference_hash
+            .map(|reference_hash| reference_hash.0 == env::sha256(&bytes.0))
+            .unwrap_or(false)
+    }
+
+    /// Returns the largest icon the metadata can have, in bytes.
+    pub fn get_max_icon_len(&self) -> u32 {
+        self.max_icon_len
+    }
+
+    /// Adds or replaces the name and symbol shown to users in `locale` (a BCP 47 tag such as `es` or `pt-BR`),
+    /// or removes the translation if `localization` is `null`. Only the owner can change translations and
+    /// exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_localization(&mut self, locale: String, localization: Option<LocalizedMetadata>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        require_or(is_valid_locale(&locale), FtError::InvalidLocale);
+
+        match localization {
+            Some(localization) => {
+                self.localizations.insert(&locale, &localization);
+                require_or(self.localizations.len() as usize <= MAX_LOCALIZATIONS, FtError::TooManyLocalizations);
+            }
+            None => {
+                self.localizations.remove(&locale);
+            }
+        }
+    }
+
+    /// Returns the metadata with the name and symbol in `locale`. If there's no translation for the exact
+    /// locale, the one for its language is used (e.g. `pt` for `pt-BR`), and otherwise the default metadata.
+    pub fn ft_metadata_localized(&self, locale: String) -> LocalizedTokenMetadata {
+        let mut metadata = self.ft_metadata();
+        let language = locale.split('-').next().unwrap_or_default().to_string();
+        let translation = [locale, language]
+            .into_iter()
+            .find_map(|locale| self.localizations.get(&locale).map(|localization| (locale, localization)));
+
+        let Some((locale, localization)) = translation else {
+            return LocalizedTokenMetadata { metadata, locale: None, description: None };
+        };
+        metadata.name = localization.name;
+        metadata.symbol = localization.symbol;
+        LocalizedTokenMetadata { metadata, locale: Some(locale), description: localization.description }
+    }
+
+    /// Returns the locales the metadata has been translated into.
+    pub fn get_localized_locales(&self) -> Vec<String> {
+        self.localizations.keys().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    #[test]
+    fn icons_must_be_small_data_urls() {
+        assert!(is_valid_icon(None, 0));
+        assert!(is_valid_icon(Some("data:image/svg+xml,<svg/>"), DEFAULT_MAX_ICON_LEN));
+        assert!(!is_valid_icon(Some("https://example.com/icon.png"), DEFAULT_MAX_ICON_LEN));
+        assert!(!is_valid_icon(Some("data:image/svg+xml,<svg/>"), 8));
+    }
+
+    #[test]
+    fn references_are_verified_against_their_hash() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        let json = br#"{"website": "https://near.org"}"#.to_vec();
+        Context::new().one_yocto().set();
+        contract.set_reference(Some("https://example.com/ft.json".to_string()), Some(env::sha256(&json).into()));
+
+        assert!(contract.verify_reference(json.into()));
+        assert!(!contract.verify_reference(b"{}".to_vec().into()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_REFERENCE")]
+    fn references_need_their_hash() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        Context::new().one_yocto().set();
+        contract.set_reference(Some("https://example.com/ft.json".to_string()), None);
+    }
+
+    #[test]
+    fn lowering_the_limit_clears_oversized_icons() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        Context::new().one_yocto().set();
+        contract.set_icon(Some("data:image/svg+xml,<svg/>".to_string()));
+        assert_eq!(contract.ft_metadata().icon.as_deref(), Some("data:image/svg+xml,<svg/>"));
+
+        contract.set_max_icon_len(8);
+        assert!(contract.ft_metadata().icon.is_none());
+    }
+
+    #[test]
+    fn localized_metadata_falls_back_to_the_language_then_the_default() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        Context::new().one_yocto().set();
+        let localization = LocalizedMetadata {
+            name: "Ficha del Equipo".to_string(),
+            symbol: "gtNEAR".to_string(),
+            description: None,
+        };
+        contract.set_localization("es".to_string(), Some(localization));
+
+        let localized = contract.ft_metadata_localized("es-MX".to_string());
+        assert_eq!(localized.locale.as_deref(), Some("es"));
+        assert_eq!(localized.metadata.name, "Ficha del Equipo");
+
+        let localized = contract.ft_metadata_localized("fr".to_string());
+        assert!(localized.locale.is_none());
+        assert_eq!(localized.metadata.name, "Team Token FT Tutorial");
+    }
+
+    #[test]
+    fn locales_must_be_tags() {
+        assert!(is_valid_locale("es"));
+        assert!(is_valid_locale("pt-BR"));
+        assert!(is_valid_locale("zh-Hant-TW"));
+        assert!(!is_valid_locale(""));
+        assert!(!is_valid_locale("pt--BR"));
+        assert!(!is_valid_locale("1-es"));
+        assert!(!is_valid_locale("es_MX"));
+    }
+}
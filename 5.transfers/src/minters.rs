@@ -0,0 +1,145 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The longest reference a minter can attach to a mint. References are kept forever, so this bounds their storage.
+pub const MAX_MINT_REFERENCE_LEN: usize = 64;
+
+/// A mint made with `ft_mint_with_reference`, as returned by `get_mint_by_reference`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReferencedMint {
+    /// The minter that made the mint
+    pub minter_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: NearToken,
+    /// When the mint was made (in nanoseconds)
+    pub minted_at: U64,
+}
+
+impl Contract {
+    /// Internal method for asserting that the predecessor is a minter, and returning it.
+    pub(crate) fn assert_minter(&self) -> AccountId {
+        let minter_id = env::predecessor_account_id();
+        require_or(self.minters.contains(&minter_id), FtError::NotMinter);
+        minter_id
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Stops `minter_id` from calling `ft_mint_with_reference`. Only the owner can remove minters and exactly 1
+    /// yoctoNEAR must be attached.
+    #[payable]
+    pub fn remove_minter(&mut self, minter_id: AccountId) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.minters.remove(&minter_id);
+    }
+
+    /// Returns whether `account_id` is allowed to call `ft_mint_with_reference`. Minters can mint up to the mint
+    /// threshold per window, so they're added by proposing `AdminOp::AddMinter` with `propose_admin_op`.
+    pub fn is_minter(&self, account_id: AccountId) -> bool {
+        self.minters.contains(&account_id)
+    }
+
+    /// Mints `amount` tokens into a registered account for an off-chain deposit, e.g. a bank transfer received by
+    /// a custodial issuer. `reference_id` is the issuer's ID for the deposit and can only be used once, so
+    /// retrying a mint that already went through panics instead of minting twice. Only a minter can mint.
+    ///
+    /// The mint counts towards the mint threshold of the current window, together with the owner's batches and
+    /// the faucet.
+    pub fn ft_mint_with_reference(&mut self, receiver_id: AccountId, amount: NearToken, reference_id: String) {
+        let minter_id = self.assert_minter();
+        require_or(
+            !reference_id.is_empty() && reference_id.len() <= MAX_MINT_REFERENCE_LEN,
+            FtError::InvalidMintReference,
+        );
+        require_or(!self.mint_references.contains_key(&reference_id), FtError::MintReferenceUsed);
+        self.internal_record_untimelocked_mint(amount);
+
+        self.internal_mint(&receiver_id, amount);
+        self.mint_references.insert(
+            &reference_id,
+            &ReferencedMint {
+                minter_id,
+                receiver_id: receiver_id.clone(),
+                amount,
+                minted_at: U64(env::block_timestamp()),
+            },
+        );
+
+        // Emit an event showing that the FTs were minted. The memo carries the reference for indexers
+        FtMint {
+            owner_id: &receiver_id,
+            amount: &amount,
+            memo: Some(&reference_id),
+        }
+        .emit();
+    }
+
+    /// Returns the mint made with `reference_id`, or `null` if the reference hasn't been used.
+    pub fn get_mint_by_reference(&self, reference_id: String) -> Option<ReferencedMint> {
+        self.mint_references.get(&reference_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+    use crate::admin_timelock::{AdminOp, ADMIN_TIMELOCK_NS};
+
+    fn minter() -> AccountId {
+        "minter.near".parse().unwrap()
+    }
+
+    /// Creates the contract with `minter()` as a minter and alice registered
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(AdminOp::AddMinter { minter_id: minter() });
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+        contract
+    }
+
+    #[test]
+    fn mints_are_indexed_by_reference() {
+        let mut contract = setup();
+        Context::new().predecessor(minter()).set();
+        contract.ft_mint_with_reference(alice(), NearToken::from_yoctonear(100), "wire-42".to_string());
+
+        assert_eq!(contract.internal_balance_of(&alice()), NearToken::from_yoctonear(100));
+        let mint = contract.get_mint_by_reference("wire-42".to_string()).unwrap();
+        assert_eq!((mint.minter_id, mint.receiver_id), (minter(), alice()));
+        assert!(contract.get_mint_by_reference("wire-43".to_string()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MINT_REFERENCE_USED")]
+    fn references_can_only_be_used_once() {
+        let mut contract = setup();
+        Context::new().predecessor(minter()).set();
+        contract.ft_mint_with_reference(alice(), NearToken::from_yoctonear(100), "wire-42".to_string());
+        contract.ft_mint_with_reference(alice(), NearToken::from_yoctonear(100), "wire-42".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_MINTER")]
+    fn only_minters_can_mint() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).set();
+        contract.ft_mint_with_reference(alice(), NearToken::from_yoctonear(100), "wire-42".to_string());
+    }
+}
@@ -0,0 +1,41 @@
+use near_sdk::{assert_one_yocto, log, require};
+
+use crate::*;
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Changes the total supply to `new_total_supply` and rescales every balance proportionally.
+    /// Balances are stored as shares, so this only updates the supply - no account is touched.
+    /// Only the owner can rebase the token and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn rebase(&mut self, new_total_supply: U128) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        // Only the owner can change the supply
+        self.assert_owner();
+
+        // A supply of 0 would make every account's shares worthless forever
+        require!(new_total_supply.0 > 0, "The new total supply should be a positive number");
+        require!(self.total_shares > 0, "There are no shares to rebase");
+
+        let old_total_supply = self.total_supply;
+        self.total_supply = NearToken::from_yoctonear(new_total_supply.0);
+
+        log!(
+            "Rebased the total supply from {} to {}",
+            old_total_supply.as_yoctonear(),
+            new_total_supply.0
+        );
+    }
+
+    /// Returns the number of shares held by the given account. If the account doesn't exist, returns `"0"`.
+    pub fn shares_of(&self, account_id: AccountId) -> U128 {
+        U128(self.accounts.get(&account_id).unwrap_or(0))
+    }
+
+    /// Returns the total number of shares across all accounts.
+    pub fn total_shares(&self) -> U128 {
+        U128(self.total_shares)
+    }
+}
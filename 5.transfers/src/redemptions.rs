@@ -0,0 +1,186 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::log;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_one_yocto, FtError};
+use crate::*;
+
+/// Memo of the `ft_burn` event logged when tokens are burnt to request a redemption.
+pub const REDEMPTION_MEMO: &str = "redemption requested";
+/// Memo of the `ft_mint` event logged when a rejected redemption is minted back.
+pub const REDEMPTION_REJECTED_MEMO: &str = "redemption rejected";
+
+/// A redemption waiting on an issuer, as returned by `get_redemption` and `get_pending_redemptions`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Redemption {
+    pub redemption_id: U64,
+    /// The account whose tokens were burnt, and that gets them back if the redemption is rejected
+    pub account_id: AccountId,
+    pub amount: NearToken,
+    /// Hash of the off-chain payout details (e.g. bank account), which the issuer gets from the account directly
+    pub payout_details_hash: Base58CryptoHash,
+    /// When the redemption was requested (in nanoseconds)
+    pub requested_at: U64,
+}
+
+impl Contract {
+    /// Internal method for taking a pending redemption out of the queue, once an issuer has dealt with it.
+    pub(crate) fn internal_take_redemption(&mut self, redemption_id: U64) -> Redemption {
+        let redemption = self
+            .redemptions
+            .remove(&redemption_id.0)
+            .unwrap_or_else(|| FtError::RedemptionNotFound.panic());
+        // The account can unregister again once none of its redemptions could still be minted back
+        self.internal_finish_in_flight(&redemption.account_id);
+        redemption
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Burns `amount` of the caller's tokens and queues a request for the issuer to pay them out off-chain, to
+    /// the details whose hash is `payout_details_hash`. A minter then either fulfils the redemption or rejects
+    /// it, which mints the tokens back. The caller can't unregister while a redemption is pending. Exactly 1
+    /// yoctoNEAR must be attached.
+    ///
+    /// Returns the ID of the redemption.
+    #[payable]
+    pub fn request_redemption(&mut self, amount: NearToken, payout_details_hash: Base58CryptoHash) -> U64 {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.internal_burn(&account_id, amount);
+
+        // Emit an event showing that the FTs were burnt
+        FtBurn {
+            owner_id: &account_id,
+            amount: &amount,
+            memo: Some(REDEMPTION_MEMO),
+        }
+        .emit();
+
+        let redemption_id = self.next_redemption_id;
+        self.next_redemption_id += 1;
+        self.redemptions.insert(
+            &redemption_id,
+            &Redemption {
+                redemption_id: U64(redemption_id),
+                account_id: account_id.clone(),
+                amount,
+                payout_details_hash,
+                requested_at: U64(env::block_timestamp()),
+            },
+        );
+        // A rejection mints the tokens back, so the account has to stay registered until then
+        self.internal_start_in_flight(&account_id);
+
+        U64(redemption_id)
+    }
+
+    /// Marks a pending redemption as paid out and takes it out of the queue. The tokens stay burnt. Only a
+    /// minter can fulfil redemptions.
+    pub fn fulfill_redemption(&mut self, redemption_id: U64) {
+        self.assert_minter();
+        let redemption = self.internal_take_redemption(redemption_id);
+        log!("Fulfilled redemption {} of {} for {}", redemption_id.0, redemption.amount, redemption.account_id);
+    }
+
+    /// Rejects a pending redemption, e.g. because the payout details were invalid, and mints the tokens back
+    /// to the account that requested it. Only a minter can reject redemptions.
+    pub fn reject_redemption(&mut self, redemption_id: U64) {
+        self.assert_minter();
+        let redemption = self.internal_take_redemption(redemption_id);
+        self.internal_mint(&redemption.account_id, redemption.amount);
+
+        // Emit an event showing that the FTs were minted back
+        FtMint {
+            owner_id: &redemption.account_id,
+            amount: &redemption.amount,
+            memo: Some(REDEMPTION_REJECTED_MEMO),
+        }
+        .emit();
+    }
+
+    /// Returns the pending redemption with the given ID, or `null` if there's none.
+    pub fn get_redemption(&self, redemption_id: U64) -> Option<Redemption> {
+        self.redemptions.get(&redemption_id.0)
+    }
+
+    /// Returns up to `limit` pending redemptions.
+    pub fn get_pending_redemptions(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Redemption> {
+        // Where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        // Iterate through the queue, skipping to the start and taking the first "limit" redemptions (50 by default)
+        self.redemptions
+            .values()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+    use crate::admin_timelock::{AdminOp, ADMIN_TIMELOCK_NS};
+
+    fn minter() -> AccountId {
+        "minter.near".parse().unwrap()
+    }
+
+    /// Creates the contract with `minter()` as a minter, then has alice request a redemption of 40 of her
+    /// 100 tokens
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(AdminOp::AddMinter { minter_id: minter() });
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.request_redemption(tokens(40), [7; 32].into());
+        contract
+    }
+
+    #[test]
+    fn fulfilled_redemptions_stay_burnt() {
+        let mut contract = setup();
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(60));
+        assert_eq!(contract.get_pending_redemptions(None, None).len(), 1);
+        assert_eq!(contract.ft_pending_transfers(alice()), 1);
+
+        Context::new().predecessor(minter()).set();
+        contract.fulfill_redemption(U64(0));
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(60));
+        assert!(contract.get_redemption(U64(0)).is_none());
+        assert_eq!(contract.ft_pending_transfers(alice()), 0);
+    }
+
+    #[test]
+    fn rejected_redemptions_are_minted_back() {
+        let mut contract = setup();
+        Context::new().predecessor(minter()).set();
+        contract.reject_redemption(U64(0));
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(100));
+        assert!(contract.get_pending_redemptions(None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_MINTER")]
+    fn only_minters_can_resolve_redemptions() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).set();
+        contract.reject_redemption(U64(0));
+    }
+}
@@ -0,0 +1,291 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{CurveType, PublicKey};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The key an account signs relayed transfers with, and the nonce its next relayed transfer has to use.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelaySigner {
+    /// The ed25519 key relayed transfers have to be signed with, or `None` if the account removed it
+    pub public_key: Option<PublicKey>,
+    /// The nonce the next relayed transfer has to use. It's kept when the key is replaced or removed, so that
+    /// transfers signed with an old key can't be replayed
+    pub next_nonce: U64,
+}
+
+/// A transfer an account signed off-chain for a relayer to submit with `ft_transfer_on_behalf`. The account
+/// signs the sha256 hash of the borsh-serialized contract ID and payload, which `get_relayed_transfer_hash`
+/// returns.
+#[derive(BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelayedTransferPayload {
+    /// The account the tokens are taken from. It must have registered the key the payload is signed with
+    pub signer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: NearToken,
+    pub memo: Option<String>,
+    /// Has to be the signer's next nonce, so that each payload can only be used once and in order
+    pub nonce: U64,
+    /// When the payload can no longer be used (in nanoseconds since the Unix epoch)
+    pub deadline_timestamp: U64,
+}
+
+/// Returns the hash an account signs for a relayed transfer. The contract ID is part of it, so that a payload
+/// can't be replayed on another token.
+fn relayed_transfer_hash(payload: &RelayedTransferPayload) -> Vec<u8> {
+    env::sha256(&near_sdk::borsh::to_vec(&(env::current_account_id(), payload)).unwrap())
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Allows `relayer_id` to call `ft_transfer_on_behalf`. Only the owner can add relayers and exactly 1
+    /// yoctoNEAR must be attached.
+    #[payable]
+    pub fn add_relayer(&mut self, relayer_id: AccountId) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.relayers.insert(&relayer_id);
+    }
+
+    /// Stops `relayer_id` from calling `ft_transfer_on_behalf`. Only the owner can remove relayers and exactly 1
+    /// yoctoNEAR must be attached.
+    #[payable]
+    pub fn remove_relayer(&mut self, relayer_id: AccountId) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.relayers.remove(&relayer_id);
+    }
+
+    /// Returns whether `account_id` is allowed to call `ft_transfer_on_behalf`.
+    pub fn is_relayer(&self, account_id: AccountId) -> bool {
+        self.relayers.contains(&account_id)
+    }
+
+    /// Registers the ed25519 key the caller signs relayed transfers with, or removes it if `public_key` is
+    /// `None`. Replacing or removing the key keeps the caller's nonce.
+    ///
+    /// The caller must be registered, and must attach at least 1 yoctoNEAR plus the storage cost of the key.
+    /// What isn't needed is refunded.
+    #[payable]
+    pub fn set_relay_key(&mut self, public_key: Option<PublicKey>) {
+        // Assert that the user attached at least 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_or(env::attached_deposit() >= NearToken::from_yoctonear(1), FtError::OneYoctoRequired);
+        let account_id = env::predecessor_account_id();
+        require_or(self.accounts.contains_key(&account_id), FtError::AccountNotRegistered(account_id.clone()));
+        if let Some(public_key) = &public_key {
+            require_or(public_key.curve_type() == CurveType::ED25519, FtError::InvalidRelayKey);
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let next_nonce = self.relay_signers.get(&account_id).map(|signer| signer.next_nonce).unwrap_or(U64(0));
+        self.relay_signers.insert(&account_id, &RelaySigner { public_key, next_nonce });
+        self.internal_settle_storage(&account_id, initial_storage_usage);
+    }
+
+    /// Returns the relay key and next nonce of `account_id`, or `null` if it never registered a key.
+    pub fn get_relay_signer(&self, account_id: AccountId) -> Option<RelaySigner> {
+        self.relay_signers.get(&account_id)
+    }
+
+    /// Returns the hash `payload.signer_id` has to sign for `ft_transfer_on_behalf`.
+    pub fn get_relayed_transfer_hash(&self, payload: RelayedTransferPayload) -> Base64VecU8 {
+        Base64VecU8(relayed_transfer_hash(&payload))
+    }
+
+    /// Transfers tokens for an account that signed the transfer off-chain, e.g. in a gasless onboarding flow where
+    /// a relayer pays for the user's transactions.
+    ///
+    /// A relayer can only ever make the transfers the account signed:
+    /// - the caller must be an approved relayer, and exactly 1 yoctoNEAR must be attached
+    /// - `signature` must be the ed25519 signature of `get_relayed_transfer_hash(payload)` by the key
+    ///   `payload.signer_id` registered with `set_relay_key`
+    /// - `payload.nonce` must be the signer's next nonce, so that a payload can't be used twice
+    /// - the block must not be past `payload.deadline_timestamp`
+    ///
+    /// Besides the usual `ft_transfer` event, a `relayed_transfer` event names the relayer.
+    #[payable]
+    pub fn ft_transfer_on_behalf(&mut self, payload: RelayedTransferPayload, signature: Base64VecU8) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let relayer_id = env::predecessor_account_id();
+        require_or(self.relayers.contains(&relayer_id), FtError::NotRelayer);
+        self.assert_before_deadline(payload.deadline_timestamp);
+
+        // The payload has to be signed by the account the tokens are taken from, with the nonce it expects next
+        let mut signer = self.relay_signers.get(&payload.signer_id).unwrap_or_else(|| FtError::NoRelayKey.panic());
+        let Some(public_key) = &signer.public_key else {
+            FtError::NoRelayKey.panic()
+        };
+        require_or(payload.nonce == signer.next_nonce, FtError::InvalidRelayNonce);
+        let signature: Result<[u8; 64], _> = signature.0.try_into();
+        let public_key: [u8; 32] = public_key.as_bytes()[1..].try_into().unwrap();
+        require_or(
+            signature.is_ok_and(|signature| {
+                env::ed25519_verify(&signature, &relayed_transfer_hash(&payload), &public_key)
+            }),
+            FtError::InvalidRelaySignature,
+        );
+        signer.next_nonce = U64(signer.next_nonce.0 + 1);
+        self.relay_signers.insert(&payload.signer_id, &signer);
+
+        // Reject oversized or malformed payloads before any tokens move
+        self.assert_valid_memo(payload.memo.as_deref());
+
+        let RelayedTransferPayload { signer_id, receiver_id, amount, memo, .. } = payload;
+        self.internal_transfer(&signer_id, &receiver_id, amount, memo);
+
+        // Mark the transfer as relayed
+        RelayedTransfer {
+            relayer_id: &relayer_id,
+            signer_id: &signer_id,
+            receiver_id: &receiver_id,
+            amount: &amount,
+        }
+        .emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+    use ft_testing::*;
+    use near_sdk::test_utils::get_logs;
+
+    use super::*;
+
+    fn relayer() -> AccountId {
+        "relayer.near".parse().unwrap()
+    }
+
+    fn alice_key() -> SigningKey {
+        SigningKey::from_bytes(&[1; 32])
+    }
+
+    fn public_key(key: &SigningKey) -> PublicKey {
+        PublicKey::from_parts(CurveType::ED25519, key.verifying_key().to_bytes().to_vec()).unwrap()
+    }
+
+    /// A payload for alice sending `amount` to bob with the given nonce, valid until timestamp 100
+    fn payload(amount: u128, nonce: u64) -> RelayedTransferPayload {
+        RelayedTransferPayload {
+            signer_id: alice(),
+            receiver_id: bob(),
+            amount: tokens(amount),
+            memo: None,
+            nonce: U64(nonce),
+            deadline_timestamp: U64(100),
+        }
+    }
+
+    fn sign(payload: &RelayedTransferPayload, key: &SigningKey) -> Base64VecU8 {
+        Base64VecU8(key.sign(&relayed_transfer_hash(payload)).to_bytes().to_vec())
+    }
+
+    /// Creates the contract with `relayer()` approved, and alice and bob registered with alice holding 100 tokens
+    /// and `alice_key()` registered as her relay key
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+
+        Context::new().one_yocto().set();
+        contract.add_relayer(relayer());
+
+        Context::new().predecessor(alice()).attached_deposit(NearToken::from_millinear(10)).set();
+        contract.set_relay_key(Some(public_key(&alice_key())));
+        contract
+    }
+
+    /// Sets the context to `relayer()` calling with 1 yoctoNEAR
+    fn as_relayer() {
+        Context::new().predecessor(relayer()).one_yocto().set();
+    }
+
+    #[test]
+    fn relayers_make_signed_transfers() {
+        let mut contract = setup();
+        as_relayer();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(60));
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(40));
+        assert_eq!(contract.get_relay_signer(alice()).unwrap().next_nonce, U64(1));
+        assert!(get_logs().iter().any(|log| log.contains(r#""standard":"relay","version":"1.0.0","event":"relayed_transfer""#)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEADLINE_EXPIRED")]
+    fn relayed_transfers_expire() {
+        let mut contract = setup();
+        Context::new().predecessor(relayer()).one_yocto().block_timestamp(101).set();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_RELAY_SIGNATURE")]
+    fn relayers_cannot_change_what_was_signed() {
+        let mut contract = setup();
+        as_relayer();
+        // Alice signed for 40 tokens, but the relayer asks for all of them
+        contract.ft_transfer_on_behalf(payload(100, 0), sign(&payload(40, 0), &alice_key()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_RELAY_SIGNATURE")]
+    fn transfers_signed_with_another_key_are_refused() {
+        let mut contract = setup();
+        as_relayer();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &SigningKey::from_bytes(&[2; 32])));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_RELAY_NONCE")]
+    fn signed_transfers_cannot_be_replayed() {
+        let mut contract = setup();
+        as_relayer();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_RELAY_KEY")]
+    fn removing_the_key_stops_relayed_transfers() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.set_relay_key(None);
+        assert_eq!(contract.get_relay_signer(alice()).unwrap().public_key, None);
+
+        as_relayer();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_RELAYER")]
+    fn only_approved_relayers_can_transfer() {
+        let mut contract = setup();
+        Context::new().predecessor(charlie()).one_yocto().set();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_RELAYER")]
+    fn removed_relayers_can_no_longer_transfer() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.remove_relayer(relayer());
+
+        as_relayer();
+        contract.ft_transfer_on_behalf(payload(40, 0), sign(&payload(40, 0), &alice_key()));
+    }
+}
@@ -0,0 +1,102 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::IntoStorageKey;
+
+/// A fixed-capacity log kept in contract storage. Once it's full, each push overwrites the oldest item, so
+/// the storage it uses never grows past `capacity` items. Every item gets a sequence number that keeps
+/// counting up across overwrites, so clients can tell which items they've already seen.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct RingBuffer<T> {
+    items: LookupMap<u64, T>,
+    capacity: u64,
+    /// How many items were ever pushed. Also the sequence number of the next item
+    pushed: u64,
+}
+
+impl<T: BorshSerialize + BorshDeserialize> RingBuffer<T> {
+    pub fn new<S: IntoStorageKey>(prefix: S, capacity: u64) -> Self {
+        assert!(capacity > 0, "The capacity should be a positive number");
+        Self { items: LookupMap::new(prefix), capacity, pushed: 0 }
+    }
+
+    /// Adds an item, overwriting the oldest one if the buffer is full. Returns the item's sequence number.
+    pub fn push(&mut self, item: &T) -> u64 {
+        let sequence = self.pushed;
+        self.items.insert(&(sequence % self.capacity), item);
+        self.pushed += 1;
+        sequence
+    }
+
+    /// How many items the buffer currently holds.
+    pub fn len(&self) -> u64 {
+        self.pushed.min(self.capacity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pushed == 0
+    }
+
+    /// The sequence number of the oldest item still held.
+    pub fn first_sequence(&self) -> u64 {
+        self.pushed - self.len()
+    }
+
+    /// Returns the item with the given sequence number, if it hasn't been overwritten yet.
+    pub fn get(&self, sequence: u64) -> Option<T> {
+        if sequence < self.first_sequence() || sequence >= self.pushed {
+            return None;
+        }
+        self.items.get(&(sequence % self.capacity))
+    }
+
+    /// Iterates over the held items from oldest to newest, along with their sequence numbers.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, T)> + '_ {
+        (self.first_sequence()..self.pushed).filter_map(move |sequence| self.get(sequence).map(|item| (sequence, item)))
+    }
+
+    /// Iterates over the held items from newest to oldest, along with their sequence numbers.
+    pub fn iter_rev(&self) -> impl Iterator<Item = (u64, T)> + '_ {
+        (self.first_sequence()..self.pushed)
+            .rev()
+            .filter_map(move |sequence| self.get(sequence).map(|item| (sequence, item)))
+    }
+
+    /// Removes every item from storage. The buffer can't be used afterwards.
+    pub fn clear(mut self) {
+        for sequence in self.first_sequence()..self.pushed {
+            self.items.remove(&(sequence % self.capacity));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    #[test]
+    fn overwrites_the_oldest_items_once_full() {
+        Context::new().set();
+        let mut buffer = RingBuffer::new(b"r".to_vec(), 3);
+        for item in 0..5u32 {
+            assert_eq!(buffer.push(&item), item as u64);
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.first_sequence(), 2);
+        assert_eq!(buffer.get(1), None);
+        assert_eq!(buffer.iter().collect::<Vec<_>>(), vec![(2, 2), (3, 3), (4, 4)]);
+        assert_eq!(buffer.iter_rev().map(|(_, item)| item).collect::<Vec<_>>(), vec![4, 3, 2]);
+    }
+
+    #[test]
+    fn starts_out_empty() {
+        Context::new().set();
+        let buffer: RingBuffer<u32> = RingBuffer::new(b"r".to_vec(), 3);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.iter().count(), 0);
+        assert_eq!(buffer.get(0), None);
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::batch::{assert_batch_size, MAX_BATCH_SIZE};
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// What the shares of a split preset have to add up to: 100% in basis points.
+pub const SPLIT_TOTAL_BPS: u16 = 10_000;
+/// The most split presets an account can keep.
+pub const MAX_SPLIT_PRESETS: usize = 10;
+/// The longest name a split preset can have.
+pub const MAX_SPLIT_NAME_LEN: usize = 32;
+
+/// A named split an account saved with `set_split`, so that recurring distributions only need the name.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SplitPreset {
+    pub name: String,
+    /// Each receiver's share of the amount, in basis points. The shares add up to `SPLIT_TOTAL_BPS`
+    pub shares: Vec<(AccountId, u16)>,
+}
+
+impl SplitPreset {
+    /// Splits `amount` by the preset's shares. Rounding leftovers go to the first receiver, so the amounts
+    /// always add up to `amount`.
+    pub fn amounts(&self, amount: NearToken) -> Vec<(AccountId, NearToken)> {
+        let total = amount.as_yoctonear();
+        // Split the amount in two so that multiplying by the share can't overflow
+        let (whole, rest) = (total / SPLIT_TOTAL_BPS as u128, total % SPLIT_TOTAL_BPS as u128);
+        let mut amounts: Vec<(AccountId, u128)> = self
+            .shares
+            .iter()
+            .map(|(account_id, bps)| {
+                let bps = *bps as u128;
+                (account_id.clone(), whole * bps + rest * bps / SPLIT_TOTAL_BPS as u128)
+            })
+            .collect();
+        let sent: u128 = amounts.iter().map(|(_, amount)| amount).sum();
+        amounts[0].1 += total - sent;
+
+        amounts
+            .into_iter()
+            .map(|(account_id, amount)| (account_id, NearToken::from_yoctonear(amount)))
+            .collect()
+    }
+}
+
+/// Returns whether the preset's name is valid and its shares are positive, go to distinct receivers and add up
+/// to `SPLIT_TOTAL_BPS`.
+pub(crate) fn is_valid_split_preset(preset: &SplitPreset) -> bool {
+    let mut receivers = HashSet::new();
+    (1..=MAX_SPLIT_NAME_LEN).contains(&preset.name.len())
+        && (1..=MAX_BATCH_SIZE).contains(&preset.shares.len())
+        && preset.shares.iter().all(|(account_id, bps)| *bps > 0 && receivers.insert(account_id))
+        && preset.shares.iter().map(|(_, bps)| *bps as u32).sum::<u32>() == SPLIT_TOTAL_BPS as u32
+}
+
+/// Returns the sum of the split's amounts, panicking if it overflows.
+pub(crate) fn split_total(splits: &[(AccountId, NearToken)]) -> NearToken {
+    splits.iter().fold(ZERO_TOKEN, |total, (_, amount)| {
+        total.checked_add(*amount).unwrap_or_else(|| FtError::BalanceOverflow.panic())
+    })
+}
+
+impl Contract {
+    /// Internal method for paying several receivers out of the sender's balance at once. The whole split is
+    /// checked before anything moves: every receiver appears once and the sender holds the total. A single
+    /// `ft_transfer` event lists every payment. Returns the total that was sent.
+    pub(crate) fn internal_transfer_split(
+        &mut self,
+        sender_id: &AccountId,
+        splits: &[(AccountId, NearToken)],
+        memo: Option<&str>,
+    ) -> NearToken {
+        assert_batch_size(splits);
+        let mut receivers = HashSet::new();
+        require_or(
+            splits.iter().all(|(receiver_id, _)| receivers.insert(receiver_id)),
+            FtError::DuplicateSplitReceiver,
+        );
+        let total = split_total(splits);
+        require_or(self.internal_balance_of(sender_id) >= total, FtError::InsufficientBalance);
+
+        for (receiver_id, amount) in splits {
+            self.internal_transfer_without_event(sender_id, receiver_id, *amount);
+            self.internal_record_deposit(sender_id, receiver_id, *amount, memo);
+        }
+
+        // Emit one event covering the whole split
+        let events: Vec<FtTransfer> = splits
+            .iter()
+            .map(|(new_owner_id, amount)| FtTransfer {
+                old_owner_id: sender_id,
+                new_owner_id,
+                amount,
+                memo,
+            })
+            .collect();
+        FtTransfer::emit_many(&events);
+        total
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Pays several receivers different amounts out of the caller's balance in one go, e.g. for payroll or
+    /// revenue sharing. Either every payment goes through or none does: each receiver can only appear once and
+    /// the caller must hold the whole total up front. Every receiver must be registered and exactly 1
+    /// yoctoNEAR must be attached.
+    ///
+    /// Returns the total that was sent.
+    #[payable]
+    pub fn ft_transfer_split(&mut self, splits: Vec<(AccountId, NearToken)>, memo: Option<String>) -> NearToken {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_valid_memo(memo.as_deref());
+
+        // The sender is the user who called the method
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer_split(&sender_id, &splits, memo.as_deref())
+    }
+
+    /// Saves a split preset named `name` for the caller, replacing any preset with the same name. `shares` are in
+    /// basis points and must add up to 10000 (100%), e.g. `[["a.near", 5000], ["b.near", 3000], ["c.near", 2000]]`.
+    /// The caller must be registered, and must attach at least 1 yoctoNEAR plus the storage cost of the preset.
+    /// What isn't needed is refunded.
+    #[payable]
+    pub fn set_split(&mut self, name: String, shares: Vec<(AccountId, u16)>) {
+        // Assert that the user attached at least 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_or(env::attached_deposit() >= NearToken::from_yoctonear(1), FtError::OneYoctoRequired);
+        let account_id = env::predecessor_account_id();
+        require_or(self.accounts.contains_key(&account_id), FtError::AccountNotRegistered(account_id.clone()));
+        let preset = SplitPreset { name, shares };
+        require_or(is_valid_split_preset(&preset), FtError::InvalidSplitPreset);
+
+        let initial_storage_usage = env::storage_usage();
+        let mut presets = self.split_presets.get(&account_id).unwrap_or_default();
+        presets.retain(|existing| existing.name != preset.name);
+        presets.push(preset);
+        require_or(presets.len() <= MAX_SPLIT_PRESETS, FtError::TooManySplitPresets);
+        self.split_presets.insert(&account_id, &presets);
+        self.internal_settle_storage(&account_id, initial_storage_usage);
+    }
+
+    /// Removes the caller's split preset named `name`. At least 1 yoctoNEAR must be attached, and the storage
+    /// freed is refunded.
+    #[payable]
+    pub fn remove_split(&mut self, name: String) {
+        // Assert that the user attached at least 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_or(env::attached_deposit() >= NearToken::from_yoctonear(1), FtError::OneYoctoRequired);
+        let account_id = env::predecessor_account_id();
+
+        let initial_storage_usage = env::storage_usage();
+        let mut presets = self.split_presets.get(&account_id).unwrap_or_default();
+        presets.retain(|existing| existing.name != name);
+        if presets.is_empty() {
+            self.split_presets.remove(&account_id);
+        } else {
+            self.split_presets.insert(&account_id, &presets);
+        }
+        self.internal_settle_storage(&account_id, initial_storage_usage);
+    }
+
+    /// Splits `amount` of the caller's tokens by its preset named `preset`, exactly like `ft_transfer_split`.
+    /// Exactly 1 yoctoNEAR must be attached.
+    ///
+    /// Returns the total that was sent, which is always `amount`.
+    #[payable]
+    pub fn ft_transfer_split_preset(&mut self, preset: String, amount: NearToken, memo: Option<String>) -> NearToken {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_valid_memo(memo.as_deref());
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+
+        // The sender is the user who called the method
+        let sender_id = env::predecessor_account_id();
+        let splits: Vec<(AccountId, NearToken)> = self
+            .internal_split_preset(&sender_id, &preset)
+            .unwrap_or_else(|| FtError::SplitPresetNotFound.panic())
+            .amounts(amount)
+            .into_iter()
+            // Receivers whose share rounds down to nothing are left out
+            .filter(|(_, amount)| amount.gt(&ZERO_TOKEN))
+            .collect();
+        self.internal_transfer_split(&sender_id, &splits, memo.as_deref())
+    }
+
+    /// Returns the split presets `account_id` saved.
+    pub fn get_splits(&self, account_id: AccountId) -> Vec<SplitPreset> {
+        self.split_presets.get(&account_id).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    /// Internal method for getting an account's split preset by name.
+    pub(crate) fn internal_split_preset(&self, account_id: &AccountId, name: &str) -> Option<SplitPreset> {
+        self.split_presets.get(account_id)?.into_iter().find(|preset| preset.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    /// Creates the contract with alice holding 100 tokens and bob and charlie registered
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        for account_id in [alice(), bob(), charlie()] {
+            contract.internal_register_account(&account_id);
+        }
+        contract.internal_transfer(&owner(), &alice(), tokens(100), None);
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract
+    }
+
+    #[test]
+    fn pays_every_receiver() {
+        let mut contract = setup();
+        let total = contract.ft_transfer_split(vec![(bob(), tokens(70)), (charlie(), tokens(30))], None);
+
+        assert_eq!(total, tokens(100));
+        assert_eq!(contract.internal_balance_of(&alice()), ZERO_TOKEN);
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(70));
+        assert_eq!(contract.internal_balance_of(&charlie()), tokens(30));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_BALANCE")]
+    fn the_whole_total_must_be_covered() {
+        let mut contract = setup();
+        contract.ft_transfer_split(vec![(bob(), tokens(70)), (charlie(), tokens(31))], None);
+    }
+
+    #[test]
+    fn presets_split_by_share() {
+        let preset = SplitPreset { name: "royalties".to_string(), shares: vec![(bob(), 5000), (charlie(), 5000)] };
+        assert!(is_valid_split_preset(&preset));
+        // The odd yocto goes to the first receiver
+        assert_eq!(preset.amounts(tokens(101)), vec![(bob(), tokens(51)), (charlie(), tokens(50))]);
+        assert_eq!(
+            preset.amounts(NearToken::from_yoctonear(u128::MAX))[1].1,
+            NearToken::from_yoctonear(u128::MAX / 2)
+        );
+
+        let uneven = SplitPreset { name: "royalties".to_string(), shares: vec![(bob(), 5000), (charlie(), 4000)] };
+        assert!(!is_valid_split_preset(&uneven));
+    }
+
+    #[test]
+    fn transfers_by_preset() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).attached_deposit(NearToken::from_near(1)).set();
+        contract.set_split("royalties".to_string(), vec![(bob(), 7000), (charlie(), 3000)]);
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert_eq!(contract.ft_transfer_split_preset("royalties".to_string(), tokens(50), None), tokens(50));
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(35));
+        assert_eq!(contract.internal_balance_of(&charlie()), tokens(15));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DUPLICATE_SPLIT_RECEIVER")]
+    fn receivers_can_only_appear_once() {
+        let mut contract = setup();
+        contract.ft_transfer_split(vec![(bob(), tokens(10)), (bob(), tokens(10))], None);
+    }
+}
@@ -0,0 +1,62 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Lifetime counters kept by the contract, returned by `ft_stats`. They only ever go up, so dashboards can show
+/// them without an indexer.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtStats {
+    /// Number of times tokens moved between two accounts, counting each entry of a batch and each refund
+    pub transfers: u64,
+    /// Every token ever minted, including the initial supply
+    pub total_minted: NearToken,
+    /// Every token ever burnt, including balances burnt when an account was force unregistered or swept as dust
+    pub total_burned: NearToken,
+    /// Number of accounts ever registered. An account that unregisters and registers again counts twice
+    pub holders_ever: u64,
+}
+
+impl Default for FtStats {
+    fn default() -> Self {
+        Self { transfers: 0, total_minted: ZERO_TOKEN, total_burned: ZERO_TOKEN, holders_ever: 0 }
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Returns the contract's lifetime transfer, mint, burn and registration counters.
+    pub fn ft_stats(&self) -> FtStats {
+        self.stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    #[test]
+    fn counts_transfers_mints_and_burns() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(100), None);
+        contract.internal_mint(&alice(), NearToken::from_yoctonear(10));
+        contract.internal_burn(&alice(), NearToken::from_yoctonear(30));
+
+        assert_eq!(
+            contract.ft_stats(),
+            FtStats {
+                transfers: 1,
+                total_minted: NearToken::from_yoctonear(TOTAL_SUPPLY + 10),
+                total_burned: NearToken::from_yoctonear(30),
+                holders_ever: 2,
+            }
+        );
+    }
+}
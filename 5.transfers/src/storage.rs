@@ -1,42 +1,10 @@
 use near_sdk::{env, log, AccountId, Promise};
-use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 
+use crate::errors::{require_one_yocto, require_or, FtError};
 use crate::*;
 
-// The structure that will be returned for the methods:
-// * `storage_deposit`
-// * `storage_withdraw`
-// * `storage_balance_of`
-// The `total` and `available` values are string representations of unsigned
-// 128-bit integers showing the balance of a specific account in yoctoⓃ.
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct StorageBalance {
-    pub total: NearToken,
-    pub available: NearToken,
-}
-
-// The below structure will be returned for the method `storage_balance_bounds`.
-// Both `min` and `max` are string representations of unsigned 128-bit integers.
-//
-// `min` is the amount of tokens required to start using this contract at all
-// (eg to register with the contract). If a new contract user attaches `min`
-// NEAR to a `storage_deposit` call, subsequent calls to `storage_balance_of`
-// for this user must show their `total` equal to `min` and `available=0` .
-//
-// A contract may implement `max` equal to `min` if it only charges for initial
-// registration, and does not adjust per-user storage over time. A contract
-// which implements `max` must refund deposits that would increase a user's
-// storage balance beyond this amount.
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
-#[borsh(crate = "near_sdk::borsh")]
-#[serde(crate = "near_sdk::serde")]
-pub struct StorageBalanceBounds {
-    pub min: NearToken,
-    pub max: Option<NearToken>,
-}
+pub use ft_common::storage::{StorageBalance, StorageBalanceBounds};
 
 pub trait StorageManagement {
     /************************************/
@@ -62,6 +30,20 @@ pub trait StorageManagement {
         registration_only: Option<bool>,
     ) -> StorageBalance;
 
+    // Withdraws `amount` of the predecessor's available storage balance, or all of it if `amount` is omitted.
+    // Requires exactly 1 yoctoⓃ attached. Panics if the predecessor isn't registered or `amount` is more than
+    // what's available.
+    //
+    // Returns the StorageBalance structure showing updated balances.
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance;
+
+    // Unregisters the predecessor account and refunds its storage deposit. Requires exactly 1 yoctoⓃ attached.
+    //
+    // If the account still holds tokens, panics unless `force=true`, in which case the tokens are burnt.
+    //
+    // Returns `true` if the account was unregistered and `false` if it wasn't registered.
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
     /****************/
     /* VIEW METHODS */
     /****************/
@@ -76,6 +58,7 @@ pub trait StorageManagement {
     fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
 }
 
+#[cfg(feature = "contract")]
 #[near_bindgen]
 impl StorageManagement for Contract {
     #[allow(unused_variables)]
@@ -89,52 +72,390 @@ impl StorageManagement for Contract {
         let amount = env::attached_deposit();
         // If an account was specified, use that. Otherwise, use the predecessor account.
         let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
-        
-        // If the account is already registered, refund the deposit.
+
+        // If the account is already registered, the whole deposit is refunded.
         if self.accounts.contains_key(&account_id) {
             log!("The account is already registered, refunding the deposit");
-            if amount.gt(&ZERO_TOKEN) {
-                Promise::new(env::predecessor_account_id()).transfer(amount);
-            } 
+        }
         // Register the account and refund any excess $NEAR
-        } else {
-            // Get the minimum required storage and ensure the deposit is at least that amount
-            let min_balance = self.storage_balance_bounds().min;
-            if amount < min_balance {
-                env::panic_str("The attached deposit is less than the minimum storage balance");
-            }
-
-            // Register the account
-            self.internal_register_account(&account_id);
-            // Perform a refund
-            let refund = amount.saturating_sub(min_balance);
-            if refund.gt(&ZERO_TOKEN) {
-                Promise::new(env::predecessor_account_id()).transfer(refund);
-            }
+        let refund = self.internal_storage_deposit(&account_id, amount);
+        if refund.gt(&ZERO_TOKEN) {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
         }
 
         // Return the storage balance of the account
-        StorageBalance { total: self.storage_balance_bounds().min, available: ZERO_TOKEN }
+        StorageBalance { total: self.storage_balance_required, available: ZERO_TOKEN }
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_balance = self
+            .storage_balance_of(account_id.clone())
+            .unwrap_or_else(|| FtError::AccountNotRegistered(account_id.clone()).panic());
+
+        // The deposit is always exactly the minimum, so nothing is ever available and only 0 can be withdrawn
+        let amount = amount.unwrap_or(storage_balance.available);
+        require_or(amount <= storage_balance.available, FtError::StorageWithdrawTooLarge);
+        StorageWithdraw { account_id: &account_id, amount: &amount }.emit();
+
+        storage_balance
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        // Unregister the account and refund its storage deposit
+        let Some(refund) = self.internal_storage_unregister(&account_id, force.unwrap_or(false)) else {
+            log!("The account {} is not registered", account_id);
+            return false;
+        };
+        Promise::new(account_id).transfer(refund);
+
+        true
     }
 
     fn storage_balance_bounds(&self) -> StorageBalanceBounds {
-        // Calculate the required storage balance by taking the bytes for the longest account ID and multiplying by the current byte cost
-        let required_storage_balance =
-            env::storage_byte_cost().saturating_mul(self.bytes_for_longest_account_id.into());
-        
-        // Storage balance bounds will have min == max == required_storage_balance
+        self.internal_storage_balance_bounds()
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(&account_id)
+    }
+}
+
+impl Contract {
+    /// Internal method for registering an account with a storage deposit of `amount`. Returns how much of the
+    /// deposit is left over to refund: all of it if the account was already registered, and whatever is above
+    /// the required balance otherwise. Panics if the deposit doesn't cover the required balance.
+    pub fn internal_storage_deposit(&mut self, account_id: &AccountId, amount: NearToken) -> NearToken {
+        // If the account is already registered, the whole deposit is left over
+        if self.accounts.contains_key(account_id) {
+            return amount;
+        }
+
+        // Get the minimum required storage and ensure the deposit is at least that amount
+        let min_balance = self.storage_balance_required;
+        require_or(amount >= min_balance, FtError::InsufficientStorageDeposit);
+
+        // Register the account
+        self.internal_register_account(account_id);
+        StorageRegister { account_id, amount: &min_balance }.emit();
+
+        amount.saturating_sub(min_balance)
+    }
+
+    /// Internal method for unregistering an account. If it still holds tokens, they're burnt when `force` is set
+    /// and it panics otherwise. Returns the storage deposit to refund, or `None` if the account wasn't registered.
+    /// The caller is responsible for sending the refund.
+    pub fn internal_storage_unregister(&mut self, account_id: &AccountId, force: bool) -> Option<NearToken> {
+        // The treasury has to stay registered, the owner methods depend on it
+        require_or(account_id != &self.owner_id, FtError::OwnerCannotUnregister);
+
+        let shares = self.accounts.get(account_id)?;
+        // Pending refunds would otherwise be burnt, or credited to a new account with the same ID
+        self.assert_nothing_in_flight(account_id);
+        // Tokens in the vault would be lost, so they have to be withdrawn first
+        self.assert_no_vault_position(account_id);
+
+        // Burn whatever is left in the account if `force` is set
+        if shares > 0 {
+            require_or(force, FtError::UnregisterPositiveBalance);
+            let balance = self.internal_shares_to_amount(shares);
+            self.total_shares -= shares;
+            self.total_supply = self.total_supply.saturating_sub(balance);
+            self.stats.total_burned = self.stats.total_burned.saturating_add(balance);
+            FtBurn { owner_id: account_id, amount: &balance, memo: Some("Force unregister") }.emit();
+        }
+
+        // Remove the account along with its history, allowlist and split presets
+        self.accounts.remove(account_id);
+        self.internal_clear_history(account_id);
+        self.outbound_allowlists.remove(account_id);
+        self.split_presets.remove(account_id);
+        // The last faucet claim is kept, otherwise re-registering would be a way around the claim limit
+        let refund = self.storage_balance_required;
+        StorageUnregister { account_id, amount: &refund }.emit();
+
+        Some(refund)
+    }
+
+    /// Internal method for handing an account's storage credit over to an unregistered account. `account_id` is
+    /// unregistered exactly like `internal_storage_unregister` and its refund becomes `to`'s storage deposit, so
+    /// no NEAR leaves the contract. Returns whatever is left of the refund once `to` is registered, which is only
+    /// ever more than 0 if the required balance went down since `account_id` registered.
+    pub fn internal_storage_transfer(&mut self, account_id: &AccountId, to: &AccountId, force: bool) -> NearToken {
+        require_or(account_id != to, FtError::SelfTransfer);
+        // Handing the credit to an account that's already registered would just refund it to the caller
+        require_or(!self.accounts.contains_key(to), FtError::AlreadyRegistered);
+
+        let credit = self
+            .internal_storage_unregister(account_id, force)
+            .unwrap_or_else(|| FtError::AccountNotRegistered(account_id.clone()).panic());
+        self.internal_storage_deposit(to, credit)
+    }
+
+    /// Internal method for charging the attached deposit for the storage an account's own data grew by since
+    /// `initial_storage_usage`, e.g. its outbound allowlist. Whatever isn't needed is refunded to the account, along
+    /// with the cost of any storage freed. Panics if the deposit doesn't cover the extra storage.
+    pub(crate) fn internal_settle_storage(&self, account_id: &AccountId, initial_storage_usage: StorageUsage) {
+        let final_storage_usage = env::storage_usage();
+
+        let attached = env::attached_deposit();
+        let refund = if final_storage_usage > initial_storage_usage {
+            let cost = env::storage_byte_cost().saturating_mul((final_storage_usage - initial_storage_usage).into());
+            require_or(attached >= cost, FtError::InsufficientStorageDeposit);
+            attached.saturating_sub(cost)
+        } else {
+            let freed = env::storage_byte_cost().saturating_mul((initial_storage_usage - final_storage_usage).into());
+            attached.saturating_add(freed)
+        };
+        if refund.gt(&ZERO_TOKEN) {
+            Promise::new(account_id.clone()).transfer(refund);
+        }
+    }
+
+    /// Internal method for getting the storage balance bounds. Min == max == the required storage balance worked out at init
+    pub fn internal_storage_balance_bounds(&self) -> StorageBalanceBounds {
         StorageBalanceBounds {
-            min: required_storage_balance,
-            max: Some(required_storage_balance),
+            min: self.storage_balance_required,
+            max: Some(self.storage_balance_required),
         }
     }
 
-    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
-        // Get the storage balance of the account. Available will always be 0 since you can't overpay for storage.
-        if self.accounts.contains_key(&account_id) {
-            Some(StorageBalance { total: self.storage_balance_bounds().min, available: ZERO_TOKEN })
+    /// Internal method for getting the storage balance of an account, or `None` if it isn't registered. Available
+    /// will always be 0 since you can't overpay for storage.
+    pub fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        if self.accounts.contains_key(account_id) {
+            Some(StorageBalance { total: self.storage_balance_required, available: ZERO_TOKEN })
         } else {
             None
         }
     }
 }
+
+// The structure returned by `storage_cost_estimate`. All amounts are in yoctoⓃ.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageCostEstimate {
+    // What `storage_deposit` needs to register the account. 0 if it's already registered.
+    pub registration: NearToken,
+    // The extra deposit needed for each of the requested features the contract supports
+    pub features: Vec<(String, NearToken)>,
+    // Requested features this contract doesn't have, so they cost nothing
+    pub unsupported: Vec<String>,
+    // The registration plus every supported feature
+    pub total: NearToken,
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    // Recomputes the storage balance bounds from the current storage price. They're worked out once at init
+    // rather than on every call, so this is needed if the price changes. Only the owner can refresh them and
+    // exactly 1 yoctoⓃ must be attached.
+    //
+    // Returns the new bounds.
+    #[payable]
+    pub fn refresh_storage_balance_bounds(&mut self) -> StorageBalanceBounds {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.internal_refresh_storage_balance_required();
+        self.storage_balance_bounds()
+    }
+
+    // Unregisters the predecessor account and uses its storage deposit to register `to` instead, saving a round
+    // trip of NEAR through the wallet when an account is rotated. `to` must not be registered yet. Requires
+    // exactly 1 yoctoⓃ attached.
+    //
+    // If the account still holds tokens, panics unless `force=true`, in which case the tokens are burnt.
+    //
+    // Returns the storage balance of `to`.
+    #[payable]
+    pub fn storage_transfer(&mut self, to: AccountId, force: Option<bool>) -> StorageBalance {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let refund = self.internal_storage_transfer(&account_id, &to, force.unwrap_or(false));
+        if refund.gt(&ZERO_TOKEN) {
+            Promise::new(account_id).transfer(refund);
+        }
+
+        self.internal_storage_balance_of(&to).unwrap()
+    }
+
+    // Estimates the deposit an account needs before it can use the contract and, on top of that, each of the
+    // given optional features. Costs come from byte sizes measured when the contract was initialized, so the
+    // estimate follows changes to the storage price. If no account is passed in, it's treated as unregistered.
+    //
+    // This contract only charges for registration. Features that other token contracts charge extra for
+    // (e.g. "allowances", "locks" or "vesting") are listed as unsupported.
+    pub fn storage_cost_estimate(&self, account_id: Option<AccountId>, features: Vec<String>) -> StorageCostEstimate {
+        let is_registered = account_id.map(|account_id| self.accounts.contains_key(&account_id)).unwrap_or(false);
+        let registration = if is_registered { ZERO_TOKEN } else { self.storage_balance_required };
+
+        StorageCostEstimate {
+            registration,
+            features: vec![],
+            unsupported: features,
+            total: registration,
+        }
+    }
+
+    // Returns the accounts out of `account_ids` that aren't registered yet, in the order they were passed in. This
+    // lets airdrop tools find which recipients still need a `storage_deposit` in a single view call.
+    pub fn filter_unregistered(&self, account_ids: Vec<AccountId>) -> Vec<AccountId> {
+        account_ids.into_iter().filter(|account_id| !self.accounts.contains_key(account_id)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::test_utils::get_logs;
+
+    use super::*;
+
+    fn setup() -> Contract {
+        Context::new().set();
+        Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY))
+    }
+
+    /// Registers `account_id` with 1 NEAR attached
+    fn register(contract: &mut Contract, account_id: AccountId) {
+        Context::new().predecessor(account_id).attached_deposit(NearToken::from_near(1)).set();
+        contract.storage_deposit(None, None);
+    }
+
+    /// Returns the `event` of each EVENT_JSON log of the call
+    fn events() -> Vec<String> {
+        get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(json).unwrap())
+            .map(|event| format!("{}:{}", event["standard"].as_str().unwrap(), event["event"].as_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn registering_emits_an_event() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+        assert_eq!(events(), vec!["storage:storage_register"]);
+    }
+
+    #[test]
+    fn unregistering_emits_an_event() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert!(contract.storage_unregister(None));
+
+        assert_eq!(events(), vec!["storage:storage_unregister"]);
+        assert!(contract.storage_balance_of(alice()).is_none());
+    }
+
+    #[test]
+    fn unregistering_an_unknown_account_returns_false() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert!(!contract.storage_unregister(None));
+        assert!(events().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNREGISTER_POSITIVE_BALANCE")]
+    fn unregistering_with_a_balance_needs_force() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(10), None);
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn force_unregistering_burns_the_balance() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+        contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(10), None);
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert!(contract.storage_unregister(Some(true)));
+
+        assert_eq!(events(), vec!["nep141:ft_burn", "storage:storage_unregister"]);
+        assert_eq!(contract.total_supply, NearToken::from_yoctonear(TOTAL_SUPPLY - 10));
+        assert_eq!(contract.total_shares, TOTAL_SUPPLY - 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OWNER_CANNOT_UNREGISTER")]
+    fn the_owner_cannot_unregister() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn transferring_storage_moves_the_registration() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        let balance = contract.storage_transfer(bob(), None);
+
+        assert_eq!(events(), vec!["storage:storage_unregister", "storage:storage_register"]);
+        assert_eq!(balance.total, contract.storage_balance_required);
+        assert!(contract.storage_balance_of(alice()).is_none());
+        assert!(contract.storage_balance_of(bob()).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_REGISTERED")]
+    fn transferring_storage_to_a_registered_account_fails() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+        register(&mut contract, bob());
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.storage_transfer(bob(), None);
+    }
+
+    #[test]
+    fn filtering_keeps_only_unregistered_accounts() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+
+        assert_eq!(contract.filter_unregistered(vec![owner(), alice(), bob(), charlie()]), vec![bob(), charlie()]);
+    }
+
+    #[test]
+    fn withdrawing_nothing_emits_an_event() {
+        let mut contract = setup();
+        register(&mut contract, alice());
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.storage_withdraw(None);
+
+        assert_eq!(events(), vec!["storage:storage_withdraw"]);
+    }
+
+    #[test]
+    fn bounds_are_worked_out_at_init() {
+        let mut contract = setup();
+        let expected = env::storage_byte_cost().saturating_mul(contract.bytes_for_longest_account_id.into());
+        assert_eq!(contract.storage_balance_bounds().min, expected);
+
+        // Refreshing at the same storage price keeps the same bounds
+        Context::new().one_yocto().set();
+        assert_eq!(contract.refresh_storage_balance_bounds().min, expected);
+    }
+}
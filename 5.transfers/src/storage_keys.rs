@@ -0,0 +1,46 @@
+use crate::*;
+
+/// Hashes an account ID for use in a storage prefix. Account IDs can be up to 64 bytes, so hashing keeps every
+/// per-account prefix at 33 bytes (one for the variant and 32 for the hash) however long the account is.
+pub fn hash_account_id(account_id: &AccountId) -> CryptoHash {
+    env::sha256_array(account_id.as_bytes())
+}
+
+/// Builders for the prefixes of collections that belong to a single account. Each per-account collection gets
+/// its own `...Inner { account_id_hash }` variant of `StorageKey` and a builder here, so that the prefix is
+/// always hashed the same way and can't collide with another account's or another feature's collection.
+impl StorageKey {
+    /// The prefix of an account's transfer history.
+    pub fn transfer_history_of(account_id: &AccountId) -> Self {
+        StorageKey::TransferHistoryInner { account_id_hash: hash_account_id(account_id) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ft_testing::*;
+    use near_sdk::borsh;
+
+    use super::*;
+
+    /// Returns the prefix `key` gives its collection
+    fn storage_prefix(key: StorageKey) -> Vec<u8> {
+        borsh::to_vec(&key).unwrap()
+    }
+
+    #[test]
+    fn prefixes_are_short_and_distinct() {
+        let prefixes = [
+            storage_prefix(StorageKey::Accounts),
+            storage_prefix(StorageKey::TransferHistory),
+            storage_prefix(StorageKey::TransferWhitelist),
+            storage_prefix(StorageKey::transfer_history_of(&alice())),
+            storage_prefix(StorageKey::transfer_history_of(&"a".repeat(64).parse().unwrap())),
+        ];
+        assert!(prefixes[..3].iter().all(|prefix| prefix.len() == 1));
+        assert!(prefixes[3..].iter().all(|prefix| prefix.len() == 33));
+        assert_eq!(prefixes.iter().collect::<HashSet<_>>().len(), prefixes.len());
+    }
+}
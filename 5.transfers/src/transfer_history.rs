@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// How many of its most recent transfers are kept for each account.
+pub const TRANSFER_HISTORY_LENGTH: u64 = 10;
+
+/// Whether a transfer added to or took from the account's balance.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum TransferDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// A transfer as it's kept in an account's history.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct TransferRecord {
+    pub direction: TransferDirection,
+    /// The other side of the transfer
+    pub counterparty_id: AccountId,
+    pub amount: NearToken,
+    /// When the transfer was made (in nanoseconds)
+    pub timestamp: u64,
+}
+
+/// The structure returned by `ft_transfer_history`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferHistoryEntry {
+    /// How many transfers the account had made or received before this one
+    pub sequence: U64,
+    pub direction: TransferDirection,
+    pub counterparty_id: AccountId,
+    pub amount: NearToken,
+    /// When the transfer was made (in nanoseconds)
+    pub timestamp: U64,
+}
+
+impl Contract {
+    /// Internal method for adding a transfer to the sender's and the receiver's history.
+    pub(crate) fn internal_record_transfer(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: NearToken) {
+        let timestamp = env::block_timestamp();
+        self.internal_push_history(
+            sender_id,
+            &TransferRecord { direction: TransferDirection::Outgoing, counterparty_id: receiver_id.clone(), amount, timestamp },
+        );
+        self.internal_push_history(
+            receiver_id,
+            &TransferRecord { direction: TransferDirection::Incoming, counterparty_id: sender_id.clone(), amount, timestamp },
+        );
+    }
+
+    fn internal_push_history(&mut self, account_id: &AccountId, record: &TransferRecord) {
+        let mut history = self.transfer_history.get(account_id).unwrap_or_else(|| {
+            RingBuffer::new(
+                StorageKey::transfer_history_of(account_id),
+                TRANSFER_HISTORY_LENGTH,
+            )
+        });
+        history.push(record);
+        self.transfer_history.insert(account_id, &history);
+    }
+
+    /// Internal method for removing an account's history, e.g. when it unregisters.
+    pub(crate) fn internal_clear_history(&mut self, account_id: &AccountId) {
+        if let Some(history) = self.transfer_history.remove(account_id) {
+            history.clear();
+        }
+    }
+
+    /// Internal method for measuring how many bytes a full transfer history can take up, so that it's
+    /// covered by the storage deposit. Called right after measuring the longest account ID.
+    pub(crate) fn measure_bytes_for_transfer_history(&mut self) -> StorageUsage {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = AccountId::from_str(&"a".repeat(64)).unwrap();
+        for _ in 0..TRANSFER_HISTORY_LENGTH {
+            self.internal_push_history(
+                &tmp_account_id,
+                &TransferRecord {
+                    direction: TransferDirection::Incoming,
+                    counterparty_id: tmp_account_id.clone(),
+                    amount: NearToken::from_yoctonear(u128::MAX),
+                    timestamp: u64::MAX,
+                },
+            );
+        }
+        let bytes = env::storage_usage() - initial_storage_usage;
+        self.internal_clear_history(&tmp_account_id);
+        bytes
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Returns the most recent transfers to and from `account_id`, newest first. Only the last
+    /// `TRANSFER_HISTORY_LENGTH` transfers of each account are kept.
+    pub fn ft_transfer_history(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<TransferHistoryEntry> {
+        let Some(history) = self.transfer_history.get(&account_id) else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        history
+            .iter_rev()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(sequence, record)| TransferHistoryEntry {
+                sequence: U64(sequence),
+                direction: record.direction,
+                counterparty_id: record.counterparty_id,
+                amount: record.amount,
+                timestamp: U64(record.timestamp),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_transfers_of_both_sides() {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+
+        for amount in 1..=(TRANSFER_HISTORY_LENGTH as u128 + 2) {
+            contract.internal_transfer(&owner(), &alice(), NearToken::from_yoctonear(amount), None);
+        }
+
+        let history = contract.ft_transfer_history(alice(), None, None);
+        assert_eq!(history.len() as u64, TRANSFER_HISTORY_LENGTH);
+        assert_eq!(history[0].amount, NearToken::from_yoctonear(TRANSFER_HISTORY_LENGTH as u128 + 2));
+        assert_eq!(history[0].direction, TransferDirection::Incoming);
+        assert_eq!(history[0].counterparty_id, owner());
+
+        let history = contract.ft_transfer_history(owner(), Some(U128(1)), Some(1));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].direction, TransferDirection::Outgoing);
+        assert_eq!(history[0].amount, NearToken::from_yoctonear(TRANSFER_HISTORY_LENGTH as u128 + 1));
+    }
+
+    #[test]
+    fn the_measurement_leaves_no_history_behind() {
+        Context::new().set();
+        let contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        assert!(contract.ft_transfer_history("a".repeat(64).parse().unwrap(), None, None).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// Memo of the `ft_transfer` event logged when a transfer fee is paid to the owner.
+pub const TRANSFER_FEE_MEMO: &str = "transfer fee";
+/// The largest fee `FeeOnTransfer` can charge: 10% in basis points.
+pub const MAX_TRANSFER_FEE_BPS: u16 = 1_000;
+
+/// A rule every transfer between accounts has to pass. Policies are consulted once per transfer in
+/// `internal_transfer_without_event`, so adding a new kind of restriction is a matter of adding an
+/// implementation rather than an if-statement in every transfer method. Refunds aren't checked, since the
+/// tokens only go back to where they came from.
+pub trait TransferPolicy {
+    /// Panics if the transfer isn't allowed. Otherwise returns the fee the sender pays the owner on top of
+    /// `amount`, which is 0 for most policies.
+    fn check_transfer(&self, sender_id: &AccountId, receiver_id: &AccountId, amount: NearToken) -> NearToken;
+}
+
+/// Anyone can send tokens to anyone.
+pub struct Open;
+
+impl TransferPolicy for Open {
+    fn check_transfer(&self, _sender_id: &AccountId, _receiver_id: &AccountId, _amount: NearToken) -> NearToken {
+        ZERO_TOKEN
+    }
+}
+
+/// Tokens can only move between whitelisted accounts and the owner.
+pub struct Whitelist<'a> {
+    pub owner_id: &'a AccountId,
+    pub accounts: &'a LookupSet<AccountId>,
+}
+
+impl TransferPolicy for Whitelist<'_> {
+    fn check_transfer(&self, sender_id: &AccountId, receiver_id: &AccountId, _amount: NearToken) -> NearToken {
+        for account_id in [sender_id, receiver_id] {
+            require_or(
+                account_id == self.owner_id || self.accounts.contains(account_id),
+                FtError::NotWhitelisted(account_id.clone()),
+            );
+        }
+        ZERO_TOKEN
+    }
+}
+
+/// Senders pay the owner a share of every transfer on top of the amount. Transfers from the owner, and from or to
+/// a fee-exempt account, are free. Custodial contracts like markets have to be exempt, since they send out exactly
+/// what was deposited with them and couldn't pay the fee on top.
+pub struct FeeOnTransfer<'a> {
+    pub owner_id: &'a AccountId,
+    pub exempt: &'a LookupSet<AccountId>,
+    pub fee_bps: u16,
+}
+
+impl TransferPolicy for FeeOnTransfer<'_> {
+    fn check_transfer(&self, sender_id: &AccountId, receiver_id: &AccountId, amount: NearToken) -> NearToken {
+        if sender_id == self.owner_id || self.exempt.contains(sender_id) || self.exempt.contains(receiver_id) {
+            return ZERO_TOKEN;
+        }
+        // Split the amount in two so that multiplying by the fee can't overflow
+        let (amount, fee_bps) = (amount.as_yoctonear(), self.fee_bps as u128);
+        NearToken::from_yoctonear(amount / 10_000 * fee_bps + amount % 10_000 * fee_bps / 10_000)
+    }
+}
+
+/// No transfers go through at all, e.g. while an incident is investigated. Minting, burning and refunds still work.
+pub struct Paused;
+
+impl TransferPolicy for Paused {
+    fn check_transfer(&self, _sender_id: &AccountId, _receiver_id: &AccountId, _amount: NearToken) -> NearToken {
+        FtError::TransfersPaused.panic()
+    }
+}
+
+/// Which `TransferPolicy` the contract applies, as set by the owner with `set_transfer_policy`, or through the admin
+/// timelock for changes that restrict transfers further.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum TransferPolicyConfig {
+    Open,
+    /// Only accounts added with `set_transfer_whitelisted` can send and receive
+    Whitelist,
+    /// Senders pay `fee_bps` basis points of every transfer to the owner
+    FeeOnTransfer { fee_bps: u16 },
+    Paused,
+}
+
+impl TransferPolicyConfig {
+    /// Whether switching from `current` to this policy can only make transfers cheaper or less restricted:
+    /// opening transfers up, or lowering the fee. Any other change has to go through the admin timelock.
+    pub fn loosens(&self, current: &TransferPolicyConfig) -> bool {
+        match (self, current) {
+            (TransferPolicyConfig::Open, _) => true,
+            (
+                TransferPolicyConfig::FeeOnTransfer { fee_bps },
+                TransferPolicyConfig::FeeOnTransfer { fee_bps: current_fee_bps },
+            ) => fee_bps <= current_fee_bps,
+            _ => self == current,
+        }
+    }
+}
+
+/// Panics if `policy` charges more than `MAX_TRANSFER_FEE_BPS`.
+pub(crate) fn assert_valid_transfer_policy(policy: &TransferPolicyConfig) {
+    if let TransferPolicyConfig::FeeOnTransfer { fee_bps } = policy {
+        require_or(*fee_bps <= MAX_TRANSFER_FEE_BPS, FtError::TransferFeeTooHigh);
+    }
+}
+
+impl Contract {
+    /// Internal method for getting the policy transfers are checked against.
+    pub(crate) fn transfer_policy(&self) -> Box<dyn TransferPolicy + '_> {
+        match self.transfer_policy {
+            TransferPolicyConfig::Open => Box::new(Open),
+            TransferPolicyConfig::Whitelist => {
+                Box::new(Whitelist { owner_id: &self.owner_id, accounts: &self.transfer_whitelist })
+            }
+            TransferPolicyConfig::FeeOnTransfer { fee_bps } => {
+                Box::new(FeeOnTransfer { owner_id: &self.owner_id, exempt: &self.fee_exempt, fee_bps })
+            }
+            TransferPolicyConfig::Paused => Box::new(Paused),
+        }
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Switches the policy every transfer is checked against, as long as the switch only loosens it. Pausing,
+    /// whitelisting or raising the fee has to be proposed with `propose_admin_op` instead. Only the owner can
+    /// change it and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_transfer_policy(&mut self, policy: TransferPolicyConfig) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        assert_valid_transfer_policy(&policy);
+        require_or(policy.loosens(&self.transfer_policy), FtError::AdminOpTimelocked);
+        self.transfer_policy = policy;
+    }
+
+    /// Returns the policy every transfer is checked against.
+    pub fn get_transfer_policy(&self) -> TransferPolicyConfig {
+        self.transfer_policy
+    }
+
+    /// Adds `account_id` to the whitelist used by the `Whitelist` policy, or removes it. Only the owner can
+    /// change the whitelist and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_transfer_whitelisted(&mut self, account_id: AccountId, whitelisted: bool) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        if whitelisted {
+            self.transfer_whitelist.insert(&account_id);
+        } else {
+            self.transfer_whitelist.remove(&account_id);
+        }
+    }
+
+    /// Returns whether `account_id` is on the whitelist used by the `Whitelist` policy.
+    pub fn is_transfer_whitelisted(&self, account_id: AccountId) -> bool {
+        self.transfer_whitelist.contains(&account_id)
+    }
+
+    /// Exempts `account_id` from the `FeeOnTransfer` fee, or takes the exemption away. Transfers from or to an
+    /// exempt account are free. Only the owner can change exemptions and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn set_fee_exempt(&mut self, account_id: AccountId, exempt: bool) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        if exempt {
+            self.fee_exempt.insert(&account_id);
+        } else {
+            self.fee_exempt.remove(&account_id);
+        }
+    }
+
+    /// Returns whether transfers from and to `account_id` are exempt from the `FeeOnTransfer` fee.
+    pub fn is_fee_exempt(&self, account_id: AccountId) -> bool {
+        self.fee_exempt.contains(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+    use crate::admin_timelock::{AdminOp, ADMIN_TIMELOCK_NS};
+
+    /// Creates the contract with alice holding 1000 tokens and bob registered, then switches to `policy` through
+    /// the admin timelock
+    fn setup(policy: TransferPolicyConfig) -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_transfer(&owner(), &alice(), tokens(1000), None);
+
+        Context::new().one_yocto().set();
+        let id = contract.propose_admin_op(AdminOp::SetTransferPolicy { policy });
+        Context::new().one_yocto().block_timestamp(ADMIN_TIMELOCK_NS).set();
+        contract.execute_admin_op(id);
+        contract
+    }
+
+    #[test]
+    fn fees_are_paid_to_the_owner_on_top() {
+        let mut contract = setup(TransferPolicyConfig::FeeOnTransfer { fee_bps: 100 });
+        contract.internal_transfer(&alice(), &bob(), tokens(500), None);
+
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(500));
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(495));
+        assert_eq!(contract.internal_balance_of(&owner()), tokens(TOTAL_SUPPLY - 995));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_WHITELISTED")]
+    fn whitelists_apply_to_both_sides() {
+        let mut contract = setup(TransferPolicyConfig::Whitelist);
+        contract.set_transfer_whitelisted(alice(), true);
+        contract.internal_transfer(&alice(), &bob(), tokens(500), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TRANSFERS_PAUSED")]
+    fn paused_tokens_cannot_move() {
+        let mut contract = setup(TransferPolicyConfig::Paused);
+        contract.internal_transfer(&alice(), &bob(), tokens(500), None);
+    }
+
+    #[test]
+    fn exempt_accounts_and_whole_balances_pay_no_fee() {
+        let mut contract = setup(TransferPolicyConfig::FeeOnTransfer { fee_bps: 100 });
+        contract.set_fee_exempt(bob(), true);
+        contract.internal_transfer(&alice(), &bob(), tokens(500), None);
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(500));
+
+        contract.set_fee_exempt(bob(), false);
+        contract.internal_transfer(&alice(), &bob(), tokens(500), None);
+        assert_eq!(contract.internal_balance_of(&bob()), tokens(1000));
+        assert_eq!(contract.internal_balance_of(&owner()), tokens(TOTAL_SUPPLY - 1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ADMIN_OP_TIMELOCKED")]
+    fn pausing_cannot_skip_the_timelock() {
+        let mut contract = setup(TransferPolicyConfig::Open);
+        contract.set_transfer_policy(TransferPolicyConfig::Paused);
+    }
+
+    #[test]
+    fn fees_can_be_lowered_directly() {
+        let mut contract = setup(TransferPolicyConfig::FeeOnTransfer { fee_bps: 100 });
+        contract.set_transfer_policy(TransferPolicyConfig::FeeOnTransfer { fee_bps: 50 });
+        contract.set_transfer_policy(TransferPolicyConfig::Open);
+        assert_eq!(contract.get_transfer_policy(), TransferPolicyConfig::Open);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::*;
+
+/// The longest name a treasury bucket can have.
+pub const MAX_BUCKET_NAME_LEN: usize = 32;
+
+/// Whether `bucket` is 1 to `MAX_BUCKET_NAME_LEN` characters of `a-z`, `0-9`, `-` and `_`, e.g. `ecosystem`.
+pub(crate) fn is_valid_bucket_name(bucket: &str) -> bool {
+    (1..=MAX_BUCKET_NAME_LEN).contains(&bucket.len())
+        && bucket.bytes().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-' || c == b'_')
+}
+
+impl Contract {
+    /// Internal method for asserting that the predecessor is the owner or a treasurer, and returning it.
+    pub(crate) fn assert_treasurer(&self) -> AccountId {
+        let treasurer_id = env::predecessor_account_id();
+        require_or(treasurer_id == self.owner_id || self.treasurers.contains(&treasurer_id), FtError::NotTreasurer);
+        treasurer_id
+    }
+
+    /// Internal method for adding shares to a bucket, creating it if needed.
+    fn internal_treasury_deposit_shares(&mut self, bucket: &String, shares: u128) {
+        let new_shares = self
+            .treasury_buckets
+            .get(bucket)
+            .unwrap_or(0)
+            .checked_add(shares)
+            .unwrap_or_else(|| FtError::BalanceOverflow.panic());
+        self.treasury_buckets.insert(bucket, &new_shares);
+        self.treasury_shares = self
+            .treasury_shares
+            .checked_add(shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+    }
+
+    /// Internal method for taking shares from a bucket. Buckets that end up empty are removed.
+    fn internal_treasury_withdraw_shares(&mut self, bucket: &String, shares: u128) {
+        let new_shares = self
+            .treasury_buckets
+            .get(bucket)
+            .and_then(|bucket_shares| bucket_shares.checked_sub(shares))
+            .unwrap_or_else(|| FtError::InsufficientTreasuryBalance.panic());
+        if new_shares == 0 {
+            self.treasury_buckets.remove(bucket);
+        } else {
+            self.treasury_buckets.insert(bucket, &new_shares);
+        }
+        self.treasury_shares = self
+            .treasury_shares
+            .checked_sub(shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Allows `treasurer_id` to move tokens between and out of the treasury buckets. Only the owner can add
+    /// treasurers and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn add_treasurer(&mut self, treasurer_id: AccountId) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.treasurers.insert(&treasurer_id);
+    }
+
+    /// Stops `treasurer_id` from managing the treasury buckets. Only the owner can remove treasurers and exactly
+    /// 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn remove_treasurer(&mut self, treasurer_id: AccountId) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        self.treasurers.remove(&treasurer_id);
+    }
+
+    /// Returns whether `account_id` is a treasurer. The owner can always manage the treasury, whether or not
+    /// it's a treasurer.
+    pub fn is_treasurer(&self, account_id: AccountId) -> bool {
+        self.treasurers.contains(&account_id)
+    }
+
+    /// Moves `amount` tokens from the caller's balance into the treasury bucket named `bucket`, creating the
+    /// bucket if it doesn't exist yet. Only the owner or a treasurer can fund the treasury and exactly
+    /// 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn treasury_deposit(&mut self, bucket: String, amount: NearToken) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let treasurer_id = self.assert_treasurer();
+        require_or(is_valid_bucket_name(&bucket), FtError::InvalidTreasuryBucket);
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+
+        let shares = self.internal_amount_to_shares(amount);
+        self.internal_withdraw_shares(&treasurer_id, shares);
+        self.internal_treasury_deposit_shares(&bucket, shares);
+
+        // The bucket's tokens count as held by the contract account
+        FtTransfer {
+            old_owner_id: &treasurer_id,
+            new_owner_id: &env::current_account_id(),
+            amount: &amount,
+            memo: Some(bucket.as_str()),
+        }
+        .emit();
+        TreasuryDeposit { bucket: &bucket, sender_id: &treasurer_id, amount: &amount }.emit();
+    }
+
+    /// Moves `amount` tokens from the `from_bucket` treasury bucket to `to_bucket`, creating it if it doesn't
+    /// exist yet. Only the owner or a treasurer can move tokens and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn treasury_move(&mut self, from_bucket: String, to_bucket: String, amount: NearToken) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let treasurer_id = self.assert_treasurer();
+        require_or(is_valid_bucket_name(&to_bucket) && from_bucket != to_bucket, FtError::InvalidTreasuryBucket);
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+
+        let shares = self.internal_amount_to_shares(amount);
+        self.internal_treasury_withdraw_shares(&from_bucket, shares);
+        self.internal_treasury_deposit_shares(&to_bucket, shares);
+
+        TreasuryMove { from_bucket: &from_bucket, to_bucket: &to_bucket, amount: &amount, treasurer_id: &treasurer_id }
+            .emit();
+    }
+
+    /// Transfers `amount` tokens out of the treasury bucket named `bucket` to `receiver_id`, which has to be
+    /// registered. Only the owner or a treasurer can spend from the treasury and exactly 1 yoctoNEAR must be
+    /// attached.
+    ///
+    /// Besides the usual `ft_transfer` event from the contract account, a `treasury_transfer` event names the
+    /// bucket the tokens came from.
+    #[payable]
+    pub fn treasury_transfer(&mut self, bucket: String, receiver_id: AccountId, amount: NearToken, memo: Option<String>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let treasurer_id = self.assert_treasurer();
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+        require_or(self.accounts.get(&receiver_id).is_some(), FtError::ReceiverNotRegistered(receiver_id.clone()));
+        // Reject oversized or malformed payloads before any tokens move
+        self.assert_valid_memo(memo.as_deref());
+
+        let shares = self.internal_amount_to_shares(amount);
+        self.internal_treasury_withdraw_shares(&bucket, shares);
+        self.internal_deposit_shares(&receiver_id, shares);
+
+        let contract_id = env::current_account_id();
+        self.internal_record_deposit(&contract_id, &receiver_id, amount, memo.as_deref());
+
+        FtTransfer {
+            old_owner_id: &contract_id,
+            new_owner_id: &receiver_id,
+            amount: &amount,
+            memo: memo.as_deref(),
+        }
+        .emit();
+        TreasuryTransfer {
+            bucket: &bucket,
+            receiver_id: &receiver_id,
+            amount: &amount,
+            treasurer_id: &treasurer_id,
+            memo: memo.as_deref(),
+        }
+        .emit();
+    }
+
+    /// Returns the balance of the treasury bucket named `bucket`. Buckets that don't exist have a balance of 0.
+    pub fn treasury_balance_of(&self, bucket: String) -> NearToken {
+        self.treasury_buckets
+            .get(&bucket)
+            .map(|shares| self.internal_shares_to_amount(shares))
+            .unwrap_or(ZERO_TOKEN)
+    }
+
+    /// Returns every treasury bucket that holds tokens, with its balance.
+    pub fn treasury_bucket_balances(&self) -> Vec<(String, NearToken)> {
+        self.treasury_buckets
+            .iter()
+            .map(|(bucket, shares)| (bucket, self.internal_shares_to_amount(shares)))
+            .collect()
+    }
+
+    /// Returns the balance held across all treasury buckets.
+    pub fn treasury_total(&self) -> NearToken {
+        self.internal_shares_to_amount(self.treasury_shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::test_utils::get_logs;
+
+    use super::*;
+
+    fn treasurer() -> AccountId {
+        "treasurer.near".parse().unwrap()
+    }
+
+    /// Creates the contract with `treasurer()` approved, alice registered, and 100 tokens in the "ecosystem" bucket
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+
+        Context::new().one_yocto().set();
+        contract.add_treasurer(treasurer());
+        contract.treasury_deposit("ecosystem".to_string(), tokens(100));
+        contract
+    }
+
+    #[test]
+    fn deposits_come_out_of_the_callers_balance() {
+        let contract = setup();
+        assert_eq!(contract.internal_balance_of(&owner()), tokens(TOTAL_SUPPLY - 100));
+        assert_eq!(contract.treasury_balance_of("ecosystem".to_string()), tokens(100));
+        assert_eq!(contract.treasury_total(), tokens(100));
+        // Bucket shares still count towards the total
+        assert!(contract.assert_supply_invariant(U128(TOTAL_SUPPLY - 100)));
+    }
+
+    #[test]
+    fn treasurers_move_between_buckets_and_transfer_out() {
+        let mut contract = setup();
+        Context::new().predecessor(treasurer()).one_yocto().set();
+        contract.treasury_move("ecosystem".to_string(), "rewards".to_string(), tokens(30));
+        assert_eq!(contract.treasury_balance_of("ecosystem".to_string()), tokens(70));
+        assert_eq!(contract.treasury_balance_of("rewards".to_string()), tokens(30));
+
+        Context::new().predecessor(treasurer()).one_yocto().set();
+        contract.treasury_transfer("rewards".to_string(), alice(), tokens(30), None);
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(30));
+        assert!(get_logs().iter().any(|log| log.contains(r#""standard":"treasury","version":"1.0.0","event":"treasury_transfer""#)));
+        assert!(get_logs().iter().any(|log| log.contains(r#""bucket":"rewards""#)));
+
+        // Emptied buckets are removed
+        assert_eq!(contract.treasury_bucket_balances(), vec![("ecosystem".to_string(), tokens(70))]);
+        assert!(contract.assert_supply_invariant(U128(TOTAL_SUPPLY - 70)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_TREASURY_BALANCE")]
+    fn buckets_cannot_overspend() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.treasury_transfer("ecosystem".to_string(), alice(), tokens(101), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_TREASURER")]
+    fn only_treasurers_can_spend() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.treasury_transfer("ecosystem".to_string(), alice(), tokens(10), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TREASURY_BUCKET")]
+    fn bucket_names_are_validated() {
+        let mut contract = setup();
+        Context::new().one_yocto().set();
+        contract.treasury_move("ecosystem".to_string(), "Team Budget".to_string(), tokens(10));
+    }
+}
@@ -0,0 +1,106 @@
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, log, require, Gas, Promise};
+
+use crate::*;
+
+/// How long staged code has to wait before it can be deployed (24 hours in nanoseconds).
+/// This gives token holders time to review an upgrade before it goes live.
+pub const UPGRADE_TIMELOCK_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// GAS attached to the `migrate` call that runs right after the new code is deployed.
+const GAS_FOR_MIGRATE: Gas = Gas::from_tgas(50);
+
+/// Contract code that's been staged by the owner but not deployed yet.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct StagedCode {
+    /// The raw wasm to deploy
+    pub code: Vec<u8>,
+    /// When the code was staged (in nanoseconds)
+    pub staged_at: u64,
+}
+
+/// The structure returned by `get_staged_code`. The code itself is left out since it's large; clients
+/// can compare the hash against a build they trust.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StagedCodeInfo {
+    /// The sha256 hash of the staged code
+    pub code_hash: Base64VecU8,
+    /// When the code was staged (in nanoseconds)
+    pub staged_at: U64,
+    /// The earliest time the code can be deployed (in nanoseconds)
+    pub deployable_at: U64,
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Stages new contract code to be deployed once the timelock has passed. The code is read as raw
+    /// bytes from the input instead of JSON so that it doesn't need to be encoded. Only the owner (which
+    /// can be a DAO) can stage code, and staging again replaces whatever was staged before. Exactly 1
+    /// yoctoNEAR must be attached.
+    #[payable]
+    pub fn stage_code(&mut self) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        // Only the owner can upgrade the contract
+        self.assert_owner();
+
+        let code = env::input().unwrap_or_else(|| env::panic_str("Expected the contract code as input"));
+        require!(!code.is_empty(), "The contract code should not be empty");
+
+        log!("Staged {} bytes of contract code", code.len());
+        self.staged_code.set(&StagedCode { code, staged_at: env::block_timestamp() });
+    }
+
+    /// Removes the staged code without deploying it. Only the owner can discard staged code.
+    #[payable]
+    pub fn discard_staged_code(&mut self) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        self.assert_owner();
+
+        require!(self.staged_code.remove().is_some(), "No code is staged");
+    }
+
+    /// Deploys the staged code to this account and calls `migrate` on it. The timelock must have passed
+    /// since the code was staged. Only the owner can commit an upgrade.
+    #[payable]
+    pub fn commit_staged_code(&mut self) -> Promise {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        self.assert_owner();
+
+        let staged = self.staged_code.get().unwrap_or_else(|| env::panic_str("No code is staged"));
+        require!(
+            env::block_timestamp() >= staged.staged_at.saturating_add(UPGRADE_TIMELOCK_NS),
+            "The staged code is still timelocked"
+        );
+        self.staged_code.remove();
+
+        // Deploy the code and migrate the state in the same batch so the contract is never left with old state
+        Promise::new(env::current_account_id())
+            .deploy_contract(staged.code)
+            .function_call("migrate".to_string(), vec![], ZERO_TOKEN, GAS_FOR_MIGRATE)
+    }
+
+    /// Returns information about the staged code, or `null` if nothing is staged.
+    pub fn get_staged_code(&self) -> Option<StagedCodeInfo> {
+        self.staged_code.get().map(|staged| StagedCodeInfo {
+            code_hash: Base64VecU8(env::sha256(&staged.code)),
+            staged_at: U64(staged.staged_at),
+            deployable_at: U64(staged.staged_at.saturating_add(UPGRADE_TIMELOCK_NS)),
+        })
+    }
+
+    /// Called by the contract itself right after new code is deployed. Reads the state written by the
+    /// previous code and converts it to the current layout. When a new version changes the `Contract`
+    /// struct, this is where the old struct gets deserialized and mapped onto the new one.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        env::state_read().unwrap_or_else(|| env::panic_str("Failed to read the contract state"))
+    }
+}
@@ -0,0 +1,370 @@
+use std::str::FromStr;
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::errors::{require_one_yocto, require_or, FtError};
+use crate::internal::{mul_div, U256};
+use crate::*;
+
+/// The memo on the `ft_transfer` events logged when tokens go into the vault.
+pub const VAULT_DEPOSIT_MEMO: &str = "vault deposit";
+/// The memo on the `ft_transfer` events logged when tokens come out of the vault.
+pub const VAULT_WITHDRAW_MEMO: &str = "vault withdraw";
+/// The memo on the `ft_transfer` events logged when the owner funds interest.
+pub const VAULT_INTEREST_MEMO: &str = "vault interest";
+
+/// Computes `a * b / c` rounding up, using 256-bit intermediate math.
+fn mul_div_ceil(a: u128, b: u128, c: u128) -> u128 {
+    let c = U256::from(c);
+    ((U256::from(a) * U256::from(b) + c - 1) / c).as_u128()
+}
+
+/// The state of the interest-bearing vault. Depositors get vault shares for their tokens, and interest funded
+/// by the owner is released into the vault over time, so that each vault share is worth more and more tokens.
+///
+/// Tokens are tracked in the contract's own shares, the same way account balances are, so that rebases apply
+/// to the vault as well.
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Vault {
+    /// Shares of the total supply held by the vault, including interest that isn't released yet
+    pub token_shares: u128,
+    /// Vault shares issued to depositors
+    pub total_shares: u128,
+    /// Interest, in shares of the total supply, that was still locked at `last_update`
+    pub locked_token_shares: u128,
+    /// When the locked interest was last worked out, in nanoseconds
+    pub last_update: u64,
+    /// When all of the interest is released, in nanoseconds
+    pub release_end: u64,
+}
+
+impl Vault {
+    /// The interest that is still locked at `now`. It's released linearly until `release_end`.
+    fn locked_at(&self, now: u64) -> u128 {
+        if now >= self.release_end || self.release_end <= self.last_update {
+            return 0;
+        }
+        mul_div(
+            self.locked_token_shares,
+            (self.release_end - now) as u128,
+            (self.release_end - self.last_update) as u128,
+        )
+    }
+
+    /// Shares of the total supply that belong to depositors at `now`.
+    fn released_at(&self, now: u64) -> u128 {
+        self.token_shares - self.locked_at(now)
+    }
+
+    /// Locks in the interest released so far.
+    fn update(&mut self, now: u64) {
+        self.locked_token_shares = self.locked_at(now);
+        self.last_update = now;
+    }
+
+    /// How many vault shares `token_shares` are worth at `now`, rounding down.
+    fn to_vault_shares(&self, token_shares: u128, now: u64) -> u128 {
+        let released = self.released_at(now);
+        if self.total_shares == 0 || released == 0 {
+            return token_shares;
+        }
+        mul_div(token_shares, self.total_shares, released)
+    }
+
+    /// How many vault shares it takes to withdraw `token_shares` at `now`, rounding up so that the vault never
+    /// pays out more than it holds.
+    fn to_vault_shares_ceil(&self, token_shares: u128, now: u64) -> u128 {
+        let released = self.released_at(now);
+        if self.total_shares == 0 || released == 0 {
+            return token_shares;
+        }
+        mul_div_ceil(token_shares, self.total_shares, released)
+    }
+
+    /// How many shares of the total supply `vault_shares` are worth at `now`, rounding down.
+    fn to_token_shares(&self, vault_shares: u128, now: u64) -> u128 {
+        if self.total_shares == 0 {
+            return 0;
+        }
+        mul_div(vault_shares, self.released_at(now), self.total_shares)
+    }
+}
+
+/// The structure returned by `vault_info`.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VaultInfo {
+    /// Tokens that belong to depositors, including the interest released so far
+    pub total_assets: NearToken,
+    /// Vault shares issued to depositors
+    pub total_shares: U128,
+    /// Interest that the owner funded but that isn't released yet
+    pub locked_interest: NearToken,
+    /// When all of the funded interest is released, in nanoseconds
+    pub release_end: U64,
+}
+
+impl Contract {
+    /// Internal method for asserting that the account has nothing left in the vault, e.g. before it unregisters.
+    pub(crate) fn assert_no_vault_position(&self, account_id: &AccountId) {
+        require_or(self.vault_shares.get(account_id).unwrap_or(0) == 0, FtError::VaultPositionOpen);
+    }
+
+    /// Internal method for measuring how many bytes a vault position takes. It's covered by the storage deposit.
+    pub(crate) fn measure_bytes_for_vault_position(&mut self) -> StorageUsage {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = AccountId::from_str(&"a".repeat(64)).unwrap();
+        self.vault_shares.insert(&tmp_account_id, &u128::MAX);
+        let bytes = env::storage_usage() - initial_storage_usage;
+        self.vault_shares.remove(&tmp_account_id);
+        bytes
+    }
+}
+
+#[cfg(feature = "contract")]
+#[near_bindgen]
+impl Contract {
+    /// Moves `amount` tokens from the caller's balance into the vault, in exchange for vault shares at the
+    /// current exchange rate. Exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn vault_deposit(&mut self, amount: NearToken) -> U128 {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+        let account_id = env::predecessor_account_id();
+        let now = env::block_timestamp();
+
+        // Work out the vault shares before the tokens go in, so that they're priced at the current rate
+        let token_shares = self.internal_amount_to_shares(amount);
+        let vault_shares = self.vault.to_vault_shares(token_shares, now);
+        require_or(vault_shares > 0, FtError::ZeroAmount);
+
+        self.internal_withdraw_shares(&account_id, token_shares);
+        self.vault.token_shares = self
+            .vault
+            .token_shares
+            .checked_add(token_shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+        self.vault.total_shares = self
+            .vault
+            .total_shares
+            .checked_add(vault_shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+        let new_vault_shares = self.vault_shares.get(&account_id).unwrap_or(0) + vault_shares;
+        self.vault_shares.insert(&account_id, &new_vault_shares);
+
+        // The vault's tokens count as held by the contract account
+        FtTransfer {
+            old_owner_id: &account_id,
+            new_owner_id: &env::current_account_id(),
+            amount: &amount,
+            memo: Some(VAULT_DEPOSIT_MEMO),
+        }
+        .emit();
+
+        U128(vault_shares)
+    }
+
+    /// Moves `amount` tokens out of the vault back to the caller's balance, or everything the caller has in the
+    /// vault if `amount` isn't given. Exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn vault_withdraw(&mut self, amount: Option<NearToken>) -> NearToken {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let now = env::block_timestamp();
+        let account_vault_shares = self.vault_shares.get(&account_id).unwrap_or(0);
+
+        // Work out how many vault shares to burn and how many shares of the supply they pay out
+        let (vault_shares, token_shares) = match amount {
+            Some(amount) => {
+                let token_shares = self.internal_amount_to_shares(amount);
+                (self.vault.to_vault_shares_ceil(token_shares, now), token_shares)
+            }
+            None => (account_vault_shares, self.vault.to_token_shares(account_vault_shares, now)),
+        };
+        require_or(token_shares > 0, FtError::ZeroAmount);
+        let new_vault_shares = account_vault_shares
+            .checked_sub(vault_shares)
+            .unwrap_or_else(|| FtError::InsufficientBalance.panic());
+
+        self.vault.update(now);
+        self.vault.total_shares -= vault_shares;
+        self.vault.token_shares -= token_shares;
+        if new_vault_shares == 0 {
+            self.vault_shares.remove(&account_id);
+        } else {
+            self.vault_shares.insert(&account_id, &new_vault_shares);
+        }
+        self.internal_deposit_shares(&account_id, token_shares);
+
+        let amount = self.internal_shares_to_amount(token_shares);
+        FtTransfer {
+            old_owner_id: &env::current_account_id(),
+            new_owner_id: &account_id,
+            amount: &amount,
+            memo: Some(VAULT_WITHDRAW_MEMO),
+        }
+        .emit();
+
+        amount
+    }
+
+    /// Funds `amount` tokens of interest from the owner's balance. The interest is released to the depositors
+    /// linearly over `duration_sec` seconds, together with whatever is still locked from earlier funding, so
+    /// that depositing right before a funding and withdrawing right after doesn't capture it. Only the owner can
+    /// fund interest and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn vault_fund_interest(&mut self, amount: NearToken, duration_sec: u64) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        require_one_yocto();
+        self.assert_owner();
+        require_or(amount.gt(&ZERO_TOKEN), FtError::ZeroAmount);
+        let now = env::block_timestamp();
+
+        let owner_id = self.owner_id.clone();
+        let token_shares = self.internal_amount_to_shares(amount);
+        self.internal_withdraw_shares(&owner_id, token_shares);
+        self.vault.update(now);
+        self.vault.token_shares = self
+            .vault
+            .token_shares
+            .checked_add(token_shares)
+            .unwrap_or_else(|| FtError::TotalSharesOverflow.panic());
+        self.vault.locked_token_shares += token_shares;
+        self.vault.release_end = now.saturating_add(duration_sec.saturating_mul(1_000_000_000));
+
+        FtTransfer {
+            old_owner_id: &owner_id,
+            new_owner_id: &env::current_account_id(),
+            amount: &amount,
+            memo: Some(VAULT_INTEREST_MEMO),
+        }
+        .emit();
+    }
+
+    /// Returns how many tokens `account_id` can withdraw from the vault right now, interest included.
+    pub fn vault_balance_of(&self, account_id: AccountId) -> NearToken {
+        let vault_shares = self.vault_shares.get(&account_id).unwrap_or(0);
+        self.internal_shares_to_amount(self.vault.to_token_shares(vault_shares, env::block_timestamp()))
+    }
+
+    /// Returns how many vault shares `account_id` holds.
+    pub fn vault_shares_of(&self, account_id: AccountId) -> U128 {
+        U128(self.vault_shares.get(&account_id).unwrap_or(0))
+    }
+
+    /// Returns how many tokens one whole vault share (10^decimals of them) is worth right now.
+    pub fn vault_exchange_rate(&self) -> NearToken {
+        let one = 10u128.pow(self.decimals as u32);
+        let now = env::block_timestamp();
+        if self.vault.total_shares == 0 {
+            return NearToken::from_yoctonear(one);
+        }
+        self.internal_shares_to_amount(mul_div(one, self.vault.released_at(now), self.vault.total_shares))
+    }
+
+    /// Returns the vault's totals.
+    pub fn vault_info(&self) -> VaultInfo {
+        let now = env::block_timestamp();
+        VaultInfo {
+            total_assets: self.internal_shares_to_amount(self.vault.released_at(now)),
+            total_shares: U128(self.vault.total_shares),
+            locked_interest: self.internal_shares_to_amount(self.vault.locked_at(now)),
+            release_end: U64(self.vault.release_end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    /// Creates the contract with alice and bob registered and holding 1000 tokens each
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+        contract.internal_register_account(&alice());
+        contract.internal_register_account(&bob());
+        contract.internal_transfer(&owner(), &alice(), tokens(1000), None);
+        contract.internal_transfer(&owner(), &bob(), tokens(1000), None);
+        contract
+    }
+
+    #[test]
+    fn deposits_and_withdrawals_round_trip() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert_eq!(contract.vault_deposit(tokens(400)), U128(400));
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(600));
+        assert_eq!(contract.vault_balance_of(alice()), tokens(400));
+        // The vault's shares still count towards the total
+        assert!(contract.assert_supply_invariant(U128(TOTAL_SUPPLY - 400)));
+
+        Context::new().predecessor(alice()).one_yocto().set();
+        assert_eq!(contract.vault_withdraw(None), tokens(400));
+        assert_eq!(contract.internal_balance_of(&alice()), tokens(1000));
+        assert_eq!(contract.vault_shares_of(alice()), U128(0));
+    }
+
+    #[test]
+    fn interest_is_released_over_time() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.vault_deposit(tokens(1000));
+
+        Context::new().one_yocto().set();
+        contract.vault_fund_interest(tokens(100), 100);
+        // Nothing is released right away
+        assert_eq!(contract.vault_balance_of(alice()), tokens(1000));
+
+        Context::new().block_timestamp(50 * SECOND).set();
+        assert_eq!(contract.vault_balance_of(alice()), tokens(1050));
+        Context::new().block_timestamp(200 * SECOND).set();
+        assert_eq!(contract.vault_balance_of(alice()), tokens(1100));
+        assert_eq!(contract.vault_exchange_rate(), tokens(11 * 10u128.pow(23)));
+    }
+
+    #[test]
+    fn late_depositors_only_get_later_interest() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.vault_deposit(tokens(1000));
+        Context::new().one_yocto().set();
+        contract.vault_fund_interest(tokens(100), 100);
+
+        // Bob joins when half of the interest is released, at 1.05 tokens per vault share
+        Context::new().predecessor(bob()).one_yocto().block_timestamp(50 * SECOND).set();
+        contract.vault_deposit(tokens(1050));
+
+        Context::new().block_timestamp(100 * SECOND).set();
+        // The other 50 tokens are split evenly between the two
+        assert_eq!(contract.vault_balance_of(alice()), tokens(1075));
+        assert_eq!(contract.vault_balance_of(bob()), tokens(1075));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_BALANCE")]
+    fn cannot_withdraw_more_than_deposited() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.vault_deposit(tokens(400));
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.vault_withdraw(Some(tokens(401)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_funds_interest() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.vault_fund_interest(tokens(100), 100);
+    }
+}
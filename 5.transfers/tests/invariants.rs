@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use fungible_token::ft_core::FungibleTokenCore;
+use fungible_token::storage::StorageManagement;
+use fungible_token::Contract;
+use ft_testing::*;
+use near_sdk::json_types::U128;
+use near_sdk::{AccountId, NearToken, PromiseResult};
+use proptest::prelude::*;
+
+/// The accounts the operations pick from. Only the owner is registered to begin with
+fn account(index: usize) -> AccountId {
+    [owner(), alice(), bob(), charlie(), "dave.near".parse().unwrap()][index].clone()
+}
+
+const ACCOUNTS: usize = 5;
+
+/// How the receiver's `ft_on_transfer` call ends
+#[derive(Debug, Clone)]
+enum Outcome {
+    /// Returns this many tokens as unused. Can be more than were transferred
+    Unused(u128),
+    /// Returns something that isn't an amount
+    Garbage,
+    /// Panics
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Register(usize),
+    Transfer { from: usize, to: usize, amount: u128 },
+    /// `ft_transfer_call` where the receiver moves `spent` tokens elsewhere before the transfer is resolved
+    TransferCall { from: usize, to: usize, amount: u128, spent: u128, outcome: Outcome },
+    Burn(u128),
+}
+
+fn outcome() -> impl Strategy<Value = Outcome> {
+    prop_oneof![
+        any::<u128>().prop_map(Outcome::Unused),
+        (0..TOTAL_SUPPLY).prop_map(Outcome::Unused),
+        Just(Outcome::Garbage),
+        Just(Outcome::Failed),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let index = 0..ACCOUNTS;
+    prop_oneof![
+        index.clone().prop_map(Op::Register),
+        (index.clone(), index.clone(), any::<u128>()).prop_map(|(from, to, amount)| Op::Transfer { from, to, amount }),
+        (index.clone(), index, any::<u128>(), any::<u128>(), outcome())
+            .prop_map(|(from, to, amount, spent, outcome)| Op::TransferCall { from, to, amount, spent, outcome }),
+        any::<u128>().prop_map(Op::Burn),
+    ]
+}
+
+fn balance_of(contract: &Contract, account_id: &AccountId) -> u128 {
+    contract.ft_balance_of(account_id.clone()).as_yoctonear()
+}
+
+fn is_registered(contract: &Contract, account_id: &AccountId) -> bool {
+    contract.accounts.get(account_id).is_some()
+}
+
+/// Scales an arbitrary amount down to somewhere between 1 and `max`, so that the operation is valid
+fn clamp(amount: u128, max: u128) -> u128 {
+    amount % max + 1
+}
+
+fn transfer(contract: &mut Contract, from: &AccountId, to: &AccountId, amount: u128) {
+    Context::new().predecessor(from.clone()).one_yocto().set();
+    contract.ft_transfer(to.clone(), NearToken::from_yoctonear(amount), None);
+}
+
+/// Applies an operation. Operations that would panic are skipped, since the mocked storage isn't rolled back
+/// on a panic the way it would be on-chain
+fn apply(contract: &mut Contract, op: Op) {
+    match op {
+        Op::Register(index) => {
+            Context::new().predecessor(account(index)).attached_deposit(NearToken::from_near(1)).set();
+            contract.storage_deposit(None, None);
+        }
+        Op::Transfer { from, to, amount } => {
+            let (from, to) = (account(from), account(to));
+            let balance = balance_of(contract, &from);
+            if from == to || balance == 0 || !is_registered(contract, &to) {
+                return;
+            }
+            transfer(contract, &from, &to, clamp(amount, balance));
+        }
+        Op::TransferCall { from, to, amount, spent, outcome } => {
+            let (sender_id, receiver_id) = (account(from), account(to));
+            let balance = balance_of(contract, &sender_id);
+            if sender_id == receiver_id || balance == 0 || !is_registered(contract, &receiver_id) {
+                return;
+            }
+            let amount = clamp(amount, balance);
+            let sender_before = balance_of(contract, &sender_id);
+
+            Context::new().predecessor(sender_id.clone()).one_yocto().set();
+            contract.ft_transfer_call(receiver_id.clone(), NearToken::from_yoctonear(amount), None, String::new());
+
+            // Half of the time, the receiver spends some of what it holds on another account before the transfer is resolved
+            let sink = (0..ACCOUNTS)
+                .map(account)
+                .find(|id| id != &receiver_id && id != &sender_id && is_registered(contract, id));
+            let receiver_balance = balance_of(contract, &receiver_id);
+            if let Some(sink) = sink {
+                if spent % 2 == 0 && receiver_balance > 0 {
+                    transfer(contract, &receiver_id, &sink, clamp(spent, receiver_balance));
+                }
+            }
+            let receiver_balance = balance_of(contract, &receiver_id);
+
+            let result = match outcome {
+                Outcome::Unused(unused) => PromiseResult::Successful(format!("\"{}\"", unused).into_bytes()),
+                Outcome::Garbage => PromiseResult::Successful(b"{\"not\": \"an amount\"}".to_vec()),
+                Outcome::Failed => PromiseResult::Failed,
+            };
+            Context::new().cross_contract_caller(ft_testing::contract()).set_with_promise_result(result);
+            let used = contract
+                .ft_resolve_transfer(&sender_id, receiver_id.clone(), NearToken::from_yoctonear(amount))
+                .as_yoctonear();
+
+            // Refunds never exceed the transfer, nor what the receiver still holds
+            let refund = balance_of(contract, &sender_id) + amount - sender_before;
+            assert!(used <= amount, "Used {} of a transfer of {}", used, amount);
+            assert_eq!(refund, amount - used);
+            assert!(refund <= receiver_balance, "Refunded {} but the receiver only held {}", refund, receiver_balance);
+        }
+        Op::Burn(amount) => {
+            let balance = balance_of(contract, &owner());
+            if balance == 0 {
+                return;
+            }
+            Context::new().one_yocto().set();
+            contract.buyback_and_burn(U128(clamp(amount, balance)), None);
+        }
+    }
+}
+
+/// The balances and shares held by the accounts add up to the totals
+fn assert_invariants(contract: &Contract) {
+    let shares: HashMap<AccountId, u128> = contract.accounts.iter().collect();
+    let shares_sum: u128 = shares.values().sum();
+    let balance_sum: u128 = shares.keys().map(|account_id| balance_of(contract, account_id)).sum();
+
+    assert_eq!(shares_sum, contract.total_shares);
+    assert_eq!(balance_sum, contract.total_supply.as_yoctonear());
+    assert_eq!(contract.ft_total_supply().0, contract.total_shares, "Shares and balances are 1:1 without a rebase");
+}
+
+proptest! {
+    #[test]
+    fn operations_preserve_the_supply(ops in prop::collection::vec(op(), 1..40)) {
+        clear_storage();
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner(), U128(TOTAL_SUPPLY));
+
+        for op in ops {
+            apply(&mut contract, op);
+            assert_invariants(&contract);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Wiring the fungible token from `5.transfers` up to the `market-contract`.
+//!
+//! The market never holds an allowance on your FTs. Everything goes through `ft_transfer_call`, which
+//! moves the FTs to the market and then calls `ft_on_transfer` on it with the `msg` you passed in. The
+//! market uses the `msg` to decide what to do with them:
+//!
+//! 1. **Register.** The market and every account that's paid in FTs (sellers, royalty holders) must be
+//!    registered on the FT contract with `storage_deposit`, otherwise the transfers out of the market fail.
+//!    Sellers also pay the market for the storage their listings use with the market's `storage_deposit`.
+//! 2. **List.** The seller approves the market on their NFT with `nft_approve`. The `msg` holds the price
+//!    (see [`sale_msg`]) and the NFT contract calls `nft_on_approve` on the market to list it.
+//! 3. **Deposit.** The buyer calls `ft_transfer_call` on the FT contract with the market as the receiver and
+//!    an empty `msg` ([`DEPOSIT_MSG`]). The FTs are credited to the buyer's deposits on the market
+//!    (`ft_deposits_of`).
+//! 4. **Purchase.** The buyer calls `offer` on the market, which spends their deposits, transfers the NFT
+//!    with `nft_transfer_payout` and pays the seller with `ft_transfer`. Steps 3 and 4 can also be done in
+//!    one call by passing [`purchase_msg`] to `ft_transfer_call`; whatever isn't needed is refunded.
+//! 5. **Withdraw.** Anything left in the buyer's deposits can be sent back with `ft_withdraw`.
+//!
+//! The tests in `tests/` deploy all three contracts to a sandbox and walk through these steps.
+
+use serde_json::json;
+
+/// The `msg` to pass to `ft_transfer_call` to deposit FTs into your balance on the market.
+pub const DEPOSIT_MSG: &str = "";
+
+/// Builds the `msg` to pass to `nft_approve` to list a token on the market for `price` FTs (in the FT's
+/// smallest unit). If no `ft_token_id` is passed in, the market's default FT is used.
+pub fn sale_msg(price: u128, ft_token_id: Option<&str>) -> String {
+    let mut msg = json!({ "sale_conditions": price.to_string() });
+    if let Some(ft_token_id) = ft_token_id {
+        msg["ft_token_id"] = json!(ft_token_id);
+    }
+    msg.to_string()
+}
+
+/// Builds the `msg` to pass to `ft_transfer_call` to buy a listed token straight away with the FTs being
+/// transferred. Any FTs over the price are refunded to the sender.
+pub fn purchase_msg(nft_contract_id: &str, token_id: &str) -> String {
+    json!({
+        "action": "purchase",
+        "nft_contract_id": nft_contract_id,
+        "token_id": token_id,
+    })
+    .to_string()
+}
@@ -0,0 +1,205 @@
+use market_integration::{purchase_msg, sale_msg, DEPOSIT_MSG};
+use near_workspaces::network::Sandbox;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract, Worker};
+use serde_json::json;
+
+const TOKEN_ID: &str = "token-1";
+const PRICE: u128 = 600;
+
+struct Setup {
+    _worker: Worker<Sandbox>,
+    ft: Contract,
+    market: Contract,
+    nft: Contract,
+    seller: Account,
+    buyer: Account,
+}
+
+// Deploys the FT, the market and an NFT contract, registers everyone on the FT and lists `TOKEN_ID` for `PRICE`
+async fn init() -> anyhow::Result<Setup> {
+    let worker = near_workspaces::sandbox().await?;
+    let ft = worker.dev_deploy(&near_workspaces::compile_project("../5.transfers").await?).await?;
+    let market = worker.dev_deploy(&near_workspaces::compile_project("../market-contract").await?).await?;
+    let nft = worker.dev_deploy(&near_workspaces::compile_project("../test-nft").await?).await?;
+
+    let root = worker.root_account()?;
+    let seller = root
+        .create_subaccount("seller")
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+    let buyer = root
+        .create_subaccount("buyer")
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // The FT contract owns the whole supply to start with
+    ft.call("new_default_meta")
+        .args_json(json!({ "owner_id": ft.id(), "total_supply": "1000000" }))
+        .transact()
+        .await?
+        .into_result()?;
+    market
+        .call("new")
+        .args_json(json!({ "owner_id": market.id(), "ft_id": ft.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    nft.call("new")
+        .args_json(json!({ "owner_id": nft.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    market
+        .call("add_approved_nft_contract")
+        .args_json(json!({ "nft_contract_id": nft.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // 1. Register the market, the seller and the buyer on the FT contract
+    for account_id in [market.id(), seller.id(), buyer.id()] {
+        ft.as_account()
+            .call(ft.id(), "storage_deposit")
+            .args_json(json!({ "account_id": account_id }))
+            .deposit(NearToken::from_millinear(100))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+    ft.call("ft_transfer")
+        .args_json(json!({ "receiver_id": buyer.id(), "amount": "1000" }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // 2. The seller pays for their listing's storage, mints a token and lists it by approving the market
+    seller
+        .call(market.id(), "storage_deposit")
+        .deposit(NearToken::from_millinear(100))
+        .transact()
+        .await?
+        .into_result()?;
+    seller
+        .call(nft.id(), "nft_mint")
+        .args_json(json!({
+            "token_id": TOKEN_ID,
+            "receiver_id": seller.id(),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+    seller
+        .call(nft.id(), "nft_approve")
+        .args_json(json!({
+            "token_id": TOKEN_ID,
+            "account_id": market.id(),
+            "msg": sale_msg(PRICE, None),
+        }))
+        .deposit(NearToken::from_millinear(10))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(Setup { _worker: worker, ft, market, nft, seller, buyer })
+}
+
+async fn ft_balance_of(ft: &Contract, account: &Account) -> anyhow::Result<u128> {
+    let balance: String = ft.view("ft_balance_of").args_json(json!({ "account_id": account.id() })).await?.json()?;
+    Ok(balance.parse()?)
+}
+
+async fn ft_deposits_of(setup: &Setup) -> anyhow::Result<u128> {
+    let deposits: String = setup
+        .market
+        .view("ft_deposits_of")
+        .args_json(json!({ "account_id": setup.buyer.id() }))
+        .await?
+        .json()?;
+    Ok(deposits.parse()?)
+}
+
+async fn nft_owner(setup: &Setup) -> anyhow::Result<String> {
+    let token: serde_json::Value = setup.nft.view("nft_token").args_json(json!({ "token_id": TOKEN_ID })).await?.json()?;
+    Ok(token["owner_id"].as_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn deposit_purchase_and_withdraw() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    // 3. The buyer deposits FTs into the market
+    setup
+        .buyer
+        .call(setup.ft.id(), "ft_transfer_call")
+        .args_json(json!({ "receiver_id": setup.market.id(), "amount": "1000", "msg": DEPOSIT_MSG }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    assert_eq!(ft_deposits_of(&setup).await?, 1000);
+    assert_eq!(ft_balance_of(&setup.ft, &setup.buyer).await?, 0);
+
+    // 4. The buyer buys the token with their deposits
+    setup
+        .buyer
+        .call(setup.market.id(), "offer")
+        .args_json(json!({ "nft_contract_id": setup.nft.id(), "token_id": TOKEN_ID, "amount": PRICE.to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    assert_eq!(nft_owner(&setup).await?, setup.buyer.id().to_string());
+    assert_eq!(ft_balance_of(&setup.ft, &setup.seller).await?, PRICE);
+    assert_eq!(ft_deposits_of(&setup).await?, 1000 - PRICE);
+
+    // 5. The buyer withdraws what's left
+    setup
+        .buyer
+        .call(setup.market.id(), "ft_withdraw")
+        .args_json(json!({ "ft_token_id": setup.ft.id(), "amount": (1000 - PRICE).to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    assert_eq!(ft_deposits_of(&setup).await?, 0);
+    assert_eq!(ft_balance_of(&setup.ft, &setup.buyer).await?, 1000 - PRICE);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn purchase_in_one_ft_transfer_call() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    // Steps 3 and 4 at once: the FTs over the price are refunded by ft_transfer_call
+    setup
+        .buyer
+        .call(setup.ft.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": setup.market.id(),
+            "amount": "1000",
+            "msg": purchase_msg(setup.nft.id().as_str(), TOKEN_ID),
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert_eq!(nft_owner(&setup).await?, setup.buyer.id().to_string());
+    assert_eq!(ft_balance_of(&setup.ft, &setup.seller).await?, PRICE);
+    assert_eq!(ft_balance_of(&setup.ft, &setup.buyer).await?, 1000 - PRICE);
+    assert_eq!(ft_deposits_of(&setup).await?, 0);
+
+    Ok(())
+}
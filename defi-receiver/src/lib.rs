@@ -0,0 +1,112 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::serde::Deserialize;
+use near_sdk::{env, near_bindgen, AccountId, BorshStorageKey, Gas, NearToken, PanicOnDefault, PromiseOrValue};
+
+use ft_common::receiver::{accept_all, parse_msg, use_exact};
+
+/// Gas for the delayed call back into this contract before the refund is returned
+const GAS_FOR_RESOLVE_DELAYED: Gas = Gas::from_tgas(5);
+
+pub const ZERO_TOKEN: NearToken = NearToken::from_yoctonear(0);
+
+/// What the receiver should do with the tokens, passed in as the `msg` of `ft_transfer_call`. An empty `msg`
+/// accepts the whole transfer. Examples:
+/// - `{"action": "accept_all"}` keeps every token
+/// - `{"action": "accept_partial", "refund": "50"}` keeps all but 50 tokens
+/// - `{"action": "panic"}` fails the call so the whole transfer is refunded
+/// - `{"action": "delayed", "refund": "50"}` does the same as `accept_partial` after a cross-contract call
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde", tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ReceiverMsg {
+    AcceptAll,
+    AcceptPartial { refund: NearToken },
+    Panic,
+    Delayed { refund: NearToken },
+}
+
+/// An example DeFi contract to call `ft_transfer_call` against. It keeps track of how many tokens it has
+/// accepted from each FT contract so the refunds can be checked.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// The tokens accepted so far, keyed by the FT contract they came from
+    pub received: UnorderedMap<AccountId, NearToken>,
+}
+
+/// Helper structure for keys of the persistent collections.
+#[derive(BorshSerialize, BorshStorageKey)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    Received,
+}
+
+/// The receiver interface from the FT standard. See `5.transfers/src/ft_core.rs` for what's expected of it.
+pub trait FungibleTokenReceiver {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: NearToken, msg: String) -> PromiseOrValue<NearToken>;
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Initializes the contract with nothing received.
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            received: UnorderedMap::new(StorageKey::Received),
+        }
+    }
+
+    /// Returns how many tokens have been accepted from the given FT contract.
+    pub fn ft_received(&self, ft_contract_id: AccountId) -> NearToken {
+        self.received.get(&ft_contract_id).unwrap_or(ZERO_TOKEN)
+    }
+
+    /// Callback for the `delayed` action. Records the tokens that were kept and returns the refund, which the
+    /// FT contract reads as the result of `ft_on_transfer`.
+    #[private]
+    pub fn resolve_delayed(&mut self, ft_contract_id: AccountId, amount: NearToken, refund: NearToken) -> NearToken {
+        self.internal_record(&ft_contract_id, amount.saturating_sub(refund));
+        refund
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// Does whatever the `msg` asks for with the transferred tokens and returns how many should be refunded.
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: NearToken, msg: String) -> PromiseOrValue<NearToken> {
+        // The FT contract is the one calling us
+        let ft_contract_id = env::predecessor_account_id();
+        let action = parse_msg(&msg).unwrap_or(ReceiverMsg::AcceptAll);
+
+        match action {
+            ReceiverMsg::AcceptAll => {
+                self.internal_record(&ft_contract_id, amount);
+                accept_all()
+            }
+            ReceiverMsg::AcceptPartial { refund } => {
+                assert!(refund <= amount, "Can't refund more than was transferred");
+                let used = amount.saturating_sub(refund);
+                self.internal_record(&ft_contract_id, used);
+                use_exact(amount, used)
+            }
+            ReceiverMsg::Panic => env::panic_str(&format!("Rejecting {} tokens from {}", amount.as_yoctonear(), sender_id)),
+            ReceiverMsg::Delayed { refund } => {
+                assert!(refund <= amount, "Can't refund more than was transferred");
+                // The refund is only known once this call resolves, so the FT contract has to wait on it
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_DELAYED)
+                    .resolve_delayed(ft_contract_id, amount, refund)
+                    .into()
+            }
+        }
+    }
+}
+
+impl Contract {
+    /// Adds to the tokens accepted from an FT contract
+    fn internal_record(&mut self, ft_contract_id: &AccountId, amount: NearToken) {
+        let received = self.ft_received(ft_contract_id.clone()).saturating_add(amount);
+        self.received.insert(ft_contract_id, &received);
+    }
+}
@@ -0,0 +1,28 @@
+//! Typed cross-contract interface to the views of a fungible token contract.
+//!
+//! Other contracts can call the views through the generated `ext_ft_views` module instead of building the
+//! function calls by hand, e.g.
+//!
+//! ```ignore
+//! ext_ft_views::ext(ft_contract_id)
+//!     .with_static_gas(Gas::from_tgas(5))
+//!     .storage_balance_of(account_id)
+//!     .then(/* callback reading an Option<StorageBalance> */)
+//! ```
+
+use near_sdk::json_types::U128;
+use near_sdk::{ext_contract, AccountId, NearToken};
+
+use crate::storage::StorageBalance;
+
+#[ext_contract(ext_ft_views)]
+pub trait FtViews {
+    /// Returns the balance of the account, or 0 if it isn't registered
+    fn ft_balance_of(&self, account_id: AccountId) -> NearToken;
+
+    /// Returns the total supply of the token
+    fn ft_total_supply(&self) -> U128;
+
+    /// Returns the storage balance of the account, or `null` if it isn't registered
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+}
@@ -0,0 +1,17 @@
+//! Types shared by every step of the fungible token tutorial and by the market contract.
+//!
+//! Each step used to carry its own copy of these, and the copies had started to drift apart. They're
+//! defined once here and re-exported from each crate, so the JSON and borsh layouts stay identical everywhere.
+//!
+//! [`receiver`] has helpers for contracts on the other side of `ft_transfer_call`, and [`ext`] has a typed
+//! interface for calling a token's views from another contract.
+
+pub mod events;
+pub mod ext;
+pub mod metadata;
+pub mod receiver;
+pub mod storage;
+
+pub use crate::events::{FtBurn, FtMint, FtTransfer};
+pub use crate::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
+pub use crate::storage::{StorageBalance, StorageBalanceBounds};
@@ -0,0 +1,46 @@
+//! Helpers for writing `ft_on_transfer`.
+//!
+//! The value `ft_on_transfer` returns is the amount of tokens the receiver did *not* use, which the FT contract
+//! hands back to the sender in `ft_resolve_transfer`. It's easy to get this backwards and return the amount that
+//! was kept instead, so these helpers name the outcome rather than the number:
+//!
+//! - [`accept_all`] keeps every token that was transferred
+//! - [`refund_all`] hands every token back to the sender
+//! - [`use_exact`] keeps `used` tokens and hands back the rest
+//!
+//! [`parse_msg`] reads the `msg` the sender passed to `ft_transfer_call` into the receiver's own message type.
+
+use near_sdk::serde::de::DeserializeOwned;
+use near_sdk::{env, NearToken, PromiseOrValue};
+
+/// Keeps every token that was transferred. Nothing is refunded to the sender.
+pub fn accept_all() -> PromiseOrValue<NearToken> {
+    PromiseOrValue::Value(NearToken::from_yoctonear(0))
+}
+
+/// Refunds every token that was transferred, as if the call never happened. Returning this is cheaper than
+/// panicking since the FT contract doesn't have to handle a failed receipt.
+pub fn refund_all(amount: NearToken) -> PromiseOrValue<NearToken> {
+    PromiseOrValue::Value(amount)
+}
+
+/// Keeps `used` out of the `amount` that was transferred and refunds the rest. Panics if `used` is more than
+/// was transferred, since the receiver can't keep tokens it was never sent.
+pub fn use_exact(amount: NearToken, used: NearToken) -> PromiseOrValue<NearToken> {
+    let unused = amount
+        .checked_sub(used)
+        .unwrap_or_else(|| env::panic_str("Can't use more than was transferred"));
+    PromiseOrValue::Value(unused)
+}
+
+/// Parses the `msg` of `ft_transfer_call` into the receiver's message type, usually an enum tagged by action.
+/// Returns `None` for an empty `msg` so the receiver can pick its own default, and panics if the `msg` isn't
+/// valid, which refunds the whole transfer.
+pub fn parse_msg<T: DeserializeOwned>(msg: &str) -> Option<T> {
+    if msg.is_empty() {
+        return None;
+    }
+    let parsed = near_sdk::serde_json::from_str(msg)
+        .unwrap_or_else(|e| env::panic_str(&format!("Invalid msg: {}", e)));
+    Some(parsed)
+}
@@ -0,0 +1,86 @@
+use crate::*;
+
+//what an account has spent, earned and paid in fees in a single currency
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct CurrencyActivity {
+    //the fungible token the totals are in. If it's not set, they're in NEAR
+    pub ft_token_id: Option<FungibleTokenId>,
+    //the total the account paid for the tokens it bought
+    pub spent: NearToken,
+    //the total the account was paid for the tokens it sold, after fees and royalties
+    pub earned: NearToken,
+    //the market's commission and referral fees taken out of the account's sales
+    pub fees_paid: NearToken,
+}
+
+//an account's lifetime trading on the market. Only completed purchases are counted
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountActivity {
+    //the number of tokens the account bought, including bundled tokens and tokens bought as gifts
+    pub items_bought: u64,
+    //the number of tokens the account sold, including bundled tokens
+    pub items_sold: u64,
+    //the totals for every currency the account has traded in
+    pub currencies: Vec<CurrencyActivity>,
+}
+
+impl AccountActivity {
+    //returns the totals for a currency, adding them if the account hasn't traded in it yet
+    fn currency(&mut self, ft_token_id: &Option<FungibleTokenId>) -> &mut CurrencyActivity {
+        let index = match self.currencies.iter().position(|currency| &currency.ft_token_id == ft_token_id) {
+            Some(index) => index,
+            None => {
+                self.currencies.push(CurrencyActivity {
+                    ft_token_id: ft_token_id.clone(),
+                    spent: ZERO_TOKEN,
+                    earned: ZERO_TOKEN,
+                    fees_paid: ZERO_TOKEN,
+                });
+                self.currencies.len() - 1
+            }
+        };
+        &mut self.currencies[index]
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// views
+
+    //returns how much an account has bought and sold on the market over its lifetime
+    pub fn get_account_activity(&self, account_id: AccountId) -> AccountActivity {
+        self.account_activity.get(&account_id).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for recording a completed sale of `items` tokens on the buyer's and the seller's activity.
+    //`earned` is what the seller was paid and `fees` is what was taken out of the price for the market and the referrer
+    pub(crate) fn internal_record_sale(
+        &mut self,
+        buyer_id: &AccountId,
+        seller_id: &AccountId,
+        ft_token_id: &Option<FungibleTokenId>,
+        items: u64,
+        price: NearToken,
+        earned: NearToken,
+        fees: NearToken,
+    ) {
+        let mut buyer_activity = self.account_activity.get(buyer_id).unwrap_or_default();
+        buyer_activity.items_bought += items;
+        let currency = buyer_activity.currency(ft_token_id);
+        currency.spent = currency.spent.saturating_add(price);
+        self.account_activity.insert(buyer_id, &buyer_activity);
+
+        let mut seller_activity = self.account_activity.get(seller_id).unwrap_or_default();
+        seller_activity.items_sold += items;
+        let currency = seller_activity.currency(ft_token_id);
+        currency.earned = currency.earned.saturating_add(earned);
+        currency.fees_paid = currency.fees_paid.saturating_add(fees);
+        self.account_activity.insert(seller_id, &seller_activity);
+    }
+}
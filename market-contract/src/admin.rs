@@ -0,0 +1,118 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //Allows the owner to give an account the admin role. Admins can pause the market and manage the NFT contract whitelist
+    pub fn add_admin(&mut self, account_id: AccountId) {
+        //make sure only the owner can add admins
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can add admins"
+        );
+        self.admin_ids.insert(&account_id);
+    }
+
+    //Allows the owner to take the admin role away from an account
+    pub fn remove_admin(&mut self, account_id: AccountId) {
+        //make sure only the owner can remove admins
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can remove admins"
+        );
+        self.admin_ids.remove(&account_id);
+    }
+
+    //Pauses the market. While paused, tokens can't be listed, offers and bids can't be placed, and FTs can't be withdrawn
+    pub fn pause(&mut self) {
+        self.assert_admin();
+        self.paused = true;
+    }
+
+    //Unpauses the market
+    pub fn unpause(&mut self) {
+        self.assert_admin();
+        self.paused = false;
+    }
+
+    //Allows an admin to add an NFT contract whose tokens can be listed on the market
+    pub fn add_approved_nft_contract(&mut self, nft_contract_id: AccountId) {
+        self.assert_admin();
+        self.approved_nft_contract_ids.insert(&nft_contract_id);
+    }
+
+    //Allows an admin to stop new listings from an NFT contract. Listings that are already up stay on the market
+    pub fn remove_approved_nft_contract(&mut self, nft_contract_id: AccountId) {
+        self.assert_admin();
+        self.approved_nft_contract_ids.remove(&nft_contract_id);
+    }
+
+    //Allows an admin to add fungible tokens that can be used to purchase NFTs on the market
+    pub fn add_approved_ft_token_ids(&mut self, ft_token_ids: Vec<FungibleTokenId>) {
+        self.assert_admin();
+
+        //insert each fungible token into the set of approved tokens
+        for ft_token_id in ft_token_ids {
+            self.approved_ft_token_ids.insert(&ft_token_id);
+        }
+    }
+
+    //Allows an admin to stop fungible tokens from being used for new listings and payments. FTs that were already
+    //deposited can still be withdrawn, and tokens sent with ft_transfer_call are returned to the sender
+    pub fn remove_approved_ft_token_ids(&mut self, ft_token_ids: Vec<FungibleTokenId>) {
+        self.assert_admin();
+
+        for ft_token_id in ft_token_ids {
+            //the default FT is what sales are priced in when they don't name one, so it always stays approved
+            assert_ne!(ft_token_id, self.ft_id, "The default FT can't be removed");
+            self.approved_ft_token_ids.remove(&ft_token_id);
+        }
+    }
+
+    /// views
+
+    //returns whether the market is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    //returns whether an account is an admin (the owner always is)
+    pub fn is_admin(&self, account_id: AccountId) -> bool {
+        account_id == self.owner_id || self.admin_ids.contains(&account_id)
+    }
+
+    //returns the fungible tokens that can be used to purchase NFTs on the market
+    pub fn get_approved_ft_token_ids(&self) -> Vec<FungibleTokenId> {
+        self.approved_ft_token_ids.to_vec()
+    }
+
+    //returns the NFT contracts whose tokens can be listed on the market
+    pub fn get_approved_nft_contracts(&self) -> Vec<AccountId> {
+        self.approved_nft_contract_ids.to_vec()
+    }
+}
+
+impl Contract {
+    //internal method for making sure the caller is the owner or an admin
+    pub(crate) fn assert_admin(&self) {
+        assert!(
+            self.is_admin(env::predecessor_account_id()),
+            "Only an admin can call this method"
+        );
+    }
+
+    //internal method for making sure the market isn't paused
+    pub(crate) fn assert_not_paused(&self) {
+        assert!(!self.paused, "The market is paused");
+    }
+
+    //internal method for making sure tokens from an NFT contract can be listed on the market
+    pub(crate) fn assert_approved_nft_contract(&self, nft_contract_id: &AccountId) {
+        assert!(
+            self.approved_nft_contract_ids.contains(nft_contract_id),
+            "NFT contract {} is not approved for listings",
+            nft_contract_id
+        );
+    }
+}
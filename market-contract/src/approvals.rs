@@ -0,0 +1,117 @@
+use near_sdk::{PromiseOrValue, PromiseResult};
+
+use crate::*;
+
+/*
+    An owner can revoke the market's approval (or transfer the token) without taking their listing down. Purchases
+    check the approval on the NFT contract before the buyer is charged for a transfer that's bound to fail, and
+    anyone can take a listing down once its approval is gone.
+*/
+#[near_bindgen]
+impl Contract {
+    //private method used to resolve the approval check made before a purchase. If the market is still approved,
+    //the token is transferred and the seller paid. Otherwise the buyer is refunded
+    #[private]
+    pub fn resolve_approval_check(
+        &mut self,
+        purchase: Purchase,
+        approval_id: u64,
+        memo: String,
+    ) -> PromiseOrValue<NearToken> {
+        if parse_is_approved() {
+            return PromiseOrValue::Promise(self.internal_transfer_with_payout(purchase, approval_id, &memo));
+        }
+
+        //the listing was already taken off the market so all that's left is to give the buyer their money back
+        env::log_str(&format!(
+            "The market is no longer approved on token {} on {}. Refunding {}",
+            purchase.token_id, purchase.nft_contract_id, purchase.buyer_id
+        ));
+        self.internal_refund_purchase(&purchase);
+        PromiseOrValue::Value(ZERO_TOKEN)
+    }
+
+    /// Take down a sale or auction whose approval has been revoked or whose token has changed hands. Anyone can call
+    /// this. If the auction had a bid, the bidder gets their FTs back in their deposits. Returns whether the listing was removed
+    pub fn remove_stale_listing(&mut self, nft_contract_id: AccountId, token_id: TokenId) -> Promise {
+        //get the approval ID the listing was made with
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let approval_id = self
+            .sales
+            .get(&contract_and_token_id)
+            .map(|sale| sale.approval_id)
+            .or_else(|| self.auctions.get(&contract_and_token_id).map(|auction| auction.approval_id))
+            .expect("No listing");
+
+        //ask the NFT contract whether the market is still approved with that approval ID
+        ext_nft_contract::ext(nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_IS_APPROVED)
+            .nft_is_approved(token_id.clone(), env::current_account_id(), Some(approval_id))
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the check. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_STALE_LISTING)
+            .resolve_stale_listing(nft_contract_id, token_id, approval_id)
+        )
+    }
+
+    //private method used to resolve remove_stale_listing. The listing is only removed if it's still the one that was checked
+    #[private]
+    pub fn resolve_stale_listing(&mut self, nft_contract_id: AccountId, token_id: TokenId, approval_id: u64) -> bool {
+        if parse_is_approved() {
+            return false;
+        }
+
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        if self.sales.get(&contract_and_token_id).map(|sale| sale.approval_id) == Some(approval_id) {
+            let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
+            SaleRemoved {
+                owner_id: &sale.owner_id,
+                nft_contract_id: &nft_contract_id,
+                token_id: &token_id,
+            }
+            .emit();
+            return true;
+        }
+
+        if self.auctions.get(&contract_and_token_id).map(|auction| auction.approval_id) == Some(approval_id) {
+            let auction = self.internal_remove_auction(nft_contract_id, token_id);
+            //release the highest bid back into the bidder's deposits
+            if let Some(bid) = auction.highest_bid {
+                self.internal_increase_ft_deposit(&bid.bidder_id, &auction.ft_token_id, bid.amount);
+            }
+            return true;
+        }
+
+        false
+    }
+}
+
+impl Contract {
+    //internal method for transferring a token through nft_transfer_payout once the NFT contract confirms the market is
+    //still approved on it. Used when the listing may have been up long enough for the approval to be revoked
+    pub(crate) fn internal_checked_transfer_with_payout(
+        &mut self,
+        purchase: Purchase,
+        approval_id: u64,
+        memo: &str,
+    ) -> Promise {
+        ext_nft_contract::ext(purchase.nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_IS_APPROVED)
+            .nft_is_approved(purchase.token_id.clone(), env::current_account_id(), Some(approval_id))
+        .then(
+            // No attached deposit with static GAS equal to the GAS for the transfer and payout. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(self.gas_config.resolve_approval_check())
+            .resolve_approval_check(purchase, approval_id, memo.to_string())
+        )
+    }
+}
+
+//reads the result of an nft_is_approved call. If the call failed, the market is treated as not approved
+fn parse_is_approved() -> bool {
+    match env::promise_result(0) {
+        PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(false),
+        PromiseResult::Failed => false,
+    }
+}
@@ -0,0 +1,475 @@
+use crate::*;
+
+//the highest bid placed on an auction so far
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bid {
+    //account that placed the bid
+    pub bidder_id: AccountId,
+    //amount of FTs escrowed for the bid
+    pub amount: SalePriceInFTs,
+}
+
+//how much a new bid has to beat the highest bid by
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum BidIncrement {
+    //a fixed amount of FTs
+    Absolute(SalePriceInFTs),
+    //a share of the highest bid, in basis points
+    Bps(u16),
+}
+
+//optional rules a seller can set when putting a token up for auction. Auctions without rules accept any bid at or
+//above the reserve price that beats the highest bid
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionRules {
+    //the lowest first bid. If it's below the reserve price, bidding can start below the reserve but the sale is
+    //voided at settlement unless the winning bid reaches it. If it's not set, bidding starts at the reserve price
+    pub starting_price: Option<SalePriceInFTs>,
+    //how much each bid has to beat the highest bid by. If it's not set, any higher bid is accepted
+    pub min_bid_increment: Option<BidIncrement>,
+}
+
+//struct that holds important information about each auction on the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+    //owner of the auction
+    pub owner_id: AccountId,
+    //market contract's approval ID to transfer the token on behalf of the owner
+    pub approval_id: u64,
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID being auctioned
+    pub token_id: TokenId,
+    //fungible token that bids have to be placed in
+    pub ft_token_id: FungibleTokenId,
+    //the lowest winning bid the token will be sold for
+    pub reserve_price: SalePriceInFTs,
+    //when bidding closes (in nanoseconds)
+    pub end_timestamp: U64,
+    //the highest bid so far. The FTs for this bid are escrowed by the market
+    pub highest_bid: Option<Bid>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Place a bid on an auction. The amount is escrowed from your FT deposits and the previous
+    /// highest bidder gets their bid back in their deposits.
+    #[payable]
+    pub fn place_bid(&mut self, nft_contract_id: AccountId, token_id: TokenId, amount: NearToken) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //bids can't be placed while the market is paused
+        self.assert_not_paused();
+
+        //get the auction object from the unique auction ID. If the auction doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let mut auction = self.auctions.get(&contract_and_token_id).expect("No auction");
+
+        //make sure bidding is still open
+        assert!(
+            env::block_timestamp() < auction.end_timestamp.0,
+            "Auction has ended"
+        );
+
+        //get the bidder ID which is the person who called the function and make sure they're not the owner of the auction
+        let bidder_id = env::predecessor_account_id();
+        assert_ne!(auction.owner_id, bidder_id, "Cannot bid on your own auction.");
+        self.assert_trading_enabled(&nft_contract_id);
+
+        //make sure the bid is at least the starting price and beats the current highest bid by the minimum increment
+        let next_valid_bid = self.internal_next_valid_bid(&contract_and_token_id, &auction);
+        assert!(
+            amount.ge(&next_valid_bid),
+            "Bid must be greater than or equal to the next valid bid: {}",
+            next_valid_bid
+        );
+
+        //escrow the bid from the bidder's deposits. This panics if they haven't deposited enough
+        self.internal_decrease_ft_deposit(&bidder_id, &auction.ft_token_id, amount);
+
+        //refund the previous highest bidder into their deposits
+        if let Some(previous_bid) = auction.highest_bid.take() {
+            self.internal_increase_ft_deposit(
+                &previous_bid.bidder_id,
+                &auction.ft_token_id,
+                previous_bid.amount,
+            );
+        }
+
+        //record the new highest bid and insert the auction back into the map
+        auction.highest_bid = Some(Bid { bidder_id, amount });
+        self.auctions.insert(&contract_and_token_id, &auction);
+    }
+
+    /// Settle an auction once bidding has closed. Anyone can call this. If there was a winning bid,
+    /// the token is transferred to the winner and the seller is paid. If the winning bid is below the reserve price, the
+    /// sale is voided and the bid goes back to the bidder's deposits. Otherwise the auction is simply removed.
+    pub fn settle_auction(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //get the auction object as the return value from removing the auction internally
+        let auction = self.internal_remove_auction(nft_contract_id.clone(), token_id.clone());
+
+        //make sure bidding has closed. If this fails, the removal will revert
+        assert!(
+            env::block_timestamp() >= auction.end_timestamp.0,
+            "Auction has not ended yet"
+        );
+
+        //if nobody bid, there's nothing left to do
+        let Some(winning_bid) = auction.highest_bid else {
+            return;
+        };
+
+        //the sale is voided if bidding never reached the reserve price. The bidder gets their FTs back in their deposits
+        if winning_bid.amount.lt(&auction.reserve_price) {
+            env::log_str(&format!(
+                "Auction for token {} on {} ended below the reserve price. Refunding {}",
+                auction.token_id, auction.nft_contract_id, winning_bid.bidder_id
+            ));
+            self.internal_increase_ft_deposit(&winning_bid.bidder_id, &auction.ft_token_id, winning_bid.amount);
+            return;
+        }
+
+        //check the market is still approved on the token and then initiate the transfer and payout. The winner and bid are passed
+        //along incase something goes wrong and we need to refund them
+        self.internal_checked_transfer_with_payout(
+            Purchase {
+                nft_contract_id,
+                token_id,
+                seller_id: auction.owner_id,
+                buyer_id: winning_bid.bidder_id,
+                receiver_id: None,
+                ft_token_id: Some(auction.ft_token_id),
+                price: winning_bid.amount,
+                //the market's commission comes off the top. Royalties are worked out on what's left
+                protocol_fee: self.internal_protocol_fee(winning_bid.amount),
+                referrer_id: None,
+                referral_fee: ZERO_TOKEN,
+            },
+            auction.approval_id,
+            "auction won on market",
+        );
+    }
+
+    //removes an auction from the market. This can only be done by the owner before anyone has bid.
+    #[payable]
+    pub fn remove_auction(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //get the auction object as the return value from removing the auction internally
+        let auction = self.internal_remove_auction(nft_contract_id, token_id);
+        //make sure the caller is the owner of the auction and nobody has bid yet. If this fails, the removal will revert
+        assert_eq!(env::predecessor_account_id(), auction.owner_id, "Must be auction owner");
+        assert!(auction.highest_bid.is_none(), "Cannot remove an auction that has bids");
+    }
+
+    /// views
+
+    //get the auction information for a given nft contract and token ID
+    pub fn get_auction(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Auction> {
+        self.auctions.get(&(nft_contract_id.clone(), token_id.clone()))
+    }
+
+    //get the highest bid placed on an auction so far
+    pub fn get_highest_bid(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Bid> {
+        self.auctions.get(&(nft_contract_id, token_id)).and_then(|auction| auction.highest_bid)
+    }
+
+    //get the lowest amount the next bid on an auction can be
+    pub fn get_next_valid_bid(&self, nft_contract_id: AccountId, token_id: TokenId) -> SalePriceInFTs {
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let auction = self.auctions.get(&contract_and_token_id).expect("No auction");
+        self.internal_next_valid_bid(&contract_and_token_id, &auction)
+    }
+
+    //get the starting price and minimum bid increment of an auction, if the seller set any
+    pub fn get_auction_rules(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<AuctionRules> {
+        self.auction_rules.get(&(nft_contract_id, token_id))
+    }
+
+    //returns the number of auctions the marketplace has up (as a string)
+    pub fn get_supply_auctions(&self) -> U64 {
+        U64(self.auctions.len())
+    }
+
+    //returns the number of auctions for a given account (result is a string)
+    pub fn get_supply_auctions_by_owner_id(&self, account_id: AccountId) -> U64 {
+        U64(self.auctions_by_owner_id.get(&account_id).map(|s| s.len()).unwrap_or(0))
+    }
+
+    //returns paginated auction objects for a given NFT contract. (result is a vector of auctions)
+    pub fn get_auctions_by_nft_contract_id(
+        &self,
+        nft_contract_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<Auction> {
+        //get the set of token IDs up for auction for the given NFT contract. If there's no set, there are no auctions
+        let Some(by_nft_contract_id) = self.auctions_by_nft_contract_id.get(&nft_contract_id) else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_nft_contract_id
+            .as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //we'll map the token IDs into Auction objects
+            .map(|token_id| self.auctions.get(&(nft_contract_id.clone(), token_id)).unwrap())
+            .collect()
+    }
+
+    //returns paginated auction objects. (result is a vector of auctions)
+    pub fn get_auctions(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<Auction> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.auctions
+            .values_as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+}
+
+impl Contract {
+    //internal method for listing an auction. Called from nft_on_approve once the caller and storage have been checked
+    pub(crate) fn internal_list_auction(&mut self, auction: Auction) {
+        let contract_and_token_id =
+            (auction.nft_contract_id.clone(), auction.token_id.clone());
+
+        //a token can only be listed one way at a time
+        assert!(
+            self.sales.get(&contract_and_token_id).is_none(),
+            "Token is already listed for sale"
+        );
+        assert!(
+            self.rentals.get(&contract_and_token_id).is_none(),
+            "Token is already listed for rent"
+        );
+        //make sure bidding closes in the future
+        assert!(
+            auction.end_timestamp.0 > env::block_timestamp(),
+            "Auction must end in the future"
+        );
+
+        //if the token was already up for auction, the new approval replaces it (but not while there are bids)
+        if let Some(existing) = self.auctions.get(&contract_and_token_id) {
+            assert!(existing.highest_bid.is_none(), "Token is already up for auction with bids");
+            self.internal_remove_auction(auction.nft_contract_id.clone(), auction.token_id.clone());
+        }
+
+        //get the auctions by owner ID for the given owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.auctions_by_owner_id.get(&auction.owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::AuctionsByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&auction.owner_id),
+                }
+            )
+        });
+        //insert the unique auction ID into the set and the set back into the collection for the owner
+        by_owner_id.insert(&contract_and_token_id);
+        self.auctions_by_owner_id.insert(&auction.owner_id, &by_owner_id);
+        //add the token to the auctions for its NFT contract
+        self.internal_index_auction_by_nft_contract(&auction);
+
+        //insert the key value pair into the auctions map. Key is the unique ID. value is the auction object
+        self.auctions.insert(&contract_and_token_id, &auction);
+    }
+
+    //internal method for removing an auction from the market. This returns the removed auction object
+    pub(crate) fn internal_remove_auction(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+    ) -> Auction {
+        //get the auction object by removing the unique auction ID. If there was no auction, panic
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let auction = self.auctions.remove(&contract_and_token_id).expect("No auction");
+
+        //remove the unique auction ID from the owner's set, and the set itself if it's now empty
+        let mut by_owner_id = self
+            .auctions_by_owner_id
+            .get(&auction.owner_id)
+            .expect("No auction by_owner_id");
+        by_owner_id.remove(&contract_and_token_id);
+        if by_owner_id.is_empty() {
+            self.auctions_by_owner_id.remove(&auction.owner_id);
+        } else {
+            self.auctions_by_owner_id.insert(&auction.owner_id, &by_owner_id);
+        }
+
+        //remove the token ID from the NFT contract's set, and the set itself if it's now empty
+        if let Some(mut by_nft_contract_id) = self.auctions_by_nft_contract_id.get(&nft_contract_id) {
+            by_nft_contract_id.remove(&token_id);
+            if by_nft_contract_id.is_empty() {
+                self.auctions_by_nft_contract_id.remove(&nft_contract_id);
+            } else {
+                self.auctions_by_nft_contract_id.insert(&nft_contract_id, &by_nft_contract_id);
+            }
+        }
+
+        //the auction's rules go with it
+        self.auction_rules.remove(&contract_and_token_id);
+
+        //return the auction object
+        auction
+    }
+
+    //internal method for working out the lowest amount the next bid on an auction can be. The first bid has to be at
+    //least the starting price and every bid after that has to beat the highest bid by the minimum increment
+    pub(crate) fn internal_next_valid_bid(
+        &self,
+        contract_and_token_id: &ContractAndTokenId,
+        auction: &Auction,
+    ) -> SalePriceInFTs {
+        let rules = self.auction_rules.get(contract_and_token_id);
+        let Some(highest_bid) = &auction.highest_bid else {
+            return rules.and_then(|rules| rules.starting_price).unwrap_or(auction.reserve_price);
+        };
+
+        let increment = match rules.and_then(|rules| rules.min_bid_increment) {
+            Some(BidIncrement::Absolute(amount)) => amount,
+            Some(BidIncrement::Bps(bps)) => NearToken::from_yoctonear(
+                highest_bid.amount.as_yoctonear() / 10_000 * bps as u128
+                    + highest_bid.amount.as_yoctonear() % 10_000 * bps as u128 / 10_000,
+            ),
+            None => ZERO_TOKEN,
+        };
+        //a new bid always has to be higher than the highest bid, even if the increment rounds down to nothing
+        highest_bid.amount.saturating_add(increment.max(NearToken::from_yoctonear(1)))
+    }
+
+    //internal method for adding an auction's token ID to the auctions for its NFT contract
+    pub(crate) fn internal_index_auction_by_nft_contract(&mut self, auction: &Auction) {
+        //get the token IDs up for auction for the NFT contract. If there are none, we create a new empty set
+        let mut by_nft_contract_id = self
+            .auctions_by_nft_contract_id
+            .get(&auction.nft_contract_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::AuctionsByNFTContractIdInner {
+                        //we get a new unique prefix for the collection by hashing the NFT contract
+                        account_id_hash: hash_account_id(&auction.nft_contract_id),
+                    }
+                )
+            });
+        by_nft_contract_id.insert(&auction.token_id);
+        self.auctions_by_nft_contract_id.insert(&auction.nft_contract_id, &by_nft_contract_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    fn ft() -> AccountId {
+        "token.near".parse().unwrap()
+    }
+
+    fn nft() -> AccountId {
+        "nft.near".parse().unwrap()
+    }
+
+    //creates the market with alice auctioning token 1 until 1000ns with a reserve price of 100 FTs. Bidding starts at
+    //50 and each bid has to beat the highest by 10%. Bob and charlie hold 1000 FTs in deposits
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new(owner(), ft());
+        contract.approved_nft_contract_ids.insert(&nft());
+        contract.internal_list_auction(Auction {
+            owner_id: alice(),
+            approval_id: 0,
+            nft_contract_id: nft(),
+            token_id: "1".to_string(),
+            ft_token_id: ft(),
+            reserve_price: tokens(100),
+            end_timestamp: U64(1_000),
+            highest_bid: None,
+        });
+        contract.auction_rules.insert(
+            &(nft(), "1".to_string()),
+            &AuctionRules {
+                starting_price: Some(tokens(50)),
+                min_bid_increment: Some(BidIncrement::Bps(1_000)),
+            },
+        );
+        contract.internal_increase_ft_deposit(&bob(), &ft(), tokens(1000));
+        contract.internal_increase_ft_deposit(&charlie(), &ft(), tokens(1000));
+        contract
+    }
+
+    fn place_bid(contract: &mut Contract, bidder_id: AccountId, amount: NearToken) {
+        Context::new().predecessor(bidder_id).one_yocto().set();
+        contract.place_bid(nft(), "1".to_string(), amount);
+    }
+
+    fn settle(contract: &mut Contract) {
+        Context::new().predecessor(charlie()).block_timestamp(1_000).set();
+        contract.settle_auction(nft(), "1".to_string());
+    }
+
+    #[test]
+    fn outbid_bidders_are_refunded() {
+        let mut contract = setup();
+
+        place_bid(&mut contract, bob(), tokens(60));
+        assert_eq!(contract.get_next_valid_bid(nft(), "1".to_string()), tokens(66));
+        place_bid(&mut contract, charlie(), tokens(66));
+
+        assert_eq!(contract.get_highest_bid(nft(), "1".to_string()).unwrap().bidder_id, charlie());
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(1000));
+        assert_eq!(contract.internal_ft_deposit_of(&charlie(), &ft()), tokens(934));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid must be greater than or equal to the next valid bid")]
+    fn bids_have_to_beat_the_highest_by_the_increment() {
+        let mut contract = setup();
+
+        place_bid(&mut contract, bob(), tokens(60));
+        place_bid(&mut contract, charlie(), tokens(65));
+    }
+
+    #[test]
+    fn auctions_below_the_reserve_are_voided() {
+        let mut contract = setup();
+        place_bid(&mut contract, bob(), tokens(60));
+
+        settle(&mut contract);
+
+        assert!(contract.get_auction(nft(), "1".to_string()).is_none());
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(1000));
+    }
+
+    #[test]
+    fn auctions_at_the_reserve_are_sold() {
+        let mut contract = setup();
+        place_bid(&mut contract, bob(), tokens(100));
+
+        settle(&mut contract);
+
+        //the bid stays escrowed while the token is transferred
+        assert!(contract.get_auction(nft(), "1".to_string()).is_none());
+        assert!(contract.get_auction_rules(nft(), "1".to_string()).is_none());
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(900));
+    }
+}
@@ -0,0 +1,203 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+use crate::nft_callbacks::SaleArgs;
+use crate::storage::StorageBalance;
+
+//the most listings that can be added or removed in a single call so that we don't run out of GAS
+pub const MAX_BATCH_SIZE: usize = 20;
+
+//a token to list in a batch. The market has to already be approved on the token with the given approval ID
+#[derive(Serialize, Deserialize, NearSchema, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchListing {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID to list
+    pub token_id: TokenId,
+    //market contract's approval ID on the token
+    pub approval_id: u64,
+    //the sale conditions, same as the msg passed to nft_approve for a sale
+    pub sale_args: SaleArgs,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// List several tokens for sale at once. The market has to already be approved on every token (for example
+    /// with nft_approve and no msg). Each token's owner and approval are checked on its NFT contract before
+    /// it's listed, and tokens that don't check out are skipped. Returns the unique sale IDs that were listed.
+    pub fn list_sales(&mut self, listings: Vec<BatchListing>) -> Promise {
+        //make sure the market is taking listings
+        self.assert_not_paused();
+        assert!(!listings.is_empty(), "No listings passed in");
+        assert!(
+            listings.len() <= MAX_BATCH_SIZE,
+            "Cannot list more than {} tokens at once",
+            MAX_BATCH_SIZE
+        );
+
+        //the seller is always the caller
+        let seller_id = env::predecessor_account_id();
+
+        //check everything we can before making the cross contract calls
+        for listing in &listings {
+            self.assert_approved_nft_contract(&listing.nft_contract_id);
+            let ft_token_id = self.internal_validate_sale_args(&listing.nft_contract_id, &listing.sale_args);
+            //we don't check FT registrations in a batch so the seller has to be known to be registered already
+            if listing.sale_args.sale_conditions.is_some() {
+                assert!(
+                    self.internal_is_registered_seller(&seller_id, &ft_token_id),
+                    "Seller is not known to be registered on {}. Call verify_ft_registration first",
+                    ft_token_id
+                );
+            }
+        }
+        self.assert_storage_for_listings(&seller_id, listings.len() as u64);
+
+        //get every token from its NFT contract so that we can check the owner and approval
+        let mut tokens: Option<Promise> = None;
+        for listing in &listings {
+            let token = ext_nft_contract::ext(listing.nft_contract_id.clone())
+                .with_static_gas(GAS_FOR_NFT_TOKEN)
+                .nft_token(listing.token_id.clone());
+            tokens = Some(match tokens {
+                Some(tokens) => tokens.and(token),
+                None => token,
+            });
+        }
+
+        //once we have all the tokens, we list the ones that belong to the seller and have approved the market
+        let resolve_gas = Gas::from_gas(GAS_FOR_RESOLVE_LIST_SALE.as_gas() * listings.len() as u64);
+        tokens.unwrap().then(
+            // No attached deposit with static GAS for resolving each listing. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(resolve_gas)
+            .resolve_list_sales(seller_id, listings)
+        )
+    }
+
+    //private method used to resolve the nft_token calls made by list_sales. Lists every token that belongs to the seller
+    //and has approved the market with the given approval ID
+    #[private]
+    pub fn resolve_list_sales(
+        &mut self,
+        seller_id: AccountId,
+        listings: Vec<BatchListing>,
+    ) -> Vec<ContractAndTokenId> {
+        let market_id = env::current_account_id();
+
+        //check the result for each token
+        let mut verified = vec![];
+        for (index, listing) in listings.into_iter().enumerate() {
+            let token = match env::promise_result(index as u64) {
+                PromiseResult::Successful(value) => {
+                    near_sdk::serde_json::from_slice::<Option<JsonToken>>(&value).ok().flatten()
+                }
+                PromiseResult::Failed => None,
+            };
+
+            let is_valid = token
+                .map(|token| {
+                    token.owner_id == seller_id
+                        && token.approved_account_ids.get(&market_id) == Some(&listing.approval_id)
+                })
+                .unwrap_or(false);
+            if is_valid {
+                verified.push(listing);
+            } else {
+                env::log_str(&format!(
+                    "Skipping token {} on {}: not owned by {} or the market isn't approved",
+                    listing.token_id, listing.nft_contract_id, seller_id
+                ));
+            }
+        }
+
+        //the seller may have withdrawn storage since list_sales was called so we check it again
+        self.assert_storage_for_listings(&seller_id, verified.len() as u64);
+
+        //list each verified token
+        let sales: Vec<Sale> = verified
+            .into_iter()
+            .map(|listing| {
+                self.internal_list_sale(
+                    seller_id.clone(),
+                    listing.approval_id,
+                    listing.nft_contract_id,
+                    listing.token_id,
+                    listing.sale_args,
+                )
+            })
+            .collect();
+
+        //let indexers know about all the new listings in a single event
+        let events: Vec<SaleListed> = sales
+            .iter()
+            .map(|sale| SaleListed {
+                owner_id: &sale.owner_id,
+                nft_contract_id: &sale.nft_contract_id,
+                token_id: &sale.token_id,
+                ft_token_id: &sale.sale_conditions.ft_token_id,
+                price: &sale.sale_conditions.price,
+                near_price: sale.sale_conditions.near_price.as_ref(),
+                quantity: None,
+            })
+            .collect();
+        if !events.is_empty() {
+            SaleListed::emit_many(&events);
+        }
+
+        //return the unique sale IDs that were listed
+        sales
+            .iter()
+            .map(|sale| (sale.nft_contract_id.clone(), sale.token_id.clone()))
+            .collect()
+    }
+
+    /// Remove several of your sales from the market at once. The storage that the sales were using is
+    /// refunded in a single transfer.
+    #[payable]
+    pub fn remove_sales(&mut self, sales: Vec<(AccountId, TokenId)>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        assert!(!sales.is_empty(), "No sales passed in");
+        assert!(
+            sales.len() <= MAX_BATCH_SIZE,
+            "Cannot remove more than {} sales at once",
+            MAX_BATCH_SIZE
+        );
+
+        //the owner of the sales is always the caller
+        let owner_id = env::predecessor_account_id();
+
+        //remove each sale and make sure it belongs to the caller. If this fails, all the removals will revert
+        for (nft_contract_id, token_id) in &sales {
+            let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
+            assert_eq!(owner_id, sale.owner_id, "Must be sale owner");
+        }
+
+        //let indexers know about all the removals in a single event
+        let events: Vec<SaleRemoved> = sales
+            .iter()
+            .map(|(nft_contract_id, token_id)| SaleRemoved {
+                owner_id: &owner_id,
+                nft_contract_id,
+                token_id,
+            })
+            .collect();
+        SaleRemoved::emit_many(&events);
+
+        //refund the storage the sales were using (as long as it isn't needed by the owner's other listings)
+        let freed = storage_per_sale().saturating_mul(sales.len() as u128);
+        let StorageBalance { total, available } = self.internal_storage_balance_of(&owner_id);
+        let refund = if freed.lt(&available) { freed } else { available };
+        if refund.gt(&ZERO_TOKEN) {
+            let remaining = total.saturating_sub(refund);
+            if remaining.gt(&ZERO_TOKEN) {
+                self.storage_deposits.insert(&owner_id, &remaining);
+            } else {
+                self.storage_deposits.remove(&owner_id);
+            }
+            Promise::new(owner_id).transfer(refund);
+        }
+    }
+}
@@ -0,0 +1,559 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+
+//the most tokens that can be sold in a single bundle so that buying it fits in the GAS limit
+pub const MAX_BUNDLE_SIZE: usize = 5;
+
+//a token in a bundle along with the market's approval ID on it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct BundleItem {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID in the bundle
+    pub token_id: TokenId,
+    //market contract's approval ID to transfer the token on behalf of the owner
+    pub approval_id: u64,
+}
+
+//several tokens that are sold together for a single price
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bundle {
+    //unique ID of the bundle
+    pub bundle_id: U64,
+    //owner of the bundle / tokens
+    pub owner_id: AccountId,
+    //tokens in the bundle
+    pub items: Vec<BundleItem>,
+    //fungible token the bundle is priced in
+    pub ft_token_id: FungibleTokenId,
+    //price for the whole bundle
+    pub price: SalePriceInFTs,
+}
+
+impl Bundle {
+    //splits the amount the seller and royalty recipients are paid evenly across the tokens. The first token
+    //takes whatever doesn't split evenly
+    pub fn item_balances(&self, payout_balance: NearToken) -> Vec<NearToken> {
+        let count = self.items.len() as u128;
+        let share = payout_balance.as_yoctonear() / count;
+        let remainder = payout_balance.as_yoctonear() % count;
+        (0..count)
+            .map(|index| NearToken::from_yoctonear(if index == 0 { share + remainder } else { share }))
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// List several tokens to be sold together for a single price. The market has to already be approved on every
+    /// token. Each token's owner and approval are checked on its NFT contract, and the bundle is only listed if
+    /// every token checks out. A bundle takes up the same storage as a listing. Returns the bundle ID.
+    pub fn list_bundle(
+        &mut self,
+        items: Vec<BundleItem>,
+        ft_token_id: Option<FungibleTokenId>,
+        price: NearToken,
+    ) -> Promise {
+        //make sure the market is taking listings
+        self.assert_not_paused();
+        assert!(items.len() > 1, "A bundle needs at least 2 tokens");
+        assert!(
+            items.len() <= MAX_BUNDLE_SIZE,
+            "A bundle can't have more than {} tokens",
+            MAX_BUNDLE_SIZE
+        );
+        assert!(price.gt(&ZERO_TOKEN), "Bundle price must be greater than 0");
+
+        //if no fungible token was passed in, the bundle is priced in the market's default FT
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        self.assert_approved_ft_token_id(&ft_token_id);
+
+        //the owner is always the caller and has to pay for the storage of the bundle
+        let owner_id = env::predecessor_account_id();
+        self.assert_storage_for_listings(&owner_id, 1);
+
+        //get every token from its NFT contract so that we can check the owner and approval
+        let mut tokens: Option<Promise> = None;
+        for (index, item) in items.iter().enumerate() {
+            self.assert_approved_nft_contract(&item.nft_contract_id);
+            //every token has to follow the rules of its own collection
+            let collection_config = self.internal_collection_config(&item.nft_contract_id);
+            collection_config.assert_trading_enabled(&item.nft_contract_id);
+            collection_config.assert_accepts_ft(&ft_token_id);
+            //the same token can't be in a bundle twice
+            assert!(
+                !items[..index]
+                    .iter()
+                    .any(|other| other.nft_contract_id == item.nft_contract_id && other.token_id == item.token_id),
+                "Token {} is in the bundle twice",
+                item.token_id
+            );
+
+            let token = ext_nft_contract::ext(item.nft_contract_id.clone())
+                .with_static_gas(GAS_FOR_NFT_TOKEN)
+                .nft_token(item.token_id.clone());
+            tokens = Some(match tokens {
+                Some(tokens) => tokens.and(token),
+                None => token,
+            });
+        }
+
+        //once we have all the tokens, we list the bundle if they all belong to the owner and have approved the market
+        tokens.unwrap().then(
+            // No attached deposit with static GAS for resolving the listing. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_LIST_SALE)
+            .resolve_list_bundle(owner_id, items, ft_token_id, price)
+        )
+    }
+
+    //private method used to resolve the nft_token calls made by list_bundle. Lists the bundle only if every token
+    //belongs to the owner and has approved the market with the given approval ID
+    #[private]
+    pub fn resolve_list_bundle(
+        &mut self,
+        owner_id: AccountId,
+        items: Vec<BundleItem>,
+        ft_token_id: FungibleTokenId,
+        price: NearToken,
+    ) -> U64 {
+        let market_id = env::current_account_id();
+
+        //check the result for each token. If any of them don't check out, nothing gets listed
+        for (index, item) in items.iter().enumerate() {
+            let token = match env::promise_result(index as u64) {
+                PromiseResult::Successful(value) => {
+                    near_sdk::serde_json::from_slice::<Option<JsonToken>>(&value).ok().flatten()
+                }
+                PromiseResult::Failed => None,
+            };
+            let is_valid = token
+                .map(|token| {
+                    token.owner_id == owner_id
+                        && token.approved_account_ids.get(&market_id) == Some(&item.approval_id)
+                })
+                .unwrap_or(false);
+            assert!(
+                is_valid,
+                "Token {} on {} is not owned by {} or the market isn't approved",
+                item.token_id, item.nft_contract_id, owner_id
+            );
+        }
+
+        //the owner may have withdrawn storage since list_bundle was called so we check it again
+        self.assert_storage_for_listings(&owner_id, 1);
+
+        //record the bundle under a new unique ID
+        let bundle_id = self.next_bundle_id;
+        self.next_bundle_id += 1;
+        self.bundles.insert(
+            &bundle_id,
+            &Bundle {
+                bundle_id: U64(bundle_id),
+                owner_id: owner_id.clone(),
+                items,
+                ft_token_id,
+                price,
+            },
+        );
+
+        //get the bundles for the owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.bundles_by_owner_id.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::BundlesByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+            )
+        });
+        by_owner_id.insert(&bundle_id);
+        self.bundles_by_owner_id.insert(&owner_id, &by_owner_id);
+
+        U64(bundle_id)
+    }
+
+    //removes a bundle from the market. Only the owner of the bundle can remove it
+    #[payable]
+    pub fn remove_bundle(&mut self, bundle_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //get the bundle object as the return value from removing the bundle internally
+        let bundle = self.internal_remove_bundle(bundle_id.0);
+        //if this fails, the removal will revert
+        assert_eq!(env::predecessor_account_id(), bundle.owner_id, "Must be bundle owner");
+    }
+
+    /// Buy a bundle with your FT deposits. Either every token in the bundle is transferred to you or none are.
+    /// The tokens are first moved to the market with `nft_transfer_payout`, so royalties are paid on each of them.
+    /// If every transfer goes through, they're sent on to you and the seller and royalty recipients are paid. If any
+    /// transfer fails, the tokens that made it are sent back to the seller and you're refunded. A token that can't
+    /// be sent on is held by the market until it's claimed with `claim_held_token`.
+    #[payable]
+    pub fn buy_bundle(&mut self, bundle_id: U64) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
+
+        //get the bundle object as the return value from removing the bundle internally
+        let bundle = self.internal_remove_bundle(bundle_id.0);
+
+        //get the buyer ID which is the person who called the function and make sure they're not the owner of the bundle
+        let buyer_id = env::predecessor_account_id();
+        assert_ne!(bundle.owner_id, buyer_id, "Cannot buy your own bundle.");
+        for item in &bundle.items {
+            self.assert_trading_enabled(&item.nft_contract_id);
+        }
+
+        //escrow the price from the buyer's deposits. This panics if they haven't deposited enough
+        self.internal_decrease_ft_deposit(&buyer_id, &bundle.ft_token_id, bundle.price);
+
+        //the market takes its commission from the price and the rest is split across the tokens' payouts
+        let protocol_fee = self.internal_protocol_fee(bundle.price);
+        let item_balances = bundle.item_balances(bundle.price.saturating_sub(protocol_fee));
+
+        //move every token to the market so that the whole bundle can be rolled back if one of the transfers fails.
+        //Each transfer returns the payout for its share of the price
+        let market_id = env::current_account_id();
+        let max_payees = self.internal_bundle_max_payees(&bundle);
+        let mut transfers: Option<Promise> = None;
+        for (item, balance) in bundle.items.iter().zip(item_balances) {
+            let transfer = ext_nft_contract::ext(item.nft_contract_id.clone())
+                // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+                .with_attached_deposit(NearToken::from_yoctonear(1))
+                .with_static_gas(self.gas_config.nft_transfer)
+                .nft_transfer_payout(
+                    market_id.clone(), //the market holds the token until every transfer has gone through
+                    item.token_id.clone(), //token ID to transfer
+                    Some(item.approval_id), //market contract's approval ID in order to transfer the token on behalf of the owner
+                    Some("bundle escrow".to_string()), //memo (to include some context)
+                    U128(balance.as_yoctonear()), //the token's share of the price (after fees)
+                    max_payees, //the maximum amount of accounts the market can payout for the token
+                );
+            transfers = Some(match transfers {
+                Some(transfers) => transfers.and(transfer),
+                None => transfer,
+            });
+        }
+
+        //resolve the bundle once all the transfers are done. This needs enough GAS to send every token on and
+        //resolve those transfers
+        transfers.unwrap().then(
+            // No attached deposit with static GAS for resolving the bundle. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(self.gas_config.resolve_buy_bundle(bundle.items.len()))
+            .resolve_buy_bundle(bundle, buyer_id, protocol_fee)
+        )
+    }
+
+    /// Claim a token the market is holding for you. Bundled tokens are held when the market can't send them on
+    /// after a purchase or a rollback. If the transfer fails again, the token stays claimable.
+    #[payable]
+    pub fn claim_held_token(&mut self, nft_contract_id: AccountId, token_id: TokenId) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let owner_id = self.held_tokens.remove(&contract_and_token_id).expect("No held token");
+        //if this fails, the removal will revert
+        assert_eq!(env::predecessor_account_id(), owner_id, "Only the token's owner can claim it");
+
+        self.internal_send_held_tokens(vec![contract_and_token_id], owner_id, "held token claimed").unwrap()
+    }
+
+    //private method used to resolve the transfers made by buy_bundle. If every token made it to the market, they're
+    //sent on to the buyer and each token's payout is paid. Otherwise the tokens that made it are sent back and the buyer
+    //is refunded. Returns the amount paid for the bundle
+    #[private]
+    pub fn resolve_buy_bundle(&mut self, bundle: Bundle, buyer_id: AccountId, protocol_fee: NearToken) -> NearToken {
+        //find out which tokens the market now holds, along with the payout returned for each of them
+        let payouts: Vec<Option<Vec<u8>>> = (0..bundle.items.len())
+            .map(|index| match env::promise_result(index as u64) {
+                PromiseResult::Successful(value) => Some(value),
+                PromiseResult::Failed => None,
+            })
+            .collect();
+        let all_received = payouts.iter().all(Option::is_some);
+
+        //if everything went through, the tokens go to the buyer. Otherwise the ones that made it go back to the seller
+        let receiver_id = if all_received { &buyer_id } else { &bundle.owner_id };
+        let memo = if all_received { "bundle purchased on market" } else { "bundle purchase rolled back" };
+        let received = bundle
+            .items
+            .iter()
+            .zip(&payouts)
+            .filter(|(_, payout)| payout.is_some())
+            .map(|(item, _)| (item.nft_contract_id.clone(), item.token_id.clone()))
+            .collect();
+        self.internal_send_held_tokens(received, receiver_id.clone(), memo);
+
+        // If the bundle was rolled back, refund the buyer
+        if !all_received {
+            self.internal_increase_ft_deposit(&buyer_id, &bundle.ft_token_id, bundle.price);
+            return ZERO_TOKEN;
+        }
+
+        // The market takes its commission
+        self.internal_increase_fee_balance(&bundle.ft_token_id, protocol_fee);
+
+        // Pay out each token's share of the price. Invalid payouts, payouts with more accounts than the token's share
+        // of the payees and payouts that pay more royalties than the collection allows are ignored and the seller gets
+        // the whole share
+        let ft_token_id = Some(bundle.ft_token_id.clone());
+        let max_payees = self.internal_bundle_max_payees(&bundle) as usize;
+        let item_balances = bundle.item_balances(bundle.price.saturating_sub(protocol_fee));
+        let mut earned = ZERO_TOKEN;
+        for ((item, value), balance) in bundle.items.iter().zip(payouts).zip(item_balances) {
+            let payout = self
+                .internal_parse_payout(&value.unwrap(), balance)
+                .filter(|payout| {
+                    payout.len() <= max_payees
+                        && self
+                            .internal_collection_config(&item.nft_contract_id)
+                            .royalties_within_cap(&bundle.owner_id, payout, balance)
+                })
+                .unwrap_or_else(|| HashMap::from([(bundle.owner_id.clone(), U128(balance.as_yoctonear()))]));
+
+            earned = earned.saturating_add(
+                payout.get(&bundle.owner_id).map(|amount| NearToken::from_yoctonear(amount.0)).unwrap_or(ZERO_TOKEN),
+            );
+            for (receiver_id, amount) in payout {
+                self.internal_send_payment(
+                    &ft_token_id,
+                    receiver_id, //seller or royalty recipient
+                    NearToken::from_yoctonear(amount.0),
+                    "Bundle sale from marketplace",
+                );
+            }
+        }
+
+        self.internal_record_sale(
+            &buyer_id,
+            &bundle.owner_id,
+            &ft_token_id,
+            bundle.items.len() as u64,
+            bundle.price,
+            earned,
+            protocol_fee,
+        );
+
+        bundle.price
+    }
+
+    //private method used to resolve the transfers of tokens the market sends on. Any token that didn't make it is held
+    //for the receiver, who can claim it with claim_held_token
+    #[private]
+    pub fn resolve_held_token_transfers(&mut self, tokens: Vec<ContractAndTokenId>, receiver_id: AccountId) {
+        for (index, contract_and_token_id) in tokens.into_iter().enumerate() {
+            if let PromiseResult::Failed = env::promise_result(index as u64) {
+                env::log_str(&format!(
+                    "Token {} on {} is held by the market for {}",
+                    contract_and_token_id.1, contract_and_token_id.0, receiver_id
+                ));
+                self.held_tokens.insert(&contract_and_token_id, &receiver_id);
+            }
+        }
+    }
+
+    /// views
+
+    //get the bundle for a given bundle ID
+    pub fn get_bundle(&self, bundle_id: U64) -> Option<Bundle> {
+        self.bundles.get(&bundle_id.0)
+    }
+
+    //returns paginated bundles across the whole market. (result is a vector of bundles)
+    pub fn get_bundles(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<Bundle> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.bundles
+            .values_as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    //returns the account that can claim a token the market is holding, if it's holding the token
+    pub fn get_held_token(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<AccountId> {
+        self.held_tokens.get(&(nft_contract_id, token_id))
+    }
+
+    //returns the bundles listed by a given account
+    pub fn get_bundles_by_owner_id(&self, account_id: AccountId) -> Vec<Bundle> {
+        self.bundles_by_owner_id
+            .get(&account_id)
+            .map(|bundle_ids| {
+                bundle_ids
+                    .iter()
+                    .map(|bundle_id| self.bundles.get(&bundle_id).unwrap())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for removing a bundle from the market. This returns the removed bundle object
+    pub(crate) fn internal_remove_bundle(&mut self, bundle_id: u64) -> Bundle {
+        //get the bundle object by removing the bundle ID. If there was no bundle, panic
+        let bundle = self.bundles.remove(&bundle_id).expect("No bundle");
+
+        //remove the bundle ID from the owner's set, and the set itself if it's now empty
+        let mut by_owner_id = self
+            .bundles_by_owner_id
+            .get(&bundle.owner_id)
+            .expect("No bundle by_owner_id");
+        by_owner_id.remove(&bundle_id);
+        if by_owner_id.is_empty() {
+            self.bundles_by_owner_id.remove(&bundle.owner_id);
+        } else {
+            self.bundles_by_owner_id.insert(&bundle.owner_id, &by_owner_id);
+        }
+
+        //return the bundle object
+        bundle
+    }
+
+    //internal method for the most accounts a single token in a bundle can pay out. The bundle as a whole can't pay
+    //out more accounts than a single sale
+    pub(crate) fn internal_bundle_max_payees(&self, bundle: &Bundle) -> u32 {
+        (self.max_payees / bundle.items.len() as u32).max(1)
+    }
+
+    //internal method for sending tokens the market owns to an account. The transfers are resolved so that any
+    //token that doesn't make it is held for the account. Returns None if there were no tokens to send
+    pub(crate) fn internal_send_held_tokens(
+        &self,
+        tokens: Vec<ContractAndTokenId>,
+        receiver_id: AccountId,
+        memo: &str,
+    ) -> Option<Promise> {
+        let mut transfers: Option<Promise> = None;
+        for (nft_contract_id, token_id) in &tokens {
+            let transfer = ext_nft_contract::ext(nft_contract_id.clone())
+                // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+                .with_attached_deposit(NearToken::from_yoctonear(1))
+                .with_static_gas(self.gas_config.nft_transfer)
+                .nft_transfer(
+                    receiver_id.clone(), //account the token is sent to
+                    token_id.clone(), //token ID to transfer
+                    None, //the market owns the token so it doesn't need an approval
+                    Some(memo.to_string()), //memo (to include some context)
+                );
+            transfers = Some(match transfers {
+                Some(transfers) => transfers.and(transfer),
+                None => transfer,
+            });
+        }
+
+        Some(transfers?.then(
+            // No attached deposit with static GAS for resolving the transfers. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_HELD_TOKEN_TRANSFERS)
+            .resolve_held_token_transfers(tokens, receiver_id)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+    use near_sdk::serde_json::json;
+
+    use super::*;
+
+    fn ft() -> AccountId {
+        "token.near".parse().unwrap()
+    }
+
+    fn nft() -> AccountId {
+        "nft.near".parse().unwrap()
+    }
+
+    //the result of an nft_token call for a token alice owns and has approved the market on
+    fn token_result(token_id: &str) -> PromiseResult {
+        let token = json!({ "token_id": token_id, "owner_id": alice(), "approved_account_ids": { contract(): 0 } });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
+    //the result of an nft_transfer_payout call that pays alice the token's whole share
+    fn payout_result(amount: u128) -> PromiseResult {
+        let payout = json!({ "payout": { alice(): amount.to_string() } });
+        PromiseResult::Successful(payout.to_string().into_bytes())
+    }
+
+    //creates the market with alice's bundle of two tokens up for 100 FTs and has bob buy it
+    fn setup() -> (Contract, Bundle) {
+        Context::new().set();
+        let mut contract = Contract::new(owner(), ft());
+        contract.approved_nft_contract_ids.insert(&nft());
+        contract.storage_deposits.insert(&alice(), &NearToken::from_near(1));
+        contract.internal_increase_ft_deposit(&bob(), &ft(), tokens(1000));
+
+        let items = ["1", "2"]
+            .map(|token_id| BundleItem { nft_contract_id: nft(), token_id: token_id.to_string(), approval_id: 0 })
+            .to_vec();
+        Context::new()
+            .predecessor(ft_testing::contract())
+            .set_with_promise_results(vec![token_result("1"), token_result("2")]);
+        let bundle_id = contract.resolve_list_bundle(alice(), items, ft(), tokens(100));
+        let bundle = contract.get_bundle(bundle_id).unwrap();
+
+        Context::new().predecessor(bob()).one_yocto().set();
+        let _ = contract.buy_bundle(bundle_id);
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(900));
+        (contract, bundle)
+    }
+
+    #[test]
+    fn bundles_are_paid_out_once_every_token_is_received() {
+        let (mut contract, bundle) = setup();
+
+        Context::new()
+            .predecessor(ft_testing::contract())
+            .set_with_promise_results(vec![payout_result(50), payout_result(50)]);
+        assert_eq!(contract.resolve_buy_bundle(bundle, bob(), ZERO_TOKEN), tokens(100));
+
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(900));
+        assert!(contract.get_bundles_by_owner_id(alice()).is_empty());
+    }
+
+    #[test]
+    fn bundles_are_rolled_back_if_a_transfer_fails() {
+        let (mut contract, bundle) = setup();
+
+        Context::new()
+            .predecessor(ft_testing::contract())
+            .set_with_promise_results(vec![payout_result(50), PromiseResult::Failed]);
+        assert_eq!(contract.resolve_buy_bundle(bundle, bob(), ZERO_TOKEN), ZERO_TOKEN);
+
+        //bob gets the price back and the token that made it to the market is sent back to alice
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(1000));
+        assert_eq!(contract.fee_balances.get(&ft()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not owned by alice.near or the market isn't approved")]
+    fn bundles_are_only_listed_if_every_token_checks_out() {
+        Context::new().set();
+        let mut contract = Contract::new(owner(), ft());
+        contract.storage_deposits.insert(&alice(), &NearToken::from_near(1));
+
+        let items = ["1", "2"]
+            .map(|token_id| BundleItem { nft_contract_id: nft(), token_id: token_id.to_string(), approval_id: 0 })
+            .to_vec();
+        Context::new()
+            .predecessor(ft_testing::contract())
+            .set_with_promise_results(vec![token_result("1"), PromiseResult::Failed]);
+        contract.resolve_list_bundle(alice(), items, ft(), tokens(100));
+    }
+}
@@ -0,0 +1,119 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //private method used to resolve a payment made out of a purchase (or a claim). If the transfer failed, usually
+    //because the receiver isn't registered on the FT contract, the FTs are kept for the receiver to claim later
+    #[private]
+    pub fn resolve_payment(
+        &mut self,
+        ft_token_id: FungibleTokenId,
+        receiver_id: AccountId,
+        amount: NearToken,
+    ) -> NearToken {
+        match env::promise_result(0) {
+            // If the promise was successful, the receiver got their FTs
+            PromiseResult::Successful(_) => ZERO_TOKEN,
+            // If the promise wasn't successful, the FTs become claimable
+            PromiseResult::Failed => {
+                env::log_str(&format!(
+                    "Could not send {} of {} to {}. The FTs can be claimed once the account is registered",
+                    amount.as_yoctonear(), ft_token_id, receiver_id
+                ));
+                self.internal_increase_claimable(&receiver_id, &ft_token_id, amount);
+                amount
+            }
+        }
+    }
+
+    /// Claim the FTs that couldn't be paid out to you. Make sure you're registered on the FT contract first.
+    /// If the transfer fails again, the FTs stay claimable
+    #[payable]
+    pub fn claim(&mut self, ft_token_id: FungibleTokenId) -> Promise {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        assert_one_yocto();
+        //claims can't be made while the market is paused
+        self.assert_not_paused();
+
+        //take the whole claimable amount for the FT. If anything goes wrong we add it back when we resolve the promise
+        let account_id = env::predecessor_account_id();
+        let amount = self.internal_remove_claimable(&account_id, &ft_token_id);
+        assert!(amount.gt(&ZERO_TOKEN), "Nothing to claim for {}", ft_token_id);
+
+        // Perform the cross contract call to transfer the FTs to the caller
+        ext_ft_contract::ext(ft_token_id.clone())
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .ft_transfer(
+                account_id.clone(), //caller to send the FTs to
+                amount, //amount to transfer
+                Some("Claimed from marketplace".to_string()), //memo (to include some context)
+            )
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the payment. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_PAYMENT)
+            .resolve_payment(
+                ft_token_id, //the FT that was claimed
+                account_id, //caller the FTs were sent to
+                amount, //amount to make claimable again if the transfer failed
+            )
+        )
+    }
+
+    /// views
+
+    //returns the FTs an account can claim, for each fungible token
+    pub fn claimable_of(&self, account_id: AccountId) -> Vec<(FungibleTokenId, NearToken)> {
+        self.claimable
+            .get(&account_id)
+            .map(|claimable| claimable.to_vec())
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for adding to the amount of a given fungible token an account can claim
+    pub(crate) fn internal_increase_claimable(
+        &mut self,
+        account_id: &AccountId,
+        ft_token_id: &FungibleTokenId,
+        amount: NearToken,
+    ) {
+        //get the claimable FTs for the account. If there are none, we create a new empty map
+        let mut claimable = self.claimable.get(account_id).unwrap_or_else(|| {
+            UnorderedMap::new(
+                StorageKey::ClaimableInner {
+                    //we get a new unique prefix for the collection by hashing the account
+                    account_id_hash: hash_account_id(account_id),
+                }
+            )
+        });
+
+        let cur_bal = claimable.get(ft_token_id).unwrap_or(ZERO_TOKEN);
+        claimable.insert(ft_token_id, &cur_bal.saturating_add(amount));
+        self.claimable.insert(account_id, &claimable);
+    }
+
+    //internal method for taking everything an account can claim in a given fungible token. Returns the amount
+    pub(crate) fn internal_remove_claimable(
+        &mut self,
+        account_id: &AccountId,
+        ft_token_id: &FungibleTokenId,
+    ) -> NearToken {
+        let Some(mut claimable) = self.claimable.get(account_id) else {
+            return ZERO_TOKEN;
+        };
+
+        let amount = claimable.remove(ft_token_id).unwrap_or(ZERO_TOKEN);
+        //if the account has nothing left to claim, we remove it from the map. Otherwise we insert the map back
+        if claimable.is_empty() {
+            self.claimable.remove(account_id);
+        } else {
+            self.claimable.insert(account_id, &claimable);
+        }
+        amount
+    }
+}
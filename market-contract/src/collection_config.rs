@@ -0,0 +1,128 @@
+use crate::*;
+
+//the most a royalty cap can be, in basis points (1/100th of a percent)
+const MAX_ROYALTY_BPS: u16 = 10_000;
+
+//rules an admin can set for the tokens of a single NFT contract, so that they can be tuned per collection without
+//redeploying the market. Collections without a config use the defaults, which only apply the market-wide rules
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct CollectionConfig {
+    //the lowest price in FTs tokens can be listed, auctioned or offered on for. If it's not set, any price above 0 goes
+    pub min_price: Option<SalePriceInFTs>,
+    //the lowest price in NEAR tokens can be listed for. If it's not set, any price above 0 goes
+    pub min_near_price: Option<NearToken>,
+    //the fungible tokens the collection can be traded in. If it's not set, every FT approved on the market can be used
+    pub ft_token_ids: Option<Vec<FungibleTokenId>>,
+    //the largest share of a sale that can be paid out as royalties, in basis points. Payouts that go over it are
+    //ignored and the seller is paid everything
+    pub max_royalty_bps: Option<u16>,
+    //whether the collection's tokens can be listed, offered on and bought. Listings that are already up stay on the market
+    pub trading_enabled: bool,
+}
+
+impl Default for CollectionConfig {
+    fn default() -> Self {
+        Self {
+            min_price: None,
+            min_near_price: None,
+            ft_token_ids: None,
+            max_royalty_bps: None,
+            trading_enabled: true,
+        }
+    }
+}
+
+impl CollectionConfig {
+    //makes sure the collection's tokens can be traded
+    pub fn assert_trading_enabled(&self, nft_contract_id: &AccountId) {
+        assert!(self.trading_enabled, "Trading is disabled for {}", nft_contract_id);
+    }
+
+    //makes sure the collection can be traded in a fungible token
+    pub fn assert_accepts_ft(&self, ft_token_id: &FungibleTokenId) {
+        if let Some(ft_token_ids) = &self.ft_token_ids {
+            assert!(
+                ft_token_ids.contains(ft_token_id),
+                "FT {} can't be used for this collection",
+                ft_token_id
+            );
+        }
+    }
+
+    //makes sure a price in FTs is at least the collection's minimum
+    pub fn assert_min_price(&self, price: SalePriceInFTs) {
+        if let Some(min_price) = self.min_price {
+            assert!(price.ge(&min_price), "Price must be at least {} for this collection", min_price);
+        }
+    }
+
+    //makes sure a price in NEAR is at least the collection's minimum
+    pub fn assert_min_near_price(&self, near_price: NearToken) {
+        if let Some(min_near_price) = self.min_near_price {
+            assert!(
+                near_price.ge(&min_near_price),
+                "NEAR price must be at least {} for this collection",
+                min_near_price
+            );
+        }
+    }
+
+    //returns whether a payout for `price` pays no more than the royalty cap to accounts other than the seller
+    pub fn royalties_within_cap(&self, seller_id: &AccountId, payout: &HashMap<AccountId, U128>, price: NearToken) -> bool {
+        let Some(max_royalty_bps) = self.max_royalty_bps else {
+            return true;
+        };
+        let to_seller = payout.get(seller_id).map(|amount| amount.0).unwrap_or(0);
+        let royalties = price.as_yoctonear().saturating_sub(to_seller);
+        royalties.saturating_mul(MAX_ROYALTY_BPS as u128) <= price.as_yoctonear().saturating_mul(max_royalty_bps as u128)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Allows an admin to set the rules for the tokens of an NFT contract, or go back to the defaults by passing in no config.
+    //Every FT in the config has to be approved on the market
+    pub fn set_collection_config(&mut self, nft_contract_id: AccountId, config: Option<CollectionConfig>) {
+        self.assert_admin();
+
+        let Some(config) = config else {
+            self.collection_configs.remove(&nft_contract_id);
+            return;
+        };
+        if let Some(ft_token_ids) = &config.ft_token_ids {
+            assert!(!ft_token_ids.is_empty(), "A collection has to accept at least one FT");
+            for ft_token_id in ft_token_ids {
+                self.assert_approved_ft_token_id(ft_token_id);
+            }
+        }
+        if let Some(max_royalty_bps) = config.max_royalty_bps {
+            assert!(
+                max_royalty_bps <= MAX_ROYALTY_BPS,
+                "The royalty cap can't be more than {} basis points",
+                MAX_ROYALTY_BPS
+            );
+        }
+        self.collection_configs.insert(&nft_contract_id, &config);
+    }
+
+    /// views
+
+    //returns the rules for the tokens of an NFT contract. Collections that were never configured get the defaults
+    pub fn get_collection_config(&self, nft_contract_id: AccountId) -> CollectionConfig {
+        self.internal_collection_config(&nft_contract_id)
+    }
+}
+
+impl Contract {
+    //internal method for getting the rules for an NFT contract, or the defaults if it was never configured
+    pub(crate) fn internal_collection_config(&self, nft_contract_id: &AccountId) -> CollectionConfig {
+        self.collection_configs.get(nft_contract_id).unwrap_or_default()
+    }
+
+    //internal method for making sure the tokens of an NFT contract can be traded
+    pub(crate) fn assert_trading_enabled(&self, nft_contract_id: &AccountId) {
+        self.internal_collection_config(nft_contract_id).assert_trading_enabled(nft_contract_id);
+    }
+}
@@ -0,0 +1,323 @@
+//! Market events, following the events format (nep-297):
+//! <https://github.com/near/NEPs/blob/master/specs/Standards/EventsFormat.md>
+//!
+//! These events will be picked up by indexers so that they can build activity feeds without
+//! having to poll the market's views.
+//!
+//! The events are [`SaleListed`], [`SaleUpdated`], [`SaleRemoved`], [`OfferPlaced`],
+//! [`OfferUpdated`], [`PurchaseCompleted`], [`SaleFilled`], [`Deposit`] and [`Withdraw`].
+//!
+//! These events can be logged by calling `.emit()` on them if a single event, or calling
+//! `emit_many` on the event type to log several of the same kind at once.
+
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId, NearToken};
+use near_sdk::json_types::{U64, U128};
+
+use crate::{FungibleTokenId, OfferStatus, Purchase, TokenId};
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "standard")]
+#[must_use = "don't forget to `.emit()` this event"]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum NearEvent<'a> {
+    NftMarket(NftMarketEvent<'a>),
+}
+
+impl<'a> NearEvent<'a> {
+    fn to_json_string(&self) -> String {
+        // Events cannot fail to serialize so fine to panic on error
+        #[allow(clippy::redundant_closure)]
+        near_sdk::serde_json::to_string(self).ok().unwrap_or_else(|| env::abort())
+    }
+
+    fn to_json_event_string(&self) -> String {
+        format!("EVENT_JSON:{}", self.to_json_string())
+    }
+
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub(crate) fn emit(self) {
+        near_sdk::env::log_str(&self.to_json_event_string());
+    }
+}
+
+/// Data to log when a token is listed for sale. Multi-token listings have a `quantity` and are priced per token.
+/// To log this event, call [`.emit()`](SaleListed::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleListed<'a> {
+    pub owner_id: &'a AccountId,
+    pub nft_contract_id: &'a AccountId,
+    pub token_id: &'a TokenId,
+    pub ft_token_id: &'a FungibleTokenId,
+    pub price: &'a NearToken,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub near_price: Option<&'a NearToken>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<U128>,
+}
+
+impl SaleListed<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a sale listed event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`SaleListed`] represents the data of each listing.
+    pub fn emit_many(data: &[SaleListed<'_>]) {
+        new_market_v1(NftMarketEventKind::SaleListed(data)).emit()
+    }
+}
+
+/// Data to log when the price, payment token or expiry of a sale changes. Sales that never expire don't have an
+/// `expires_at`. To log this event, call [`.emit()`](SaleUpdated::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleUpdated<'a> {
+    pub owner_id: &'a AccountId,
+    pub nft_contract_id: &'a AccountId,
+    pub token_id: &'a TokenId,
+    pub ft_token_id: &'a FungibleTokenId,
+    pub price: &'a NearToken,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<U64>,
+}
+
+impl SaleUpdated<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a sale updated event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`SaleUpdated`] represents the data of each update.
+    pub fn emit_many(data: &[SaleUpdated<'_>]) {
+        new_market_v1(NftMarketEventKind::SaleUpdated(data)).emit()
+    }
+}
+
+/// Data to log when a sale is taken off the market, either by the owner or because it was purchased.
+/// To log this event, call [`.emit()`](SaleRemoved::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleRemoved<'a> {
+    pub owner_id: &'a AccountId,
+    pub nft_contract_id: &'a AccountId,
+    pub token_id: &'a TokenId,
+}
+
+impl SaleRemoved<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a sale removed event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`SaleRemoved`] represents the data of each removal.
+    pub fn emit_many(data: &[SaleRemoved<'_>]) {
+        new_market_v1(NftMarketEventKind::SaleRemoved(data)).emit()
+    }
+}
+
+/// Data to log when a buyer makes an offer on a sale. Offers below the asking price have an `offer_id`.
+/// To log this event, call [`.emit()`](OfferPlaced::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferPlaced<'a> {
+    pub buyer_id: &'a AccountId,
+    pub nft_contract_id: &'a AccountId,
+    pub token_id: &'a TokenId,
+    pub ft_token_id: &'a FungibleTokenId,
+    pub amount: &'a NearToken,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offer_id: Option<U64>,
+}
+
+impl OfferPlaced<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits an offer placed event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`OfferPlaced`] represents the data of each offer.
+    pub fn emit_many(data: &[OfferPlaced<'_>]) {
+        new_market_v1(NftMarketEventKind::OfferPlaced(data)).emit()
+    }
+}
+
+/// Data to log when an offer's negotiation moves on, i.e. the seller counters it or the buyer declines the
+/// counter. To log this event, call [`.emit()`](OfferUpdated::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferUpdated<'a> {
+    pub buyer_id: &'a AccountId,
+    pub nft_contract_id: &'a AccountId,
+    pub token_id: &'a TokenId,
+    pub offer_id: U64,
+    #[serde(flatten)]
+    pub status: &'a OfferStatus,
+}
+
+impl OfferUpdated<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits an offer updated event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`OfferUpdated`] represents the data of each offer.
+    pub fn emit_many(data: &[OfferUpdated<'_>]) {
+        new_market_v1(NftMarketEventKind::OfferUpdated(data)).emit()
+    }
+}
+
+/// Data to log once a token has been transferred to the buyer and the payout has been sent. If the token was
+/// bought as a gift, `receiver_id` is the account it was sent to and `buyer_id` is the account that paid.
+/// To log this event, call [`.emit()`](PurchaseCompleted::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseCompleted<'a> {
+    #[serde(flatten)]
+    pub purchase: &'a Purchase,
+}
+
+impl PurchaseCompleted<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a purchase completed event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`PurchaseCompleted`] represents the data of each purchase.
+    pub fn emit_many(data: &[PurchaseCompleted<'_>]) {
+        new_market_v1(NftMarketEventKind::PurchaseCompleted(data)).emit()
+    }
+}
+
+/// Data to log once part of a multi-token listing has been transferred to the buyer and the seller has been paid.
+/// `price` is what was paid for the whole `quantity` and `remaining` is how many tokens are still listed.
+/// To log this event, call [`.emit()`](SaleFilled::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleFilled<'a> {
+    pub sale_id: U64,
+    pub owner_id: &'a AccountId,
+    pub buyer_id: &'a AccountId,
+    pub mt_contract_id: &'a AccountId,
+    pub token_id: &'a TokenId,
+    pub ft_token_id: &'a FungibleTokenId,
+    pub quantity: U128,
+    pub remaining: U128,
+    pub price: &'a NearToken,
+}
+
+impl SaleFilled<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a sale filled event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`SaleFilled`] represents the data of each fill.
+    pub fn emit_many(data: &[SaleFilled<'_>]) {
+        new_market_v1(NftMarketEventKind::SaleFilled(data)).emit()
+    }
+}
+
+/// Data to log when an account deposits FTs into the market. To log this event, call [`.emit()`](Deposit::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Deposit<'a> {
+    pub account_id: &'a AccountId,
+    pub ft_token_id: &'a FungibleTokenId,
+    pub amount: &'a NearToken,
+}
+
+impl Deposit<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a deposit event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`Deposit`] represents the data of each deposit.
+    pub fn emit_many(data: &[Deposit<'_>]) {
+        new_market_v1(NftMarketEventKind::Deposit(data)).emit()
+    }
+}
+
+/// Data to log when an account withdraws FTs from the market. To log this event, call [`.emit()`](Withdraw::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Withdraw<'a> {
+    pub account_id: &'a AccountId,
+    pub ft_token_id: &'a FungibleTokenId,
+    pub amount: &'a NearToken,
+}
+
+impl Withdraw<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits a withdraw event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`Withdraw`] represents the data of each withdrawal.
+    pub fn emit_many(data: &[Withdraw<'_>]) {
+        new_market_v1(NftMarketEventKind::Withdraw(data)).emit()
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub(crate) struct NftMarketEvent<'a> {
+    version: &'static str,
+    #[serde(flatten)]
+    event_kind: NftMarketEventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+enum NftMarketEventKind<'a> {
+    SaleListed(&'a [SaleListed<'a>]),
+    SaleUpdated(&'a [SaleUpdated<'a>]),
+    SaleRemoved(&'a [SaleRemoved<'a>]),
+    OfferPlaced(&'a [OfferPlaced<'a>]),
+    OfferUpdated(&'a [OfferUpdated<'a>]),
+    PurchaseCompleted(&'a [PurchaseCompleted<'a>]),
+    SaleFilled(&'a [SaleFilled<'a>]),
+    Deposit(&'a [Deposit<'a>]),
+    Withdraw(&'a [Withdraw<'a>]),
+}
+
+fn new_market<'a>(version: &'static str, event_kind: NftMarketEventKind<'a>) -> NearEvent<'a> {
+    NearEvent::NftMarket(NftMarketEvent { version, event_kind })
+}
+
+fn new_market_v1(event_kind: NftMarketEventKind) -> NearEvent {
+    new_market("1.0.0", event_kind)
+}
@@ -1,27 +1,112 @@
 use crate::*;
+use crate::storage::StorageBalance;
+
+//the views of the ft contract (ft_balance_of, ft_total_supply and storage_balance_of) are called through the
+//shared interface
+pub use ft_common::ext::ext_ft_views;
 
 /// external contract calls
 
-//initiate a cross contract call to the nft contract. This will transfer the token to the buyer
+//initiate a cross contract call to the nft contract. This will transfer the token to the buyer and return
+//a payout object used for the market to distribute funds to the appropriate accounts.
 #[ext_contract(ext_nft_contract)]
 trait ExtNftContract {
     fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId, // account to transfer the NFT to
+        token_id: TokenId, // token ID to transfer
+        approval_id: Option<u64>, // market contract's approval ID. Not needed when the market owns the token
+        memo: Option<String>, //memo (to include some context)
+    );
+
+    fn nft_transfer_payout(
         &mut self,
         receiver_id: AccountId, // purchaser (person to transfer the NFT to)
         token_id: TokenId, // token ID to transfer
-        approval_id: Option<u32>, // market contract's approval ID in order to transfer the token on behalf of the owner
+        approval_id: Option<u64>, // market contract's approval ID in order to transfer the token on behalf of the owner
         memo: Option<String>, //memo (to include some context)
+        /*
+            the price that the token was purchased for. This will be used in conjunction with the royalty percentages
+            for the token in order to determine how much money should go to which account. 
+        */
+        balance: U128,
+        max_len_payout: u32, //the maximum amount of accounts the market can payout at once
+    ) -> Payout;
+
+    //get the token's owner and approvals. Used to check listings that don't come through nft_on_approve
+    fn nft_token(&self, token_id: TokenId) -> Option<JsonToken>;
+
+    //check whether an account is approved on a token with the given approval ID. Used to catch listings whose approval was revoked
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: AccountId,
+        approval_id: Option<u64>,
+    ) -> bool;
+
+    //make an account the user of a token until `expires` (in nanoseconds). Only NFT contracts with per-user roles
+    //support this. Used for rentals
+    fn nft_set_user(
+        &mut self,
+        token_id: TokenId,
+        user_id: Option<AccountId>,
+        expires: U64,
+        approval_id: Option<u64>,
     );
 }
 
-//initiate a cross contract call to the nft contract. This will transfer the token to the buyer and return
-//a payout object used for the market to distribute funds to the appropriate accounts.
+//the parts of the token object returned by nft_token that the market cares about
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonToken {
+    //token ID
+    pub token_id: TokenId,
+    //owner of the token
+    pub owner_id: AccountId,
+    //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
+    #[serde(default)]
+    pub approved_account_ids: HashMap<AccountId, u64>,
+}
+
+//initiate a cross contract call to the ft contract. Used to pay out FTs and to register accounts on the FT contract
 #[ext_contract(ext_ft_contract)]
 trait ExtFtContract {
+    //transfer FTs from the market to the given account. The account has to be registered on the FT contract
     fn ft_transfer(
         &mut self,
         receiver_id: AccountId, 
         amount: NearToken, 
         memo: Option<String>
     );
-}
\ No newline at end of file
+
+    //transfer FTs from the market to a contract and call ft_on_transfer on it with the msg. Resolves to the amount
+    //the receiver actually used
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: NearToken,
+        memo: Option<String>,
+        msg: String,
+    ) -> NearToken;
+
+    //pay for an account's storage on the FT contract so that it can receive FTs. If the account ID is omitted, the
+    //market itself is registered. The attached deposit must cover storage_balance_bounds().min
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance;
+}
+
+//initiate a cross contract call to a multi token contract. Used to send tokens the market holds for multi-token listings
+#[ext_contract(ext_mt_contract)]
+trait ExtMtContract {
+    fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId, // account to transfer the tokens to
+        token_id: TokenId, // token ID to transfer
+        amount: U128, // how many of the token to transfer
+        approval: Option<(AccountId, u64)>, // not needed since the market holds the tokens
+        memo: Option<String>, //memo (to include some context)
+    );
+}
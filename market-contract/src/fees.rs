@@ -0,0 +1,175 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+
+//the highest commission the owner can set (10%)
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
+
+//basis points in 100%
+const BPS_DENOMINATOR: u128 = 10_000;
+
+//works out a fee on the given amount in basis points (rounded down)
+fn fee_for_bps(amount: NearToken, bps: u16) -> NearToken {
+    let amount = amount.as_yoctonear();
+    let bps = bps as u128;
+    //if the multiplication would overflow, divide first (losing a little precision)
+    let fee = amount
+        .checked_mul(bps)
+        .map(|x| x / BPS_DENOMINATOR)
+        .unwrap_or_else(|| amount / BPS_DENOMINATOR * bps);
+    NearToken::from_yoctonear(fee)
+}
+
+#[near_bindgen]
+impl Contract {
+    //Allows the owner to set the market's commission on purchases in basis points (1/100th of a percent)
+    pub fn set_protocol_fee(&mut self, protocol_fee_bps: u16) {
+        //make sure only the owner can change the commission
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can change the protocol fee"
+        );
+        assert!(
+            protocol_fee_bps <= MAX_PROTOCOL_FEE_BPS,
+            "The protocol fee can't be more than {} basis points",
+            MAX_PROTOCOL_FEE_BPS
+        );
+
+        self.protocol_fee_bps = protocol_fee_bps;
+    }
+
+    //Allows the owner to set the cut of the price paid to referrers in basis points (1/100th of a percent).
+    //The referral fee is paid on top of the protocol fee, out of what would otherwise go to the seller
+    pub fn set_referral_fee(&mut self, referral_fee_bps: u16) {
+        //make sure only the owner can change the referral fee
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can change the referral fee"
+        );
+        assert!(
+            referral_fee_bps <= MAX_PROTOCOL_FEE_BPS,
+            "The referral fee can't be more than {} basis points",
+            MAX_PROTOCOL_FEE_BPS
+        );
+
+        self.referral_fee_bps = referral_fee_bps;
+    }
+
+    //Allows the owner to withdraw the commission collected in a given fungible token. The FTs are sent to the owner
+    #[payable]
+    pub fn withdraw_fees(&mut self, ft_token_id: FungibleTokenId) -> Promise {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        assert_one_yocto();
+        //make sure only the owner can withdraw the commission
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can withdraw fees"
+        );
+
+        //take the whole balance out of the fee treasury. If anything goes wrong we add it back when we resolve the promise
+        let amount = self.fee_balances.remove(&ft_token_id).unwrap_or(ZERO_TOKEN);
+        assert!(amount.gt(&ZERO_TOKEN), "No fees to withdraw for {}", ft_token_id);
+
+        // Perform the cross contract call to transfer the FTs to the owner
+        ext_ft_contract::ext(ft_token_id.clone())
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .ft_transfer(
+                self.owner_id.clone(), //owner to send the commission to
+                amount, //amount to transfer
+                Some("Marketplace commission".to_string()), //memo (to tell commission apart from sale proceeds)
+            )
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the withdrawal. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_FEE_WITHDRAWAL)
+            .resolve_fee_withdrawal(
+                ft_token_id, //the FT that was withdrawn
+                amount, //amount to add back if the transfer failed
+            )
+        )
+    }
+
+    //Allows the owner to withdraw the commission collected on sales paid in NEAR. The NEAR is sent to the owner
+    #[payable]
+    pub fn withdraw_near_fees(&mut self) -> Promise {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        assert_one_yocto();
+        //make sure only the owner can withdraw the commission
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can withdraw fees"
+        );
+
+        //take the whole balance out of the fee treasury. NEAR transfers to an existing account can't fail
+        let amount = std::mem::replace(&mut self.near_fee_balance, ZERO_TOKEN);
+        assert!(amount.gt(&ZERO_TOKEN), "No NEAR fees to withdraw");
+
+        Promise::new(self.owner_id.clone()).transfer(amount)
+    }
+
+    //private method used to resolve the fee withdrawal. If the transfer failed, the amount goes back into the fee treasury
+    #[private]
+    pub fn resolve_fee_withdrawal(&mut self, ft_token_id: FungibleTokenId, amount: NearToken) -> NearToken {
+        match env::promise_result(0) {
+            // If the promise was successful, there's nothing to revert
+            PromiseResult::Successful(_) => ZERO_TOKEN,
+            // If the promise wasn't successful, add the amount back to the fee treasury
+            PromiseResult::Failed => {
+                self.internal_increase_fee_balance(&ft_token_id, amount);
+                amount
+            }
+        }
+    }
+
+    /// views
+
+    //returns the market's commission in basis points
+    pub fn get_protocol_fee(&self) -> u16 {
+        self.protocol_fee_bps
+    }
+
+    //returns the commission collected on sales paid in NEAR but not withdrawn yet
+    pub fn get_near_fee_balance(&self) -> NearToken {
+        self.near_fee_balance
+    }
+
+    //returns the cut of the price paid to referrers in basis points
+    pub fn get_referral_fee(&self) -> u16 {
+        self.referral_fee_bps
+    }
+
+    //returns the commission collected but not withdrawn yet for a given fungible token
+    pub fn get_fee_balance(&self, ft_token_id: FungibleTokenId) -> NearToken {
+        self.fee_balances.get(&ft_token_id).unwrap_or(ZERO_TOKEN)
+    }
+}
+
+impl Contract {
+    //internal method for working out the market's commission on a purchase of the given amount (rounded down)
+    pub(crate) fn internal_protocol_fee(&self, amount: NearToken) -> NearToken {
+        fee_for_bps(amount, self.protocol_fee_bps)
+    }
+
+    //internal method for working out the referrer's cut of a purchase of the given amount (rounded down).
+    //Purchases without a referrer don't pay a referral fee
+    pub(crate) fn internal_referral_fee(&self, amount: NearToken, referrer_id: &Option<AccountId>) -> NearToken {
+        if referrer_id.is_none() {
+            return ZERO_TOKEN;
+        }
+        fee_for_bps(amount, self.referral_fee_bps)
+    }
+
+    //internal method for crediting commission to the fee treasury
+    pub(crate) fn internal_increase_fee_balance(&mut self, ft_token_id: &FungibleTokenId, amount: NearToken) {
+        if amount.is_zero() {
+            return;
+        }
+        let cur_bal = self.fee_balances.get(ft_token_id).unwrap_or(ZERO_TOKEN);
+        self.fee_balances.insert(ft_token_id, &cur_bal.saturating_add(amount));
+    }
+}
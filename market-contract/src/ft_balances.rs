@@ -2,8 +2,46 @@ use near_sdk::{require, PromiseResult};
 
 use crate::*;
 
+//the shortest time deposits can be left idle before they can be returned (1 day in seconds)
+pub const MIN_IDLE_DEPOSIT_PERIOD_SEC: u64 = 24 * 60 * 60;
+
+//an account's opt-in to having its FT deposits returned once they've been left idle
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct IdleDepositReturn {
+    //how long the deposits have to be left idle before anyone can return them (in seconds)
+    pub idle_period_sec: u64,
+    //the last time the account deposited, withdrew or spent FTs on the market (in nanoseconds)
+    pub last_activity: U64,
+}
+
+impl IdleDepositReturn {
+    //returns when the deposits can be returned (in nanoseconds)
+    pub fn returnable_at(&self) -> u64 {
+        self.last_activity.0.saturating_add(self.idle_period_sec.saturating_mul(1_000_000_000))
+    }
+}
+
 /// transfer callbacks from FT Contracts
 
+//the msg that can be passed to ft_transfer_call to do something with the FTs other than depositing them
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", tag = "action", rename_all = "snake_case")]
+pub enum FtTransferAction {
+    //buy a sale outright. Any FTs that aren't needed are returned to the sender
+    Purchase {
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        //account that referred the buyer. They're paid the market's referral fee
+        #[serde(default)]
+        referrer_id: Option<AccountId>,
+        //account to send the token to if the sender is buying it for someone else
+        #[serde(default)]
+        receiver_id: Option<AccountId>,
+    },
+}
+
 /*
     trait that will be used as the callback from the FT contract. When ft_transfer_call is
     called, it will fire a cross contract call to this marketplace and this is the function
@@ -13,42 +51,56 @@ trait FungibleTokenReceiver {
     fn ft_on_transfer(
         &mut self,
         sender_id: AccountId,
-        amount: NearToken
+        amount: NearToken,
+        msg: String
     ) -> NearToken;
 
     fn ft_withdraw(
         &mut self,
+        ft_token_id: FungibleTokenId,
         amount: NearToken
     );
 
+    fn ft_withdraw_all(&mut self);
+
     fn resolve_refund(
         &mut self,
         caller: AccountId,
+        ft_token_id: FungibleTokenId,
         amount: NearToken
     ) -> NearToken;
 
     fn ft_deposits_of(
         &self,
-        account_id: AccountId
+        account_id: AccountId,
+        ft_token_id: Option<FungibleTokenId>
     ) -> NearToken;
+
+    fn ft_deposits_of_batch(
+        &self,
+        account_id: AccountId
+    ) -> Vec<(FungibleTokenId, NearToken)>;
 }
 
 //implementation of the trait
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
-    /// This is how users will fund their FT balances in the contract
+    /// This is how users will fund their FT balances in the contract. If the msg asks for a purchase, the FTs
+    /// are used to buy the sale straight away and anything that wasn't needed is returned to the sender
     fn ft_on_transfer(
         &mut self,
         sender_id: AccountId,
-        amount: NearToken
+        amount: NearToken,
+        msg: String
     ) -> NearToken {
         // get the contract ID which is the predecessor
         let ft_contract_id = env::predecessor_account_id();
-        // Ensure only the specified FT can be used
-        require!(
-            ft_contract_id == self.ft_id,
-            "FT contract ID does not match"
-        );
+        // Only approved FTs can be used. Anything else is handed straight back instead of panicking, so the FT
+        // contract refunds the sender in full rather than going through a failed receipt
+        if !self.approved_ft_token_ids.contains(&ft_contract_id) {
+            env::log_str(&format!("FT contract {} is not approved for payments, returning the tokens", ft_contract_id));
+            return amount;
+        }
         
         //get the signer which is the person who initiated the transaction
         let signer_id = env::signer_account_id();
@@ -67,10 +119,49 @@ impl FungibleTokenReceiver for Contract {
             "owner_id should be signer_id"
         );
 
-        // Add the amount to the user's current balance
-        let mut cur_bal = self.ft_deposits.get(&signer_id).unwrap_or(ZERO_TOKEN);
-        cur_bal = cur_bal.saturating_add(amount);
-        self.ft_deposits.insert(&signer_id, &cur_bal);
+        // If the sender wants to buy a sale, we use the FTs they sent to pay for it
+        if !msg.is_empty() {
+            let FtTransferAction::Purchase { nft_contract_id, token_id, referrer_id, receiver_id } =
+                near_sdk::serde_json::from_str(&msg)
+                    .unwrap_or_else(|e| env::panic_str(&format!("Not valid FtTransferAction: {}", e)));
+
+            // Purchases can't be made while the market is paused
+            self.assert_not_paused();
+
+            // Only the sale's current price is charged, so the sender can send more than that and get the rest back.
+            // If they sent less, the purchase is rejected
+            let current_price = self
+                .sales
+                .get(&(nft_contract_id.clone(), token_id.clone()))
+                .expect("No sale")
+                .current_price();
+
+            // Start the purchase and make sure the sale is priced in the FT that was sent
+            let (ft_token_id, price) = self.internal_buy(
+                sender_id,
+                nft_contract_id,
+                token_id,
+                amount.min(current_price),
+                referrer_id,
+                receiver_id,
+            );
+            require!(ft_token_id == ft_contract_id, "The sale is priced in a different FT");
+
+            // Return whatever wasn't needed to the sender
+            return amount.saturating_sub(price);
+        }
+
+        // Add the amount to the user's current balance for this FT
+        self.internal_increase_ft_deposit(&signer_id, &ft_contract_id, amount);
+        self.internal_record_deposit_activity(&signer_id);
+
+        // Let indexers know about the deposit
+        Deposit {
+            account_id: &signer_id,
+            ft_token_id: &ft_contract_id,
+            amount: &amount,
+        }
+        .emit();
 
         // We don't return any FTs to the sender because we're storing all of them in their balance
         ZERO_TOKEN
@@ -79,55 +170,63 @@ impl FungibleTokenReceiver for Contract {
     #[payable]
     fn ft_withdraw(
         &mut self,
+        ft_token_id: FungibleTokenId,
         amount: NearToken
     ) {
         //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
         //this will redirect them to the NEAR wallet (or requires a full access key). 
         assert_one_yocto();
+        // Withdrawals can't be made while the market is paused
+        self.assert_not_paused();
 
         // Get the caller and ensure they have enough balance
         let caller = env::predecessor_account_id();
-        let cur_bal = self.ft_deposits.get(&caller).unwrap_or(ZERO_TOKEN);
+        let cur_bal = self.internal_ft_deposit_of(&caller, &ft_token_id);
         require!(
             cur_bal.ge(&amount),
             "Insufficient balance"
         );
 
         // Subtract the amount from the caller's balance
-        let new_bal = cur_bal.saturating_sub(amount);
-        self.ft_deposits.insert(&caller, &new_bal);
+        self.internal_decrease_ft_deposit(&caller, &ft_token_id, amount);
 
-        // Perform the cross contract call to transfer the FTs to the caller. If anything goes wrong
-        // We increment their balance back when we resolve the promise
-        ext_ft_contract::ext(self.ft_id.clone())
-            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
-            .with_attached_deposit(NearToken::from_yoctonear(1))
-            .ft_transfer(
-                caller.clone(), //caller to refund the FTs to
-                amount, //amount to transfer
-                Some("Withdrawing from Marketplace".to_string()), //memo (to include some context)
-            )
-        .then(
-            // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
-            Self::ext(env::current_account_id())
-            .with_static_gas(GAS_FOR_RESOLVE_REFUND)
-            .resolve_refund(
-                caller, //caller to refund the FTs to
-                amount, //amount to transfer
-            )
-        );
+        // Send the FTs to the caller
+        self.internal_send_withdrawal(caller, ft_token_id, amount);
+    }
+
+    /// Withdraw everything you've deposited, in every fungible token
+    #[payable]
+    fn ft_withdraw_all(&mut self) {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        //this will redirect them to the NEAR wallet (or requires a full access key). 
+        assert_one_yocto();
+        // Withdrawals can't be made while the market is paused
+        self.assert_not_paused();
+
+        // Take all of the caller's deposits. Any withdrawal that fails is added back when we resolve its promise
+        let caller = env::predecessor_account_id();
+        self.internal_record_deposit_activity(&caller);
+        self.internal_withdraw_all(caller);
     }
 
     #[private]
     fn resolve_refund(
         &mut self,
         caller: AccountId,
+        ft_token_id: FungibleTokenId,
         amount: NearToken
     ) -> NearToken {
         // Get the amount to revert the caller's balance with
         let revert_amount = match env::promise_result(0) {
-            // If the promise was successful, get the return value
+            // If the promise was successful, the withdrawal went through and there's nothing to revert
             PromiseResult::Successful(_) => {
+                // Let indexers know about the withdrawal
+                Withdraw {
+                    account_id: &caller,
+                    ft_token_id: &ft_token_id,
+                    amount: &amount,
+                }
+                .emit();
                 ZERO_TOKEN
             }
             // If the promise wasn't successful, return the original amount.
@@ -135,21 +234,134 @@ impl FungibleTokenReceiver for Contract {
         };
 
         if revert_amount.gt(&ZERO_TOKEN) {
-            // Get the caller's current balance
-            let cur_bal = self.ft_deposits.get(&caller).unwrap_or(ZERO_TOKEN);
-            // Add the amount to the caller's balance
-            let new_bal = cur_bal.saturating_add(revert_amount);
-            self.ft_deposits.insert(&caller, &new_bal);
+            // Add the amount back to the caller's balance
+            self.internal_increase_ft_deposit(&caller, &ft_token_id, revert_amount);
         }
 
         revert_amount
     }
 
-    /// Get the amount of FTs the user has deposited into the contract
+    /// Get the amount of a given FT the user has deposited into the contract. Defaults to the market's default FT
     fn ft_deposits_of(
         &self,
-        account_id: AccountId
+        account_id: AccountId,
+        ft_token_id: Option<FungibleTokenId>
     ) -> NearToken {
-        self.ft_deposits.get(&account_id).unwrap_or(ZERO_TOKEN)
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        self.internal_ft_deposit_of(&account_id, &ft_token_id)
+    }
+
+    /// Get the amount the user has deposited into the contract for every fungible token they have a balance in
+    fn ft_deposits_of_batch(
+        &self,
+        account_id: AccountId
+    ) -> Vec<(FungibleTokenId, NearToken)> {
+        self.ft_deposits
+            .get(&account_id)
+            .map(|deposits| deposits.to_vec())
+            .unwrap_or_default()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Opt in to having your FT deposits sent back to you once you haven't deposited, withdrawn or spent FTs on the
+    /// market for `idle_period_sec` seconds. Anyone can return them with `return_idle_deposit` after that.
+    /// Pass in no period to opt out
+    #[payable]
+    pub fn set_idle_deposit_return(&mut self, idle_period_sec: Option<u64>) {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let Some(idle_period_sec) = idle_period_sec else {
+            self.idle_deposit_returns.remove(&account_id);
+            return;
+        };
+        assert!(
+            idle_period_sec >= MIN_IDLE_DEPOSIT_PERIOD_SEC,
+            "The idle period must be at least {} seconds",
+            MIN_IDLE_DEPOSIT_PERIOD_SEC
+        );
+        //opting in counts as activity, so the deposits are never returned straight away
+        self.idle_deposit_returns.insert(
+            &account_id,
+            &IdleDepositReturn { idle_period_sec, last_activity: U64(env::block_timestamp()) },
+        );
+    }
+
+    /// Send every FT an account has deposited back to it, once the deposits have been left idle for longer than the
+    /// account opted in to. Anyone can call this
+    pub fn return_idle_deposit(&mut self, account_id: AccountId) {
+        //withdrawals can't be made while the market is paused
+        self.assert_not_paused();
+
+        let idle_deposit_return = self
+            .idle_deposit_returns
+            .get(&account_id)
+            .expect("The account hasn't opted in to having idle deposits returned");
+        assert!(
+            env::block_timestamp() >= idle_deposit_return.returnable_at(),
+            "The deposits can't be returned until {}",
+            idle_deposit_return.returnable_at()
+        );
+
+        self.internal_withdraw_all(account_id);
+    }
+
+    /// views
+
+    //returns an account's opt-in to having its idle deposits returned, if it opted in
+    pub fn get_idle_deposit_return(&self, account_id: AccountId) -> Option<IdleDepositReturn> {
+        self.idle_deposit_returns.get(&account_id)
+    }
+}
+
+impl Contract {
+    //internal method for recording that an account deposited, withdrew or spent FTs. This pushes back when its deposits
+    //can be returned, if it opted in
+    pub(crate) fn internal_record_deposit_activity(&mut self, account_id: &AccountId) {
+        if let Some(mut idle_deposit_return) = self.idle_deposit_returns.get(account_id) {
+            idle_deposit_return.last_activity = U64(env::block_timestamp());
+            self.idle_deposit_returns.insert(account_id, &idle_deposit_return);
+        }
+    }
+
+    //internal method for taking all of an account's deposits and sending them back to it. Any withdrawal that fails
+    //is added back when we resolve its promise
+    pub(crate) fn internal_withdraw_all(&mut self, account_id: AccountId) {
+        let mut deposits = self.ft_deposits.remove(&account_id).expect("Nothing to withdraw");
+        let balances = deposits.to_vec();
+        deposits.clear();
+
+        // Send each FT to the account
+        for (ft_token_id, amount) in balances {
+            self.internal_send_withdrawal(account_id.clone(), ft_token_id, amount);
+        }
+    }
+
+    //internal method for sending FTs that were taken out of an account's deposits back to the account. If the transfer
+    //fails, the FTs are added back to their deposits when the promise is resolved
+    pub(crate) fn internal_send_withdrawal(&self, account_id: AccountId, ft_token_id: FungibleTokenId, amount: NearToken) {
+        // Perform the cross contract call to transfer the FTs to the account. If anything goes wrong
+        // We increment their balance back when we resolve the promise
+        ext_ft_contract::ext(ft_token_id.clone())
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .ft_transfer(
+                account_id.clone(), //account to refund the FTs to
+                amount, //amount to transfer
+                Some("Withdrawing from Marketplace".to_string()), //memo (to include some context)
+            )
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_REFUND)
+            .resolve_refund(
+                account_id, //account to refund the FTs to
+                ft_token_id, //the FT that was withdrawn
+                amount, //amount to transfer
+            )
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use crate::*;
+
+//the range nft_transfer can be set to. The NFT contract has to fit the transfer and, for purchases, the payout
+const MIN_GAS_FOR_NFT_TRANSFER: Gas = Gas::from_tgas(5);
+const MAX_GAS_FOR_NFT_TRANSFER: Gas = Gas::from_tgas(20);
+
+//the range resolve_purchase can be set to. Resolving pays out every payee, so it can't go too low
+const MIN_GAS_FOR_RESOLVE_PURCHASE: Gas = Gas::from_tgas(50);
+const MAX_GAS_FOR_RESOLVE_PURCHASE: Gas = Gas::from_tgas(150);
+
+//the most GAS a transaction can use. Buying the largest bundle has to fit in it
+const MAX_GAS_PER_TRANSACTION: Gas = Gas::from_tgas(300);
+
+//what buy_bundle uses itself, on top of the transfers and the resolve it attaches GAS to
+const GAS_FOR_BUY_BUNDLE: Gas = Gas::from_tgas(20);
+
+//the static GAS attached to the NFT transfers the market makes and to resolving purchases. The owner can adjust
+//it when the protocol's GAS costs change, without redeploying the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct GasConfig {
+    //GAS attached to nft_transfer and nft_transfer_payout calls
+    pub nft_transfer: Gas,
+    //GAS attached to resolve_purchase, and the base GAS for resolving a bundle
+    pub resolve_purchase: Gas,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            nft_transfer: GAS_FOR_NFT_TRANSFER,
+            resolve_purchase: GAS_FOR_RESOLVE_PURCHASE,
+        }
+    }
+}
+
+impl GasConfig {
+    //the GAS a transfer and payout needs: the nft_transfer_payout call and resolving the purchase
+    pub(crate) fn transfer_with_payout(&self) -> Gas {
+        self.nft_transfer.saturating_add(self.resolve_purchase)
+    }
+
+    //the GAS for resolving an approval check, which starts a transfer and payout if the market is still approved
+    pub(crate) fn resolve_approval_check(&self) -> Gas {
+        self.transfer_with_payout().saturating_add(GAS_FOR_RESOLVE_APPROVAL_CHECK)
+    }
+
+    //the GAS for resolving a standing offer acceptance, which starts a transfer and payout if the owner is registered
+    pub(crate) fn resolve_accept_offer(&self) -> Gas {
+        self.transfer_with_payout().saturating_add(GAS_FOR_RESOLVE_ACCEPT_OFFER)
+    }
+
+    //the GAS for resolving a bundle purchase, which sends every token on and resolves those transfers
+    pub(crate) fn resolve_buy_bundle(&self, items: usize) -> Gas {
+        Gas::from_gas(
+            self.resolve_purchase.as_gas()
+                + self.nft_transfer.as_gas() * items as u64
+                + GAS_FOR_RESOLVE_HELD_TOKEN_TRANSFERS.as_gas(),
+        )
+    }
+
+    //the GAS a bundle purchase needs in total: moving every token to the market and then resolving the bundle
+    pub(crate) fn buy_bundle(&self, items: usize) -> Gas {
+        Gas::from_gas(
+            self.nft_transfer.as_gas() * items as u64
+                + self.resolve_buy_bundle(items).as_gas()
+                + GAS_FOR_BUY_BUNDLE.as_gas(),
+        )
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Allows the owner to change the GAS attached to NFT transfers and to resolving purchases. Each value has to be within its bounds,
+    //and buying the largest bundle with them has to fit in a single transaction
+    pub fn set_gas_config(&mut self, gas_config: GasConfig) {
+        //make sure only the owner can change the GAS
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can change the GAS config"
+        );
+        assert!(
+            (MIN_GAS_FOR_NFT_TRANSFER..=MAX_GAS_FOR_NFT_TRANSFER).contains(&gas_config.nft_transfer),
+            "The NFT transfer GAS must be between {} and {}",
+            MIN_GAS_FOR_NFT_TRANSFER,
+            MAX_GAS_FOR_NFT_TRANSFER
+        );
+        assert!(
+            (MIN_GAS_FOR_RESOLVE_PURCHASE..=MAX_GAS_FOR_RESOLVE_PURCHASE).contains(&gas_config.resolve_purchase),
+            "The resolve purchase GAS must be between {} and {}",
+            MIN_GAS_FOR_RESOLVE_PURCHASE,
+            MAX_GAS_FOR_RESOLVE_PURCHASE
+        );
+        //the callbacks forward these amounts, so the largest chain has to fit in a single transaction
+        assert!(
+            gas_config.buy_bundle(MAX_BUNDLE_SIZE) <= MAX_GAS_PER_TRANSACTION,
+            "Buying a bundle of {} tokens would need more than {}",
+            MAX_BUNDLE_SIZE,
+            MAX_GAS_PER_TRANSACTION
+        );
+
+        self.gas_config = gas_config;
+    }
+
+    //returns the GAS attached to NFT transfers and to resolving purchases
+    pub fn get_gas_config(&self) -> GasConfig {
+        self.gas_config
+    }
+}
@@ -9,22 +9,38 @@ pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     hash
 }
 
+//used to generate a unique prefix for collections that belong to a sale (this is to avoid data collisions)
+pub(crate) fn hash_sale_id(contract_and_token_id: &ContractAndTokenId) -> CryptoHash {
+    //get the default hash
+    let mut hash = CryptoHash::default();
+    //we hash the serialized unique sale ID and return it
+    hash.copy_from_slice(&env::sha256(&near_sdk::borsh::to_vec(contract_and_token_id).unwrap()));
+    hash
+}
+
 //the minimum storage to have a sale on the contract
 pub(crate) fn storage_per_sale() -> NearToken {
   env::storage_byte_cost().saturating_mul(1000)
 }
 
 impl Contract {
-    //internal method for removing a sale from the market. This returns the previously removed sale object
+    //internal method for removing a sale from the market. This returns the previously removed sale object.
+    //callers are responsible for emitting the sale_removed event so that batches can be logged together
     pub(crate) fn internal_remove_sale(
         &mut self,
         nft_contract_id: AccountId,
         token_id: TokenId,
     ) -> Sale {
-        //get the unique sale ID (contract + DELIMITER + token ID)
-        let contract_and_token_id = format!("{}{}{}", &nft_contract_id, DELIMETER, token_id);
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
         //get the sale object by removing the unique sale ID. If there was no sale, panic
         let sale = self.sales.remove(&contract_and_token_id).expect("No sale");
+        //take the sale out of the price index and drop its expiry
+        self.internal_unindex_sale_price(&sale);
+        self.sale_expirations.remove(&contract_and_token_id);
+
+        //release any offers that were placed on the sale back into the buyers' deposits
+        self.internal_release_offers(&contract_and_token_id, &sale.sale_conditions.ft_token_id);
 
         //get the set of sales for the sale's owner. If there's no sale, panic. 
         let mut by_owner_id = self.by_owner_id.get(&sale.owner_id).expect("No sale by_owner_id");
@@ -60,4 +76,105 @@ impl Contract {
         //return the sale object
         sale
     }
+
+    //internal method for making sure a fungible token can be used to purchase NFTs on the market
+    pub(crate) fn assert_approved_ft_token_id(&self, ft_token_id: &FungibleTokenId) {
+        assert!(
+            self.approved_ft_token_ids.contains(ft_token_id),
+            "FT {} is not approved for payments",
+            ft_token_id
+        );
+    }
+
+    //internal method for getting how many listings (sales, multi-token sales, auctions, bundles, standing offers, rentals and leases) an account
+    //has on the market. each listing takes up storage_per_sale() worth of the account's storage deposit
+    pub(crate) fn internal_listing_count(&self, account_id: &AccountId) -> u64 {
+        let sales = self.by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let auctions = self.auctions_by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let bundles = self.bundles_by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let standing_offers = self.standing_offers_by_buyer_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let rentals = self.rentals_by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let leases = self.leases_by_renter_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let mt_sales = self.mt_sales_by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        //a key for signing orders takes up the same storage as a listing
+        let order_signers = self.order_signers.contains_key(account_id) as u64;
+        //sales listed before the market was upgraded from V0 count until they're migrated
+        let v0_sales = migrate::v0_sale_count(account_id);
+        sales + v0_sales + mt_sales + auctions + bundles + standing_offers + rentals + leases + order_signers
+    }
+
+    //internal method for getting how many FTs of a given fungible token an account has deposited
+    pub(crate) fn internal_ft_deposit_of(
+        &self,
+        account_id: &AccountId,
+        ft_token_id: &FungibleTokenId,
+    ) -> NearToken {
+        self.ft_deposits
+            .get(account_id)
+            .and_then(|deposits| deposits.get(ft_token_id))
+            .unwrap_or(ZERO_TOKEN)
+    }
+
+    //internal method for adding to the amount of a given fungible token an account has deposited
+    pub(crate) fn internal_increase_ft_deposit(
+        &mut self,
+        account_id: &AccountId,
+        ft_token_id: &FungibleTokenId,
+        amount: NearToken,
+    ) {
+        //get the deposits for the account. If there are none, we create a new empty map
+        let mut deposits = self.ft_deposits.get(account_id).unwrap_or_else(|| {
+            UnorderedMap::new(
+                StorageKey::FTDepositsInner {
+                    //we get a new unique prefix for the collection by hashing the account
+                    account_id_hash: hash_account_id(account_id),
+                }
+            )
+        });
+
+        //add the amount to the account's current balance for that fungible token
+        let cur_bal = deposits.get(ft_token_id).unwrap_or(ZERO_TOKEN);
+        deposits.insert(ft_token_id, &cur_bal.saturating_add(amount));
+        //insert the deposits back into the map for the account
+        self.ft_deposits.insert(account_id, &deposits);
+    }
+
+    //internal method for subtracting from the amount of a given fungible token an account has deposited.
+    //If the account doesn't have enough deposited, panic.
+    pub(crate) fn internal_decrease_ft_deposit(
+        &mut self,
+        account_id: &AccountId,
+        ft_token_id: &FungibleTokenId,
+        amount: NearToken,
+    ) {
+        //get the account's current balance for that fungible token and make sure it covers the amount
+        let cur_bal = self.internal_ft_deposit_of(account_id, ft_token_id);
+        assert!(
+            cur_bal.ge(&amount),
+            "Not enough {} deposited to cover {}",
+            ft_token_id,
+            amount
+        );
+
+        //there's a balance so the deposits map must exist
+        let mut deposits = self.ft_deposits.get(account_id).unwrap();
+        let new_bal = cur_bal.saturating_sub(amount);
+
+        //if the balance is now empty, we remove the fungible token from the map. Otherwise we insert the new balance
+        if new_bal.is_zero() {
+            deposits.remove(ft_token_id);
+        } else {
+            deposits.insert(ft_token_id, &new_bal);
+        }
+
+        //if the account has no deposits left, we remove them from the map. Otherwise we insert the deposits back
+        if deposits.is_empty() {
+            self.ft_deposits.remove(account_id);
+        } else {
+            self.ft_deposits.insert(account_id, &deposits);
+        }
+
+        //spending or withdrawing deposits means the account is still using them
+        self.internal_record_deposit_activity(account_id);
+    }
 }
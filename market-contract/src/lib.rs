@@ -1,5 +1,5 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LookupMap, LookupSet, TreeMap, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::{U64, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
@@ -8,34 +8,86 @@ use near_sdk::{
 };
 use std::collections::HashMap;
 
+use crate::activity::*;
+use crate::auction::*;
+use crate::bundles::*;
+use crate::collection_config::*;
+use crate::events::*;
 use crate::external::*;
+use crate::ft_balances::*;
+use crate::gas_config::*;
 use crate::internal::*;
+use crate::mt_sales::*;
+use crate::offers::*;
+use crate::rentals::*;
 use crate::sale::*;
+use crate::signed_orders::*;
+use crate::standing_offers::*;
+use crate::token_history::*;
 
+mod activity;
+mod admin;
+mod approvals;
+mod auction;
+mod batch;
+mod bundles;
+mod claims;
+mod collection_config;
+mod events;
 mod external;
+mod fees;
+mod gas_config;
 mod internal;
+mod migrate;
 mod ft_balances;
+mod mt_sales;
 mod nft_callbacks;
+mod offers;
+mod price_index;
+mod registration;
+mod rentals;
 mod sale;
 mod sale_views;
+mod signed_orders;
+mod standing_offers;
+mod storage;
+mod token_history;
 
-//GAS constants to attach to calls
+//GAS constants to attach to calls. GAS_FOR_RESOLVE_PURCHASE and GAS_FOR_NFT_TRANSFER are only the defaults, the
+//owner can change them with set_gas_config
 const GAS_FOR_RESOLVE_PURCHASE: Gas = Gas::from_tgas(115);
 const GAS_FOR_RESOLVE_REFUND: Gas = Gas::from_tgas(30);
 const GAS_FOR_NFT_TRANSFER: Gas = Gas::from_tgas(15);
+const GAS_FOR_NFT_TOKEN: Gas = Gas::from_tgas(5);
+const GAS_FOR_RESOLVE_LIST_SALE: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_FEE_WITHDRAWAL: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_PAYMENT: Gas = Gas::from_tgas(5);
+const GAS_FOR_STORAGE_BALANCE_OF: Gas = Gas::from_tgas(5);
+//what resolving a standing offer acceptance uses itself, on top of the transfer and payout it starts
+const GAS_FOR_RESOLVE_ACCEPT_OFFER: Gas = Gas::from_tgas(20);
+const GAS_FOR_NFT_IS_APPROVED: Gas = Gas::from_tgas(5);
+//what resolving an approval check uses itself, on top of the transfer and payout it starts
+const GAS_FOR_RESOLVE_APPROVAL_CHECK: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_STALE_LISTING: Gas = Gas::from_tgas(10);
+const GAS_FOR_NFT_SET_USER: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_RENT: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_MT_PURCHASE: Gas = Gas::from_tgas(40);
+const GAS_FOR_RESOLVE_HELD_TOKEN_TRANSFERS: Gas = Gas::from_tgas(10);
 
-pub const ZERO_TOKEN: NearToken = NearToken::from_yoctonear(0);
+//the default maximum number of accounts (seller + royalty recipients) the market will pay out for a single sale
+const DEFAULT_MAX_PAYEES: u32 = 10;
 
-//every sale will have a unique ID which is `CONTRACT + DELIMITER + TOKEN_ID`
-static DELIMETER: &str = ".";
+pub const ZERO_TOKEN: NearToken = NearToken::from_yoctonear(0);
 
 //Creating custom types to use within the contract. This makes things more readable. 
 pub type SalePriceInFTs = NearToken;
 pub type TokenId = String;
 pub type FungibleTokenId = AccountId;
-pub type ContractAndTokenId = String;
+//every sale has a unique ID made up of the NFT contract and token ID. It's borsh serialized as a pair so that
+//token IDs can't collide with each other no matter what characters they contain
+pub type ContractAndTokenId = (AccountId, TokenId);
 //defines the payout type we'll be parsing from the NFT contract as a part of the royalty standard.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, NearSchema)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Payout {
     pub payout: HashMap<AccountId, U128>,
@@ -49,13 +101,40 @@ pub struct Contract {
     //keep track of the owner of the contract
     pub owner_id: AccountId,
 
-    //which fungible token can be used to purchase NFTs
+    //accounts that can pause the market and manage the NFT contract whitelist (along with the owner)
+    pub admin_ids: UnorderedSet<AccountId>,
+
+    //while the market is paused, tokens can't be listed, offers and bids can't be placed, and FTs can't be withdrawn
+    pub paused: bool,
+
+    //keep track of the NFT contracts whose tokens can be listed on the market
+    pub approved_nft_contract_ids: UnorderedSet<AccountId>,
+
+    //the fungible token that sales are priced in when a listing doesn't specify one
     pub ft_id: AccountId,
+
+    //the maximum number of accounts the market will pay out for a single sale. Payouts with more accounts are ignored
+    pub max_payees: u32,
+
+    //the market's commission on every purchase in basis points (1/100th of a percent)
+    pub protocol_fee_bps: u16,
+
+    //the cut of the price paid to whoever referred a buyer, in basis points (1/100th of a percent)
+    pub referral_fee_bps: u16,
+
+    //the commission the market has collected but not withdrawn yet, for each fungible token
+    pub fee_balances: UnorderedMap<FungibleTokenId, NearToken>,
+
+    //the commission the market has collected on sales paid in NEAR but not withdrawn yet
+    pub near_fee_balance: NearToken,
+
+    //keep track of all the fungible tokens that can be used to purchase NFTs
+    pub approved_ft_token_ids: UnorderedSet<FungibleTokenId>,
     
     /*
         to keep track of the sales, we map the ContractAndTokenId to a Sale. 
         the ContractAndTokenId is the unique identifier for every sale. It is made
-        up of the `contract ID + DELIMITER + token ID`
+        up of the `(contract ID, token ID)` pair
     */
     pub sales: UnorderedMap<ContractAndTokenId, Sale>,
     
@@ -65,26 +144,155 @@ pub struct Contract {
     //keep track of all the token IDs for sale for a given contract
     pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
 
+    //keep track of the sales for every NFT contract and fungible token, ordered by price. Used for floor price and price range queries
+    pub sales_by_price: LookupMap<(AccountId, FungibleTokenId), TreeMap<(u128, ContractAndTokenId), ()>>,
+
+    //keep track of the auctions. Auctions use the same unique ID as sales (contract ID, token ID)
+    pub auctions: UnorderedMap<ContractAndTokenId, Auction>,
+
+    //keep track of all the auction IDs for every account ID
+    pub auctions_by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+
+    //keep track of all the token IDs up for auction for a given contract
+    pub auctions_by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+
+    //keep track of the offers below the asking price for every sale, keyed by offer ID
+    pub offers_by_sale: LookupMap<ContractAndTokenId, UnorderedMap<u64, Offer>>,
+
+    //keep track of the tokens listed for rent. Rentals use the same unique ID as sales (contract ID, token ID)
+    pub rentals: UnorderedMap<ContractAndTokenId, Rental>,
+
+    //keep track of all the rental IDs for every account ID
+    pub rentals_by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+
+    //keep track of all the rental IDs every account is renting
+    pub leases_by_renter_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+
+    //keep track of the offers on tokens that don't have to be listed, keyed by offer ID
+    pub standing_offers: UnorderedMap<u64, StandingOffer>,
+
+    //keep track of all the standing offer IDs for every buyer
+    pub standing_offers_by_buyer_id: LookupMap<AccountId, UnorderedSet<u64>>,
+
+    //the ID that will be given to the next offer
+    pub next_offer_id: u64,
+
+    //keep track of the bundles of tokens that are sold together, keyed by bundle ID
+    pub bundles: UnorderedMap<u64, Bundle>,
+
+    //keep track of all the bundle IDs for every account ID
+    pub bundles_by_owner_id: LookupMap<AccountId, UnorderedSet<u64>>,
+
+    //the ID that will be given to the next bundle
+    pub next_bundle_id: u64,
+
     //keep track of the storage that accounts have payed
     pub storage_deposits: LookupMap<AccountId, NearToken>,
 
-    //keep track of how many FTs each account has deposited in order to purchase NFTs with
-    pub ft_deposits: LookupMap<AccountId, NearToken>,
+    //keep track of how many FTs each account has deposited (per fungible token contract) in order to purchase NFTs with
+    pub ft_deposits: LookupMap<AccountId, UnorderedMap<FungibleTokenId, NearToken>>,
+
+    //FTs that couldn't be paid out to an account (usually because they aren't registered on the FT contract),
+    //per fungible token contract. The account can claim them once they've registered
+    pub claimable: LookupMap<AccountId, UnorderedMap<FungibleTokenId, NearToken>>,
+
+    //accounts that are known to be registered on a fungible token contract, so that they can be paid in it
+    pub registered_sellers: LookupSet<(AccountId, FungibleTokenId)>,
+
+    //the GAS attached to NFT transfers and to resolving purchases
+    pub gas_config: GasConfig,
+
+    //the rules an admin has set for the tokens of each NFT contract. Collections that aren't in here use the defaults
+    pub collection_configs: LookupMap<AccountId, CollectionConfig>,
+
+    //keep track of how much every account has bought and sold on the market
+    pub account_activity: LookupMap<AccountId, AccountActivity>,
+
+    //keep track of the accounts that opted in to having their FT deposits returned once they've been left idle
+    pub idle_deposit_returns: LookupMap<AccountId, IdleDepositReturn>,
+
+    //keep track of the multi-token listings, keyed by sale ID. The market holds the listed tokens until they're sold
+    pub mt_sales: UnorderedMap<u64, MtSale>,
+
+    //keep track of all the multi-token sale IDs for every account ID
+    pub mt_sales_by_owner_id: LookupMap<AccountId, UnorderedSet<u64>>,
+
+    //the ID that will be given to the next multi-token listing
+    pub next_mt_sale_id: u64,
+
+    //keep track of when sales expire. Sales that aren't in here never expire
+    pub sale_expirations: LookupMap<ContractAndTokenId, U64>,
+
+    //keep track of the most recent sales of every token that has been sold on the market
+    pub token_sale_history: LookupMap<ContractAndTokenId, Vec<TokenSale>>,
+
+    //keep track of the keys sellers sign off-chain orders with
+    pub order_signers: LookupMap<AccountId, OrderSigner>,
+
+    //keep track of the starting price and minimum bid increment of the auctions whose sellers set them
+    pub auction_rules: LookupMap<ContractAndTokenId, AuctionRules>,
+
+    //keep track of the bundled tokens the market couldn't send on, and who can claim them
+    pub held_tokens: LookupMap<ContractAndTokenId, AccountId>,
 }
 
 /// Helper structure to for keys of the persistent collections.
 #[derive(BorshStorageKey, BorshSerialize)]
 #[borsh(crate = "near_sdk::borsh")]
 pub enum StorageKey {
+    V0Sales,
+    V0ByOwnerId,
+    V0ByOwnerIdInner { account_id_hash: CryptoHash },
+    V0ByNFTContractId,
+    V0ByNFTContractIdInner { account_id_hash: CryptoHash },
+    ByNFTTokenType,
+    ByNFTTokenTypeInner { token_type_hash: CryptoHash },
+    FTTokenIds,
+    StorageDeposits,
+    V0FTDeposits,
+    FTDepositsInner { account_id_hash: CryptoHash },
+    Auctions,
+    AuctionsByOwnerId,
+    AuctionsByOwnerIdInner { account_id_hash: CryptoHash },
+    OffersBySale,
+    OffersBySaleInner { sale_id_hash: CryptoHash },
+    FeeBalances,
+    AdminIds,
+    NFTContractIds,
+    StandingOffers,
+    StandingOffersByBuyerId,
+    StandingOffersByBuyerIdInner { account_id_hash: CryptoHash },
+    Bundles,
+    BundlesByOwnerId,
+    BundlesByOwnerIdInner { account_id_hash: CryptoHash },
+    Claimable,
+    ClaimableInner { account_id_hash: CryptoHash },
+    RegisteredSellers,
+    SalesByPrice,
+    SalesByPriceInner { index_hash: CryptoHash },
+    Rentals,
+    RentalsByOwnerId,
+    RentalsByOwnerIdInner { account_id_hash: CryptoHash },
+    LeasesByRenterId,
+    LeasesByRenterIdInner { account_id_hash: CryptoHash },
+    AuctionsByNFTContractId,
+    AuctionsByNFTContractIdInner { account_id_hash: CryptoHash },
+    CollectionConfigs,
+    AccountActivity,
+    IdleDepositReturns,
+    MtSales,
+    MtSalesByOwnerId,
+    MtSalesByOwnerIdInner { account_id_hash: CryptoHash },
+    SaleExpirations,
+    TokenSaleHistory,
+    OrderSigners,
+    AuctionRules,
+    HeldTokens,
     Sales,
     ByOwnerId,
     ByOwnerIdInner { account_id_hash: CryptoHash },
     ByNFTContractId,
     ByNFTContractIdInner { account_id_hash: CryptoHash },
-    ByNFTTokenType,
-    ByNFTTokenTypeInner { token_type_hash: CryptoHash },
-    FTTokenIds,
-    StorageDeposits,
     FTDeposits,
 }
 
@@ -97,98 +305,89 @@ impl Contract {
     */
     #[init]
     pub fn new(owner_id: AccountId, ft_id: AccountId) -> Self {
-        let this = Self {
+        let mut this = Self {
             //set the owner_id field equal to the passed in owner_id. 
             owner_id,
 
+            //the market starts unpaused with no extra admins and no approved NFT contracts
+            admin_ids: UnorderedSet::new(StorageKey::AdminIds),
+            paused: false,
+            approved_nft_contract_ids: UnorderedSet::new(StorageKey::NFTContractIds),
+
             //set the FT ID equal to the passed in ft_id.
-            ft_id,
+            ft_id: ft_id.clone(),
+
+            //use the default payout cap until the owner changes it
+            max_payees: DEFAULT_MAX_PAYEES,
+
+            //the market doesn't take a commission until the owner sets one
+            protocol_fee_bps: 0,
+
+            //referrers aren't paid until the owner sets a referral fee
+            referral_fee_bps: 0,
 
             //Storage keys are simply the prefixes used for the collections. This helps avoid data collision
+            fee_balances: UnorderedMap::new(StorageKey::FeeBalances),
+            near_fee_balance: ZERO_TOKEN,
+            approved_ft_token_ids: UnorderedSet::new(StorageKey::FTTokenIds),
             sales: UnorderedMap::new(StorageKey::Sales),
             by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
             by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId),
+            sales_by_price: LookupMap::new(StorageKey::SalesByPrice),
+            auctions: UnorderedMap::new(StorageKey::Auctions),
+            auctions_by_owner_id: LookupMap::new(StorageKey::AuctionsByOwnerId),
+            auctions_by_nft_contract_id: LookupMap::new(StorageKey::AuctionsByNFTContractId),
+            offers_by_sale: LookupMap::new(StorageKey::OffersBySale),
+            rentals: UnorderedMap::new(StorageKey::Rentals),
+            rentals_by_owner_id: LookupMap::new(StorageKey::RentalsByOwnerId),
+            leases_by_renter_id: LookupMap::new(StorageKey::LeasesByRenterId),
+            standing_offers: UnorderedMap::new(StorageKey::StandingOffers),
+            standing_offers_by_buyer_id: LookupMap::new(StorageKey::StandingOffersByBuyerId),
+            next_offer_id: 0,
+            bundles: UnorderedMap::new(StorageKey::Bundles),
+            bundles_by_owner_id: LookupMap::new(StorageKey::BundlesByOwnerId),
+            next_bundle_id: 0,
             storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
             ft_deposits: LookupMap::new(StorageKey::FTDeposits),
+            claimable: LookupMap::new(StorageKey::Claimable),
+            registered_sellers: LookupSet::new(StorageKey::RegisteredSellers),
+            //start with the default GAS until the owner changes it
+            gas_config: GasConfig::default(),
+            //every collection uses the default rules until an admin configures it
+            collection_configs: LookupMap::new(StorageKey::CollectionConfigs),
+            account_activity: LookupMap::new(StorageKey::AccountActivity),
+            idle_deposit_returns: LookupMap::new(StorageKey::IdleDepositReturns),
+            mt_sales: UnorderedMap::new(StorageKey::MtSales),
+            mt_sales_by_owner_id: LookupMap::new(StorageKey::MtSalesByOwnerId),
+            next_mt_sale_id: 0,
+            sale_expirations: LookupMap::new(StorageKey::SaleExpirations),
+            token_sale_history: LookupMap::new(StorageKey::TokenSaleHistory),
+            order_signers: LookupMap::new(StorageKey::OrderSigners),
+            auction_rules: LookupMap::new(StorageKey::AuctionRules),
+            held_tokens: LookupMap::new(StorageKey::HeldTokens),
         };
 
+        //the default FT can always be used to purchase NFTs
+        this.approved_ft_token_ids.insert(&ft_id);
+
+        //record which layout the state is stored with so that it can be migrated later
+        migrate::write_state_version();
+
         //return the Contract object
         this
     }
 
-    //Allows users to deposit storage. This is to cover the cost of storing sale objects on the contract
-    //Optional account ID is to users can pay for storage for other people.
-    #[payable]
-    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) {
-        //get the account ID to pay for storage for
-        let storage_account_id = account_id 
-            //convert the valid account ID into an account ID
-            .map(|a| a.into())
-            //if we didn't specify an account ID, we simply use the caller of the function
-            .unwrap_or_else(env::predecessor_account_id);
-
-        //get the deposit value which is how much the user wants to add to their storage
-        let deposit = env::attached_deposit();
-
-        //make sure the deposit is greater than or equal to the minimum storage for a sale
-        assert!(
-            deposit.ge(&storage_per_sale()),
-            "Requires minimum deposit of {}",
-            storage_per_sale()
+    //Allows the owner to change the maximum number of accounts the market will pay out for a single sale
+    pub fn set_max_payees(&mut self, max_payees: u32) {
+        //make sure only the owner can change the payout cap
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can change the payout cap"
         );
+        //the seller always has to fit in the payout
+        assert!(max_payees > 0, "The payout cap must be at least 1");
 
-        //get the balance of the account (if the account isn't in the map we default to a balance of 0)
-        let mut balance = self.storage_deposits.get(&storage_account_id).unwrap_or(ZERO_TOKEN);
-        //add the deposit to their balance
-        balance = balance.saturating_add(deposit);
-        //insert the balance back into the map for that account ID
-        self.storage_deposits.insert(&storage_account_id, &balance);
-    }
-
-    //Allows users to withdraw any excess storage that they're not using. Say Bob pays 0.01N for 1 sale
-    //Alice then buys Bob's token. This means bob has paid 0.01N for a sale that's no longer on the marketplace
-    //Bob could then withdraw this 0.01N back into his account. 
-    #[payable]
-    pub fn storage_withdraw(&mut self) {
-        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
-        //this will redirect them to the NEAR wallet (or requires a full access key). 
-        assert_one_yocto();
-
-        //the account to withdraw storage to is always the function caller
-        let owner_id = env::predecessor_account_id();
-        //get the amount that the user has by removing them from the map. If they're not in the map, default to 0
-        let mut amount = self.storage_deposits.remove(&owner_id).unwrap_or(ZERO_TOKEN);
-        
-        //how many sales is that user taking up currently. This returns a set
-        let sales = self.by_owner_id.get(&owner_id);
-        //get the length of that set. 
-        let len = sales.map(|s| s.len()).unwrap_or_default();
-        //how much NEAR is being used up for all the current sales on the account 
-        let diff = storage_per_sale().saturating_mul(len.into());
-
-        //the excess to withdraw is the total storage paid - storage being used up.
-        amount = amount.saturating_sub(diff);
-
-        //if that excess to withdraw is > 0, we transfer the amount to the user.
-        if amount.gt(&ZERO_TOKEN) {
-            Promise::new(owner_id.clone()).transfer(amount);
-        }
-        //we need to add back the storage being used up into the map if it's greater than 0.
-        //this is so that if the user had 500 sales on the market, we insert that value here so
-        //if those sales get taken down, the user can then go and withdraw 500 sales worth of storage.
-        if diff.gt(&ZERO_TOKEN) {
-            self.storage_deposits.insert(&owner_id, &diff);
-        }
-    }
-
-    /// views
-    //return the minimum storage for 1 sale
-    pub fn storage_minimum_balance(&self) -> NearToken {
-        storage_per_sale()
-    }
-
-    //return how much storage an account has paid for
-    pub fn storage_balance_of(&self, account_id: AccountId) -> NearToken {
-        self.storage_deposits.get(&account_id).unwrap_or(ZERO_TOKEN)
+        self.max_payees = max_payees;
     }
 }
\ No newline at end of file
@@ -0,0 +1,327 @@
+use crate::*;
+
+/*
+    The market's state is versioned so that fields can be added to a live contract. The version is kept in its own
+    storage key, next to the contract state. The market was first deployed without the key, so a state that doesn't
+    have it is on V0.
+
+    Migrating from V0 only rewrites the contract struct. Moving every sale and FT deposit in the same call could run
+    out of GAS, so those are left under their old prefixes and moved over afterwards:
+    - `migrate_v0_sales` moves a batch of the old sales at a time and can be called by anyone until none are left
+    - `migrate_ft_deposit` moves one account's old FT deposit and can be called by anyone for any account
+
+    To add a field once the current layout is deployed:
+    - copy the current `Contract` struct below as `ContractV<N>` and add a `V<N>` variant to `VersionedContract`
+    - add the field to `Contract` and bump `CURRENT_STATE_VERSION`
+    - add a `migrate_from_v<N>` that moves everything over and fills in the new field
+*/
+
+//the storage key the state version is kept under
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+//the storage key the V0 sales map is kept under until all of its sales are migrated
+const V0_SALES_KEY: &[u8] = b"V0_SALES";
+
+//the version of the current `Contract` layout
+pub const CURRENT_STATE_VERSION: u8 = 1;
+
+//every layout the market's state has had
+pub enum VersionedContract {
+    V0(ContractV0),
+    Current(Contract),
+}
+
+impl VersionedContract {
+    //reads the contract state with the layout given by the stored state version
+    pub fn read() -> Self {
+        match env::storage_read(STATE_VERSION_KEY).and_then(|version| version.first().copied()) {
+            None => Self::V0(env::state_read().expect("Failed to read the contract state")),
+            Some(CURRENT_STATE_VERSION) => Self::Current(env::state_read().expect("Failed to read the contract state")),
+            Some(version) => env::panic_str(&format!("Unknown state version {}", version)),
+        }
+    }
+
+    //migrates the state to the current layout
+    pub fn upgrade(self) -> Contract {
+        match self {
+            Self::V0(old) => Contract::migrate_from_v0(old),
+            Self::Current(contract) => contract,
+        }
+    }
+}
+
+//records that the state is on the current layout. Called whenever the state is initialized or migrated
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[CURRENT_STATE_VERSION]);
+}
+
+//how many V0 sales an account still has waiting to be migrated. They take up storage until they are
+pub(crate) fn v0_sale_count(account_id: &AccountId) -> u64 {
+    let by_owner_id: LookupMap<AccountId, UnorderedSet<String>> = LookupMap::new(StorageKey::V0ByOwnerId);
+    by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default()
+}
+
+//reads the V0 sales that haven't been migrated yet, if there are any
+fn read_v0_sales() -> Option<UnorderedMap<String, V0Sale>> {
+    env::storage_read(V0_SALES_KEY)
+        .map(|sales| UnorderedMap::try_from_slice(&sales).expect("Failed to read the V0 sales"))
+}
+
+//takes a value out of one of the V0 indexes, removing the set once it's empty
+fn remove_from_v0_index<T: BorshSerialize + BorshDeserialize>(
+    index: &mut LookupMap<AccountId, UnorderedSet<T>>,
+    key: &AccountId,
+    value: &T,
+) {
+    let Some(mut set) = index.get(key) else {
+        return;
+    };
+    set.remove(value);
+    if set.is_empty() {
+        index.remove(key);
+    } else {
+        index.insert(key, &set);
+    }
+}
+
+/*
+    V0 is the layout the market was first deployed with. Sales could only be bought with the market's one FT, so
+    sales just had a price and every account had a single FT deposit. Sale IDs were `contract ID + "." + token ID`
+    strings.
+*/
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct V0Sale {
+    pub owner_id: AccountId,
+    pub approval_id: u32,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub sale_conditions: SalePriceInFTs,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct ContractV0 {
+    pub owner_id: AccountId,
+    pub ft_id: AccountId,
+    pub sales: UnorderedMap<String, V0Sale>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<String>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub storage_deposits: LookupMap<AccountId, NearToken>,
+    pub ft_deposits: LookupMap<AccountId, NearToken>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        Called by the contract itself right after the new code is deployed. Reads the state with whatever layout
+        it was stored with, migrates it to the current one and records the new state version. Calling it when
+        the state is already current does nothing.
+    */
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let contract = VersionedContract::read().upgrade();
+        write_state_version();
+        contract
+    }
+
+    /*
+        Moves up to `limit` of the sales listed before the market was upgraded from V0 and returns how many are
+        left. Anyone can call this until it returns 0. A sale can't be bought until it's been moved, and a sale
+        whose token has been listed again since the upgrade is dropped.
+    */
+    pub fn migrate_v0_sales(&mut self, limit: u64) -> u64 {
+        let Some(mut old_sales) = read_v0_sales() else {
+            return 0;
+        };
+        let mut old_by_owner_id: LookupMap<AccountId, UnorderedSet<String>> =
+            LookupMap::new(StorageKey::V0ByOwnerId);
+        let mut old_by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>> =
+            LookupMap::new(StorageKey::V0ByNFTContractId);
+
+        for _ in 0..limit.min(old_sales.len()) {
+            //take the sales from the end so that removing them doesn't move any of the others
+            let sale_id = old_sales.keys_as_vector().get(old_sales.len() - 1).unwrap();
+            let sale = old_sales.remove(&sale_id).unwrap();
+            let nft_contract_id: AccountId = sale.nft_contract_id.parse().unwrap();
+
+            remove_from_v0_index(&mut old_by_owner_id, &sale.owner_id, &sale_id);
+            remove_from_v0_index(&mut old_by_nft_contract_id, &nft_contract_id, &sale.token_id);
+            self.internal_migrate_v0_sale(nft_contract_id, sale);
+        }
+
+        //the map's length is kept in the struct, so it has to be written back after every batch
+        let remaining = old_sales.len();
+        if remaining == 0 {
+            env::storage_remove(V0_SALES_KEY);
+        } else {
+            env::storage_write(V0_SALES_KEY, &near_sdk::borsh::to_vec(&old_sales).unwrap());
+        }
+        remaining
+    }
+
+    /*
+        Moves the FT deposit an account had before the market was upgraded from V0 into its deposit of the
+        market's FT, and returns the amount moved. Anyone can call this for any account.
+    */
+    pub fn migrate_ft_deposit(&mut self, account_id: AccountId) -> NearToken {
+        let mut old_ft_deposits: LookupMap<AccountId, NearToken> = LookupMap::new(StorageKey::V0FTDeposits);
+        let amount = old_ft_deposits.remove(&account_id).unwrap_or(ZERO_TOKEN);
+        if !amount.is_zero() {
+            let ft_id = self.ft_id.clone();
+            self.internal_increase_ft_deposit(&account_id, &ft_id, amount);
+        }
+        amount
+    }
+}
+
+impl Contract {
+    /*
+        Builds the current layout from V0. Everything V0 didn't have starts out the way `new` sets it up, and the
+        storage deposits are kept under the same prefix. The sales map is saved under its own key for
+        `migrate_v0_sales`, while the old indexes and FT deposits stay where they are, under the `V0` storage keys.
+    */
+    pub(crate) fn migrate_from_v0(old: ContractV0) -> Self {
+        if !old.sales.is_empty() {
+            env::storage_write(V0_SALES_KEY, &near_sdk::borsh::to_vec(&old.sales).unwrap());
+        }
+        Self::new(old.owner_id, old.ft_id)
+    }
+
+    //moves one V0 sale over, priced in the market's FT like before
+    fn internal_migrate_v0_sale(&mut self, nft_contract_id: AccountId, old: V0Sale) {
+        let contract_and_token_id = (nft_contract_id.clone(), old.token_id.clone());
+        //the token has been listed again since the upgrade, which replaces the old sale
+        if self.sales.get(&contract_and_token_id).is_some()
+            || self.auctions.get(&contract_and_token_id).is_some()
+            || self.rentals.get(&contract_and_token_id).is_some()
+        {
+            return;
+        }
+
+        //the NFT contracts that had sales up are approved so that those sales can still be bought
+        self.approved_nft_contract_ids.insert(&nft_contract_id);
+        self.internal_add_sale(&Sale {
+            owner_id: old.owner_id,
+            approval_id: old.approval_id as u64,
+            nft_contract_id,
+            token_id: old.token_id,
+            sale_conditions: SaleConditions {
+                ft_token_id: self.ft_id.clone(),
+                price: old.sale_conditions,
+                near_price: None,
+            },
+            dutch_auction: None,
+            allowed_buyer_id: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ft_testing::*;
+
+    fn ft() -> AccountId {
+        "token.near".parse().unwrap()
+    }
+
+    fn nft() -> AccountId {
+        "nft.near".parse().unwrap()
+    }
+
+    //writes a V0 state where alice has two sales up and bob has FTs deposited
+    fn write_v0_state() {
+        let mut old = ContractV0 {
+            owner_id: owner(),
+            ft_id: ft(),
+            sales: UnorderedMap::new(StorageKey::V0Sales),
+            by_owner_id: LookupMap::new(StorageKey::V0ByOwnerId),
+            by_nft_contract_id: LookupMap::new(StorageKey::V0ByNFTContractId),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            ft_deposits: LookupMap::new(StorageKey::V0FTDeposits),
+        };
+
+        let mut by_owner_id =
+            UnorderedSet::new(StorageKey::V0ByOwnerIdInner { account_id_hash: hash_account_id(&alice()) });
+        let mut by_nft_contract_id =
+            UnorderedSet::new(StorageKey::V0ByNFTContractIdInner { account_id_hash: hash_account_id(&nft()) });
+        for token_id in ["1", "2"] {
+            let sale_id = format!("{}.{}", nft(), token_id);
+            old.sales.insert(&sale_id, &V0Sale {
+                owner_id: alice(),
+                approval_id: 7,
+                nft_contract_id: nft().to_string(),
+                token_id: token_id.to_string(),
+                sale_conditions: tokens(100),
+            });
+            by_owner_id.insert(&sale_id);
+            by_nft_contract_id.insert(&token_id.to_string());
+        }
+        old.by_owner_id.insert(&alice(), &by_owner_id);
+        old.by_nft_contract_id.insert(&nft(), &by_nft_contract_id);
+        old.ft_deposits.insert(&bob(), &tokens(50));
+
+        env::state_write(&old);
+    }
+
+    #[test]
+    fn migrates_a_v0_state() {
+        Context::new().predecessor(contract()).set();
+        write_v0_state();
+
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.owner_id, owner());
+        assert!(contract.approved_ft_token_ids.contains(&ft()));
+        //the sales that haven't been moved yet still count against alice's storage
+        assert_eq!(contract.internal_listing_count(&alice()), 2);
+        assert_eq!(env::storage_read(STATE_VERSION_KEY), Some(vec![CURRENT_STATE_VERSION]));
+
+        //anyone can move the sales, a batch at a time
+        Context::new().predecessor(charlie()).set();
+        assert_eq!(contract.migrate_v0_sales(1), 1);
+        assert_eq!(contract.migrate_v0_sales(1), 0);
+        assert_eq!(contract.migrate_v0_sales(1), 0);
+        assert!(read_v0_sales().is_none());
+        assert_eq!(v0_sale_count(&alice()), 0);
+        assert_eq!(contract.internal_listing_count(&alice()), 2);
+        assert!(contract.approved_nft_contract_ids.contains(&nft()));
+        for token_id in ["1", "2"] {
+            let sale = contract.sales.get(&(nft(), token_id.to_string())).unwrap();
+            assert_eq!(sale.owner_id, alice());
+            assert_eq!(sale.approval_id, 7);
+            assert_eq!(sale.sale_conditions.ft_token_id, ft());
+            assert_eq!(sale.sale_conditions.price, tokens(100));
+        }
+
+        //and any account's old FT deposit
+        assert_eq!(contract.migrate_ft_deposit(bob()), tokens(50));
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(50));
+        assert_eq!(contract.migrate_ft_deposit(bob()), ZERO_TOKEN);
+    }
+
+    #[test]
+    fn sales_for_relisted_tokens_are_dropped() {
+        Context::new().predecessor(contract()).set();
+        write_v0_state();
+        let mut contract = Contract::migrate();
+
+        //alice lists token 1 again before the old sales are moved
+        contract.internal_add_sale(&Sale {
+            owner_id: alice(),
+            approval_id: 8,
+            nft_contract_id: nft(),
+            token_id: "1".to_string(),
+            sale_conditions: SaleConditions { ft_token_id: ft(), price: tokens(200), near_price: None },
+            dutch_auction: None,
+            allowed_buyer_id: None,
+        });
+
+        assert_eq!(contract.migrate_v0_sales(10), 0);
+        let sale = contract.sales.get(&(nft(), "1".to_string())).unwrap();
+        assert_eq!(sale.approval_id, 8);
+        assert_eq!(sale.sale_conditions.price, tokens(200));
+        assert_eq!(contract.internal_listing_count(&alice()), 2);
+    }
+}
@@ -0,0 +1,462 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+
+//a listing of several of the same token from a multi token contract. Buyers can take any part of what's left at the
+//unit price. Multi token contracts don't have approvals, so the market holds the listed tokens until they're sold or
+//the listing is removed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtSale {
+    //unique ID of the listing
+    pub sale_id: U64,
+    //owner of the listing / tokens
+    pub owner_id: AccountId,
+    //multi token contract where the token was minted
+    pub mt_contract_id: AccountId,
+    //actual token ID being listed
+    pub token_id: TokenId,
+    //fungible token the listing is priced in
+    pub ft_token_id: FungibleTokenId,
+    //price for a single token
+    pub unit_price: SalePriceInFTs,
+    //how many tokens are still for sale. The market holds this many of the token
+    pub remaining: U128,
+}
+
+//the msg passed to mt_transfer_call to list the transferred tokens
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct MtSaleArgs {
+    //price for a single token
+    pub unit_price: SalePriceInFTs,
+    //fungible token the listing is priced in. If it's not set, the market's default FT is used
+    #[serde(default)]
+    pub ft_token_id: Option<FungibleTokenId>,
+}
+
+/*
+    trait that will be used as the callback from the MT contract. When mt_transfer_call is
+    called to send tokens to the market, it will fire a cross contract call to this marketplace
+    and this is the function that is invoked.
+*/
+trait MultiTokenReceiver {
+    fn mt_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_ids: Vec<AccountId>,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        msg: String,
+    ) -> Vec<U128>;
+}
+
+//implementation of the trait
+#[near_bindgen]
+impl MultiTokenReceiver for Contract {
+    /// List tokens from a multi token contract by sending them to the market with mt_transfer_call and a msg like
+    /// `{"unit_price": "...", "ft_token_id": "..."}`. Every token that was sent is listed, so this returns 0 unused
+    /// tokens. If the listing can't be made, the call panics and the MT contract hands the tokens back.
+    fn mt_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_ids: Vec<AccountId>,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        msg: String,
+    ) -> Vec<U128> {
+        //tokens can't be listed while the market is paused
+        self.assert_not_paused();
+
+        // get the contract ID which is the predecessor and make sure its tokens can be traded on the market
+        let mt_contract_id = env::predecessor_account_id();
+        self.assert_approved_nft_contract(&mt_contract_id);
+
+        //a listing is for a single token ID owned by the account that sent it
+        assert_eq!(token_ids.len(), 1, "Only one token ID can be listed at a time");
+        assert_eq!(amounts.len(), 1, "Only one token ID can be listed at a time");
+        assert_eq!(previous_owner_ids.first(), Some(&sender_id), "Only the owner can list their tokens");
+        let quantity = amounts[0].0;
+        assert!(quantity > 0, "Quantity must be greater than 0");
+
+        //if the msg isn't a valid listing, panic so that the tokens are returned
+        let MtSaleArgs { unit_price, ft_token_id } =
+            near_sdk::serde_json::from_str(&msg).expect("Not valid MtSaleArgs");
+
+        //if no fungible token was passed in, the listing is priced in the market's default FT
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        self.assert_approved_ft_token_id(&ft_token_id);
+
+        //make sure the listing follows the rules of its collection
+        let collection_config = self.internal_collection_config(&mt_contract_id);
+        collection_config.assert_trading_enabled(&mt_contract_id);
+        collection_config.assert_accepts_ft(&ft_token_id);
+        assert!(unit_price.gt(&ZERO_TOKEN), "Unit price must be greater than 0");
+        collection_config.assert_min_price(unit_price);
+        //make sure the whole quantity can be bought at once without the price overflowing
+        unit_price.checked_mul(quantity).expect("Price overflow");
+
+        //the listing takes up storage just like a sale of a single token
+        self.assert_storage_for_listings(&sender_id, 1);
+
+        //record the listing under a new unique ID
+        let sale_id = self.next_mt_sale_id;
+        self.next_mt_sale_id += 1;
+        let token_id = token_ids.into_iter().next().unwrap();
+        let mt_sale = MtSale {
+            sale_id: U64(sale_id),
+            owner_id: sender_id.clone(),
+            mt_contract_id: mt_contract_id.clone(),
+            token_id: token_id.clone(),
+            ft_token_id: ft_token_id.clone(),
+            unit_price,
+            remaining: U128(quantity),
+        };
+        self.mt_sales.insert(&sale_id, &mt_sale);
+
+        //get the multi-token listings for the owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.mt_sales_by_owner_id.get(&sender_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::MtSalesByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&sender_id),
+                }
+            )
+        });
+        by_owner_id.insert(&sale_id);
+        self.mt_sales_by_owner_id.insert(&sender_id, &by_owner_id);
+
+        //let indexers know about the listing. The price is per token
+        SaleListed {
+            owner_id: &sender_id,
+            nft_contract_id: &mt_contract_id,
+            token_id: &token_id,
+            ft_token_id: &ft_token_id,
+            price: &unit_price,
+            near_price: None,
+            quantity: Some(U128(quantity)),
+        }
+        .emit();
+
+        //every token was kept for the listing
+        vec![U128(0)]
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Buy `quantity` tokens from a multi-token listing with your FT deposits. You pay the unit price for each one
+    /// and the rest stay listed. If the tokens can't be sent to you, you're refunded and they're listed again.
+    #[payable]
+    pub fn buy_mt_sale(&mut self, sale_id: U64, quantity: U128) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
+
+        //get the listing object from the sale ID. If the listing doesn't exist, panic.
+        let mut mt_sale = self.mt_sales.get(&sale_id.0).expect("No sale");
+
+        //get the buyer ID which is the person who called the function and make sure they're not the owner of the listing
+        let buyer_id = env::predecessor_account_id();
+        assert_ne!(mt_sale.owner_id, buyer_id, "Cannot buy your own sale.");
+        self.assert_trading_enabled(&mt_sale.mt_contract_id);
+
+        //make sure there are enough tokens left and work out what they cost
+        assert!(quantity.0 > 0, "Quantity must be greater than 0");
+        assert!(
+            quantity.0 <= mt_sale.remaining.0,
+            "Only {} tokens are left in this sale",
+            mt_sale.remaining.0
+        );
+        let price = mt_sale.unit_price.checked_mul(quantity.0).expect("Price overflow");
+
+        //escrow the price from the buyer's deposits. This panics if they haven't deposited enough
+        self.internal_decrease_ft_deposit(&buyer_id, &mt_sale.ft_token_id, price);
+
+        //take the tokens off the listing, and the listing off the market once it's sold out
+        mt_sale.remaining = U128(mt_sale.remaining.0 - quantity.0);
+        if mt_sale.remaining.0 == 0 {
+            self.internal_remove_mt_sale(sale_id.0);
+        } else {
+            self.mt_sales.insert(&sale_id.0, &mt_sale);
+        }
+
+        //the market holds the tokens so it can send them straight to the buyer
+        ext_mt_contract::ext(mt_sale.mt_contract_id.clone())
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(self.gas_config.nft_transfer)
+            .mt_transfer(
+                buyer_id.clone(), //buyer to transfer the tokens to
+                mt_sale.token_id.clone(), //token ID to transfer
+                quantity, //how many tokens were bought
+                None, //the market holds the tokens so it doesn't need an approval
+                Some("purchased from market".to_string()), //memo (to include some context)
+            )
+        //after the transfer, the seller is paid or the buyer is refunded
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_MT_PURCHASE)
+            .resolve_mt_purchase(
+                mt_sale, //the listing as it was after the purchase
+                buyer_id, //the buyer to refund if the transfer failed
+                quantity, //how many tokens were bought
+                price, //the price that was escrowed
+            )
+        )
+    }
+
+    //private method used to resolve the transfer made by buy_mt_sale. If the buyer got the tokens, the market takes its
+    //commission and the seller is paid. Otherwise the buyer is refunded and the tokens go back on the listing, or back
+    //to the seller if the listing has been removed since. Returns the amount paid
+    #[private]
+    pub fn resolve_mt_purchase(
+        &mut self,
+        mt_sale: MtSale,
+        buyer_id: AccountId,
+        quantity: U128,
+        price: NearToken,
+    ) -> NearToken {
+        if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+            //refund the buyer into their deposits
+            self.internal_increase_ft_deposit(&buyer_id, &mt_sale.ft_token_id, price);
+
+            //put the tokens back on the listing if it's still up
+            if let Some(mut current) = self.mt_sales.get(&mt_sale.sale_id.0) {
+                current.remaining = U128(current.remaining.0 + quantity.0);
+                self.mt_sales.insert(&mt_sale.sale_id.0, &current);
+                return ZERO_TOKEN;
+            }
+
+            //otherwise the listing sold out or was removed, so the tokens go back to the seller
+            ext_mt_contract::ext(mt_sale.mt_contract_id.clone())
+                // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+                .with_attached_deposit(NearToken::from_yoctonear(1))
+                .with_static_gas(self.gas_config.nft_transfer)
+                .mt_transfer(
+                    mt_sale.owner_id.clone(), //seller to return the tokens to
+                    mt_sale.token_id.clone(), //token ID to transfer
+                    quantity, //how many tokens weren't sold
+                    None, //the market holds the tokens so it doesn't need an approval
+                    Some("purchase rolled back".to_string()), //memo (to include some context)
+                );
+            return ZERO_TOKEN;
+        }
+
+        // The market takes its commission and the seller is paid the rest
+        let protocol_fee = self.internal_protocol_fee(price);
+        self.internal_increase_fee_balance(&mt_sale.ft_token_id, protocol_fee);
+        self.internal_record_sale(
+            &buyer_id,
+            &mt_sale.owner_id,
+            &Some(mt_sale.ft_token_id.clone()),
+            u64::try_from(quantity.0).unwrap_or(u64::MAX),
+            price,
+            price.saturating_sub(protocol_fee),
+            protocol_fee,
+        );
+        self.internal_send_payment(
+            &Some(mt_sale.ft_token_id.clone()),
+            mt_sale.owner_id.clone(), //seller to transfer the FTs to
+            price.saturating_sub(protocol_fee),
+            "Sale from marketplace",
+        );
+
+        //let indexers know about the fill along with how many tokens are still listed
+        let remaining = self.mt_sales.get(&mt_sale.sale_id.0).map(|current| current.remaining).unwrap_or(U128(0));
+        SaleFilled {
+            sale_id: mt_sale.sale_id,
+            owner_id: &mt_sale.owner_id,
+            buyer_id: &buyer_id,
+            mt_contract_id: &mt_sale.mt_contract_id,
+            token_id: &mt_sale.token_id,
+            ft_token_id: &mt_sale.ft_token_id,
+            quantity,
+            remaining,
+            price: &price,
+        }
+        .emit();
+
+        price
+    }
+
+    //removes a multi-token listing from the market and sends the tokens that weren't sold back to the owner
+    #[payable]
+    pub fn remove_mt_sale(&mut self, sale_id: U64) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //get the listing object as the return value from removing the listing internally
+        let mt_sale = self.internal_remove_mt_sale(sale_id.0);
+        //make sure the caller is the owner of the listing. If this fails, the removal will revert
+        assert_eq!(env::predecessor_account_id(), mt_sale.owner_id, "Must be sale owner");
+
+        SaleRemoved {
+            owner_id: &mt_sale.owner_id,
+            nft_contract_id: &mt_sale.mt_contract_id,
+            token_id: &mt_sale.token_id,
+        }
+        .emit();
+
+        //send the tokens the market was holding back to the owner
+        ext_mt_contract::ext(mt_sale.mt_contract_id)
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(self.gas_config.nft_transfer)
+            .mt_transfer(
+                mt_sale.owner_id, //owner to return the tokens to
+                mt_sale.token_id, //token ID to transfer
+                mt_sale.remaining, //how many tokens weren't sold
+                None, //the market holds the tokens so it doesn't need an approval
+                Some("sale removed from market".to_string()), //memo (to include some context)
+            )
+    }
+
+    /// views
+
+    //get the multi-token listing for a given sale ID
+    pub fn get_mt_sale(&self, sale_id: U64) -> Option<MtSale> {
+        self.mt_sales.get(&sale_id.0)
+    }
+
+    //returns paginated multi-token listings across the whole market. (result is a vector of listings)
+    pub fn get_mt_sales(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<MtSale> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.mt_sales
+            .values_as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    //returns the multi-token listings of a given account
+    pub fn get_mt_sales_by_owner_id(&self, account_id: AccountId) -> Vec<MtSale> {
+        self.mt_sales_by_owner_id
+            .get(&account_id)
+            .map(|sale_ids| {
+                sale_ids
+                    .iter()
+                    .map(|sale_id| self.mt_sales.get(&sale_id).unwrap())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for removing a multi-token listing from the market. This returns the removed listing object
+    pub(crate) fn internal_remove_mt_sale(&mut self, sale_id: u64) -> MtSale {
+        //get the listing object by removing the sale ID. If there was no listing, panic
+        let mt_sale = self.mt_sales.remove(&sale_id).expect("No sale");
+
+        //remove the sale ID from the owner's set, and the set itself if it's now empty
+        let mut by_owner_id = self
+            .mt_sales_by_owner_id
+            .get(&mt_sale.owner_id)
+            .expect("No sale by_owner_id");
+        by_owner_id.remove(&sale_id);
+        if by_owner_id.is_empty() {
+            self.mt_sales_by_owner_id.remove(&mt_sale.owner_id);
+        } else {
+            self.mt_sales_by_owner_id.insert(&mt_sale.owner_id, &by_owner_id);
+        }
+
+        //return the listing object
+        mt_sale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    fn ft() -> AccountId {
+        "token.near".parse().unwrap()
+    }
+
+    fn mt() -> AccountId {
+        "mt.near".parse().unwrap()
+    }
+
+    //creates the market with alice selling 10 gold tokens for 20 FTs each and bob holding 1000 FTs in deposits
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new(owner(), ft());
+        contract.approved_nft_contract_ids.insert(&mt());
+        contract.storage_deposits.insert(&alice(), &NearToken::from_near(1));
+        contract.internal_increase_ft_deposit(&bob(), &ft(), tokens(1000));
+
+        Context::new().cross_contract_caller(mt()).set();
+        contract.mt_on_transfer(
+            alice(),
+            vec![alice()],
+            vec!["gold".to_string()],
+            vec![U128(10)],
+            r#"{"unit_price": "20"}"#.to_string(),
+        );
+        contract
+    }
+
+    //has bob buy `quantity` tokens from alice's listing
+    fn buy(contract: &mut Contract, quantity: u128) {
+        Context::new().predecessor(bob()).one_yocto().set();
+        let _ = contract.buy_mt_sale(U64(0), U128(quantity));
+    }
+
+    #[test]
+    fn buyers_can_take_part_of_a_listing() {
+        let mut contract = setup();
+
+        buy(&mut contract, 3);
+        let mt_sale = contract.get_mt_sale(U64(0)).unwrap();
+        assert_eq!(mt_sale.remaining, U128(7));
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(940));
+
+        Context::new()
+            .predecessor(ft_testing::contract())
+            .set_with_promise_result(PromiseResult::Successful(vec![]));
+        assert_eq!(contract.resolve_mt_purchase(mt_sale, bob(), U128(3), tokens(60)), tokens(60));
+        assert_eq!(contract.get_mt_sale(U64(0)).unwrap().remaining, U128(7));
+    }
+
+    #[test]
+    fn failed_fills_go_back_on_the_listing() {
+        let mut contract = setup();
+        buy(&mut contract, 3);
+        let mt_sale = contract.get_mt_sale(U64(0)).unwrap();
+
+        Context::new().predecessor(ft_testing::contract()).set_with_promise_result(PromiseResult::Failed);
+        assert_eq!(contract.resolve_mt_purchase(mt_sale, bob(), U128(3), tokens(60)), ZERO_TOKEN);
+
+        assert_eq!(contract.get_mt_sale(U64(0)).unwrap().remaining, U128(10));
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(1000));
+    }
+
+    #[test]
+    fn listings_are_removed_once_sold_out() {
+        let mut contract = setup();
+
+        buy(&mut contract, 10);
+
+        assert!(contract.get_mt_sale(U64(0)).is_none());
+        assert_eq!(contract.internal_listing_count(&alice()), 0);
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(800));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 10 tokens are left in this sale")]
+    fn buyers_cannot_take_more_than_what_is_left() {
+        let mut contract = setup();
+
+        buy(&mut contract, 11);
+    }
+}
@@ -3,10 +3,99 @@ use crate::*;
 /// approval callbacks from NFT Contracts
 
 //struct for keeping track of the sale conditions for a Sale
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
+#[derive(Serialize, Deserialize, NearSchema, Clone)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
 pub struct SaleArgs {
-    pub sale_conditions: SalePriceInFTs,
+    //price in the sale's fungible token. If it's not passed in, the sale can only be bought with NEAR
+    pub sale_conditions: Option<SalePriceInFTs>,
+    //price in NEAR. If it's passed in, the sale can also be bought with offer_near
+    pub near_price: Option<NearToken>,
+    //fungible token the sale is priced in. If it's not passed in, the market's default FT is used
+    pub ft_token_id: Option<FungibleTokenId>,
+    //if passed in, the price declines from the dutch auction's start price down to sale_conditions
+    pub dutch_auction: Option<DutchAuction>,
+    //if passed in, this is a private sale and only this account can buy the token
+    pub allowed_buyer_id: Option<AccountId>,
+}
+
+//struct for keeping track of the conditions for an Auction
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct AuctionArgs {
+    pub auction: AuctionConditions,
+}
+
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct AuctionConditions {
+    //the lowest winning bid the token will be sold for
+    pub reserve_price: SalePriceInFTs,
+    //when bidding closes (in nanoseconds)
+    pub end_timestamp: U64,
+    //fungible token bids have to be placed in. If it's not passed in, the market's default FT is used
+    pub ft_token_id: Option<FungibleTokenId>,
+    //the lowest first bid, which can be below the reserve price. If it's not passed in, bidding starts at the reserve price
+    pub starting_price: Option<SalePriceInFTs>,
+    //how much each bid has to beat the highest bid by. If it's not passed in, any higher bid is accepted
+    pub min_bid_increment: Option<BidIncrement>,
+}
+
+//struct for keeping track of the conditions for a Rental
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct RentalArgs {
+    pub rental: RentalConditions,
+}
+
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct RentalConditions {
+    //rent for a single day
+    pub price_per_day: SalePriceInFTs,
+    //the most days the token can be rented for at once
+    pub max_days: u32,
+    //fungible token rent has to be paid in. If it's not passed in, the market's default FT is used
+    pub ft_token_id: Option<FungibleTokenId>,
+}
+
+//the msg passed to nft_approve can either list the token for a fixed price sale, put it up for auction,
+//list it for rent or accept a standing offer on it
+pub enum ListingArgs {
+    Sale(SaleArgs),
+    Auction(AuctionArgs),
+    Rental(RentalArgs),
+    AcceptOffer(AcceptOfferArgs),
+}
+
+impl ListingArgs {
+    //parses the msg passed to nft_approve. Messages with an "auction" field are auctions, messages with a "rental"
+    //field are rentals, messages with an "accept_offer" field accept a standing offer and everything else is a sale. Unknown fields are rejected so that typos don't silently list a token with the wrong conditions
+    pub fn from_msg(msg: &str) -> Self {
+        let value: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(msg)
+            .unwrap_or_else(|e| env::panic_str(&format!("msg is not valid JSON: {}", e)));
+
+        if value.get("accept_offer").is_some() {
+            ListingArgs::AcceptOffer(
+                near_sdk::serde_json::from_value(value)
+                    .unwrap_or_else(|e| env::panic_str(&format!("Not valid AcceptOfferArgs: {}", e))),
+            )
+        } else if value.get("auction").is_some() {
+            ListingArgs::Auction(
+                near_sdk::serde_json::from_value(value)
+                    .unwrap_or_else(|e| env::panic_str(&format!("Not valid AuctionArgs: {}", e))),
+            )
+        } else if value.get("rental").is_some() {
+            ListingArgs::Rental(
+                near_sdk::serde_json::from_value(value)
+                    .unwrap_or_else(|e| env::panic_str(&format!("Not valid RentalArgs: {}", e))),
+            )
+        } else {
+            ListingArgs::Sale(
+                near_sdk::serde_json::from_value(value)
+                    .unwrap_or_else(|e| env::panic_str(&format!("Not valid SaleArgs: {}", e))),
+            )
+        }
+    }
 }
 
 /*
@@ -19,7 +108,7 @@ trait NonFungibleTokenApprovalsReceiver {
         &mut self,
         token_id: TokenId,
         owner_id: AccountId,
-        approval_id: u32,
+        approval_id: u64,
         msg: String,
     );
 }
@@ -33,7 +122,7 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
         &mut self,
         token_id: TokenId,
         owner_id: AccountId,
-        approval_id: u32,
+        approval_id: u64,
         msg: String,
     ) {
         // get the contract ID which is the predecessor
@@ -54,52 +143,284 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
             signer_id,
             "owner_id should be signer_id"
         );
+        //make sure the market isn't calling itself
+        assert_ne!(
+            nft_contract_id,
+            env::current_account_id(),
+            "nft_on_approve should be called by an NFT contract"
+        );
+
+        //make sure the market is taking listings and the token comes from an approved NFT contract that can be traded
+        self.assert_not_paused();
+        self.assert_approved_nft_contract(&nft_contract_id);
+        self.assert_trading_enabled(&nft_contract_id);
+
+        //the listing conditions come from the msg field. If it doesn't match the schema for a sale, an auction,
+        //a rental or accepting an offer, it panics.
+        let listing_args = ListingArgs::from_msg(&msg);
+
+        //accepting a standing offer sells the token straight away so it doesn't need any storage
+        if let ListingArgs::AcceptOffer(AcceptOfferArgs { accept_offer }) = listing_args {
+            let offer_id = accept_offer.offer_id.0;
+            let ft_token_id = self.standing_offers.get(&offer_id).expect("No standing offer").ft_token_id;
+
+            //if we already know the owner can be paid in the offer's FT, we can accept the offer straight away
+            if self.internal_is_registered_seller(&owner_id, &ft_token_id) {
+                self.internal_accept_standing_offer(offer_id, owner_id, approval_id, nft_contract_id, token_id);
+                return;
+            }
+
+            //otherwise we check the owner is registered on the FT contract before accepting the offer
+            ext_ft_views::ext(ft_token_id)
+                .with_static_gas(GAS_FOR_STORAGE_BALANCE_OF)
+                .storage_balance_of(owner_id.clone())
+            .then(
+                // No attached deposit with static GAS equal to the GAS for accepting the offer. Also attach an unused GAS weight of 1 by default.
+                Self::ext(env::current_account_id())
+                .with_static_gas(self.gas_config.resolve_accept_offer())
+                .resolve_accept_standing_offer(offer_id, owner_id, approval_id, nft_contract_id, token_id)
+            );
+            return;
+        }
+
+        //we need to enforce that the user has enough storage for 1 EXTRA listing.
+        self.assert_storage_for_listings(&signer_id, 1);
+
+        //if all these checks pass we can create the listing.
+
+        let sale_args = match listing_args {
+            ListingArgs::Sale(sale_args) => sale_args,
+            //standing offers were handled above
+            ListingArgs::AcceptOffer(_) => unreachable!(),
+            //auctions are stored separately from fixed price sales
+            ListingArgs::Auction(AuctionArgs { auction }) => {
+                let ft_token_id = auction.ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+                self.assert_approved_ft_token_id(&ft_token_id);
+                //the collection's rules apply to the lowest bid like they do to a sale price
+                let collection_config = self.internal_collection_config(&nft_contract_id);
+                collection_config.assert_accepts_ft(&ft_token_id);
+                let starting_price = auction.starting_price.unwrap_or(auction.reserve_price);
+                collection_config.assert_min_price(starting_price);
+                assert!(starting_price.le(&auction.reserve_price), "Starting price cannot be above the reserve price");
+                if let Some(BidIncrement::Bps(bps)) = auction.min_bid_increment {
+                    assert!(bps <= 10_000, "Bid increment cannot be more than 10000 basis points");
+                }
+                let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+                self.internal_list_auction(Auction {
+                    owner_id, //owner of the auction / token
+                    approval_id, //approval ID for that token that was given to the market
+                    nft_contract_id, //NFT contract the token was minted on
+                    token_id, //the actual token ID
+                    ft_token_id, //the fungible token bids have to be placed in
+                    reserve_price: auction.reserve_price, //the lowest winning bid the token will be sold for
+                    end_timestamp: auction.end_timestamp, //when bidding closes
+                    highest_bid: None,
+                });
+                //auctions without rules don't take up any more storage
+                if auction.starting_price.is_some() || auction.min_bid_increment.is_some() {
+                    self.auction_rules.insert(
+                        &contract_and_token_id,
+                        &AuctionRules {
+                            starting_price: auction.starting_price,
+                            min_bid_increment: auction.min_bid_increment,
+                        },
+                    );
+                }
+                return;
+            }
+            //rentals are stored separately too. The owner keeps the token and only its use is rented out
+            ListingArgs::Rental(RentalArgs { rental }) => {
+                let ft_token_id = rental.ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+                self.assert_approved_ft_token_id(&ft_token_id);
+                self.internal_collection_config(&nft_contract_id).assert_accepts_ft(&ft_token_id);
+                self.internal_list_rental(Rental {
+                    owner_id, //owner of the rental / token
+                    approval_id, //approval ID for that token that was given to the market
+                    nft_contract_id, //NFT contract the token was minted on
+                    token_id, //the actual token ID
+                    ft_token_id, //the fungible token rent has to be paid in
+                    price_per_day: rental.price_per_day, //rent for a single day
+                    max_days: rental.max_days, //the most days the token can be rented for at once
+                    lease: None,
+                });
+                return;
+            }
+        };
+
+        //if the sale is only priced in NEAR, or we already know the seller can be paid in the sale's FT, we can list it straight away
+        let ft_token_id = self.internal_validate_sale_args(&nft_contract_id, &sale_args);
+        if sale_args.sale_conditions.is_none() || self.internal_is_registered_seller(&owner_id, &ft_token_id) {
+            self.internal_list_sale_and_emit(owner_id, approval_id, nft_contract_id, token_id, sale_args);
+            return;
+        }
+
+        //otherwise we check the seller is registered on the FT contract so that they don't lose their proceeds
+        ext_ft_views::ext(ft_token_id)
+            .with_static_gas(GAS_FOR_STORAGE_BALANCE_OF)
+            .storage_balance_of(owner_id.clone())
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the listing. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_LIST_SALE)
+            .resolve_list_sale(owner_id, approval_id, nft_contract_id, token_id, sale_args)
+        );
+    }
+}
 
-        //we need to enforce that the user has enough storage for 1 EXTRA sale.  
+impl Contract {
+    //internal method for listing a token for sale and letting indexers know it's up for sale
+    pub(crate) fn internal_list_sale_and_emit(
+        &mut self,
+        owner_id: AccountId,
+        approval_id: u64,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        sale_args: SaleArgs,
+    ) {
+        let sale = self.internal_list_sale(
+            owner_id,
+            approval_id,
+            nft_contract_id.clone(),
+            token_id,
+            sale_args,
+        );
+        SaleListed {
+            owner_id: &sale.owner_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &sale.token_id,
+            ft_token_id: &sale.sale_conditions.ft_token_id,
+            price: &sale.sale_conditions.price,
+            near_price: sale.sale_conditions.near_price.as_ref(),
+            quantity: None,
+        }
+        .emit();
+    }
 
+    //internal method for making sure an account has paid enough storage for `extra` more listings on top of what it has up
+    pub(crate) fn assert_storage_for_listings(&self, account_id: &AccountId, extra: u64) {
         //get the storage for a sale
         let storage_amount = self.storage_minimum_balance();
         //get the total storage paid by the owner
-        let owner_paid_storage = self.storage_deposits.get(&signer_id).unwrap_or(ZERO_TOKEN);
-        //get the storage required which is simply the storage for the number of sales they have + 1 
-        let signer_storage_required = storage_amount.saturating_mul(self.get_supply_by_owner_id(signer_id).0 as u128 + 1);
-        
+        let owner_paid_storage = self.storage_deposits.get(account_id).unwrap_or(ZERO_TOKEN);
+        //get the storage required which is simply the storage for the number of listings they have + the extra ones
+        let storage_required = storage_amount.saturating_mul((self.internal_listing_count(account_id) + extra) as u128);
+
         //make sure that the total paid is >= the required storage
         assert!(
-            owner_paid_storage >= signer_storage_required,
+            owner_paid_storage >= storage_required,
             "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
-            owner_paid_storage, signer_storage_required.saturating_div(storage_per_sale().as_yoctonear()), storage_per_sale()
+            owner_paid_storage, storage_required.saturating_div(storage_per_sale().as_yoctonear()), storage_per_sale()
+        );
+    }
+
+    //internal method for making sure the sale args can be used to list a token from an NFT contract. Returns the fungible token the sale is priced in
+    pub(crate) fn internal_validate_sale_args(&self, nft_contract_id: &AccountId, sale_args: &SaleArgs) -> FungibleTokenId {
+        //if no fungible token was passed in, the sale is priced in the market's default FT
+        let ft_token_id = sale_args.ft_token_id.clone().unwrap_or_else(|| self.ft_id.clone());
+        //make sure the fungible token can be used to purchase NFTs on the market
+        self.assert_approved_ft_token_id(&ft_token_id);
+
+        //make sure the sale follows the rules of its collection
+        let collection_config = self.internal_collection_config(nft_contract_id);
+        collection_config.assert_trading_enabled(nft_contract_id);
+        collection_config.assert_accepts_ft(&ft_token_id);
+
+        //the sale has to be priced in at least one of the FT or NEAR
+        assert!(
+            sale_args.sale_conditions.is_some() || sale_args.near_price.is_some(),
+            "Sale must have a price in FTs or NEAR"
         );
 
-        //if all these checks pass we can create the sale conditions object.
-        let SaleArgs { sale_conditions } =
-            //the sale conditions come from the msg field. The market assumes that the user passed
-            //in a proper msg. If they didn't, it panics. 
-            near_sdk::serde_json::from_str(&msg).expect("Not valid SaleArgs");
+        //make sure the token isn't being given away by mistake
+        let price = sale_args.sale_conditions.unwrap_or(ZERO_TOKEN);
+        if sale_args.sale_conditions.is_some() {
+            assert!(price.gt(&ZERO_TOKEN), "Sale price must be greater than 0");
+            collection_config.assert_min_price(price);
+        }
+        if let Some(near_price) = sale_args.near_price {
+            assert!(near_price.gt(&ZERO_TOKEN), "NEAR price must be greater than 0");
+            collection_config.assert_min_near_price(near_price);
+        }
 
-        //create the unique sale ID which is the contract + DELIMITER + token ID
-        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        //make sure the dutch auction's price actually declines over a valid window. Only the FT price declines
+        if let Some(dutch_auction) = &sale_args.dutch_auction {
+            assert!(sale_args.sale_conditions.is_some(), "Dutch auctions must have a price in FTs");
+            assert!(
+                dutch_auction.start_price.ge(&price),
+                "Dutch auction start price must be greater than or equal to the sale price"
+            );
+            assert!(
+                dutch_auction.end_timestamp.0 > dutch_auction.start_timestamp.0,
+                "Dutch auction must end after it starts"
+            );
+        }
+
+        ft_token_id
+    }
+
+    //internal method for listing a token for sale once the owner and approval have been checked. Returns the new sale object
+    pub(crate) fn internal_list_sale(
+        &mut self,
+        owner_id: AccountId,
+        approval_id: u64,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        sale_args: SaleArgs,
+    ) -> Sale {
+        let ft_token_id = self.internal_validate_sale_args(&nft_contract_id, &sale_args);
+        let SaleArgs { sale_conditions, near_price, dutch_auction, allowed_buyer_id, .. } = sale_args;
+
+        //create the unique sale ID which is the (contract, token ID) pair
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+
+        //a token can only be listed one way at a time
+        assert!(
+            self.auctions.get(&contract_and_token_id).is_none(),
+            "Token is already up for auction"
+        );
+        assert!(
+            self.rentals.get(&contract_and_token_id).is_none(),
+            "Token is already listed for rent"
+        );
         
+        let sale = Sale {
+            owner_id, //owner of the sale / token
+            approval_id, //approval ID for that token that was given to the market
+            nft_contract_id, //NFT contract the token was minted on
+            token_id, //the actual token ID
+            sale_conditions: SaleConditions {
+                ft_token_id, //the fungible token the sale is priced in
+                price: sale_conditions.unwrap_or(ZERO_TOKEN), //the sale price in FTs (0 if it can only be bought with NEAR)
+                near_price, //the sale price in NEAR, if any
+            },
+            dutch_auction, //the declining price schedule, if any
+            allowed_buyer_id, //the only account that can buy a private sale
+        };
+        self.internal_add_sale(&sale);
+
+        //return the sale object
+        sale
+    }
+
+    //internal method for putting a sale on the market and adding it to the view indexes. The sale isn't checked
+    pub(crate) fn internal_add_sale(&mut self, sale: &Sale) {
+        let owner_id = &sale.owner_id;
+        let nft_contract_id = &sale.nft_contract_id;
+        let contract_and_token_id = (nft_contract_id.clone(), sale.token_id.clone());
+
         //insert the key value pair into the sales map. Key is the unique ID. value is the sale object
-        self.sales.insert(
-            &contract_and_token_id,
-            &Sale {
-                owner_id: owner_id.clone(), //owner of the sale / token
-                approval_id, //approval ID for that token that was given to the market
-                nft_contract_id: nft_contract_id.to_string(), //NFT contract the token was minted on
-                token_id: token_id.clone(), //the actual token ID
-                sale_conditions, //the sale conditions 
-           },
-        );
+        self.sales.insert(&contract_and_token_id, sale);
+        //add the sale to the price index for its NFT contract
+        self.internal_index_sale_price(sale);
 
         //Extra functionality that populates collections necessary for the view calls 
 
         //get the sales by owner ID for the given owner. If there are none, we create a new empty set
-        let mut by_owner_id = self.by_owner_id.get(&owner_id).unwrap_or_else(|| {
+        let mut by_owner_id = self.by_owner_id.get(owner_id).unwrap_or_else(|| {
             UnorderedSet::new(
                 StorageKey::ByOwnerIdInner {
                     //we get a new unique prefix for the collection by hashing the owner
-                    account_id_hash: hash_account_id(&owner_id),
+                    account_id_hash: hash_account_id(owner_id),
                 }
             )
         });
@@ -107,25 +428,25 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
         //insert the unique sale ID into the set
         by_owner_id.insert(&contract_and_token_id);
         //insert that set back into the collection for the owner
-        self.by_owner_id.insert(&owner_id, &by_owner_id);
+        self.by_owner_id.insert(owner_id, &by_owner_id);
 
         //get the token IDs for the given nft contract ID. If there are none, we create a new empty set
         let mut by_nft_contract_id = self
             .by_nft_contract_id
-            .get(&nft_contract_id)
+            .get(nft_contract_id)
             .unwrap_or_else(|| {
                 UnorderedSet::new(
                     StorageKey::ByNFTContractIdInner {
                         //we get a new unique prefix for the collection by hashing the owner
-                        account_id_hash: hash_account_id(&nft_contract_id),
+                        account_id_hash: hash_account_id(nft_contract_id),
                     }
                 )
             });
         
         //insert the token ID into the set
-        by_nft_contract_id.insert(&token_id);
+        by_nft_contract_id.insert(&sale.token_id);
         //insert the set back into the collection for the given nft contract ID
         self.by_nft_contract_id
-            .insert(&nft_contract_id, &by_nft_contract_id);
+            .insert(nft_contract_id, &by_nft_contract_id);
     }
 }
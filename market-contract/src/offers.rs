@@ -0,0 +1,500 @@
+use crate::*;
+
+//the most offers that can be open on a single sale at once. When a sale is removed, every open offer
+//has to be released in the same call so this keeps that bounded. Once a sale is full, a new offer has to
+//beat the lowest one, which is released back to its buyer
+pub const MAX_OFFERS_PER_SALE: u64 = 10;
+
+//the smallest offer that can be placed, in basis points of the sale's current price. This stops a sale's
+//offers from being filled up with dust
+pub const MIN_OFFER_BPS: u128 = 5_000;
+
+//an offer below the asking price on a sale. The FTs for the offer are escrowed by the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Offer {
+    //unique ID of the offer
+    pub offer_id: U64,
+    //account that placed the offer
+    pub buyer_id: AccountId,
+    //amount of the sale's FT escrowed for the offer
+    pub amount: SalePriceInFTs,
+    //when the offer can no longer be accepted (in nanoseconds). If it's not set, the offer never expires
+    pub expires_at: Option<U64>,
+    //where the offer is in its negotiation with the seller
+    pub status: OfferStatus,
+}
+
+/*
+    The negotiation on an offer. The seller can counter an open offer with a higher price, which the buyer
+    can accept or decline. Once declined, the seller can counter again. The seller can accept the buyer's
+    escrowed amount and the buyer can cancel in any state.
+
+    Open --counter_offer--> Countered --decline_counter_offer--> Declined --counter_offer--> Countered
+    Countered --accept_counter_offer--> (sold)
+*/
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, PartialEq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum OfferStatus {
+    //the offer is waiting on the seller
+    Open,
+    //the seller has asked for `price` instead. This is waiting on the buyer
+    Countered { price: SalePriceInFTs },
+    //the buyer declined the seller's last counter price. This is waiting on the seller again
+    Declined { price: SalePriceInFTs },
+}
+
+impl Offer {
+    //returns whether the offer has expired at the current block timestamp
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|t| env::block_timestamp() >= t.0).unwrap_or(false)
+    }
+}
+
+//the smallest offer that can be placed on a sale with the given price
+pub(crate) fn min_offer(price: NearToken) -> NearToken {
+    let price = price.as_yoctonear();
+    NearToken::from_yoctonear(price / 10_000 * MIN_OFFER_BPS + price % 10_000 * MIN_OFFER_BPS / 10_000)
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Place an offer below the asking price on a sale. The amount is escrowed from your FT deposits
+    /// until the seller accepts it, you cancel it, or the sale is removed. Returns the offer ID.
+    /// Offers have to be at least half the price. If the sale already has the most offers it can, the
+    /// new offer has to beat the lowest one, which is released back to its buyer.
+    #[payable]
+    pub fn place_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        amount: NearToken,
+        expires_at: Option<U64>,
+    ) -> U64 {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //offers can't be placed while the market is paused
+        self.assert_not_paused();
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //get the buyer ID which is the person who called the function and make sure they're not the owner of the sale
+        let buyer_id = env::predecessor_account_id();
+        assert_ne!(sale.owner_id, buyer_id, "Cannot make an offer on your own sale.");
+        sale.assert_buyer_allowed(&buyer_id);
+        self.assert_trading_enabled(&nft_contract_id);
+        assert!(!self.internal_is_sale_expired(&contract_and_token_id), "Sale has expired");
+
+        //offers are escrowed in the sale's FT so the sale has to be priced in it
+        assert!(sale.sale_conditions.accepts_ft(), "Sale can only be bought with NEAR");
+
+        //offers at or above the price should just buy the token with `offer`
+        assert!(amount.gt(&ZERO_TOKEN), "Offer amount must be greater than 0");
+        self.internal_collection_config(&nft_contract_id).assert_min_price(amount);
+        let min_offer = min_offer(sale.current_price());
+        assert!(
+            amount.ge(&min_offer),
+            "Offer amount must be at least {}",
+            min_offer
+        );
+        assert!(
+            amount.lt(&sale.current_price()),
+            "Offer amount must be less than the price: {}. Use offer to buy the token",
+            sale.current_price()
+        );
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Offer must expire in the future");
+        }
+
+        //get the offers for the sale. If there are none, we create a new empty map
+        let mut offers = self.offers_by_sale.get(&contract_and_token_id).unwrap_or_else(|| {
+            UnorderedMap::new(
+                StorageKey::OffersBySaleInner {
+                    //we get a new unique prefix for the collection by hashing the unique sale ID
+                    sale_id_hash: hash_sale_id(&contract_and_token_id),
+                }
+            )
+        });
+        //if the sale is full, the new offer takes the place of the lowest one. Expired offers go first
+        if offers.len() >= MAX_OFFERS_PER_SALE {
+            let lowest = offers
+                .values_as_vector()
+                .iter()
+                .min_by_key(|offer| (!offer.is_expired(), offer.amount))
+                .unwrap();
+            assert!(
+                lowest.is_expired() || amount.gt(&lowest.amount),
+                "A sale can only have {} open offers. Offer more than the lowest one: {}",
+                MAX_OFFERS_PER_SALE,
+                lowest.amount
+            );
+
+            //release the lowest offer back into its buyer's deposits
+            offers.remove(&lowest.offer_id.0);
+            self.internal_increase_ft_deposit(&lowest.buyer_id, &sale.sale_conditions.ft_token_id, lowest.amount);
+        }
+
+        //escrow the offer from the buyer's deposits. This panics if they haven't deposited enough
+        self.internal_decrease_ft_deposit(&buyer_id, &sale.sale_conditions.ft_token_id, amount);
+
+        //record the offer under a new unique ID and insert the offers back into the map for the sale
+        let offer_id = self.next_offer_id;
+        self.next_offer_id += 1;
+        offers.insert(
+            &offer_id,
+            &Offer {
+                offer_id: U64(offer_id),
+                buyer_id,
+                amount,
+                expires_at,
+                status: OfferStatus::Open,
+            },
+        );
+        self.offers_by_sale.insert(&contract_and_token_id, &offers);
+
+        //let indexers know about the offer
+        OfferPlaced {
+            buyer_id: &env::predecessor_account_id(),
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+            ft_token_id: &sale.sale_conditions.ft_token_id,
+            amount: &amount,
+            offer_id: Some(U64(offer_id)),
+        }
+        .emit();
+
+        U64(offer_id)
+    }
+
+    /// Cancel an offer and return the escrowed FTs to the buyer's deposits. The buyer can cancel at any time
+    /// and anyone can cancel an offer once it has expired.
+    #[payable]
+    pub fn cancel_offer(&mut self, nft_contract_id: AccountId, token_id: TokenId, offer_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //get the offer object as the return value from removing the offer internally
+        let offer = self.internal_remove_offer(&contract_and_token_id, offer_id.0);
+        //make sure the caller is allowed to cancel the offer. If this fails, the removal will revert
+        assert!(
+            env::predecessor_account_id() == offer.buyer_id || offer.is_expired(),
+            "Only the buyer can cancel an offer before it expires"
+        );
+
+        //release the escrow back into the buyer's deposits
+        self.internal_increase_ft_deposit(&offer.buyer_id, &sale.sale_conditions.ft_token_id, offer.amount);
+    }
+
+    /// Accept an offer on your sale. The token is sold to the buyer for the offered amount and every
+    /// other open offer on the sale is released.
+    #[payable]
+    pub fn accept_offer(&mut self, nft_contract_id: AccountId, token_id: TokenId, offer_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //make sure the caller is the owner of the sale and the token can still be traded
+        assert_eq!(env::predecessor_account_id(), sale.owner_id, "Must be sale owner");
+        self.assert_trading_enabled(&nft_contract_id);
+
+        //get the offer object as the return value from removing the offer internally and make sure it can still be accepted
+        let offer = self.internal_remove_offer(&contract_and_token_id, offer_id.0);
+        assert!(!offer.is_expired(), "Offer has expired");
+
+        //the FTs are already escrowed so we can process the purchase (which will remove the sale and release the other offers)
+        self.process_purchase(
+            nft_contract_id,
+            token_id,
+            offer.amount,
+            offer.buyer_id,
+            None,
+            Some(sale.sale_conditions.ft_token_id.clone()),
+            None,
+        );
+    }
+
+    /// Counter an offer on your sale with a higher price. The buyer can accept the counter price with
+    /// `accept_counter_offer` or decline it. The offer's escrow isn't touched until the buyer accepts.
+    #[payable]
+    pub fn counter_offer(&mut self, nft_contract_id: AccountId, token_id: TokenId, offer_id: U64, price: NearToken) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //offers can't be negotiated while the market is paused
+        self.assert_not_paused();
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //make sure the caller is the owner of the sale
+        assert_eq!(env::predecessor_account_id(), sale.owner_id, "Must be sale owner");
+
+        let mut offer = self.internal_get_offer(&contract_and_token_id, offer_id.0);
+        assert!(!offer.is_expired(), "Offer has expired");
+        assert!(
+            !matches!(offer.status, OfferStatus::Countered { .. }),
+            "Offer has already been countered. Wait for the buyer to respond"
+        );
+
+        //a counter at or above the asking price is just the asking price. The buyer can use offer for that
+        assert!(price.gt(&offer.amount), "Counter price must be greater than the offer: {}", offer.amount);
+        assert!(
+            price.lt(&sale.current_price()),
+            "Counter price must be less than the price: {}",
+            sale.current_price()
+        );
+
+        offer.status = OfferStatus::Countered { price };
+        self.internal_update_offer(&contract_and_token_id, &offer);
+
+        //let indexers know about the counter
+        OfferUpdated {
+            buyer_id: &offer.buyer_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+            offer_id: offer.offer_id,
+            status: &offer.status,
+        }
+        .emit();
+    }
+
+    /// Accept the seller's counter price on your offer. The difference between the counter price and the
+    /// escrowed amount is taken from your FT deposits and the token is sold to you.
+    #[payable]
+    pub fn accept_counter_offer(&mut self, nft_contract_id: AccountId, token_id: TokenId, offer_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //get the offer object as the return value from removing the offer internally and make sure it can still be accepted
+        let offer = self.internal_remove_offer(&contract_and_token_id, offer_id.0);
+        assert_eq!(env::predecessor_account_id(), offer.buyer_id, "Must be offer buyer");
+        assert!(!offer.is_expired(), "Offer has expired");
+        self.assert_trading_enabled(&nft_contract_id);
+        let OfferStatus::Countered { price } = offer.status else {
+            env::panic_str("Offer has no counter price to accept");
+        };
+
+        //escrow the rest of the counter price from the buyer's deposits. This panics if they haven't deposited enough
+        let ft_token_id = sale.sale_conditions.ft_token_id.clone();
+        self.internal_decrease_ft_deposit(&offer.buyer_id, &ft_token_id, price.saturating_sub(offer.amount));
+
+        //the FTs are now escrowed so we can process the purchase (which will remove the sale and release the other offers)
+        self.process_purchase(
+            nft_contract_id,
+            token_id,
+            price,
+            offer.buyer_id,
+            None,
+            Some(ft_token_id),
+            None,
+        );
+    }
+
+    /// Decline the seller's counter price on your offer. Your offer stays open at its escrowed amount and
+    /// the seller can accept it or counter again.
+    #[payable]
+    pub fn decline_counter_offer(&mut self, nft_contract_id: AccountId, token_id: TokenId, offer_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let mut offer = self.internal_get_offer(&contract_and_token_id, offer_id.0);
+        assert_eq!(env::predecessor_account_id(), offer.buyer_id, "Must be offer buyer");
+        let OfferStatus::Countered { price } = offer.status else {
+            env::panic_str("Offer has no counter price to decline");
+        };
+
+        offer.status = OfferStatus::Declined { price };
+        self.internal_update_offer(&contract_and_token_id, &offer);
+
+        //let indexers know the counter was declined
+        OfferUpdated {
+            buyer_id: &offer.buyer_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+            offer_id: offer.offer_id,
+            status: &offer.status,
+        }
+        .emit();
+    }
+
+    /// views
+
+    //returns the open offers on a sale
+    pub fn get_offers(&self, nft_contract_id: AccountId, token_id: TokenId) -> Vec<Offer> {
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        self.offers_by_sale
+            .get(&contract_and_token_id)
+            .map(|offers| offers.values().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for getting an offer on a sale. If there is no offer, panic
+    pub(crate) fn internal_get_offer(&self, contract_and_token_id: &ContractAndTokenId, offer_id: u64) -> Offer {
+        self.offers_by_sale
+            .get(contract_and_token_id)
+            .and_then(|offers| offers.get(&offer_id))
+            .expect("No offer")
+    }
+
+    //internal method for writing back an offer that's already on a sale
+    pub(crate) fn internal_update_offer(&mut self, contract_and_token_id: &ContractAndTokenId, offer: &Offer) {
+        let mut offers = self.offers_by_sale.get(contract_and_token_id).expect("No offer");
+        offers.insert(&offer.offer_id.0, offer);
+        self.offers_by_sale.insert(contract_and_token_id, &offers);
+    }
+
+    //internal method for removing an offer from a sale. This returns the removed offer object
+    pub(crate) fn internal_remove_offer(
+        &mut self,
+        contract_and_token_id: &ContractAndTokenId,
+        offer_id: u64,
+    ) -> Offer {
+        //get the offers for the sale and remove the offer. If there was no offer, panic
+        let mut offers = self.offers_by_sale.get(contract_and_token_id).expect("No offer");
+        let offer = offers.remove(&offer_id).expect("No offer");
+
+        //if the sale has no offers left, we remove it from the map. Otherwise we insert the offers back
+        if offers.is_empty() {
+            self.offers_by_sale.remove(contract_and_token_id);
+        } else {
+            self.offers_by_sale.insert(contract_and_token_id, &offers);
+        }
+
+        //return the offer object
+        offer
+    }
+
+    //internal method for releasing every open offer on a sale back into the buyers' deposits. Called whenever a sale is removed
+    pub(crate) fn internal_release_offers(
+        &mut self,
+        contract_and_token_id: &ContractAndTokenId,
+        ft_token_id: &FungibleTokenId,
+    ) {
+        if let Some(mut offers) = self.offers_by_sale.remove(contract_and_token_id) {
+            for offer in offers.values_as_vector().to_vec() {
+                self.internal_increase_ft_deposit(&offer.buyer_id, ft_token_id, offer.amount);
+            }
+            offers.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+
+    fn ft() -> AccountId {
+        "token.near".parse().unwrap()
+    }
+
+    fn nft() -> AccountId {
+        "nft.near".parse().unwrap()
+    }
+
+    fn buyer(index: u64) -> AccountId {
+        format!("buyer{}.near", index).parse().unwrap()
+    }
+
+    //creates the market with alice selling token 1 for 100 FTs and every buyer holding 1000 FTs in deposits
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new(owner(), ft());
+        contract.approved_nft_contract_ids.insert(&nft());
+        contract.internal_add_sale(&Sale {
+            owner_id: alice(),
+            approval_id: 0,
+            nft_contract_id: nft(),
+            token_id: "1".to_string(),
+            sale_conditions: SaleConditions { ft_token_id: ft(), price: tokens(100), near_price: None },
+            dutch_auction: None,
+            allowed_buyer_id: None,
+        });
+        for index in 0..=MAX_OFFERS_PER_SALE {
+            contract.internal_increase_ft_deposit(&buyer(index), &ft(), tokens(1000));
+        }
+        contract
+    }
+
+    fn place_offer(contract: &mut Contract, buyer_id: AccountId, amount: NearToken, expires_at: Option<U64>) {
+        Context::new().predecessor(buyer_id).one_yocto().set();
+        contract.place_offer(nft(), "1".to_string(), amount, expires_at);
+    }
+
+    //fills the sale with offers of 50, 51, 52... from buyer0, buyer1, buyer2...
+    fn fill_offers(contract: &mut Contract) {
+        for index in 0..MAX_OFFERS_PER_SALE {
+            place_offer(contract, buyer(index), tokens(50 + index as u128), None);
+        }
+    }
+
+    #[test]
+    fn full_sales_release_the_lowest_offer() {
+        let mut contract = setup();
+        fill_offers(&mut contract);
+        assert_eq!(contract.internal_ft_deposit_of(&buyer(0), &ft()), tokens(950));
+
+        let new_buyer = buyer(MAX_OFFERS_PER_SALE);
+        place_offer(&mut contract, new_buyer.clone(), tokens(60), None);
+
+        let offers = contract.get_offers(nft(), "1".to_string());
+        assert_eq!(offers.len() as u64, MAX_OFFERS_PER_SALE);
+        assert!(offers.iter().all(|offer| offer.buyer_id != buyer(0)));
+        assert!(offers.iter().any(|offer| offer.buyer_id == new_buyer));
+        //the released offer goes back into its buyer's deposits
+        assert_eq!(contract.internal_ft_deposit_of(&buyer(0), &ft()), tokens(1000));
+        assert_eq!(contract.internal_ft_deposit_of(&new_buyer, &ft()), tokens(940));
+    }
+
+    #[test]
+    #[should_panic(expected = "A sale can only have 10 open offers")]
+    fn offers_on_full_sales_have_to_beat_the_lowest() {
+        let mut contract = setup();
+        fill_offers(&mut contract);
+
+        place_offer(&mut contract, buyer(MAX_OFFERS_PER_SALE), tokens(50), None);
+    }
+
+    #[test]
+    fn expired_offers_are_released_first() {
+        let mut contract = setup();
+        for index in 0..MAX_OFFERS_PER_SALE - 1 {
+            place_offer(&mut contract, buyer(index), tokens(50 + index as u128), None);
+        }
+        //the highest offer expires before the sale fills up
+        let last = buyer(MAX_OFFERS_PER_SALE - 1);
+        place_offer(&mut contract, last.clone(), tokens(90), Some(U64(1_000)));
+
+        Context::new().predecessor(buyer(MAX_OFFERS_PER_SALE)).one_yocto().block_timestamp(1_000).set();
+        contract.place_offer(nft(), "1".to_string(), tokens(50), None);
+
+        let offers = contract.get_offers(nft(), "1".to_string());
+        assert!(offers.iter().all(|offer| offer.buyer_id != last));
+        assert!(offers.iter().any(|offer| offer.buyer_id == buyer(0)));
+        assert_eq!(contract.internal_ft_deposit_of(&last, &ft()), tokens(1000));
+    }
+}
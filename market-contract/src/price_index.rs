@@ -0,0 +1,112 @@
+use std::ops::Bound;
+
+use crate::*;
+
+//the key for a price index. Sales priced in different fungible tokens can't be compared so each FT has its own index
+type PriceIndexKey = (AccountId, FungibleTokenId);
+
+//used to generate a unique prefix for a price index (this is to avoid data collisions)
+fn hash_price_index_key(key: &PriceIndexKey) -> CryptoHash {
+    //get the default hash
+    let mut hash = CryptoHash::default();
+    //we hash the serialized NFT contract and fungible token and return it
+    hash.copy_from_slice(&env::sha256(&near_sdk::borsh::to_vec(key).unwrap()));
+    hash
+}
+
+#[near_bindgen]
+impl Contract {
+    /// views
+
+    //returns the sales for an NFT contract priced between `min_price` and `max_price` (inclusive), cheapest first.
+    //Only sales in the given fungible token are returned. Defaults to the market's default FT.
+    //Dutch auctions are ordered by their floor price
+    pub fn get_sales_by_price_range(
+        &self,
+        nft_contract_id: AccountId,
+        min_price: Option<NearToken>,
+        max_price: Option<NearToken>,
+        limit: Option<u32>,
+        ft_token_id: Option<FungibleTokenId>,
+    ) -> Vec<Sale> {
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        let Some(index) = self.sales_by_price.get(&(nft_contract_id.clone(), ft_token_id)) else {
+            return vec![];
+        };
+
+        //every sale in the index is from the same NFT contract so an empty token ID sorts before every other sale at the same price
+        let min_price = min_price.unwrap_or(ZERO_TOKEN).as_yoctonear();
+        let max_price = max_price.map(|p| p.as_yoctonear()).unwrap_or(u128::MAX);
+
+        index
+            .range((Bound::Included((min_price, (nft_contract_id, String::new()))), Bound::Unbounded))
+            //stop once we're past the highest price
+            .take_while(|((price, _), _)| *price <= max_price)
+            //take the first "limit" elements. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //we'll map the unique sale IDs into Sale objects
+            .map(|((_, contract_and_token_id), _)| self.sales.get(&contract_and_token_id).unwrap())
+            .collect()
+    }
+
+    //returns the lowest price an NFT contract's tokens are listed for in a given fungible token. Defaults to the
+    //market's default FT. If nothing is listed, this is null
+    pub fn get_floor_price(
+        &self,
+        nft_contract_id: AccountId,
+        ft_token_id: Option<FungibleTokenId>,
+    ) -> Option<NearToken> {
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        self.sales_by_price
+            .get(&(nft_contract_id, ft_token_id))
+            .and_then(|index| index.min())
+            .map(|(price, _)| NearToken::from_yoctonear(price))
+    }
+}
+
+impl Contract {
+    //internal method for adding a sale to the price index for its NFT contract. Sales that can only be bought
+    //with NEAR aren't indexed
+    pub(crate) fn internal_index_sale_price(&mut self, sale: &Sale) {
+        if !sale.sale_conditions.accepts_ft() {
+            return;
+        }
+
+        let key = (sale.nft_contract_id.clone(), sale.sale_conditions.ft_token_id.clone());
+        //get the price index for the NFT contract and FT. If there is none, we create a new empty tree
+        let mut index = self.sales_by_price.get(&key).unwrap_or_else(|| {
+            TreeMap::new(
+                StorageKey::SalesByPriceInner {
+                    //we get a new unique prefix for the collection by hashing the NFT contract and FT
+                    index_hash: hash_price_index_key(&key),
+                }
+            )
+        });
+
+        let contract_and_token_id = (sale.nft_contract_id.clone(), sale.token_id.clone());
+        index.insert(&(sale.sale_conditions.price.as_yoctonear(), contract_and_token_id), &());
+        self.sales_by_price.insert(&key, &index);
+    }
+
+    //internal method for taking a sale out of the price index for its NFT contract
+    pub(crate) fn internal_unindex_sale_price(&mut self, sale: &Sale) {
+        if !sale.sale_conditions.accepts_ft() {
+            return;
+        }
+
+        let key = (sale.nft_contract_id.clone(), sale.sale_conditions.ft_token_id.clone());
+        let Some(mut index) = self.sales_by_price.get(&key) else {
+            return;
+        };
+
+        let contract_and_token_id = (sale.nft_contract_id.clone(), sale.token_id.clone());
+        index.remove(&(sale.sale_conditions.price.as_yoctonear(), contract_and_token_id));
+
+        //if the index is now empty, we remove it from the map. Otherwise we insert it back
+        if index.len() == 0 {
+            self.sales_by_price.remove(&key);
+        } else {
+            self.sales_by_price.insert(&key, &index);
+        }
+    }
+}
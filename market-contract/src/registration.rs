@@ -0,0 +1,111 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+use crate::nft_callbacks::SaleArgs;
+
+/*
+    Sellers are paid with ft_transfer, which fails if they aren't registered on the FT contract. Before a token is
+    listed for FTs (or a standing offer is accepted) the market checks the seller's storage_balance_of on the FT
+    contract and remembers the sellers that are registered so that the check only has to happen once.
+*/
+#[near_bindgen]
+impl Contract {
+    /// Check that you're registered on a fungible token contract so that you can list tokens for it in a batch.
+    /// Defaults to the market's default FT. Returns whether you're registered
+    pub fn verify_ft_registration(&mut self, ft_token_id: Option<FungibleTokenId>) -> Promise {
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        self.assert_approved_ft_token_id(&ft_token_id);
+
+        let account_id = env::predecessor_account_id();
+        ext_ft_views::ext(ft_token_id.clone())
+            .with_static_gas(GAS_FOR_STORAGE_BALANCE_OF)
+            .storage_balance_of(account_id.clone())
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the check. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_PAYMENT)
+            .resolve_ft_registration(account_id, ft_token_id)
+        )
+    }
+
+    //private method used to resolve verify_ft_registration
+    #[private]
+    pub fn resolve_ft_registration(&mut self, account_id: AccountId, ft_token_id: FungibleTokenId) -> bool {
+        self.internal_resolve_registration(&account_id, &ft_token_id)
+    }
+
+    //private method used to list a sale once the seller's registration on the sale's FT has been checked
+    #[private]
+    pub fn resolve_list_sale(
+        &mut self,
+        owner_id: AccountId,
+        approval_id: u64,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        sale_args: SaleArgs,
+    ) {
+        let ft_token_id = self.internal_validate_sale_args(&nft_contract_id, &sale_args);
+        assert!(
+            self.internal_resolve_registration(&owner_id, &ft_token_id),
+            "Seller must be registered on {} to list for it",
+            ft_token_id
+        );
+
+        //the seller may have withdrawn storage since nft_on_approve was called so we check it again
+        self.assert_storage_for_listings(&owner_id, 1);
+        self.internal_list_sale_and_emit(owner_id, approval_id, nft_contract_id, token_id, sale_args);
+    }
+
+    //private method used to accept a standing offer once the owner's registration on the offer's FT has been checked
+    #[private]
+    pub fn resolve_accept_standing_offer(
+        &mut self,
+        offer_id: u64,
+        owner_id: AccountId,
+        approval_id: u64,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+    ) -> Promise {
+        let ft_token_id = self.standing_offers.get(&offer_id).expect("No standing offer").ft_token_id;
+        assert!(
+            self.internal_resolve_registration(&owner_id, &ft_token_id),
+            "Owner must be registered on {} to accept the offer",
+            ft_token_id
+        );
+
+        self.internal_accept_standing_offer(offer_id, owner_id, approval_id, nft_contract_id, token_id)
+    }
+
+    /// views
+
+    //returns whether the market knows an account is registered on a fungible token contract
+    pub fn is_registered_seller(&self, account_id: AccountId, ft_token_id: FungibleTokenId) -> bool {
+        self.internal_is_registered_seller(&account_id, &ft_token_id)
+    }
+}
+
+impl Contract {
+    //internal method for checking whether an account is known to be registered on a fungible token contract
+    pub(crate) fn internal_is_registered_seller(&self, account_id: &AccountId, ft_token_id: &FungibleTokenId) -> bool {
+        self.registered_sellers.contains(&(account_id.clone(), ft_token_id.clone()))
+    }
+
+    //internal method for reading the result of a storage_balance_of call. If the account is registered, it's remembered
+    pub(crate) fn internal_resolve_registration(&mut self, account_id: &AccountId, ft_token_id: &FungibleTokenId) -> bool {
+        let is_registered = match env::promise_result(0) {
+            //storage_balance_of returns null for accounts that aren't registered
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<Option<near_sdk::serde_json::Value>>(&value)
+                    .ok()
+                    .flatten()
+                    .is_some()
+            }
+            PromiseResult::Failed => false,
+        };
+
+        if is_registered {
+            self.registered_sellers.insert(&(account_id.clone(), ft_token_id.clone()));
+        }
+        is_registered
+    }
+}
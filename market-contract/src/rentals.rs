@@ -0,0 +1,334 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+
+//the length of a rental day in nanoseconds
+pub const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/*
+    Rentals let an owner lend out the use of a token for a number of days without giving up ownership. The NFT
+    contract has to support per-user roles (`nft_set_user`): the market sets the renter as the token's user until
+    the lease ends and the NFT contract drops the role on its own once it expires. The renter pays for the whole
+    lease upfront and the FTs are escrowed by the market until the lease is settled.
+*/
+
+//an active (or ended but not yet settled) lease on a rental
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Lease {
+    //account renting the token
+    pub renter_id: AccountId,
+    //when the lease started (in nanoseconds)
+    pub starts_at: U64,
+    //when the lease ends (in nanoseconds)
+    pub ends_at: U64,
+    //amount of the rental's FT escrowed for the lease
+    pub amount: SalePriceInFTs,
+}
+
+impl Lease {
+    //returns whether the lease has ended at the current block timestamp
+    pub fn has_ended(&self) -> bool {
+        env::block_timestamp() >= self.ends_at.0
+    }
+}
+
+//struct that holds important information about each token listed for rent on the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Rental {
+    //owner of the rental
+    pub owner_id: AccountId,
+    //market contract's approval ID to set the user of the token on behalf of the owner
+    pub approval_id: u64,
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID being rented out
+    pub token_id: TokenId,
+    //fungible token the rent is paid in
+    pub ft_token_id: FungibleTokenId,
+    //rent for a single day
+    pub price_per_day: SalePriceInFTs,
+    //the most days the token can be rented for at once
+    pub max_days: u32,
+    //the current lease, if the token is rented out
+    pub lease: Option<Lease>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Rent a token for a number of days. The whole rent is escrowed from your FT deposits upfront and
+    /// the market makes you the token's user until the lease ends. If the NFT contract refuses, the rent
+    /// goes back into your deposits.
+    #[payable]
+    pub fn rent(&mut self, nft_contract_id: AccountId, token_id: TokenId, days: u32) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //tokens can't be rented while the market is paused
+        self.assert_not_paused();
+
+        //get the rental object from the unique rental ID. If the rental doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let rental = self.rentals.get(&contract_and_token_id).expect("No rental");
+
+        //get the renter ID which is the person who called the function and make sure they're not the owner of the rental
+        let renter_id = env::predecessor_account_id();
+        assert_ne!(rental.owner_id, renter_id, "Cannot rent your own token.");
+        assert!(days > 0 && days <= rental.max_days, "Can rent for between 1 and {} days", rental.max_days);
+
+        //a lease that has ended is paid out before the token is rented again
+        if let Some(lease) = &rental.lease {
+            assert!(lease.has_ended(), "Token is already rented until {}", lease.ends_at.0);
+            self.internal_settle_lease(&contract_and_token_id);
+        }
+
+        //every lease takes up storage for the renter, the same as a listing
+        self.assert_storage_for_listings(&renter_id, 1);
+
+        //escrow the rent from the renter's deposits. This panics if they haven't deposited enough
+        let amount = rental.price_per_day.saturating_mul(days as u128);
+        self.internal_decrease_ft_deposit(&renter_id, &rental.ft_token_id, amount);
+
+        //record the lease
+        let starts_at = env::block_timestamp();
+        let ends_at = starts_at + NANOS_PER_DAY * days as u64;
+        self.internal_start_lease(
+            &contract_and_token_id,
+            Lease {
+                renter_id: renter_id.clone(),
+                starts_at: U64(starts_at),
+                ends_at: U64(ends_at),
+                amount,
+            },
+        );
+
+        //make the renter the token's user until the lease ends
+        ext_nft_contract::ext(nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_SET_USER)
+            .nft_set_user(token_id.clone(), Some(renter_id.clone()), U64(ends_at), Some(rental.approval_id))
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the rent. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(GAS_FOR_RESOLVE_RENT)
+            .resolve_rent(nft_contract_id, token_id, renter_id)
+        )
+    }
+
+    //private method used to resolve rent. If the NFT contract didn't make the renter the token's user, the lease
+    //is cancelled and the rent goes back into the renter's deposits. Returns whether the lease went through
+    #[private]
+    pub fn resolve_rent(&mut self, nft_contract_id: AccountId, token_id: TokenId, renter_id: AccountId) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        //the rental may have been settled or removed since, in which case there's nothing to undo
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let is_renters_lease = self
+            .rentals
+            .get(&contract_and_token_id)
+            .and_then(|rental| rental.lease)
+            .map(|lease| lease.renter_id == renter_id)
+            .unwrap_or(false);
+        if !is_renters_lease {
+            return false;
+        }
+
+        let (rental, lease) = self.internal_end_lease(&contract_and_token_id);
+        env::log_str(&format!(
+            "Could not make {} the user of token {} on {}. Refunding the rent",
+            renter_id, rental.token_id, rental.nft_contract_id
+        ));
+        self.internal_increase_ft_deposit(&lease.renter_id, &rental.ft_token_id, lease.amount);
+        false
+    }
+
+    /// Pay out a lease once it has ended. Anyone can call this. The owner is sent the rent minus the
+    /// market's commission and the token can be rented again.
+    pub fn settle_lease(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let rental = self.rentals.get(&contract_and_token_id).expect("No rental");
+        let lease = rental.lease.expect("Token is not rented");
+        assert!(lease.has_ended(), "Lease has not ended yet");
+
+        self.internal_settle_lease(&contract_and_token_id);
+    }
+
+    //removes a rental from the market. This can only be done by the owner while the token isn't rented out
+    #[payable]
+    pub fn remove_rental(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //make sure the caller is the owner of the rental
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let rental = self.rentals.get(&contract_and_token_id).expect("No rental");
+        assert_eq!(env::predecessor_account_id(), rental.owner_id, "Must be rental owner");
+
+        //a lease that has ended is paid out before the rental is removed
+        if let Some(lease) = &rental.lease {
+            assert!(lease.has_ended(), "Cannot remove a rental while the token is rented");
+            self.internal_settle_lease(&contract_and_token_id);
+        }
+
+        self.internal_remove_rental(&contract_and_token_id);
+    }
+
+    /// views
+
+    //get the rental information for a given nft contract and token ID
+    pub fn get_rental(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Rental> {
+        self.rentals.get(&(nft_contract_id, token_id))
+    }
+
+    //returns the number of rentals the marketplace has up (as a string)
+    pub fn get_supply_rentals(&self) -> U64 {
+        U64(self.rentals.len())
+    }
+
+    //returns paginated rental objects. (result is a vector of rentals)
+    pub fn get_rentals(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<Rental> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.rentals
+            .values_as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    //returns the rentals an account is currently renting. Leases that have ended are left out
+    pub fn get_active_leases(&self, account_id: AccountId) -> Vec<Rental> {
+        let Some(leases) = self.leases_by_renter_id.get(&account_id) else {
+            return vec![];
+        };
+
+        leases
+            .iter()
+            .filter_map(|contract_and_token_id| self.rentals.get(&contract_and_token_id))
+            .filter(|rental| rental.lease.as_ref().map(|lease| !lease.has_ended()).unwrap_or(false))
+            .collect()
+    }
+}
+
+impl Contract {
+    //internal method for listing a token for rent. Called from nft_on_approve once the caller and storage have been checked
+    pub(crate) fn internal_list_rental(&mut self, rental: Rental) {
+        let contract_and_token_id = (rental.nft_contract_id.clone(), rental.token_id.clone());
+
+        //a token can only be listed one way at a time
+        assert!(
+            self.sales.get(&contract_and_token_id).is_none(),
+            "Token is already listed for sale"
+        );
+        assert!(
+            self.auctions.get(&contract_and_token_id).is_none(),
+            "Token is already up for auction"
+        );
+        assert!(rental.price_per_day.gt(&ZERO_TOKEN), "Rent must be greater than 0");
+        assert!(rental.max_days > 0, "Must be rentable for at least 1 day");
+
+        //if the token was already listed for rent, the new approval replaces it (but not while it's rented out)
+        if let Some(existing) = self.rentals.get(&contract_and_token_id) {
+            assert!(existing.lease.is_none(), "Token is already rented out");
+            self.internal_remove_rental(&contract_and_token_id);
+        }
+
+        //get the rentals by owner ID for the given owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.rentals_by_owner_id.get(&rental.owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::RentalsByOwnerIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&rental.owner_id),
+                }
+            )
+        });
+        //insert the unique rental ID into the set and the set back into the collection for the owner
+        by_owner_id.insert(&contract_and_token_id);
+        self.rentals_by_owner_id.insert(&rental.owner_id, &by_owner_id);
+
+        //insert the key value pair into the rentals map. Key is the unique ID. value is the rental object
+        self.rentals.insert(&contract_and_token_id, &rental);
+    }
+
+    //internal method for removing a rental from the market. This returns the removed rental object
+    pub(crate) fn internal_remove_rental(&mut self, contract_and_token_id: &ContractAndTokenId) -> Rental {
+        //get the rental object by removing the unique rental ID. If there was no rental, panic
+        let rental = self.rentals.remove(contract_and_token_id).expect("No rental");
+
+        //remove the unique rental ID from the owner's set, and the set itself if it's now empty
+        let mut by_owner_id = self
+            .rentals_by_owner_id
+            .get(&rental.owner_id)
+            .expect("No rental by_owner_id");
+        by_owner_id.remove(contract_and_token_id);
+        if by_owner_id.is_empty() {
+            self.rentals_by_owner_id.remove(&rental.owner_id);
+        } else {
+            self.rentals_by_owner_id.insert(&rental.owner_id, &by_owner_id);
+        }
+
+        //return the rental object
+        rental
+    }
+
+    //internal method for recording a new lease on a rental and tracking it for the renter
+    pub(crate) fn internal_start_lease(&mut self, contract_and_token_id: &ContractAndTokenId, lease: Lease) {
+        //get the leases for the renter. If there are none, we create a new empty set
+        let mut leases = self.leases_by_renter_id.get(&lease.renter_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::LeasesByRenterIdInner {
+                    //we get a new unique prefix for the collection by hashing the renter
+                    account_id_hash: hash_account_id(&lease.renter_id),
+                }
+            )
+        });
+        leases.insert(contract_and_token_id);
+        self.leases_by_renter_id.insert(&lease.renter_id, &leases);
+
+        let mut rental = self.rentals.get(contract_and_token_id).expect("No rental");
+        rental.lease = Some(lease);
+        self.rentals.insert(contract_and_token_id, &rental);
+    }
+
+    //internal method for taking the lease off a rental and untracking it for the renter. Returns the rental
+    //(without its lease) and the lease that was removed
+    pub(crate) fn internal_end_lease(&mut self, contract_and_token_id: &ContractAndTokenId) -> (Rental, Lease) {
+        let mut rental = self.rentals.get(contract_and_token_id).expect("No rental");
+        let lease = rental.lease.take().expect("Token is not rented");
+        self.rentals.insert(contract_and_token_id, &rental);
+
+        //remove the unique rental ID from the renter's set, and the set itself if it's now empty
+        if let Some(mut leases) = self.leases_by_renter_id.get(&lease.renter_id) {
+            leases.remove(contract_and_token_id);
+            if leases.is_empty() {
+                self.leases_by_renter_id.remove(&lease.renter_id);
+            } else {
+                self.leases_by_renter_id.insert(&lease.renter_id, &leases);
+            }
+        }
+
+        (rental, lease)
+    }
+
+    //internal method for paying out a lease that has ended. The market's commission comes off the top and the
+    //rest is sent to the owner
+    pub(crate) fn internal_settle_lease(&mut self, contract_and_token_id: &ContractAndTokenId) {
+        let (rental, lease) = self.internal_end_lease(contract_and_token_id);
+
+        let protocol_fee = self.internal_protocol_fee(lease.amount);
+        self.internal_increase_fee_balance(&rental.ft_token_id, protocol_fee);
+        self.internal_send_payment(
+            &Some(rental.ft_token_id),
+            rental.owner_id,
+            lease.amount.saturating_sub(protocol_fee),
+            "rental on market",
+        );
+    }
+}
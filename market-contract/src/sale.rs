@@ -1,6 +1,96 @@
 use crate::*;
 use near_sdk::PromiseResult;
 
+//the price a sale is listed for along with the fungible token it has to be paid in. A sale can also (or instead)
+//be priced in native NEAR, in which case it can be bought with `offer_near`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleConditions {
+    //fungible token contract that the buyer has to pay with
+    pub ft_token_id: FungibleTokenId,
+    //sale price in that fungible token. This is 0 if the sale can only be bought with NEAR
+    pub price: SalePriceInFTs,
+    //sale price in NEAR. If it's not set, the sale can only be bought with FTs
+    pub near_price: Option<NearToken>,
+}
+
+impl SaleConditions {
+    //returns whether the sale can be bought with its fungible token
+    pub fn accepts_ft(&self) -> bool {
+        self.price.gt(&ZERO_TOKEN)
+    }
+}
+
+//everything the market needs to know to pay out a purchase once the token has been transferred
+#[derive(Serialize, Deserialize, NearSchema, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Purchase {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //token ID that was purchased
+    pub token_id: TokenId,
+    //the seller of the token
+    pub seller_id: AccountId,
+    //the buyer of the token. They pay for it and get refunded if the transfer fails
+    pub buyer_id: AccountId,
+    //the account the token is sent to when it's bought as a gift. If it's not set, the token goes to the buyer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receiver_id: Option<AccountId>,
+    //the fungible token the buyer paid with. If it's not set, the buyer paid in NEAR
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<FungibleTokenId>,
+    //the total amount the buyer paid
+    pub price: NearToken,
+    //the market's commission, taken out of the price
+    pub protocol_fee: NearToken,
+    //the account that referred the buyer, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referrer_id: Option<AccountId>,
+    //the referrer's cut, taken out of the price. This is 0 if there's no referrer
+    pub referral_fee: NearToken,
+}
+
+impl Purchase {
+    //returns the account the token is sent to: the gift's receiver, or the buyer
+    pub fn token_receiver_id(&self) -> &AccountId {
+        self.receiver_id.as_ref().unwrap_or(&self.buyer_id)
+    }
+
+    //returns what's left for the seller and royalty recipients once the market's commission and the referral fee are taken out
+    pub fn payout_balance(&self) -> NearToken {
+        self.price.saturating_sub(self.protocol_fee).saturating_sub(self.referral_fee)
+    }
+}
+
+//how much of a sale's fungible token a buyer has to deposit before they can buy it with `offer`
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RequiredDeposit {
+    //the fungible token to deposit with ft_transfer_call
+    pub ft_token_id: FungibleTokenId,
+    //what the sale costs right now
+    pub price: NearToken,
+    //what the buyer already has deposited in the fungible token
+    pub deposited: NearToken,
+    //how much more the buyer has to deposit. This is 0 if they can already cover the price
+    pub required: NearToken,
+}
+
+//a declining price schedule for a sale. The price starts at `start_price` and decays linearly
+//down to the sale's listed price (the floor) between `start_timestamp` and `end_timestamp`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuction {
+    //price at the start of the window
+    pub start_price: SalePriceInFTs,
+    //when the price starts declining (in nanoseconds)
+    pub start_timestamp: U64,
+    //when the price reaches the floor (in nanoseconds)
+    pub end_timestamp: U64,
+}
+
 //struct that holds important information about each sale on the market
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
 #[borsh(crate = "near_sdk::borsh")]
@@ -9,29 +99,81 @@ pub struct Sale {
     //owner of the sale
     pub owner_id: AccountId,
     //market contract's approval ID to transfer the token on behalf of the owner
-    pub approval_id: u32,
+    pub approval_id: u64,
     //nft contract where the token was minted
-    pub nft_contract_id: String,
+    pub nft_contract_id: AccountId,
     //actual token ID for sale
-    pub token_id: String,
-    //sale price in fungible tokens that the token is listed for
-    pub sale_conditions: SalePriceInFTs,
+    pub token_id: TokenId,
+    //sale price and the fungible token that the token is listed for. For dutch auctions the price is the floor
+    pub sale_conditions: SaleConditions,
+    //if set, the price declines over time from the start price down to the sale price
+    pub dutch_auction: Option<DutchAuction>,
+    //if set, this is a private sale and only this account can buy the token
+    pub allowed_buyer_id: Option<AccountId>,
+}
+
+impl Sale {
+    //makes sure an account can buy the sale. Private sales can only be bought by the named buyer
+    pub fn assert_buyer_allowed(&self, buyer_id: &AccountId) {
+        if let Some(allowed_buyer_id) = &self.allowed_buyer_id {
+            assert_eq!(allowed_buyer_id, buyer_id, "This is a private sale for {}", allowed_buyer_id);
+        }
+    }
+
+    //returns what the sale costs right now. For dutch auctions this is computed from the current block timestamp
+    pub fn current_price(&self) -> SalePriceInFTs {
+        let floor_price = self.sale_conditions.price;
+        let Some(dutch_auction) = &self.dutch_auction else {
+            return floor_price;
+        };
+
+        let now = env::block_timestamp();
+        //before the window opens the price is the start price, and once it closes it's the floor
+        if now <= dutch_auction.start_timestamp.0 {
+            return dutch_auction.start_price;
+        }
+        if now >= dutch_auction.end_timestamp.0 {
+            return floor_price;
+        }
+
+        //how far into the window we are, and how much the price drops over the whole window
+        let elapsed = (now - dutch_auction.start_timestamp.0) as u128;
+        let duration = (dutch_auction.end_timestamp.0 - dutch_auction.start_timestamp.0) as u128;
+        let total_drop = dutch_auction.start_price.saturating_sub(floor_price).as_yoctonear();
+
+        //drop the price linearly. If the multiplication would overflow, divide first (losing a little precision)
+        let drop = total_drop
+            .checked_mul(elapsed)
+            .map(|x| x / duration)
+            .unwrap_or_else(|| total_drop / duration * elapsed);
+        dutch_auction.start_price.saturating_sub(NearToken::from_yoctonear(drop))
+    }
 }
 
 #[near_bindgen]
 impl Contract {
     
-    //removes a sale from the market. 
+    //removes a sale from the market. The owner can remove it at any time and anyone can remove it once it has expired
     #[payable]
     pub fn remove_sale(&mut self, nft_contract_id: AccountId, token_id: String) {
         //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
         assert_one_yocto();
+        //check whether the sale has expired before its expiry is removed along with it
+        let expired = self.internal_is_sale_expired(&(nft_contract_id.clone(), token_id.clone()));
         //get the sale object as the return value from removing the sale internally
-        let sale = self.internal_remove_sale(nft_contract_id.into(), token_id);
-        //get the predecessor of the call and make sure they're the owner of the sale
+        let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
+        //get the predecessor of the call and make sure they're the owner of the sale (or the sale has expired)
         let owner_id = env::predecessor_account_id();
         //if this fails, the remove sale will revert
-        assert_eq!(owner_id, sale.owner_id, "Must be sale owner");
+        assert!(owner_id == sale.owner_id || expired, "Must be sale owner");
+
+        //let indexers know the sale is off the market
+        SaleRemoved {
+            owner_id: &sale.owner_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+        }
+        .emit();
     }
 
     //updates the price for a sale on the market
@@ -47,7 +189,7 @@ impl Contract {
         
         //create the unique sale ID from the nft contract and token
         let contract_id: AccountId = nft_contract_id.into();
-        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        let contract_and_token_id = (contract_id.clone(), token_id.clone());
         
         //get the sale object from the unique sale ID. If there is no token, panic. 
         let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
@@ -59,55 +201,231 @@ impl Contract {
             "Must be sale owner"
         );
         
-        //set the sale conditions equal to the passed in price
-        sale.sale_conditions = price;
+        //the new price has to follow the rules of the token's collection
+        self.internal_collection_config(&contract_id).assert_min_price(price);
+        //the price is the floor of a dutch auction, so it can't be above where the auction starts
+        if let Some(dutch_auction) = &sale.dutch_auction {
+            assert!(
+                dutch_auction.start_price.ge(&price),
+                "Dutch auction start price must be greater than or equal to the sale price"
+            );
+        }
+
+        //set the sale price equal to the passed in price. The payment token stays the same
+        //the sale is moved to its new place in the price index
+        self.internal_unindex_sale_price(&sale);
+        sale.sale_conditions.price = price;
+        self.internal_index_sale_price(&sale);
         //insert the sale back into the map for the unique sale ID
         self.sales.insert(&contract_and_token_id, &sale);
+
+        //let indexers know the price changed
+        SaleUpdated {
+            owner_id: &sale.owner_id,
+            nft_contract_id: &contract_id,
+            token_id: &token_id,
+            ft_token_id: &sale.sale_conditions.ft_token_id,
+            price: &price,
+            expires_at: self.sale_expirations.get(&contract_and_token_id),
+        }
+        .emit();
+    }
+
+    /// Replace a sale's price, payment token and expiry in one call instead of removing and relisting the token.
+    /// If no payment token is passed in, the sale is priced in the market's default FT, and if no expiry is passed
+    /// in, the sale never expires. Offers placed in the old payment token are released back to the buyers.
+    #[payable]
+    pub fn update_sale(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        new_price: NearToken,
+        new_payment_token: Option<FungibleTokenId>,
+        new_expiry: Option<U64>,
+    ) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //sales can't be changed while the market is paused
+        self.assert_not_paused();
+
+        //get the sale object from the unique sale ID. If there is no token, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        //assert that the caller of the function is the sale owner
+        assert_eq!(
+            env::predecessor_account_id(),
+            sale.owner_id,
+            "Must be sale owner"
+        );
+
+        //the new conditions have to follow the same rules as a new listing
+        let ft_token_id = new_payment_token.unwrap_or_else(|| self.ft_id.clone());
+        self.assert_approved_ft_token_id(&ft_token_id);
+        let collection_config = self.internal_collection_config(&nft_contract_id);
+        collection_config.assert_trading_enabled(&nft_contract_id);
+        collection_config.assert_accepts_ft(&ft_token_id);
+        if new_price.gt(&ZERO_TOKEN) {
+            collection_config.assert_min_price(new_price);
+        } else {
+            //a sale can only go without an FT price if it can be bought with NEAR
+            assert!(sale.sale_conditions.near_price.is_some(), "Sale price must be greater than 0");
+            assert!(sale.dutch_auction.is_none(), "Dutch auctions must have a price in FTs");
+        }
+        if let Some(dutch_auction) = &sale.dutch_auction {
+            assert!(
+                dutch_auction.start_price.ge(&new_price),
+                "Dutch auction start price must be greater than or equal to the sale price"
+            );
+        }
+        if let Some(new_expiry) = new_expiry {
+            assert!(new_expiry.0 > env::block_timestamp(), "Sale must expire in the future");
+        }
+
+        //offers are escrowed in the sale's FT, so they're released if the sale moves to a different one
+        if ft_token_id != sale.sale_conditions.ft_token_id {
+            self.internal_release_offers(&contract_and_token_id, &sale.sale_conditions.ft_token_id);
+        }
+
+        //swap in the new conditions and move the sale to its new place in the price index
+        self.internal_unindex_sale_price(&sale);
+        sale.sale_conditions.ft_token_id = ft_token_id;
+        sale.sale_conditions.price = new_price;
+        self.internal_index_sale_price(&sale);
+        self.sales.insert(&contract_and_token_id, &sale);
+        match new_expiry {
+            Some(new_expiry) => self.sale_expirations.insert(&contract_and_token_id, &new_expiry),
+            None => self.sale_expirations.remove(&contract_and_token_id),
+        };
+
+        //let indexers know about every change at once
+        SaleUpdated {
+            owner_id: &sale.owner_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+            ft_token_id: &sale.sale_conditions.ft_token_id,
+            price: &new_price,
+            expires_at: new_expiry,
+        }
+        .emit();
     }
 
     /// Place an offer on a specific sale. 
-    /// The sale will go through as long as you have enough FTs in your balance to cover the amount and the amount is greater than or equal to the sale price
+    /// The sale will go through as long as you have enough FTs in your balance to cover the amount and the amount is greater than or equal to the sale price.
+    /// If a `referrer_id` is passed in, that account is paid the market's referral fee out of the sale price
+    /// If a `receiver_id` is passed in, the token is sent to that account instead of the caller, e.g. as a gift
     #[payable]
-    pub fn offer(&mut self, nft_contract_id: AccountId, token_id: String, amount: NearToken) {
+    pub fn offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        amount: NearToken,
+        referrer_id: Option<AccountId>,
+        receiver_id: Option<AccountId>,
+    ) {
         //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
         assert_one_yocto();
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
 
-        //convert the nft_contract_id from a AccountId to an AccountId
-        let contract_id: AccountId = nft_contract_id.into();
-        //get the unique sale ID (contract + DELIMITER + token ID)
-        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
-        
-        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
-        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
-        
-        //get the buyer ID which is the person who called the function and make sure they're not the owner of the sale
+        //get the buyer ID which is the person who called the function
         let buyer_id = env::predecessor_account_id();
-        assert_ne!(sale.owner_id, buyer_id, "Cannot bid on your own sale.");
-        
-        //get the u128 price of the token
-        let price = sale.sale_conditions;
 
-        //make sure the amount offering is greater than or equal to the price of the token
-        assert!(amount.ge(&price), "Offer amount must be greater than or eqaul to the price: {:?}", price);
+        //start the purchase and work out how much the buyer pays
+        let (ft_token_id, amount) =
+            self.internal_buy(buyer_id.clone(), nft_contract_id, token_id, amount, referrer_id, receiver_id);
 
-        // get the amount of FTs the buyer has in their balance
-        let cur_bal = self.ft_deposits.get(&buyer_id).unwrap();
-        //make sure the buyer has enough FTs to cover the amount they're offering
-        assert!(cur_bal.ge(&amount), "Not enough FTs in balance to cover offer: {:?}", amount);
+        //make sure the buyer has enough FTs to cover the amount they're offering. If they don't, tell them how much to deposit
+        let required_deposit = self.internal_required_deposit(&buyer_id, ft_token_id.clone(), amount);
+        if required_deposit.required.gt(&ZERO_TOKEN) {
+            env::panic_str(&format!(
+                "Not enough FTs deposited to cover the offer. Deposit the required amount with ft_transfer_call first: {}",
+                near_sdk::serde_json::to_string(&required_deposit).unwrap()
+            ));
+        }
         // if the buyer has enough FTs, subtract the amount from their balance
-        self.ft_deposits.insert(&buyer_id, &(cur_bal.saturating_sub(amount)));
+        self.internal_decrease_ft_deposit(&buyer_id, &ft_token_id, amount);
+    }
+
+    /// Buy a sale that's priced in NEAR. Attach at least the sale's NEAR price; anything over it is refunded.
+    /// If the token can't be transferred, the NEAR price is refunded as well.
+    /// If a `referrer_id` is passed in, that account is paid the market's referral fee out of the sale price
+    /// If a `receiver_id` is passed in, the token is sent to that account instead of the caller, e.g. as a gift
+    #[payable]
+    pub fn offer_near(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        referrer_id: Option<AccountId>,
+        receiver_id: Option<AccountId>,
+    ) {
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let price = sale.sale_conditions.near_price.expect("Sale is not priced in NEAR");
+
+        //get the buyer ID which is the person who called the function and make sure they can buy the sale
+        let buyer_id = env::predecessor_account_id();
+        self.internal_assert_can_buy(&sale, &buyer_id, &referrer_id);
+        self.internal_assert_valid_gift(&sale, &buyer_id, &receiver_id);
+
+        //make sure the buyer attached enough NEAR to cover the price and refund anything over it
+        let deposit = env::attached_deposit();
+        assert!(deposit.ge(&price), "Attached deposit must be greater than or equal to the price: {}", price);
+        let excess = deposit.saturating_sub(price);
+        if excess.gt(&ZERO_TOKEN) {
+            Promise::new(buyer_id.clone()).transfer(excess);
+        }
 
         //process the purchase (which will remove the sale from the market and perform the transfer)
         self.process_purchase(
-            contract_id,
+            nft_contract_id,
             token_id,
-            amount,
+            price,
             buyer_id,
+            referrer_id,
+            None,
+            receiver_id,
         );
     }
 
+    //returns what a sale costs right now in its fungible token. For dutch auctions this declines over time
+    pub fn current_price(&self, nft_contract_id: AccountId, token_id: String) -> SalePriceInFTs {
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        self.sales.get(&contract_and_token_id).expect("No sale").current_price()
+    }
+
+    //returns how much of the sale's fungible token an account has to deposit to buy it with `offer` right now.
+    //If no account is passed in, this is the whole price
+    pub fn required_deposit_for(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        account_id: Option<AccountId>,
+    ) -> RequiredDeposit {
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let sale = self.sales.get(&(nft_contract_id, token_id)).expect("No sale");
+        assert!(sale.sale_conditions.accepts_ft(), "Sale can only be bought with NEAR");
+
+        match account_id {
+            Some(account_id) => self.internal_required_deposit(&account_id, sale.sale_conditions.ft_token_id.clone(), sale.current_price()),
+            None => RequiredDeposit {
+                ft_token_id: sale.sale_conditions.ft_token_id.clone(),
+                price: sale.current_price(),
+                deposited: ZERO_TOKEN,
+                required: sale.current_price(),
+            },
+        }
+    }
+
     //private function used when a sale is purchased. 
-    //this will remove the sale, transfer and get the payout from the nft contract, and then distribute royalties
+    //this will remove the sale, transfer and get the payout from the nft contract, and then distribute royalties.
+    //`ft_token_id` is the fungible token the buyer paid with, or None if they paid in NEAR
+    //`receiver_id` is the account the token is sent to if the buyer bought it for someone else
     #[private]
     pub fn process_purchase(
         &mut self,
@@ -115,78 +433,360 @@ impl Contract {
         token_id: String,
         amount: NearToken,
         buyer_id: AccountId,
+        referrer_id: Option<AccountId>,
+        ft_token_id: Option<FungibleTokenId>,
+        receiver_id: Option<AccountId>,
     ) -> Promise {
         //get the sale object by removing the sale
         let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
 
-        //initiate a cross contract call to the nft contract. This will transfer the token to the buyer
-        ext_nft_contract::ext(nft_contract_id)
-            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
-            .with_attached_deposit(NearToken::from_yoctonear(1))
-            .with_static_gas(GAS_FOR_NFT_TRANSFER)
-            .nft_transfer(
-                buyer_id.clone(), //purchaser (person to transfer the NFT to)
-                token_id, //token ID to transfer
-                Some(sale.approval_id), //market contract's approval ID in order to transfer the token on behalf of the owner
-                Some("payout from market".to_string()) //memo (to include some context)
-            )
-        //after the transfer payout has been initiated, we resolve the promise by calling our own resolve_purchase function. 
-        //resolve purchase will send the FTs to the owner of the sale if everything went well.
-        .then(
-            // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
-            Self::ext(env::current_account_id())
-            .with_static_gas(GAS_FOR_RESOLVE_PURCHASE)
-            .resolve_purchase(
-                sale.owner_id, //the seller of the token
-                buyer_id, //the buyer and price are passed in incase something goes wrong and we need to refund the buyer
-                amount,
-            )
+        //let indexers know the sale is off the market
+        SaleRemoved {
+            owner_id: &sale.owner_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+        }
+        .emit();
+
+        //check the market is still approved on the token and then initiate the transfer and payout. The seller, buyer and price
+        //are passed along incase something goes wrong and we need to refund the buyer
+        self.internal_checked_transfer_with_payout(
+            Purchase {
+                nft_contract_id,
+                token_id,
+                seller_id: sale.owner_id,
+                buyer_id,
+                receiver_id,
+                ft_token_id,
+                price: amount,
+                //the market's commission comes off the top. Royalties are worked out on what's left
+                protocol_fee: self.internal_protocol_fee(amount),
+                referral_fee: self.internal_referral_fee(amount, &referrer_id),
+                referrer_id,
+            },
+            sale.approval_id,
+            "payout from market",
         )
     }
 
     /*
-        private method used to resolve the promise when calling nft_transfer_payout. This will
-        transfer the tokens to the owner of the sale if the transfer was successful. If not, the buyer will be refunded.
-        IMPORTANT - the seller MUST be registered on the FT contract before this function is called or else they will NOT
-        receive their FTs
+        private method used to resolve the promise when calling nft_transfer_payout. This will take the payout object and 
+        check to see if it's authentic and there's no problems. If everything is fine, it will pay the accounts. If the
+        transfer failed, the buyer will be refunded.
+        The token has already moved once the transfer goes through, so the sale can't be rolled back. If the payout doesn't
+        add up to exactly the price or has more accounts than the market pays out at once, it's ignored and the seller
+        gets everything after the fees. The same goes for a payout that pays more royalties than the collection allows.
+        Purchases paid in NEAR are paid out and refunded in NEAR instead of FTs.
+        The protocol fee is kept by the market and credited to the fee treasury once the transfer goes through, and the
+        referral fee (if there was a referrer) is sent to the referrer.
+        If a payee isn't registered on the FT contract, their FTs are kept by the market and they can claim them once they've registered
     */
     #[private]
-    pub fn resolve_purchase(
+    pub fn resolve_purchase(&mut self, purchase: Purchase) -> NearToken {
+        let payout_balance = purchase.payout_balance();
+        let Purchase { nft_contract_id, seller_id, ft_token_id, price, protocol_fee, referrer_id, referral_fee, .. } = purchase.clone();
+
+        // Get the payout object from the NFT contract
+        let payout = match env::promise_result(0) {
+            // If the transfer went through, we'll pay out the FTs. An invalid payout is logged and left out below
+            PromiseResult::Successful(value) => {
+                let payout = self.internal_parse_payout(&value, payout_balance);
+                if payout.is_none() {
+                    env::log_str(&format!(
+                        "{} returned an invalid payout for token {}. Paying {} everything",
+                        nft_contract_id, purchase.token_id, seller_id
+                    ));
+                }
+                payout
+            }
+            // If the transfer wasn't successful, we won't pay anyone and instead refund the buyer
+            PromiseResult::Failed => {
+                self.internal_refund_purchase(&purchase);
+                return ZERO_TOKEN;
+            }
+        };
+
+        // Invalid payouts and payouts that pay more royalties than the collection allows are ignored and the seller gets
+        // everything after the fee
+        let payout = payout
+            .filter(|payout| {
+                self.internal_collection_config(&nft_contract_id).royalties_within_cap(&seller_id, payout, payout_balance)
+            })
+            .unwrap_or_else(|| HashMap::from([(seller_id.clone(), U128(payout_balance.as_yoctonear()))]));
+
+        // Credit the market's commission to the fee treasury
+        match &ft_token_id {
+            Some(ft_token_id) => self.internal_increase_fee_balance(ft_token_id, protocol_fee),
+            None => self.near_fee_balance = self.near_fee_balance.saturating_add(protocol_fee),
+        }
+
+        // Pay the referrer their cut
+        if let Some(referrer_id) = referrer_id {
+            self.internal_send_payment(&ft_token_id, referrer_id, referral_fee, "Marketplace referral fee");
+        }
+
+        // Record the sale on the buyer's and the seller's activity
+        let earned = payout.get(&seller_id).map(|amount| NearToken::from_yoctonear(amount.0)).unwrap_or(ZERO_TOKEN);
+        self.internal_record_sale(
+            &purchase.buyer_id,
+            &seller_id,
+            &ft_token_id,
+            1,
+            price,
+            earned,
+            protocol_fee.saturating_add(referral_fee),
+        );
+        self.internal_record_token_sale(
+            &(nft_contract_id.clone(), purchase.token_id.clone()),
+            TokenSale {
+                buyer_id: purchase.buyer_id.clone(),
+                seller_id: seller_id.clone(),
+                ft_token_id: ft_token_id.clone(),
+                price,
+                timestamp: U64(env::block_timestamp()),
+            },
+        );
+
+        // Pay each account in the payout
+        for (receiver_id, amount) in payout {
+            self.internal_send_payment(
+                &ft_token_id,
+                receiver_id, //seller or royalty recipient
+                NearToken::from_yoctonear(amount.0),
+                "Sale from marketplace",
+            );
+        }
+
+        // Let indexers know the token was sold
+        PurchaseCompleted { purchase: &purchase }.emit();
+
+        price
+    }
+}
+
+impl Contract {
+    //internal method for buying a sale outright. This checks the offer against the sale's current price and starts the purchase.
+    //Returns the fungible token and amount the buyer has to pay. Callers are responsible for taking the FTs from the buyer
+    pub(crate) fn internal_buy(
         &mut self,
-        seller_id: AccountId,
         buyer_id: AccountId,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        amount: NearToken,
+        referrer_id: Option<AccountId>,
+        receiver_id: Option<AccountId>,
+    ) -> (FungibleTokenId, NearToken) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        
+        //make sure the buyer can buy the sale and that it can be bought with FTs
+        self.internal_assert_can_buy(&sale, &buyer_id, &referrer_id);
+        self.internal_assert_valid_gift(&sale, &buyer_id, &receiver_id);
+        assert!(sale.sale_conditions.accepts_ft(), "Sale can only be bought with NEAR. Use offer_near");
+        
+        //get the current price of the token and the fungible token it has to be paid in
+        let price = sale.current_price();
+        let ft_token_id = sale.sale_conditions.ft_token_id.clone();
+
+        //make sure the amount offering is greater than or equal to the price of the token
+        assert!(amount.ge(&price), "Offer amount must be greater than or eqaul to the price: {:?}", price);
+
+        //for dutch auctions the offer amount is the most the buyer is willing to pay. They only pay the current price
+        let amount = if sale.dutch_auction.is_some() { price } else { amount };
+
+        //let indexers know about the offer
+        OfferPlaced {
+            buyer_id: &buyer_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id,
+            ft_token_id: &ft_token_id,
+            amount: &amount,
+            offer_id: None,
+        }
+        .emit();
+
+        //process the purchase (which will remove the sale from the market and perform the transfer)
+        self.process_purchase(
+            nft_contract_id,
+            token_id,
+            amount,
+            buyer_id,
+            referrer_id,
+            Some(ft_token_id.clone()),
+            receiver_id,
+        );
+
+        (ft_token_id, amount)
+    }
+
+    //internal method for working out how much more of a fungible token an account has to deposit to cover a price
+    pub(crate) fn internal_required_deposit(
+        &self,
+        account_id: &AccountId,
+        ft_token_id: FungibleTokenId,
         price: NearToken,
-    ) -> NearToken {
-        // Get the amount to revert the caller's balance with
-        let transfer_amount = match env::promise_result(0) {
-            // If the promise was successful, we'll transfer all the FTs
-            PromiseResult::Successful(_) => {
-                price
+    ) -> RequiredDeposit {
+        let deposited = self.internal_ft_deposit_of(account_id, &ft_token_id);
+        RequiredDeposit {
+            ft_token_id,
+            price,
+            deposited,
+            required: price.saturating_sub(deposited),
+        }
+    }
+
+    //internal method for refunding the buyer of a purchase that didn't go through
+    pub(crate) fn internal_refund_purchase(&mut self, purchase: &Purchase) {
+        match &purchase.ft_token_id {
+            // Increment the buyer's balance of the fungible token they paid with
+            Some(ft_token_id) => self.internal_increase_ft_deposit(&purchase.buyer_id, ft_token_id, purchase.price),
+            // Send the NEAR they attached back to them
+            None => {
+                Promise::new(purchase.buyer_id.clone()).transfer(purchase.price);
             }
-            // If the promise wasn't successful, we won't transfer any FTs and instead refund the buyer
-            PromiseResult::Failed => ZERO_TOKEN,
-        };
+        }
+    }
+
+    //internal method for making sure an account can buy a sale outright, either with FTs or NEAR
+    pub(crate) fn internal_assert_can_buy(&self, sale: &Sale, buyer_id: &AccountId, referrer_id: &Option<AccountId>) {
+        //make sure the buyer isn't the owner of the sale and is allowed to buy it, and that the token can still be traded
+        assert_ne!(&sale.owner_id, buyer_id, "Cannot bid on your own sale.");
+        sale.assert_buyer_allowed(buyer_id);
+        self.assert_trading_enabled(&sale.nft_contract_id);
+        assert!(
+            !self.internal_is_sale_expired(&(sale.nft_contract_id.clone(), sale.token_id.clone())),
+            "Sale has expired"
+        );
+
+        //the buyer and seller can't refer themselves to collect the referral fee
+        if let Some(referrer_id) = referrer_id {
+            assert!(
+                referrer_id != buyer_id && referrer_id != &sale.owner_id,
+                "The referrer can't be the buyer or the seller"
+            );
+        }
+    }
 
-        // If the promise was successful, we'll transfer all the FTs
-        if transfer_amount.gt(&ZERO_TOKEN) {
-            // Perform the cross contract call to transfer the FTs to the seller
-            ext_ft_contract::ext(self.ft_id.clone())
-                // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
-                .with_attached_deposit(NearToken::from_yoctonear(1))
-                .ft_transfer(
-                    seller_id, //seller to transfer the FTs to
-                    transfer_amount, //amount to transfer
-                    Some("Sale from marketplace".to_string()), //memo (to include some context)
+    //internal method for checking whether a sale's expiry has passed. Sales without an expiry never expire
+    pub(crate) fn internal_is_sale_expired(&self, contract_and_token_id: &ContractAndTokenId) -> bool {
+        self.sale_expirations
+            .get(contract_and_token_id)
+            .map(|expires_at| env::block_timestamp() >= expires_at.0)
+            .unwrap_or(false)
+    }
+
+    //internal method for making sure a token can be bought for `receiver_id`. The buyer can't "gift" the token to
+    //themselves or back to the seller
+    pub(crate) fn internal_assert_valid_gift(&self, sale: &Sale, buyer_id: &AccountId, receiver_id: &Option<AccountId>) {
+        if let Some(receiver_id) = receiver_id {
+            assert_ne!(receiver_id, buyer_id, "Leave receiver_id out to buy the token for yourself");
+            assert_ne!(receiver_id, &sale.owner_id, "The token can't be bought for its seller");
+        }
+    }
+
+    //internal method for paying an account out of a purchase. If no fungible token is passed in, the payment is made in NEAR
+    pub(crate) fn internal_send_payment(
+        &self,
+        ft_token_id: &Option<FungibleTokenId>,
+        receiver_id: AccountId,
+        amount: NearToken,
+        memo: &str,
+    ) {
+        if amount.is_zero() {
+            return;
+        }
+        match ft_token_id {
+            Some(ft_token_id) => {
+                ext_ft_contract::ext(ft_token_id.clone())
+                    // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+                    .with_attached_deposit(NearToken::from_yoctonear(1))
+                    .ft_transfer(
+                        receiver_id.clone(), //account to transfer the FTs to
+                        amount, //amount to transfer
+                        Some(memo.to_string()), //memo (to include some context)
+                    )
+                //if the transfer fails (e.g. the account isn't registered on the FT contract), the FTs become claimable
+                .then(
+                    // No attached deposit with static GAS equal to the GAS for resolving the payment. Also attach an unused GAS weight of 1 by default.
+                    Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_PAYMENT)
+                    .resolve_payment(
+                        ft_token_id.clone(), //the FT that was sent
+                        receiver_id, //account the FTs were sent to
+                        amount, //amount to make claimable if the transfer failed
+                    )
                 );
-            return transfer_amount;
-        // If the promise was not successful, we won't transfer any FTs and instead refund the buyer
-        } else {
-            // Get the buyer's current balance and increment it
-            let cur_bal = self.ft_deposits.get(&buyer_id).unwrap();
-            self.ft_deposits.insert(&buyer_id, &(cur_bal.saturating_add(price)));
-            return ZERO_TOKEN;
+            }
+            None => {
+                Promise::new(receiver_id).transfer(amount);
+            }
         }
     }
+
+    //internal method for transferring a token to the buyer through nft_transfer_payout and then resolving the purchase.
+    //this is shared by fixed price sales and auctions
+    pub(crate) fn internal_transfer_with_payout(
+        &mut self,
+        purchase: Purchase,
+        approval_id: u64,
+        memo: &str,
+    ) -> Promise {
+        //initiate a cross contract call to the nft contract. This will transfer the token to the buyer and return
+        //a payout object used for the market to distribute funds to the appropriate accounts.
+        ext_nft_contract::ext(purchase.nft_contract_id.clone())
+            // Attach 1 yoctoNEAR with static GAS equal to the configured GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(self.gas_config.nft_transfer)
+            .nft_transfer_payout(
+                purchase.token_receiver_id().clone(), //purchaser, or the account they bought the token for (person to transfer the NFT to)
+                purchase.token_id.clone(), //token ID to transfer
+                Some(approval_id), //market contract's approval ID in order to transfer the token on behalf of the owner
+                Some(memo.to_string()), //memo (to include some context)
+                /*
+                    the price that the token was purchased for (after fees). This will be used in conjunction with the royalty
+                    percentages for the token in order to determine how much money should go to which account. 
+                */
+                U128(purchase.payout_balance().as_yoctonear()),
+                self.max_payees, //the maximum amount of accounts the market can payout at once
+            )
+        //after the transfer payout has been initiated, we resolve the promise by calling our own resolve_purchase function. 
+        //resolve purchase will send the FTs to the owner of the sale if everything went well.
+        .then(
+            // No attached deposit with static GAS equal to the GAS for resolving the purchase. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(self.gas_config.resolve_purchase)
+            .resolve_purchase(purchase)
+        )
+    }
+
+    //internal method for parsing the payout object returned by nft_transfer_payout. Returns None if the payout
+    //couldn't be parsed, has more accounts than the market pays out at once, or doesn't add up to the price
+    pub(crate) fn internal_parse_payout(
+        &self,
+        value: &[u8],
+        price: NearToken,
+    ) -> Option<HashMap<AccountId, U128>> {
+        let Payout { payout } = near_sdk::serde_json::from_slice::<Payout>(value).ok()?;
+
+        //we'll only pay out to a limited number of accounts so that we don't run out of GAS
+        if payout.is_empty() || payout.len() > self.max_payees as usize {
+            return None;
+        }
+
+        //make sure the payout adds up to exactly the price
+        let mut remainder = price.as_yoctonear();
+        for amount in payout.values() {
+            remainder = remainder.checked_sub(amount.0)?;
+        }
+        if remainder != 0 {
+            return None;
+        }
+
+        Some(payout)
+    }
 }
 
 //this is the cross contract call that we call on our own contract. 
@@ -199,7 +799,6 @@ impl Contract {
 trait ExtSelf {
     fn resolve_purchase(
         &mut self,
-        buyer_id: AccountId,
-        price: NearToken,
+        purchase: Purchase,
     ) -> Promise;
 }
@@ -107,16 +107,42 @@ impl Contract {
             .skip(start as usize) 
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize) 
-            //we'll map the token IDs which are strings into Sale objects by passing in the unique sale ID (contract + DELIMITER + token ID)
-            .map(|token_id| self.sales.get(&format!("{}{}{}", nft_contract_id, DELIMETER, token_id)).unwrap())
+            //we'll map the token IDs which are strings into Sale objects by passing in the unique sale ID (contract, token ID)
+            .map(|token_id| self.sales.get(&(nft_contract_id.clone(), token_id)).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
-    //get a sale information for a given unique sale ID (contract + DELIMITER + token ID)
-    pub fn get_sale(&self, nft_contract_token: ContractAndTokenId) -> Option<Sale> {
-        //try and get the sale object for the given unique sale ID. Will return an option since
-        //we're not guaranteed that the unique sale ID passed in will be valid.
-        self.sales.get(&nft_contract_token)
+    //returns paginated sale objects across the whole market. (result is a vector of sales)
+    pub fn get_sales(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<Sale> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the sale objects
+        self.sales
+            .values_as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //since we turned the values into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+
+    //get a sale information for a given nft contract and token ID
+    pub fn get_sale(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Sale> {
+        //try and get the sale object for the unique sale ID (contract, token ID). Will return an option since
+        //we're not guaranteed that the token passed in is for sale.
+        self.sales.get(&(nft_contract_id.clone(), token_id.clone()))
+    }
+
+    //returns when a sale expires (in nanoseconds). Sales that never expire (or don't exist) return null
+    pub fn get_sale_expiry(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<U64> {
+        self.sale_expirations.get(&(nft_contract_id, token_id))
     }
-}
+}
\ No newline at end of file
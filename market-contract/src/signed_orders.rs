@@ -0,0 +1,362 @@
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{CurveType, PromiseResult, PublicKey};
+
+use crate::*;
+
+/*
+    High-volume sellers can sell without listing each token on the market. The seller registers an ed25519 key
+    once, approves the market on a token with nft_approve (without a msg, so nothing is listed) and signs orders
+    off-chain. A buyer then submits the order with fulfill_order and the token is sold just like a listing.
+
+    An order is tied to the approval ID it was signed for. Selling the token clears that approval, so the same order
+    can't be used twice. Sellers can also cancel every order below a nonce with cancel_orders_below.
+
+    A valid signature only proves who signed the order, not that they own the token. Before the buyer is charged, the
+    NFT contract is asked for the token, and the order is only fulfilled if the seller owns it and the market holds
+    the order's approval ID. Otherwise anyone could sign orders for tokens the market still has a leftover approval on.
+*/
+
+//an order a seller signed off-chain. Sellers sign the sha256 hash of the borsh-serialized order, which
+//get_order_hash returns
+#[derive(BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignedOrder {
+    //the market the order is for, so that it can't be replayed on another market
+    pub market_id: AccountId,
+    //the account selling the token. It must have registered the key the order is signed with
+    pub seller_id: AccountId,
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //token ID being sold
+    pub token_id: TokenId,
+    //market contract's approval ID to transfer the token on behalf of the seller
+    pub approval_id: U64,
+    //fungible token the price is in
+    pub ft_token_id: FungibleTokenId,
+    //the price of the token
+    pub price: SalePriceInFTs,
+    //when the order can no longer be fulfilled (in nanoseconds)
+    pub expires_at: U64,
+    //lets the seller cancel orders in bulk. Orders with a nonce below the seller's minimum can't be fulfilled
+    pub nonce: U64,
+}
+
+//the key a seller signs orders with
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderSigner {
+    //the ed25519 key orders have to be signed with
+    pub public_key: PublicKey,
+    //orders with a lower nonce have been cancelled
+    pub min_nonce: U64,
+}
+
+//returns the hash a seller signs for an order
+fn order_hash(order: &SignedOrder) -> Vec<u8> {
+    env::sha256(&near_sdk::borsh::to_vec(order).unwrap())
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Register the ed25519 key you sign orders with, or remove it by passing null. Replacing the key keeps your
+    /// minimum nonce. A registered key takes up the same storage as a listing.
+    #[payable]
+    pub fn set_order_signing_key(&mut self, public_key: Option<PublicKey>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        let seller_id = env::predecessor_account_id();
+
+        let Some(public_key) = public_key else {
+            self.order_signers.remove(&seller_id);
+            return;
+        };
+        assert!(public_key.curve_type() == CurveType::ED25519, "Order signing keys must be ed25519 keys");
+
+        //a new key needs storage just like a listing
+        let existing = self.order_signers.get(&seller_id);
+        if existing.is_none() {
+            self.assert_storage_for_listings(&seller_id, 1);
+        }
+        let min_nonce = existing.map(|signer| signer.min_nonce).unwrap_or(U64(0));
+        self.order_signers.insert(&seller_id, &OrderSigner { public_key, min_nonce });
+    }
+
+    /// Cancel every order you signed with a nonce below `nonce`.
+    #[payable]
+    pub fn cancel_orders_below(&mut self, nonce: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        let seller_id = env::predecessor_account_id();
+        let mut signer = self.order_signers.get(&seller_id).expect("No order signing key");
+        assert!(nonce.0 > signer.min_nonce.0, "Nonce must be greater than the current minimum: {}", signer.min_nonce.0);
+
+        signer.min_nonce = nonce;
+        self.order_signers.insert(&seller_id, &signer);
+    }
+
+    /// Buy a token with an order its seller signed off-chain. The price is taken from your FT deposits once the NFT
+    /// contract confirms the seller owns the token and the market holds the order's approval ID.
+    #[payable]
+    pub fn fulfill_order(&mut self, order: SignedOrder, signature: Base64VecU8) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //purchases can't be made while the market is paused
+        self.assert_not_paused();
+
+        //make sure the order is for this market and was signed by the seller
+        assert_eq!(order.market_id, env::current_account_id(), "Order is for a different market");
+        let signer = self.order_signers.get(&order.seller_id).expect("Seller has no order signing key");
+        let signature: [u8; 64] = signature.0.try_into().expect("Signature must be 64 bytes");
+        let public_key: [u8; 32] = signer.public_key.as_bytes()[1..].try_into().unwrap();
+        assert!(env::ed25519_verify(&signature, &order_hash(&order), &public_key), "Invalid order signature");
+        self.internal_assert_order_fillable(&order);
+
+        //the buyer can't fill their own order and has to have deposited enough. They're only charged once the token
+        //checks out
+        let buyer_id = env::predecessor_account_id();
+        assert_ne!(buyer_id, order.seller_id, "Cannot fulfill your own order");
+        assert!(
+            self.internal_ft_deposit_of(&buyer_id, &order.ft_token_id).ge(&order.price),
+            "Not enough {} deposited to cover {}",
+            order.ft_token_id,
+            order.price
+        );
+
+        //get the token from its NFT contract so that we can check the owner and approval
+        ext_nft_contract::ext(order.nft_contract_id.clone())
+            .with_static_gas(GAS_FOR_NFT_TOKEN)
+            .nft_token(order.token_id.clone())
+        .then(
+            // No attached deposit with static GAS equal to the GAS for the transfer and payout. Also attach an unused GAS weight of 1 by default.
+            Self::ext(env::current_account_id())
+            .with_static_gas(self.gas_config.resolve_approval_check())
+            .resolve_fulfill_order(order, buyer_id)
+        )
+    }
+
+    //private method used to resolve the nft_token call made by fulfill_order. The buyer is only charged and the token
+    //only transferred if the seller owns it and the market is approved with the order's approval ID
+    #[private]
+    pub fn resolve_fulfill_order(&mut self, order: SignedOrder, buyer_id: AccountId) -> Promise {
+        let token = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<Option<JsonToken>>(&value).ok().flatten()
+            }
+            PromiseResult::Failed => None,
+        };
+        let market_id = env::current_account_id();
+        let is_valid = token
+            .map(|token| {
+                token.owner_id == order.seller_id
+                    && token.approved_account_ids.get(&market_id) == Some(&order.approval_id.0)
+            })
+            .unwrap_or(false);
+        assert!(
+            is_valid,
+            "Token {} on {} is not owned by {} or the market isn't approved",
+            order.token_id, order.nft_contract_id, order.seller_id
+        );
+
+        //the order may have been cancelled, or the token listed, while the token was being checked
+        self.internal_assert_order_fillable(&order);
+
+        //take the price out of the buyer's deposits. This panics if they withdrew in the meantime
+        self.internal_decrease_ft_deposit(&buyer_id, &order.ft_token_id, order.price);
+
+        //the ownership and approval were just checked, so the transfer and payout can start right away
+        self.internal_transfer_with_payout(
+            Purchase {
+                nft_contract_id: order.nft_contract_id,
+                token_id: order.token_id,
+                seller_id: order.seller_id,
+                buyer_id,
+                receiver_id: None,
+                ft_token_id: Some(order.ft_token_id),
+                price: order.price,
+                //the market's commission comes off the top. Royalties are worked out on what's left
+                protocol_fee: self.internal_protocol_fee(order.price),
+                referrer_id: None,
+                referral_fee: ZERO_TOKEN,
+            },
+            order.approval_id.0,
+            "signed order fulfilled on market",
+        )
+    }
+
+    /// views
+
+    //get the key an account signs orders with, along with its minimum nonce
+    pub fn get_order_signer(&self, account_id: AccountId) -> Option<OrderSigner> {
+        self.order_signers.get(&account_id)
+    }
+
+    //returns the hash a seller has to sign for an order
+    pub fn get_order_hash(&self, order: SignedOrder) -> Base64VecU8 {
+        order_hash(&order).into()
+    }
+}
+
+impl Contract {
+    //internal method for making sure an order hasn't expired or been cancelled, follows the same rules as a listing
+    //and is for a token that isn't listed. Checked again once the token is confirmed, since that takes a block
+    pub(crate) fn internal_assert_order_fillable(&self, order: &SignedOrder) {
+        assert!(env::block_timestamp() < order.expires_at.0, "Order has expired");
+        let signer = self.order_signers.get(&order.seller_id).expect("Seller has no order signing key");
+        assert!(order.nonce.0 >= signer.min_nonce.0, "Order has been cancelled");
+
+        //the order has to follow the same rules as a listing
+        self.assert_approved_nft_contract(&order.nft_contract_id);
+        self.assert_approved_ft_token_id(&order.ft_token_id);
+        let collection_config = self.internal_collection_config(&order.nft_contract_id);
+        collection_config.assert_trading_enabled(&order.nft_contract_id);
+        collection_config.assert_accepts_ft(&order.ft_token_id);
+        collection_config.assert_min_price(order.price);
+        assert!(
+            self.internal_is_registered_seller(&order.seller_id, &order.ft_token_id),
+            "Seller must be registered on {} to sell for it. They can check with verify_ft_registration",
+            order.ft_token_id
+        );
+
+        //a token that's listed on the market has to be bought through its listing
+        let contract_and_token_id = (order.nft_contract_id.clone(), order.token_id.clone());
+        assert!(self.sales.get(&contract_and_token_id).is_none(), "Token is listed for sale");
+        assert!(self.auctions.get(&contract_and_token_id).is_none(), "Token is up for auction");
+        assert!(self.rentals.get(&contract_and_token_id).is_none(), "Token is listed for rent");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+    use ft_testing::*;
+    use near_sdk::serde_json::json;
+
+    use super::*;
+
+    fn ft() -> AccountId {
+        "token.near".parse().unwrap()
+    }
+
+    fn nft() -> AccountId {
+        "nft.near".parse().unwrap()
+    }
+
+    fn seller_key() -> SigningKey {
+        SigningKey::from_bytes(&[1; 32])
+    }
+
+    //an order from alice selling token 1 for 100 FTs with approval ID 3, until 1000ns
+    fn order() -> SignedOrder {
+        SignedOrder {
+            market_id: contract(),
+            seller_id: alice(),
+            nft_contract_id: nft(),
+            token_id: "1".to_string(),
+            approval_id: U64(3),
+            ft_token_id: ft(),
+            price: tokens(100),
+            expires_at: U64(1_000),
+            nonce: U64(0),
+        }
+    }
+
+    fn sign(key: &SigningKey, order: &SignedOrder) -> Base64VecU8 {
+        Base64VecU8(key.sign(&order_hash(order)).to_bytes().to_vec())
+    }
+
+    //the result of an nft_token call for token 1, owned by `owner_id` with the market approved under ID 3
+    fn token_result(owner_id: AccountId) -> PromiseResult {
+        let token = json!({ "token_id": "1", "owner_id": owner_id, "approved_account_ids": { contract(): 3 } });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
+    //creates the market with alice registered to sell for the market's FT and signing orders with `seller_key`. Bob
+    //holds 1000 FTs in deposits
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new(owner(), ft());
+        contract.approved_nft_contract_ids.insert(&nft());
+        contract.registered_sellers.insert(&(alice(), ft()));
+        contract.storage_deposits.insert(&alice(), &NearToken::from_near(1));
+        contract.internal_increase_ft_deposit(&bob(), &ft(), tokens(1000));
+
+        let public_key = seller_key().verifying_key().to_bytes().to_vec();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.set_order_signing_key(Some(PublicKey::from_parts(CurveType::ED25519, public_key).unwrap()));
+        contract
+    }
+
+    fn fulfill(contract: &mut Contract, order: SignedOrder, signature: Base64VecU8) {
+        Context::new().predecessor(bob()).one_yocto().set();
+        let _ = contract.fulfill_order(order, signature);
+    }
+
+    #[test]
+    fn buyers_are_charged_once_the_token_checks_out() {
+        let mut contract = setup();
+        fulfill(&mut contract, order(), sign(&seller_key(), &order()));
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(1000));
+
+        Context::new().predecessor(ft_testing::contract()).set_with_promise_result(token_result(alice()));
+        let _ = contract.resolve_fulfill_order(order(), bob());
+
+        assert_eq!(contract.internal_ft_deposit_of(&bob(), &ft()), tokens(900));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not owned by alice.near or the market isn't approved")]
+    fn orders_for_tokens_the_seller_does_not_own_are_refused() {
+        let mut contract = setup();
+        fulfill(&mut contract, order(), sign(&seller_key(), &order()));
+
+        Context::new().predecessor(ft_testing::contract()).set_with_promise_result(token_result(charlie()));
+        let _ = contract.resolve_fulfill_order(order(), bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid order signature")]
+    fn orders_signed_with_another_key_are_refused() {
+        let mut contract = setup();
+
+        fulfill(&mut contract, order(), sign(&SigningKey::from_bytes(&[2; 32]), &order()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid order signature")]
+    fn changed_orders_are_refused() {
+        let mut contract = setup();
+        let signature = sign(&seller_key(), &order());
+
+        fulfill(&mut contract, SignedOrder { price: tokens(1), ..order() }, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Order has expired")]
+    fn expired_orders_are_refused() {
+        let mut contract = setup();
+
+        Context::new().predecessor(bob()).one_yocto().block_timestamp(1_000).set();
+        let _ = contract.fulfill_order(order(), sign(&seller_key(), &order()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Order has been cancelled")]
+    fn cancelled_orders_are_refused() {
+        let mut contract = setup();
+        Context::new().predecessor(alice()).one_yocto().set();
+        contract.cancel_orders_below(U64(1));
+
+        fulfill(&mut contract, order(), sign(&seller_key(), &order()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Order is for a different market")]
+    fn orders_for_another_market_are_refused() {
+        let mut contract = setup();
+        let order = SignedOrder { market_id: "other-market.near".parse().unwrap(), ..order() };
+
+        fulfill(&mut contract, order.clone(), sign(&seller_key(), &order));
+    }
+}
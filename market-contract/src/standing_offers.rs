@@ -0,0 +1,277 @@
+use crate::*;
+
+//an offer on a token that doesn't have to be listed. Token offers are for one specific token while collection
+//offers can be accepted by the owner of any token from the NFT contract. The FTs for the offer are escrowed by the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct StandingOffer {
+    //unique ID of the offer
+    pub offer_id: U64,
+    //account that placed the offer
+    pub buyer_id: AccountId,
+    //nft contract the offer is for
+    pub nft_contract_id: AccountId,
+    //the token the offer is for. If it's not set, this is a collection offer
+    pub token_id: Option<TokenId>,
+    //fungible token the offer is in
+    pub ft_token_id: FungibleTokenId,
+    //amount of FTs escrowed for the offer
+    pub amount: SalePriceInFTs,
+    //when the offer can no longer be accepted (in nanoseconds). If it's not set, the offer never expires
+    pub expires_at: Option<U64>,
+}
+
+impl StandingOffer {
+    //returns whether the offer has expired at the current block timestamp
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|t| env::block_timestamp() >= t.0).unwrap_or(false)
+    }
+}
+
+//the msg passed to nft_approve to accept a standing offer instead of listing the token
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct AcceptOfferArgs {
+    pub accept_offer: AcceptOfferConditions,
+}
+
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde", deny_unknown_fields)]
+pub struct AcceptOfferConditions {
+    //the standing offer to accept
+    pub offer_id: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Make an offer on any token from an NFT contract. The amount is escrowed from your FT deposits. The owner of
+    /// any token from the contract can accept it by calling nft_approve with `{"accept_offer": {"offer_id": ...}}`.
+    /// Each standing offer takes up the same storage as a listing. Returns the offer ID.
+    #[payable]
+    pub fn make_collection_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        ft_token_id: Option<FungibleTokenId>,
+        amount: NearToken,
+        expires_at: Option<U64>,
+    ) -> U64 {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        self.internal_make_standing_offer(nft_contract_id, None, ft_token_id, amount, expires_at)
+    }
+
+    /// Make an offer on a specific token, whether it's listed or not. The amount is escrowed from your FT deposits.
+    /// The owner of the token can accept it by calling nft_approve with `{"accept_offer": {"offer_id": ...}}`.
+    /// Each standing offer takes up the same storage as a listing. Returns the offer ID.
+    #[payable]
+    pub fn make_token_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        ft_token_id: Option<FungibleTokenId>,
+        amount: NearToken,
+        expires_at: Option<U64>,
+    ) -> U64 {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        self.internal_make_standing_offer(nft_contract_id, Some(token_id), ft_token_id, amount, expires_at)
+    }
+
+    /// Cancel a standing offer and return the escrowed FTs to the buyer's deposits. The buyer can cancel at any time
+    /// and anyone can cancel an offer once it has expired.
+    #[payable]
+    pub fn cancel_standing_offer(&mut self, offer_id: U64) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //get the offer object as the return value from removing the offer internally
+        let offer = self.internal_remove_standing_offer(offer_id.0);
+        //make sure the caller is allowed to cancel the offer. If this fails, the removal will revert
+        assert!(
+            env::predecessor_account_id() == offer.buyer_id || offer.is_expired(),
+            "Only the buyer can cancel an offer before it expires"
+        );
+
+        //release the escrow back into the buyer's deposits
+        self.internal_increase_ft_deposit(&offer.buyer_id, &offer.ft_token_id, offer.amount);
+    }
+
+    /// views
+
+    //get the standing offer for a given offer ID
+    pub fn get_standing_offer(&self, offer_id: U64) -> Option<StandingOffer> {
+        self.standing_offers.get(&offer_id.0)
+    }
+
+    //returns paginated standing offers across the whole market. (result is a vector of offers)
+    pub fn get_standing_offers(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<StandingOffer> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.standing_offers
+            .values_as_vector()
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    //returns the standing offers made by a given account
+    pub fn get_standing_offers_by_buyer_id(&self, account_id: AccountId) -> Vec<StandingOffer> {
+        self.standing_offers_by_buyer_id
+            .get(&account_id)
+            .map(|offer_ids| {
+                offer_ids
+                    .iter()
+                    .map(|offer_id| self.standing_offers.get(&offer_id).unwrap())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for making a standing offer. Token offers have a token ID and collection offers don't
+    pub(crate) fn internal_make_standing_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: Option<TokenId>,
+        ft_token_id: Option<FungibleTokenId>,
+        amount: NearToken,
+        expires_at: Option<U64>,
+    ) -> U64 {
+        //offers can't be made while the market is paused
+        self.assert_not_paused();
+        //make sure tokens from the NFT contract can be traded on the market
+        self.assert_approved_nft_contract(&nft_contract_id);
+        let collection_config = self.internal_collection_config(&nft_contract_id);
+        collection_config.assert_trading_enabled(&nft_contract_id);
+
+        //if no fungible token was passed in, the offer is in the market's default FT
+        let ft_token_id = ft_token_id.unwrap_or_else(|| self.ft_id.clone());
+        self.assert_approved_ft_token_id(&ft_token_id);
+        collection_config.assert_accepts_ft(&ft_token_id);
+
+        assert!(amount.gt(&ZERO_TOKEN), "Offer amount must be greater than 0");
+        collection_config.assert_min_price(amount);
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Offer must expire in the future");
+        }
+
+        //the offer takes up storage just like a listing
+        let buyer_id = env::predecessor_account_id();
+        self.assert_storage_for_listings(&buyer_id, 1);
+
+        //escrow the offer from the buyer's deposits. This panics if they haven't deposited enough
+        self.internal_decrease_ft_deposit(&buyer_id, &ft_token_id, amount);
+
+        //record the offer under a new unique ID
+        let offer_id = self.next_offer_id;
+        self.next_offer_id += 1;
+        let offer = StandingOffer {
+            offer_id: U64(offer_id),
+            buyer_id: buyer_id.clone(),
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+            ft_token_id: ft_token_id.clone(),
+            amount,
+            expires_at,
+        };
+        self.standing_offers.insert(&offer_id, &offer);
+
+        //get the standing offers for the buyer. If there are none, we create a new empty set
+        let mut by_buyer_id = self.standing_offers_by_buyer_id.get(&buyer_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::StandingOffersByBuyerIdInner {
+                    //we get a new unique prefix for the collection by hashing the buyer
+                    account_id_hash: hash_account_id(&buyer_id),
+                }
+            )
+        });
+        by_buyer_id.insert(&offer_id);
+        self.standing_offers_by_buyer_id.insert(&buyer_id, &by_buyer_id);
+
+        //let indexers know about the offer. Collection offers don't have a token ID
+        OfferPlaced {
+            buyer_id: &buyer_id,
+            nft_contract_id: &nft_contract_id,
+            token_id: &token_id.unwrap_or_default(),
+            ft_token_id: &ft_token_id,
+            amount: &amount,
+            offer_id: Some(U64(offer_id)),
+        }
+        .emit();
+
+        U64(offer_id)
+    }
+
+    //internal method for removing a standing offer. This returns the removed offer object
+    pub(crate) fn internal_remove_standing_offer(&mut self, offer_id: u64) -> StandingOffer {
+        //get the offer object by removing the offer ID. If there was no offer, panic
+        let offer = self.standing_offers.remove(&offer_id).expect("No standing offer");
+
+        //remove the offer ID from the buyer's set, and the set itself if it's now empty
+        let mut by_buyer_id = self
+            .standing_offers_by_buyer_id
+            .get(&offer.buyer_id)
+            .expect("No standing offer by_buyer_id");
+        by_buyer_id.remove(&offer_id);
+        if by_buyer_id.is_empty() {
+            self.standing_offers_by_buyer_id.remove(&offer.buyer_id);
+        } else {
+            self.standing_offers_by_buyer_id.insert(&offer.buyer_id, &by_buyer_id);
+        }
+
+        //return the offer object
+        offer
+    }
+
+    //internal method for accepting a standing offer. Called from nft_on_approve once we know the owner has
+    //approved the market on the token
+    pub(crate) fn internal_accept_standing_offer(
+        &mut self,
+        offer_id: u64,
+        owner_id: AccountId,
+        approval_id: u64,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+    ) -> Promise {
+        //get the offer object as the return value from removing the offer internally
+        let offer = self.internal_remove_standing_offer(offer_id);
+
+        //make sure the offer is for this token (or the collection it's from) and can still be accepted
+        assert_eq!(offer.nft_contract_id, nft_contract_id, "Offer is for a different NFT contract");
+        if let Some(offer_token_id) = &offer.token_id {
+            assert_eq!(offer_token_id, &token_id, "Offer is for a different token");
+        }
+        assert!(!offer.is_expired(), "Offer has expired");
+        assert_ne!(offer.buyer_id, owner_id, "Cannot accept your own offer");
+
+        //a token that's up for sale or auction has to be taken off the market first
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        assert!(self.sales.get(&contract_and_token_id).is_none(), "Token is listed for sale");
+        assert!(self.auctions.get(&contract_and_token_id).is_none(), "Token is up for auction");
+
+        //the FTs are already escrowed so we can transfer the token and pay out the owner
+        self.internal_transfer_with_payout(
+            Purchase {
+                nft_contract_id,
+                token_id,
+                seller_id: owner_id,
+                buyer_id: offer.buyer_id,
+                receiver_id: None,
+                ft_token_id: Some(offer.ft_token_id),
+                price: offer.amount,
+                //the market's commission comes off the top. Royalties are worked out on what's left
+                protocol_fee: self.internal_protocol_fee(offer.amount),
+                referrer_id: None,
+                referral_fee: ZERO_TOKEN,
+            },
+            approval_id,
+            "standing offer accepted on market",
+        )
+    }
+}
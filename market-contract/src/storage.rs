@@ -0,0 +1,125 @@
+use crate::*;
+
+//the structures returned by the storage management methods are shared with the FT tutorial. `total` is what the
+//account has deposited and `available` is the part that isn't locked up by its current listings. `min` is the
+//storage for a single listing and there's no `max` since every extra listing needs another storage_per_sale() worth of NEAR
+pub use ft_common::storage::{StorageBalance, StorageBalanceBounds};
+
+#[near_bindgen]
+impl Contract {
+    //Allows users to deposit storage. This is to cover the cost of storing sale objects on the contract
+    //Optional account ID is to users can pay for storage for other people.
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) -> StorageBalance {
+        //get the account ID to pay for storage for
+        let storage_account_id = account_id
+            //if we didn't specify an account ID, we simply use the caller of the function
+            .unwrap_or_else(env::predecessor_account_id);
+
+        //get the deposit value which is how much the user wants to add to their storage
+        let deposit = env::attached_deposit();
+
+        //make sure the deposit is greater than or equal to the minimum storage for a sale
+        assert!(
+            deposit.ge(&storage_per_sale()),
+            "Requires minimum deposit of {}",
+            storage_per_sale()
+        );
+
+        //get the balance of the account (if the account isn't in the map we default to a balance of 0)
+        let mut balance = self.storage_deposits.get(&storage_account_id).unwrap_or(ZERO_TOKEN);
+        //add the deposit to their balance
+        balance = balance.saturating_add(deposit);
+        //insert the balance back into the map for that account ID
+        self.storage_deposits.insert(&storage_account_id, &balance);
+
+        //return the updated storage balance
+        self.internal_storage_balance_of(&storage_account_id)
+    }
+
+    //Allows users to withdraw any excess storage that they're not using. Say Bob pays 0.01N for 1 sale
+    //Alice then buys Bob's token. This means bob has paid 0.01N for a sale that's no longer on the marketplace
+    //Bob could then withdraw this 0.01N back into his account. If no amount is passed in, all the excess is withdrawn.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        //make sure the user attaches exactly 1 yoctoNEAR for security purposes.
+        //this will redirect them to the NEAR wallet (or requires a full access key).
+        assert_one_yocto();
+
+        //the account to withdraw storage to is always the function caller
+        let owner_id = env::predecessor_account_id();
+        //get the amount that the user has paid. If they're not in the map, default to 0
+        let total = self.storage_deposits.get(&owner_id).unwrap_or(ZERO_TOKEN);
+
+        //how much NEAR is being used up for all the current listings on the account
+        let used = self.internal_storage_used(&owner_id);
+        //the excess that can be withdrawn is the total storage paid - storage being used up.
+        let available = total.saturating_sub(used);
+
+        //withdraw the amount that was asked for, or all the excess if no amount was passed in
+        let amount = amount.unwrap_or(available);
+        assert!(
+            amount.le(&available),
+            "Cannot withdraw more than the available storage balance: {}",
+            available
+        );
+
+        //we need to keep the storage that's left in the map if it's greater than 0.
+        //this is so that if the user had 500 sales on the market, we keep that value here so
+        //if those sales get taken down, the user can then go and withdraw 500 sales worth of storage.
+        let remaining = total.saturating_sub(amount);
+        if remaining.gt(&ZERO_TOKEN) {
+            self.storage_deposits.insert(&owner_id, &remaining);
+        } else {
+            self.storage_deposits.remove(&owner_id);
+        }
+
+        //if the amount to withdraw is > 0, we transfer the amount to the user.
+        if amount.gt(&ZERO_TOKEN) {
+            Promise::new(owner_id.clone()).transfer(amount);
+        }
+
+        //return the updated storage balance
+        self.internal_storage_balance_of(&owner_id)
+    }
+
+    /// views
+    //return the minimum storage for 1 sale
+    pub fn storage_minimum_balance(&self) -> NearToken {
+        storage_per_sale()
+    }
+
+    //return the storage needed for 1 listing. There's no upper bound since every listing needs its own storage
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: storage_per_sale(),
+            max: None,
+        }
+    }
+
+    //return how much storage an account has paid for and how much of it isn't used by listings.
+    //if the account has never paid for storage, we return None
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        if self.storage_deposits.contains_key(&account_id) {
+            Some(self.internal_storage_balance_of(&account_id))
+        } else {
+            None
+        }
+    }
+}
+
+impl Contract {
+    //internal method for getting how much of an account's storage deposit is locked up by its current listings
+    pub(crate) fn internal_storage_used(&self, account_id: &AccountId) -> NearToken {
+        storage_per_sale().saturating_mul(self.internal_listing_count(account_id).into())
+    }
+
+    //internal method for getting the storage balance of an account (defaults to 0 if they never paid for storage)
+    pub(crate) fn internal_storage_balance_of(&self, account_id: &AccountId) -> StorageBalance {
+        let total = self.storage_deposits.get(account_id).unwrap_or(ZERO_TOKEN);
+        StorageBalance {
+            total,
+            available: total.saturating_sub(self.internal_storage_used(account_id)),
+        }
+    }
+}
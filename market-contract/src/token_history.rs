@@ -0,0 +1,44 @@
+use crate::*;
+
+//how many of a token's most recent sales are kept. Older sales are dropped as new ones come in
+pub const MAX_TOKEN_SALE_HISTORY: usize = 10;
+
+//a completed sale of a single token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenSale {
+    //the account that paid for the token. Gifts are recorded under the account that paid, not the receiver
+    pub buyer_id: AccountId,
+    //the account that sold the token
+    pub seller_id: AccountId,
+    //the fungible token the buyer paid with. If it's not set, the buyer paid in NEAR
+    pub ft_token_id: Option<FungibleTokenId>,
+    //the total amount the buyer paid, before fees and royalties
+    pub price: NearToken,
+    //when the sale was settled (in nanoseconds)
+    pub timestamp: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// views
+
+    //returns the most recent sales of a token on the market, oldest first. Tokens sold in bundles aren't included
+    //since a bundle doesn't have a price for each token
+    pub fn get_token_sale_history(&self, nft_contract_id: AccountId, token_id: TokenId) -> Vec<TokenSale> {
+        self.token_sale_history.get(&(nft_contract_id, token_id)).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    //internal method for adding a completed sale to a token's history, dropping the oldest sale once the history is full
+    pub(crate) fn internal_record_token_sale(&mut self, contract_and_token_id: &ContractAndTokenId, sale: TokenSale) {
+        let mut history = self.token_sale_history.get(contract_and_token_id).unwrap_or_default();
+        if history.len() >= MAX_TOKEN_SALE_HISTORY {
+            history.drain(..=history.len() - MAX_TOKEN_SALE_HISTORY);
+        }
+        history.push(sale);
+        self.token_sale_history.insert(contract_and_token_id, &history);
+    }
+}
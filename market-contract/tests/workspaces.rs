@@ -0,0 +1,405 @@
+use ed25519_dalek::{Signer, SigningKey};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{CurveType, PublicKey};
+use near_workspaces::network::Sandbox;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract, Worker};
+use serde_json::json;
+
+const TOKEN_ID: &str = "token-1";
+const DEPOSIT: u128 = 1000;
+const PRICE: u128 = 600;
+
+struct Setup {
+    _worker: Worker<Sandbox>,
+    ft: Contract,
+    market: Contract,
+    nft: Contract,
+    seller: Account,
+    buyer: Account,
+}
+
+// Deploys the FT from 5.transfers, the test NFT contract and the market, registers everyone on the FT and gives the buyer
+// some tokens
+async fn init() -> anyhow::Result<Setup> {
+    let worker = near_workspaces::sandbox().await?;
+    let ft = worker.dev_deploy(&near_workspaces::compile_project("../5.transfers").await?).await?;
+    let market = worker.dev_deploy(&near_workspaces::compile_project("./").await?).await?;
+    let nft = worker.dev_deploy(&near_workspaces::compile_project("../test-nft").await?).await?;
+
+    let root = worker.root_account()?;
+    let seller = root
+        .create_subaccount("seller")
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+    let buyer = root
+        .create_subaccount("buyer")
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    ft.call("new_default_meta")
+        .args_json(json!({ "owner_id": ft.id(), "total_supply": "1000000" }))
+        .transact()
+        .await?
+        .into_result()?;
+    market
+        .call("new")
+        .args_json(json!({ "owner_id": market.id(), "ft_id": ft.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    nft.call("new")
+        .args_json(json!({ "owner_id": nft.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    market
+        .call("add_approved_nft_contract")
+        .args_json(json!({ "nft_contract_id": nft.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    for account_id in [market.id(), seller.id(), buyer.id()] {
+        ft.as_account()
+            .call(ft.id(), "storage_deposit")
+            .args_json(json!({ "account_id": account_id }))
+            .deposit(NearToken::from_millinear(100))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+    ft.call("ft_transfer")
+        .args_json(json!({ "receiver_id": buyer.id(), "amount": DEPOSIT.to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(Setup { _worker: worker, ft, market, nft, seller, buyer })
+}
+
+// Mints a token to the seller and lists it on the market for `PRICE`
+async fn list_token(setup: &Setup) -> anyhow::Result<()> {
+    setup
+        .seller
+        .call(setup.market.id(), "storage_deposit")
+        .deposit(NearToken::from_millinear(100))
+        .transact()
+        .await?
+        .into_result()?;
+    setup
+        .seller
+        .call(setup.nft.id(), "nft_mint")
+        .args_json(json!({
+            "token_id": TOKEN_ID,
+            "receiver_id": setup.seller.id(),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+    setup
+        .seller
+        .call(setup.nft.id(), "nft_approve")
+        .args_json(json!({
+            "token_id": TOKEN_ID,
+            "account_id": setup.market.id(),
+            "msg": json!({ "sale_conditions": PRICE.to_string() }).to_string(),
+        }))
+        .deposit(NearToken::from_millinear(10))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    let sale: serde_json::Value = setup
+        .market
+        .view("get_sale")
+        .args_json(json!({ "nft_contract_id": setup.nft.id(), "token_id": TOKEN_ID }))
+        .await?
+        .json()?;
+    assert!(!sale.is_null(), "The token should be listed");
+    Ok(())
+}
+
+// Deposits all of the buyer's FTs into the market
+async fn deposit(setup: &Setup) -> anyhow::Result<()> {
+    setup
+        .buyer
+        .call(setup.ft.id(), "ft_transfer_call")
+        .args_json(json!({ "receiver_id": setup.market.id(), "amount": DEPOSIT.to_string(), "msg": "" }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+async fn offer(setup: &Setup) -> anyhow::Result<()> {
+    // A failed transfer is resolved by the market, so the outcome is checked through the state rather than the result
+    setup
+        .buyer
+        .call(setup.market.id(), "offer")
+        .args_json(json!({ "nft_contract_id": setup.nft.id(), "token_id": TOKEN_ID, "amount": PRICE.to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?;
+    Ok(())
+}
+
+// Makes the test NFT contract misbehave
+async fn set_failure_mode(setup: &Setup, failure_mode: &str) -> anyhow::Result<()> {
+    setup
+        .nft
+        .call("set_failure_mode")
+        .args_json(json!({ "failure_mode": failure_mode }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+async fn ft_withdraw(setup: &Setup, amount: u128) -> anyhow::Result<()> {
+    setup
+        .buyer
+        .call(setup.market.id(), "ft_withdraw")
+        .args_json(json!({ "ft_token_id": setup.ft.id(), "amount": amount.to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?;
+    Ok(())
+}
+
+async fn ft_balance_of(setup: &Setup, account: &Account) -> anyhow::Result<u128> {
+    let balance: String = setup
+        .ft
+        .view("ft_balance_of")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json()?;
+    Ok(balance.parse()?)
+}
+
+async fn ft_deposits_of(setup: &Setup) -> anyhow::Result<u128> {
+    let deposits: String = setup
+        .market
+        .view("ft_deposits_of")
+        .args_json(json!({ "account_id": setup.buyer.id() }))
+        .await?
+        .json()?;
+    Ok(deposits.parse()?)
+}
+
+async fn nft_owner(setup: &Setup) -> anyhow::Result<String> {
+    let token: serde_json::Value = setup.nft.view("nft_token").args_json(json!({ "token_id": TOKEN_ID })).await?.json()?;
+    Ok(token["owner_id"].as_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn deposit_through_ft_transfer_call() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    deposit(&setup).await?;
+
+    assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT);
+    assert_eq!(ft_balance_of(&setup, &setup.buyer).await?, 0);
+    assert_eq!(ft_balance_of(&setup, setup.market.as_account()).await?, DEPOSIT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn offer_transfers_the_token_and_pays_the_seller() -> anyhow::Result<()> {
+    let setup = init().await?;
+    list_token(&setup).await?;
+    deposit(&setup).await?;
+
+    offer(&setup).await?;
+
+    assert_eq!(nft_owner(&setup).await?, setup.buyer.id().to_string());
+    assert_eq!(ft_balance_of(&setup, &setup.seller).await?, PRICE);
+    assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT - PRICE);
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_nft_transfer_refunds_the_buyer() -> anyhow::Result<()> {
+    let setup = init().await?;
+    list_token(&setup).await?;
+    deposit(&setup).await?;
+
+    // The seller revokes the market's approval without taking the listing down, so the transfer fails
+    setup
+        .seller
+        .call(setup.nft.id(), "nft_revoke")
+        .args_json(json!({ "token_id": TOKEN_ID, "account_id": setup.market.id() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    offer(&setup).await?;
+
+    assert_eq!(nft_owner(&setup).await?, setup.seller.id().to_string());
+    assert_eq!(ft_balance_of(&setup, &setup.seller).await?, 0);
+    assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn nft_contract_failing_the_transfer_refunds_the_buyer() -> anyhow::Result<()> {
+    let setup = init().await?;
+    list_token(&setup).await?;
+    deposit(&setup).await?;
+    set_failure_mode(&setup, "fail_transfer").await?;
+
+    offer(&setup).await?;
+
+    assert_eq!(nft_owner(&setup).await?, setup.seller.id().to_string());
+    assert_eq!(ft_balance_of(&setup, &setup.seller).await?, 0);
+    assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_payouts_pay_the_seller_everything() -> anyhow::Result<()> {
+    for failure_mode in ["over_payout", "garbage_payout"] {
+        let setup = init().await?;
+        list_token(&setup).await?;
+        deposit(&setup).await?;
+        set_failure_mode(&setup, failure_mode).await?;
+
+        offer(&setup).await?;
+
+        // The token was transferred so the sale goes through, but the payout is ignored
+        assert_eq!(nft_owner(&setup).await?, setup.buyer.id().to_string());
+        assert_eq!(ft_balance_of(&setup, &setup.seller).await?, PRICE);
+        assert_eq!(ft_balance_of(&setup, setup.nft.as_account()).await?, 0);
+        assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT - PRICE);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_withdrawal_restores_the_deposit() -> anyhow::Result<()> {
+    let setup = init().await?;
+    deposit(&setup).await?;
+
+    // Swap the FT contract's code for one without ft_transfer so the withdrawal fails
+    setup
+        .ft
+        .as_account()
+        .deploy(&near_workspaces::compile_project("../test-nft").await?)
+        .await?
+        .into_result()?;
+    ft_withdraw(&setup, DEPOSIT).await?;
+    assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT);
+
+    // Once the FT contract is back the same withdrawal goes through
+    setup
+        .ft
+        .as_account()
+        .deploy(&near_workspaces::compile_project("../5.transfers").await?)
+        .await?
+        .into_result()?;
+    ft_withdraw(&setup, DEPOSIT).await?;
+    assert_eq!(ft_deposits_of(&setup).await?, 0);
+    assert_eq!(ft_balance_of(&setup, &setup.buyer).await?, DEPOSIT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn orders_signed_by_someone_other_than_the_owner_are_refused() -> anyhow::Result<()> {
+    let setup = init().await?;
+    deposit(&setup).await?;
+
+    // The seller approves the market without listing, so the market holds an approval on a token it doesn't sell
+    setup
+        .seller
+        .call(setup.nft.id(), "nft_mint")
+        .args_json(json!({ "token_id": TOKEN_ID, "receiver_id": setup.seller.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+    setup
+        .seller
+        .call(setup.nft.id(), "nft_approve")
+        .args_json(json!({ "token_id": TOKEN_ID, "account_id": setup.market.id() }))
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // Someone else registers a signing key and everything a seller needs
+    let attacker = setup
+        .seller
+        .create_subaccount("attacker")
+        .initial_balance(NearToken::from_near(5))
+        .transact()
+        .await?
+        .into_result()?;
+    attacker
+        .call(setup.market.id(), "storage_deposit")
+        .deposit(NearToken::from_millinear(100))
+        .transact()
+        .await?
+        .into_result()?;
+    attacker
+        .call(setup.ft.id(), "storage_deposit")
+        .args_json(json!({ "account_id": attacker.id() }))
+        .deposit(NearToken::from_millinear(100))
+        .transact()
+        .await?
+        .into_result()?;
+    attacker
+        .call(setup.market.id(), "verify_ft_registration")
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let public_key = PublicKey::from_parts(CurveType::ED25519, key.verifying_key().to_bytes().to_vec()).unwrap();
+    attacker
+        .call(setup.market.id(), "set_order_signing_key")
+        .args_json(json!({ "public_key": public_key }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // They sign an order naming themselves as the seller of the token, and the buyer fills it
+    let order = json!({
+        "market_id": setup.market.id(),
+        "seller_id": attacker.id(),
+        "nft_contract_id": setup.nft.id(),
+        "token_id": TOKEN_ID,
+        "approval_id": "0",
+        "ft_token_id": setup.ft.id(),
+        "price": PRICE.to_string(),
+        "expires_at": u64::MAX.to_string(),
+        "nonce": "0",
+    });
+    let hash: Base64VecU8 = setup.market.view("get_order_hash").args_json(json!({ "order": order })).await?.json()?;
+    let signature = Base64VecU8(key.sign(&hash.0).to_bytes().to_vec());
+    let outcome = setup
+        .buyer
+        .call(setup.market.id(), "fulfill_order")
+        .args_json(json!({ "order": order, "signature": signature }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(outcome.is_failure(), "An order from someone who doesn't own the token should fail");
+
+    // Nothing changed hands
+    assert_eq!(nft_owner(&setup).await?, setup.seller.id().to_string());
+    assert_eq!(ft_balance_of(&setup, &attacker).await?, 0);
+    assert_eq!(ft_deposits_of(&setup).await?, DEPOSIT);
+    Ok(())
+}
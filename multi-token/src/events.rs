@@ -0,0 +1,222 @@
+//! Standard for nep245 (Multi Token) events.
+//!
+//! These events will be picked up by the NEAR indexer.
+//!
+//! <https://github.com/near/NEPs/blob/master/neps/nep-0245.md#events>
+//!
+//! This is an extension of the events format (nep-297):
+//! <https://github.com/near/NEPs/blob/master/specs/Standards/EventsFormat.md>
+//!
+//! The three events in this standard are [`MtMint`], [`MtTransfer`], and [`MtBurn`]. Each one can cover
+//! several tokens at once, so a batch transfer is a single [`MtTransfer`].
+//!
+//! These events can be logged by calling `.emit()` on them if a single event, or calling
+//! [`MtMint::emit_many`], [`MtTransfer::emit_many`],
+//! or [`MtBurn::emit_many`] respectively.
+//!
+//! Like the FT contract, custom `storage` events are logged whenever the set of registered accounts changes:
+//! [`StorageRegister`], [`StorageUnregister`] and [`StorageWithdraw`].
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId, NearToken};
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "standard")]
+#[must_use = "don't forget to `.emit()` this event"]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum NearEvent<'a> {
+    Nep245(Nep245Event<'a>),
+    Storage(StorageEvent<'a>),
+}
+
+impl<'a> NearEvent<'a> {
+    fn to_json_string(&self) -> String {
+        // Events cannot fail to serialize so fine to panic on error
+        #[allow(clippy::redundant_closure)]
+        serde_json::to_string(self).ok().unwrap_or_else(|| env::abort())
+    }
+
+    fn to_json_event_string(&self) -> String {
+        format!("EVENT_JSON:{}", self.to_json_string())
+    }
+
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub(crate) fn emit(self) {
+        near_sdk::env::log_str(&self.to_json_event_string());
+    }
+}
+
+/// Data to log for an MT mint event. To log this event, call [`.emit()`](MtMint::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct MtMint<'a> {
+    pub owner_id: &'a AccountId,
+    pub token_ids: &'a [&'a str],
+    pub amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl MtMint<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits an MT mint event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`MtMint`] represents the data of each mint.
+    pub fn emit_many(data: &[MtMint<'_>]) {
+        new_245_v1(Nep245EventKind::MtMint(data)).emit()
+    }
+}
+
+/// Data to log for an MT transfer event. To log this event,
+/// call [`.emit()`](MtTransfer::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct MtTransfer<'a> {
+    pub old_owner_id: &'a AccountId,
+    pub new_owner_id: &'a AccountId,
+    pub token_ids: &'a [&'a str],
+    pub amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl MtTransfer<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits an MT transfer event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`MtTransfer`] represents the data of each transfer.
+    pub fn emit_many(data: &[MtTransfer<'_>]) {
+        new_245_v1(Nep245EventKind::MtTransfer(data)).emit()
+    }
+}
+
+/// Data to log for an MT burn event. To log this event, call [`.emit()`](MtBurn::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct MtBurn<'a> {
+    pub owner_id: &'a AccountId,
+    pub token_ids: &'a [&'a str],
+    pub amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl MtBurn<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    /// Emits an MT burn event, through [`env::log_str`](near_sdk::env::log_str),
+    /// where each [`MtBurn`] represents the data of each burn.
+    pub fn emit_many(data: &[MtBurn<'_>]) {
+        new_245_v1(Nep245EventKind::MtBurn(data)).emit()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Nep245Event<'a> {
+    version: &'static str,
+    #[serde(flatten)]
+    event_kind: Nep245EventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+enum Nep245EventKind<'a> {
+    MtMint(&'a [MtMint<'a>]),
+    MtTransfer(&'a [MtTransfer<'a>]),
+    MtBurn(&'a [MtBurn<'a>]),
+}
+
+fn new_245_v1(event_kind: Nep245EventKind) -> NearEvent {
+    NearEvent::Nep245(Nep245Event { version: "1.0.0", event_kind })
+}
+
+/// Data to log when an account registers with the contract. To log this event,
+/// call [`.emit()`](StorageRegister::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageRegister<'a> {
+    pub account_id: &'a AccountId,
+    /// The storage deposit kept for the account
+    pub amount: &'a NearToken,
+}
+
+impl StorageRegister<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_storage_v1(StorageEventKind::StorageRegister(&[self])).emit()
+    }
+}
+
+/// Data to log when an account unregisters from the contract. To log this event,
+/// call [`.emit()`](StorageUnregister::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageUnregister<'a> {
+    pub account_id: &'a AccountId,
+    /// The storage deposit refunded to the account
+    pub amount: &'a NearToken,
+}
+
+impl StorageUnregister<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_storage_v1(StorageEventKind::StorageUnregister(&[self])).emit()
+    }
+}
+
+/// Data to log when an account withdraws from its storage balance. To log this event,
+/// call [`.emit()`](StorageWithdraw::emit).
+#[must_use]
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageWithdraw<'a> {
+    pub account_id: &'a AccountId,
+    /// The amount withdrawn
+    pub amount: &'a NearToken,
+}
+
+impl StorageWithdraw<'_> {
+    /// Logs the event to the host. This is required to ensure that the event is triggered
+    /// and to consume the event.
+    pub fn emit(self) {
+        new_storage_v1(StorageEventKind::StorageWithdraw(&[self])).emit()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct StorageEvent<'a> {
+    version: &'static str,
+    #[serde(flatten)]
+    event_kind: StorageEventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+enum StorageEventKind<'a> {
+    StorageRegister(&'a [StorageRegister<'a>]),
+    StorageUnregister(&'a [StorageUnregister<'a>]),
+    StorageWithdraw(&'a [StorageWithdraw<'a>]),
+}
+
+fn new_storage_v1(event_kind: StorageEventKind) -> NearEvent {
+    NearEvent::Storage(StorageEvent { version: "1.0.0", event_kind })
+}
@@ -0,0 +1,235 @@
+use std::str::FromStr;
+
+use crate::storage::StorageAccount;
+use crate::*;
+
+impl Contract {
+    /// Internal method for asserting that the predecessor is the owner of the contract.
+    pub(crate) fn assert_owner(&self) {
+        require!(env::predecessor_account_id() == self.owner_id, "Only the owner can call this method");
+    }
+
+    /// Internal method for force getting a registered account. If the account isn't registered, panic with a custom message.
+    pub(crate) fn internal_unwrap_account(&self, account_id: &AccountId) -> StorageAccount {
+        self.accounts
+            .get(account_id)
+            .unwrap_or_else(|| env::panic_str(format!("The account {} is not registered", account_id).as_str()))
+    }
+
+    /// Internal method for getting the balance of an account for a token. Accounts that don't hold the token have a balance of 0.
+    pub(crate) fn internal_balance_of(&self, token_id: &TokenId, account_id: &AccountId) -> u128 {
+        self.balances.get(&(token_id.clone(), account_id.clone())).unwrap_or(0)
+    }
+
+    /// Internal method for working out how much of an account's storage deposit its balances are using.
+    pub(crate) fn internal_storage_used(&self, account: &StorageAccount) -> NearToken {
+        let bytes = self.bytes_per_account + account.balances * self.bytes_per_balance;
+        env::storage_byte_cost().saturating_mul(bytes.into())
+    }
+
+    /// Internal method for depositing some amount of a token into an account. If it's a token the account
+    /// didn't hold yet, its storage deposit must cover the new balance unless `metered` is false (used for
+    /// refunds, which must not fail).
+    pub(crate) fn internal_deposit(&mut self, token_id: &TokenId, account_id: &AccountId, amount: u128, metered: bool) {
+        let mut account = self.internal_unwrap_account(account_id);
+        let balance = self.internal_balance_of(token_id, account_id);
+
+        // A new balance takes up storage that has to be paid for. The owner's storage is paid for by the contract
+        if balance == 0 {
+            account.balances += 1;
+            if metered && account_id != &self.owner_id {
+                require!(
+                    account.deposit >= self.internal_storage_used(&account),
+                    format!("The account {} doesn't have enough storage balance to hold another token", account_id)
+                );
+            }
+            self.accounts.insert(account_id, &account);
+        }
+
+        let new_balance = balance.checked_add(amount).unwrap_or_else(|| env::panic_str("Balance overflow"));
+        self.balances.insert(&(token_id.clone(), account_id.clone()), &new_balance);
+    }
+
+    /// Internal method for withdrawing some amount of a token from an account. Balances that reach 0 are
+    /// removed, which frees up the account's storage deposit.
+    pub(crate) fn internal_withdraw(&mut self, token_id: &TokenId, account_id: &AccountId, amount: u128) {
+        let balance = self.internal_balance_of(token_id, account_id);
+        let new_balance =
+            balance.checked_sub(amount).unwrap_or_else(|| env::panic_str("The account doesn't have enough balance"));
+
+        if new_balance == 0 {
+            self.balances.remove(&(token_id.clone(), account_id.clone()));
+            let mut account = self.internal_unwrap_account(account_id);
+            account.balances -= 1;
+            self.accounts.insert(account_id, &account);
+        } else {
+            self.balances.insert(&(token_id.clone(), account_id.clone()), &new_balance);
+        }
+    }
+
+    /// Internal method for minting some amount of a token into an account.
+    pub(crate) fn internal_mint(&mut self, token_id: &TokenId, account_id: &AccountId, amount: u128, memo: Option<&str>) {
+        require!(amount > 0, "The amount should be a positive number");
+        let supply = self.supplies.get(token_id).unwrap_or_else(|| env::panic_str("The token doesn't exist"));
+
+        self.internal_deposit(token_id, account_id, amount, true);
+        let new_supply = supply.checked_add(amount).unwrap_or_else(|| env::panic_str("Total supply overflow"));
+        self.supplies.insert(token_id, &new_supply);
+
+        // Emit an event showing that the tokens were minted
+        MtMint { owner_id: account_id, token_ids: &[token_id.as_str()], amounts: &[U128(amount)], memo }.emit();
+    }
+
+    /// Internal method for burning some amount of a token from an account. No event is emitted, that's up to the caller.
+    pub(crate) fn internal_burn(&mut self, token_id: &TokenId, account_id: &AccountId, amount: u128) {
+        self.internal_withdraw(token_id, account_id, amount);
+        let supply = self.supplies.get(token_id).unwrap_or(0);
+        self.supplies.insert(token_id, &supply.saturating_sub(amount));
+    }
+
+    /// Internal method for transferring several tokens from one account to another. A single event covers the whole batch.
+    pub(crate) fn internal_transfer_batch(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_ids: &[TokenId],
+        amounts: &[u128],
+        memo: Option<&str>,
+    ) {
+        // Ensure the sender can't transfer to themselves
+        require!(sender_id != receiver_id, "Sender and receiver should be different");
+        require!(!token_ids.is_empty(), "At least one token should be transferred");
+        require!(token_ids.len() == amounts.len(), "There should be one amount per token");
+
+        for (token_id, amount) in token_ids.iter().zip(amounts) {
+            // Ensure the sender can't transfer 0 tokens
+            require!(*amount > 0, "The amount should be a positive number");
+            self.internal_withdraw(token_id, sender_id, *amount);
+            self.internal_deposit(token_id, receiver_id, *amount, true);
+        }
+
+        // Emit a Transfer event
+        let token_ids: Vec<&str> = token_ids.iter().map(|token_id| token_id.as_str()).collect();
+        let amounts: Vec<U128> = amounts.iter().map(|amount| U128(*amount)).collect();
+        MtTransfer {
+            old_owner_id: sender_id,
+            new_owner_id: receiver_id,
+            token_ids: &token_ids,
+            amounts: &amounts,
+            memo,
+        }
+        .emit();
+    }
+
+    /// Internal method for measuring how many bytes an account and a balance take up with the longest possible IDs.
+    /// This will insert them, measure the storage, and remove them again. It is called in the initialization function.
+    pub(crate) fn measure_storage_usage(&mut self) {
+        let tmp_account_id = AccountId::from_str(&"a".repeat(64)).unwrap();
+        let tmp_token_id = "a".repeat(MAX_TOKEN_ID_LEN);
+
+        let initial_storage_usage = env::storage_usage();
+        self.accounts.insert(&tmp_account_id, &StorageAccount { deposit: ZERO_TOKEN, balances: 0 });
+        self.bytes_per_account = env::storage_usage() - initial_storage_usage;
+        self.accounts.remove(&tmp_account_id);
+
+        let initial_storage_usage = env::storage_usage();
+        self.balances.insert(&(tmp_token_id.clone(), tmp_account_id.clone()), &u128::MAX);
+        self.bytes_per_balance = env::storage_usage() - initial_storage_usage;
+        self.balances.remove(&(tmp_token_id, tmp_account_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ft_testing::*;
+
+    use super::*;
+    use crate::storage::StorageManagement;
+
+    /// Creates the contract with "gold" and "silver" tokens owned by `owner()`, and registers alice with enough storage for one token
+    fn setup() -> Contract {
+        Context::new().set();
+        let mut contract = Contract::new_default_meta(owner());
+
+        Context::new().one_yocto().set();
+        let metadata = MtTokenMetadata {
+            name: "Gold".to_string(),
+            symbol: Some("GLD".to_string()),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: Some(0),
+        };
+        contract.mt_create_token("gold".to_string(), metadata.clone(), U128(TOTAL_SUPPLY));
+        contract.mt_create_token("silver".to_string(), metadata, U128(TOTAL_SUPPLY));
+
+        let min = contract.storage_balance_bounds().min;
+        Context::new().predecessor(alice()).attached_deposit(min).set();
+        contract.storage_deposit(None, None);
+        contract
+    }
+
+    fn gold() -> TokenId {
+        "gold".to_string()
+    }
+
+    fn silver() -> TokenId {
+        "silver".to_string()
+    }
+
+    #[test]
+    fn creating_a_token_mints_the_supply_to_the_owner() {
+        let contract = setup();
+        assert_eq!(contract.internal_balance_of(&gold(), &owner()), TOTAL_SUPPLY);
+        assert_eq!(contract.supplies.get(&gold()), Some(TOTAL_SUPPLY));
+    }
+
+    #[test]
+    fn batch_transfer_moves_every_token() {
+        let mut contract = setup();
+        contract.internal_transfer_batch(&owner(), &alice(), &[gold()], &[10], None);
+        contract.internal_transfer_batch(&alice(), &owner(), &[gold()], &[4], None);
+
+        assert_eq!(contract.internal_balance_of(&gold(), &alice()), 6);
+        assert_eq!(contract.internal_balance_of(&gold(), &owner()), TOTAL_SUPPLY - 6);
+    }
+
+    #[test]
+    fn empty_balances_free_up_storage() {
+        let mut contract = setup();
+        contract.internal_transfer_batch(&owner(), &alice(), &[gold()], &[10], None);
+        assert_eq!(contract.internal_unwrap_account(&alice()).balances, 1);
+
+        contract.internal_transfer_batch(&alice(), &owner(), &[gold()], &[10], None);
+        assert_eq!(contract.internal_unwrap_account(&alice()).balances, 0);
+        assert!(contract.balances.get(&(gold(), alice())).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't have enough storage balance to hold another token")]
+    fn holding_more_tokens_needs_more_storage() {
+        let mut contract = setup();
+        contract.internal_transfer_batch(&owner(), &alice(), &[gold(), silver()], &[10, 10], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account doesn't have enough balance")]
+    fn transfer_more_than_the_balance_panics() {
+        let mut contract = setup();
+        contract.internal_transfer_batch(&alice(), &owner(), &[gold()], &[1], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account bob.near is not registered")]
+    fn transfer_to_unregistered_account_panics() {
+        let mut contract = setup();
+        contract.internal_transfer_batch(&owner(), &bob(), &[gold()], &[1], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "There should be one amount per token")]
+    fn mismatched_amounts_panic() {
+        let mut contract = setup();
+        contract.internal_transfer_batch(&owner(), &alice(), &[gold(), silver()], &[1], None);
+    }
+}
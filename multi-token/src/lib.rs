@@ -0,0 +1,146 @@
+//! A multi-token contract following [NEP-245](https://github.com/near/NEPs/blob/master/neps/nep-0245.md),
+//! built the same way as the fungible token tutorial. One contract holds any number of tokens, each with its
+//! own supply and metadata, and accounts can move several of them in a single call.
+//!
+//! - `mt_core.rs` has the transfers (`mt_transfer`, `mt_batch_transfer` and their `_call` versions) and the
+//!   balance and supply views
+//! - `metadata.rs` has the contract metadata and the metadata of each token
+//! - `storage.rs` follows NEP-145. Unlike the FT contract, each token an account holds takes up storage, so
+//!   accounts keep a storage balance that every new holding is paid from
+//! - `events.rs` logs the NEP-245 `mt_mint`, `mt_transfer` and `mt_burn` events, as well as the same
+//!   `storage` events as the FT contract
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap};
+use near_sdk::json_types::U128;
+use near_sdk::{
+    assert_one_yocto, env, near_bindgen, require, AccountId, BorshStorageKey, NearSchema, NearToken, PanicOnDefault,
+    StorageUsage,
+};
+
+pub mod events;
+pub mod internal;
+pub mod metadata;
+pub mod mt_core;
+pub mod storage;
+
+use crate::events::*;
+use crate::metadata::*;
+use crate::storage::StorageAccount;
+
+/// Tokens are identified by a string, e.g. "gold" or "1"
+pub type TokenId = String;
+
+/// The longest token ID that can be created. Storage costs are measured with a token ID this long.
+pub const MAX_TOKEN_ID_LEN: usize = 64;
+
+pub const ZERO_TOKEN: NearToken = NearToken::from_yoctonear(0);
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// The account that's allowed to create and mint tokens
+    pub owner_id: AccountId,
+
+    /// Balance of each account for each token. Zero balances aren't stored.
+    pub balances: LookupMap<(TokenId, AccountId), u128>,
+
+    /// Total supply of each token. Also the list of tokens that exist.
+    pub supplies: UnorderedMap<TokenId, u128>,
+
+    /// Metadata of each token
+    pub token_metadata: LookupMap<TokenId, MtTokenMetadata>,
+
+    /// Storage deposits of the registered accounts
+    pub accounts: LookupMap<AccountId, StorageAccount>,
+
+    /// The bytes it takes to register the longest possible account ID
+    pub bytes_per_account: StorageUsage,
+
+    /// The bytes it takes to store a balance for the longest possible token and account IDs
+    pub bytes_per_balance: StorageUsage,
+
+    /// Metadata for the contract itself
+    pub metadata: LazyOption<MtContractMetadata>,
+}
+
+/// Helper structure for keys of the persistent collections.
+#[derive(BorshSerialize, BorshStorageKey)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    Balances,
+    Supplies,
+    TokenMetadata,
+    Accounts,
+    Metadata,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Initializes the contract with no tokens and default contract metadata (for example purposes only).
+    /// The owner is registered so that it can hold the tokens it creates.
+    #[init]
+    pub fn new_default_meta(owner_id: AccountId) -> Self {
+        Self::new(
+            owner_id,
+            MtContractMetadata { spec: MT_METADATA_SPEC.to_string(), name: "Multi Token Tutorial".to_string() },
+        )
+    }
+
+    /// Initializes the contract with no tokens and the given contract metadata.
+    #[init]
+    pub fn new(owner_id: AccountId, metadata: MtContractMetadata) -> Self {
+        let mut this = Self {
+            owner_id: owner_id.clone(),
+            balances: LookupMap::new(StorageKey::Balances),
+            supplies: UnorderedMap::new(StorageKey::Supplies),
+            token_metadata: LookupMap::new(StorageKey::TokenMetadata),
+            accounts: LookupMap::new(StorageKey::Accounts),
+            // Set the bytes to 0 temporarily until they're measured below
+            bytes_per_account: 0,
+            bytes_per_balance: 0,
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+        };
+
+        // Measure what the storage deposits have to cover
+        this.measure_storage_usage();
+
+        // Register the owner. Their storage is paid for by the contract
+        this.accounts.insert(&owner_id, &StorageAccount { deposit: ZERO_TOKEN, balances: 0 });
+        StorageRegister { account_id: &owner_id, amount: &ZERO_TOKEN }.emit();
+
+        this
+    }
+
+    /// Creates a new token with the given metadata and mints its `initial_supply` to the owner.
+    /// Only the owner can create tokens and exactly 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn mt_create_token(&mut self, token_id: TokenId, metadata: MtTokenMetadata, initial_supply: U128) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        self.assert_owner();
+        require!(
+            !token_id.is_empty() && token_id.len() <= MAX_TOKEN_ID_LEN,
+            "The token ID should be between 1 and 64 characters"
+        );
+        require!(self.supplies.get(&token_id).is_none(), "The token already exists");
+
+        self.supplies.insert(&token_id, &0);
+        self.token_metadata.insert(&token_id, &metadata);
+        if initial_supply.0 > 0 {
+            let owner_id = self.owner_id.clone();
+            self.internal_mint(&token_id, &owner_id, initial_supply.0, Some("Initial token supply is minted"));
+        }
+    }
+
+    /// Mints `amount` more of an existing token into a registered account. Only the owner can mint and exactly
+    /// 1 yoctoNEAR must be attached.
+    #[payable]
+    pub fn mt_mint(&mut self, token_id: TokenId, account_id: AccountId, amount: U128, memo: Option<String>) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_mint(&token_id, &account_id, amount.0, memo.as_deref());
+    }
+}
@@ -0,0 +1,47 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The specific version of the standard we're using
+pub const MT_METADATA_SPEC: &str = "mt-1.0.0";
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize, NearSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtContractMetadata {
+    pub spec: String, // Should be mt-1.0.0 to indicate that the contract adheres to the current versions of the Multi Token Metadata and Core specs.
+    pub name: String, // The human-readable name of the contract.
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize, NearSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtTokenMetadata {
+    pub name: String, // The human-readable name of the token.
+    pub symbol: Option<String>, // The abbreviation, like wETH or AMPL.
+    pub icon: Option<String>, // Icon of the token.
+    pub reference: Option<String>, // A link to a valid JSON file containing various keys offering supplementary details on the token
+    pub reference_hash: Option<Base64VecU8>, // The base64-encoded sha256 hash of the JSON file contained in the reference field. This is to guard against off-chain tampering.
+    pub decimals: Option<u8>, // Used in frontends to show the proper significant digits of a fungible token. Left out for non-fungible tokens.
+}
+
+pub trait MultiTokenMetadataProvider {
+    // View call for returning the contract metadata
+    fn mt_metadata_contract(&self) -> MtContractMetadata;
+
+    // View call for returning the metadata of each of the given tokens. `null` for tokens that don't exist
+    fn mt_metadata_token_all(&self, token_ids: Vec<TokenId>) -> Vec<Option<MtTokenMetadata>>;
+}
+
+#[near_bindgen]
+impl MultiTokenMetadataProvider for Contract {
+    fn mt_metadata_contract(&self) -> MtContractMetadata {
+        self.metadata.get().unwrap()
+    }
+
+    fn mt_metadata_token_all(&self, token_ids: Vec<TokenId>) -> Vec<Option<MtTokenMetadata>> {
+        token_ids.iter().map(|token_id| self.token_metadata.get(token_id)).collect()
+    }
+}
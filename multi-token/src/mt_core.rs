@@ -0,0 +1,303 @@
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{ext_contract, Gas, PromiseOrValue, PromiseResult};
+
+use crate::*;
+
+const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(5);
+/// Extra GAS for resolving each token of a batch
+const GAS_FOR_RESOLVE_TRANSFER_PER_TOKEN: Gas = Gas::from_tgas(2);
+const GAS_FOR_MT_TRANSFER_CALL: Gas = Gas::from_tgas(25);
+
+/// The structure returned by `mt_token`. Every token in this contract is fungible, so there's no single owner.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Token {
+    pub token_id: TokenId,
+    pub owner_id: Option<AccountId>,
+}
+
+#[ext_contract(ext_mt_core)]
+pub trait MultiTokenCore {
+    /// Transfers positive `amount` of `token_id` from the `env::predecessor_account_id` to `receiver_id`.
+    /// Both accounts must be registered with the contract for transfer to succeed. (See [NEP-145](https://github.com/near/NEPs/discussions/145))
+    /// This method must to be able to accept attached deposits, and must not panic on attached deposit.
+    /// Exactly 1 yoctoNEAR must be attached.
+    ///
+    /// Arguments:
+    /// - `receiver_id` - the account ID of the receiver.
+    /// - `token_id` - the token to transfer.
+    /// - `amount` - the amount of tokens to transfer. Must be a positive number in decimal string representation.
+    /// - `approval` - approvals aren't supported, so this must be left out.
+    /// - `memo` - an optional string field in a free form to associate a memo with this transfer.
+    fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        approval: Option<(AccountId, u64)>,
+        memo: Option<String>,
+    );
+
+    /// Same as `mt_transfer` for several tokens at once. `amounts` has the amount for each of the `token_ids`.
+    fn mt_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<(AccountId, u64)>>>,
+        memo: Option<String>,
+    );
+
+    /// Transfers positive `amount` of `token_id` from the `env::predecessor_account_id` to `receiver_id` account. Then
+    /// calls `mt_on_transfer` method on `receiver_id` contract and attaches a callback to resolve this transfer.
+    /// `mt_on_transfer` method must return the amount of tokens unused by the receiver contract, the remaining
+    /// tokens must be refunded to the `predecessor_account_id` at the resolve transfer callback.
+    ///
+    /// Returns a promise which will result in the amount of tokens withdrawn from sender's account.
+    fn mt_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        approval: Option<(AccountId, u64)>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>>;
+
+    /// Same as `mt_transfer_call` for several tokens at once. `amounts` has the amount for each of the `token_ids`.
+    fn mt_batch_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<(AccountId, u64)>>>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>>;
+
+    /// Returns each of the given tokens, or `null` for tokens that don't exist.
+    fn mt_token(&self, token_ids: Vec<TokenId>) -> Vec<Option<Token>>;
+
+    /// Returns the balance of the account for the token. If the account doesn't hold the token, returns `"0"`.
+    fn mt_balance_of(&self, account_id: AccountId, token_id: TokenId) -> U128;
+
+    /// Returns the balance of the account for each of the given tokens.
+    fn mt_batch_balance_of(&self, account_id: AccountId, token_ids: Vec<TokenId>) -> Vec<U128>;
+
+    /// Returns the total supply of the token, or `null` if it doesn't exist.
+    fn mt_supply(&self, token_id: TokenId) -> Option<U128>;
+
+    /// Returns the total supply of each of the given tokens.
+    fn mt_batch_supply(&self, token_ids: Vec<TokenId>) -> Vec<Option<U128>>;
+}
+
+/// Ensures no approvals were passed in, since this contract doesn't support them.
+fn assert_no_approvals(approvals: &Option<Vec<Option<(AccountId, u64)>>>) {
+    require!(
+        approvals.as_ref().map(|approvals| approvals.iter().all(Option::is_none)).unwrap_or(true),
+        "Approvals are not supported"
+    );
+}
+
+#[near_bindgen]
+impl MultiTokenCore for Contract {
+    #[payable]
+    fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        approval: Option<(AccountId, u64)>,
+        memo: Option<String>,
+    ) {
+        self.mt_batch_transfer(receiver_id, vec![token_id], vec![amount], Some(vec![approval]), memo)
+    }
+
+    #[payable]
+    fn mt_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<(AccountId, u64)>>>,
+        memo: Option<String>,
+    ) {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        assert_no_approvals(&approvals);
+        // The sender is the user who called the method
+        let sender_id = env::predecessor_account_id();
+        // Transfer the tokens
+        let amounts: Vec<u128> = amounts.iter().map(|amount| amount.0).collect();
+        self.internal_transfer_batch(&sender_id, &receiver_id, &token_ids, &amounts, memo.as_deref());
+    }
+
+    #[payable]
+    fn mt_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        approval: Option<(AccountId, u64)>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>> {
+        self.mt_batch_transfer_call(receiver_id, vec![token_id], vec![amount], Some(vec![approval]), memo, msg)
+    }
+
+    #[payable]
+    fn mt_batch_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<(AccountId, u64)>>>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>> {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        assert_no_approvals(&approvals);
+        // The sender is the user who called the method
+        let sender_id = env::predecessor_account_id();
+        // Transfer the tokens
+        let raw_amounts: Vec<u128> = amounts.iter().map(|amount| amount.0).collect();
+        self.internal_transfer_batch(&sender_id, &receiver_id, &token_ids, &raw_amounts, memo.as_deref());
+
+        // The callback has to refund each token, so it gets more GAS for bigger batches
+        let resolve_gas = GAS_FOR_RESOLVE_TRANSFER
+            .saturating_add(GAS_FOR_RESOLVE_TRANSFER_PER_TOKEN.saturating_mul(token_ids.len() as u64));
+
+        // Initiating receiver's call and the callback
+        ext_mt_receiver::ext(receiver_id.clone())
+            .with_static_gas(GAS_FOR_MT_TRANSFER_CALL)
+            .mt_on_transfer(
+                sender_id.clone(),
+                vec![sender_id.clone(); token_ids.len()],
+                token_ids.clone(),
+                amounts.clone(),
+                msg,
+            )
+            // We then resolve the promise and call mt_resolve_transfer on our own contract
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(resolve_gas)
+                    .mt_resolve_transfer(sender_id, receiver_id, token_ids, amounts),
+            )
+            .into()
+    }
+
+    fn mt_token(&self, token_ids: Vec<TokenId>) -> Vec<Option<Token>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| self.supplies.get(&token_id).map(|_| Token { token_id, owner_id: None }))
+            .collect()
+    }
+
+    fn mt_balance_of(&self, account_id: AccountId, token_id: TokenId) -> U128 {
+        U128(self.internal_balance_of(&token_id, &account_id))
+    }
+
+    fn mt_batch_balance_of(&self, account_id: AccountId, token_ids: Vec<TokenId>) -> Vec<U128> {
+        token_ids.iter().map(|token_id| U128(self.internal_balance_of(token_id, &account_id))).collect()
+    }
+
+    fn mt_supply(&self, token_id: TokenId) -> Option<U128> {
+        self.supplies.get(&token_id).map(U128)
+    }
+
+    fn mt_batch_supply(&self, token_ids: Vec<TokenId>) -> Vec<Option<U128>> {
+        token_ids.iter().map(|token_id| self.supplies.get(token_id).map(U128)).collect()
+    }
+}
+
+#[ext_contract(ext_mt_receiver)]
+pub trait MultiTokenReceiver {
+    /// Called by the multi token contract after `mt_transfer_call` or `mt_batch_transfer_call` was initiated by
+    /// `sender_id`. The tokens were already transferred to this contract account and are ready to be used.
+    ///
+    /// `previous_owner_ids`, `token_ids` and `amounts` have one entry per token. The method must return how many
+    /// of each token were *not* used, in the same order, or panic to have every token refunded.
+    fn mt_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_ids: Vec<AccountId>,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>>;
+}
+
+#[near_bindgen]
+impl Contract {
+    // Finalize an `mt_transfer_call` or `mt_batch_transfer_call` chain of cross-contract calls.
+    //
+    // Works like `ft_resolve_transfer` in the FT contract, once per token: whatever the receiver didn't use is
+    // refunded to the sender, up to what the receiver still holds. If the receiver's response can't be read,
+    // or it doesn't have one amount per token, every token is refunded.
+    //
+    // Returns how many of each token were spent by the sender.
+    #[private]
+    pub fn mt_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+    ) -> Vec<U128> {
+        // Get the unused amounts from the `mt_on_transfer` call result.
+        let unused_amounts: Vec<u128> = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<Vec<U128>>(&value)
+                .ok()
+                .filter(|unused| unused.len() == amounts.len())
+                .map(|unused| unused.iter().zip(&amounts).map(|(unused, amount)| unused.0.min(amount.0)).collect())
+                .unwrap_or_else(|| amounts.iter().map(|amount| amount.0).collect()),
+            // If the promise wasn't successful, refund everything
+            PromiseResult::Failed => amounts.iter().map(|amount| amount.0).collect(),
+        };
+
+        let sender_registered = self.accounts.get(&sender_id).is_some();
+        let mut refunded_ids: Vec<&str> = vec![];
+        let mut refunded_amounts: Vec<U128> = vec![];
+        let mut used_amounts = Vec::with_capacity(amounts.len());
+
+        for ((token_id, amount), unused) in token_ids.iter().zip(&amounts).zip(unused_amounts) {
+            // We can only refund up to what the receiver currently has
+            let refund = unused.min(self.internal_balance_of(token_id, &receiver_id));
+            if refund > 0 {
+                if sender_registered {
+                    // Refunds skip the storage check so that they can't fail
+                    self.internal_withdraw(token_id, &receiver_id, refund);
+                    self.internal_deposit(token_id, &sender_id, refund, false);
+                    refunded_ids.push(token_id);
+                    refunded_amounts.push(U128(refund));
+                } else {
+                    // The sender unregistered while the transfer was in flight, so the tokens are burnt
+                    self.internal_burn(token_id, &receiver_id, refund);
+                    MtBurn {
+                        owner_id: &receiver_id,
+                        token_ids: &[token_id.as_str()],
+                        amounts: &[U128(refund)],
+                        memo: Some("Refund to an unregistered account"),
+                    }
+                    .emit();
+                }
+            }
+            used_amounts.push(U128(amount.0 - refund));
+        }
+
+        // Emit a single Transfer event for all the refunds
+        if !refunded_ids.is_empty() {
+            MtTransfer {
+                old_owner_id: &receiver_id,
+                new_owner_id: &sender_id,
+                token_ids: &refunded_ids,
+                amounts: &refunded_amounts,
+                memo: Some("Refund"),
+            }
+            .emit();
+        }
+
+        used_amounts
+    }
+}
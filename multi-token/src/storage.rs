@@ -0,0 +1,177 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::{log, Promise};
+
+use crate::*;
+
+pub use ft_common::storage::{StorageBalance, StorageBalanceBounds};
+
+// What's kept for each registered account
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct StorageAccount {
+    // The NEAR deposited for storage
+    pub deposit: NearToken,
+    // How many tokens the account holds a non-zero balance of
+    pub balances: u64,
+}
+
+pub trait StorageManagement {
+    /************************************/
+    /* CHANGE METHODS on multi token    */
+    /************************************/
+    // Payable method that receives an attached deposit of Ⓝ for a given account.
+    //
+    // If `account_id` is omitted, the deposit MUST go toward predecessor account.
+    // If provided, deposit MUST go toward this account. If invalid, contract MUST
+    // panic.
+    //
+    // If `registration_only=true`, contract MUST refund above the minimum balance
+    // if the account wasn't registered and refund full deposit if already
+    // registered.
+    //
+    // Returns the StorageBalance structure showing updated balances.
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance;
+
+    // Withdraws `amount` of the predecessor's available storage balance, or all of it if `amount` is omitted.
+    // Requires exactly 1 yoctoⓃ attached.
+    //
+    // Returns the StorageBalance structure showing updated balances.
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance;
+
+    // Unregisters the predecessor account and refunds its storage deposit. Requires exactly 1 yoctoⓃ attached.
+    //
+    // The account must not hold any tokens. `force` isn't supported since an account's holdings can't be
+    // listed to burn them.
+    //
+    // Returns `true` if the account was unregistered and `false` if it wasn't registered.
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
+    /****************/
+    /* VIEW METHODS */
+    /****************/
+    // Returns minimum and maximum allowed balance amounts to interact with this
+    // contract. See StorageBalanceBounds.
+    //
+    // `min` is the amount needed to register and hold a single token. Every further
+    // token an account holds at the same time takes `bytes_per_balance` more, so
+    // there's no `max`.
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+
+    // Returns the StorageBalance structure of the valid `account_id`
+    // provided. Must panic if `account_id` is invalid.
+    //
+    // If `account_id` is not registered, must return `null`.
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        // Get the amount of $NEAR to deposit
+        let amount = env::attached_deposit();
+        // If an account was specified, use that. Otherwise, use the predecessor account.
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+
+        if let Some(mut account) = self.accounts.get(&account_id) {
+            if registration_only {
+                // Already registered, so the whole deposit is refunded
+                log!("The account is already registered, refunding the deposit");
+                if amount.gt(&ZERO_TOKEN) {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                // Top up the storage balance
+                account.deposit = account.deposit.saturating_add(amount);
+                self.accounts.insert(&account_id, &account);
+            }
+        } else {
+            // Get the minimum required storage and ensure the deposit is at least that amount
+            let min_balance = self.storage_balance_bounds().min;
+            require!(amount >= min_balance, "The attached deposit is less than the minimum storage balance");
+
+            // Register the account, keeping only the minimum if that's all that was asked for
+            let deposit = if registration_only { min_balance } else { amount };
+            self.accounts.insert(&account_id, &StorageAccount { deposit, balances: 0 });
+            StorageRegister { account_id: &account_id, amount: &deposit }.emit();
+
+            let refund = amount.saturating_sub(deposit);
+            if refund.gt(&ZERO_TOKEN) {
+                Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&account_id);
+
+        // Only what isn't covering the account's balances can be withdrawn
+        let available = account.deposit.saturating_sub(self.internal_storage_used(&account));
+        let amount = amount.unwrap_or(available);
+        require!(amount <= available, "The amount is greater than the available storage balance");
+
+        account.deposit = account.deposit.saturating_sub(amount);
+        self.accounts.insert(&account_id, &account);
+        if amount.gt(&ZERO_TOKEN) {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+        StorageWithdraw { account_id: &account_id, amount: &amount }.emit();
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        // Assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be required to sign with a FAK.
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        // The owner has to stay registered to hold the tokens it creates
+        require!(account_id != self.owner_id, "The owner can't unregister");
+        require!(!force.unwrap_or(false), "Force unregistering isn't supported");
+
+        let Some(account) = self.accounts.get(&account_id) else {
+            log!("The account {} is not registered", account_id);
+            return false;
+        };
+        require!(account.balances == 0, "The account still holds tokens");
+
+        // Remove the account and refund the storage deposit
+        self.accounts.remove(&account_id);
+        if account.deposit.gt(&ZERO_TOKEN) {
+            Promise::new(account_id.clone()).transfer(account.deposit);
+        }
+        StorageUnregister { account_id: &account_id, amount: &account.deposit }.emit();
+
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        // Registering has to cover the account itself and a balance of one token
+        StorageBalanceBounds {
+            min: env::storage_byte_cost().saturating_mul((self.bytes_per_account + self.bytes_per_balance).into()),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.accounts.get(&account_id).map(|account| StorageBalance {
+            total: account.deposit,
+            available: account.deposit.saturating_sub(self.internal_storage_used(&account)),
+        })
+    }
+}
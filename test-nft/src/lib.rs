@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{json, Value};
+use near_sdk::{
+    assert_one_yocto, env, ext_contract, near_bindgen, AccountId, BorshStorageKey, Gas, NearSchema, PanicOnDefault,
+};
+
+/// Gas for the `nft_on_approve` call made by `nft_approve`
+const GAS_FOR_NFT_ON_APPROVE: Gas = Gas::from_tgas(25);
+
+/// Royalties are given in basis points out of this
+const ROYALTY_DENOMINATOR: u128 = 10_000;
+
+pub type TokenId = String;
+
+/// How the contract should misbehave, so that marketplaces can test their failure paths
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, PartialEq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum FailureMode {
+    /// Behave like a normal NFT contract
+    None,
+    /// Panic in `nft_transfer` and `nft_transfer_payout` so the token never moves
+    FailTransfer,
+    /// Transfer the token but return a payout that adds up to more than the balance
+    OverPayout,
+    /// Transfer the token but return something that isn't a payout object
+    GarbagePayout,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Token {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+    /// Basis points (out of 10,000) of every sale that go to each account
+    pub royalty: HashMap<AccountId, u32>,
+}
+
+/// The token as returned by `nft_token`
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonToken {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+}
+
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+/// A minimal NFT contract for testing marketplaces against. It implements just enough of the core, approval
+/// and payout standards for a market to list, check and sell tokens, and can be told to fail in different ways.
+/// Minting is open to anyone.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// The account that's allowed to change the failure mode
+    pub owner_id: AccountId,
+    pub tokens: UnorderedMap<TokenId, Token>,
+    pub failure_mode: FailureMode,
+}
+
+/// Helper structure for keys of the persistent collections.
+#[derive(BorshSerialize, BorshStorageKey)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    Tokens,
+}
+
+#[ext_contract(ext_nft_approval_receiver)]
+trait NonFungibleTokenApprovalReceiver {
+    fn nft_on_approve(&mut self, token_id: TokenId, owner_id: AccountId, approval_id: u64, msg: String);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Initializes the contract with no tokens, behaving normally.
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        Self {
+            owner_id,
+            tokens: UnorderedMap::new(StorageKey::Tokens),
+            failure_mode: FailureMode::None,
+        }
+    }
+
+    /// Changes how the contract misbehaves. Only the owner can call this.
+    pub fn set_failure_mode(&mut self, failure_mode: FailureMode) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can change the failure mode");
+        self.failure_mode = failure_mode;
+    }
+
+    pub fn get_failure_mode(&self) -> FailureMode {
+        self.failure_mode
+    }
+
+    /// Mints a token to `receiver_id`. The storage is paid for by the contract.
+    pub fn nft_mint(&mut self, token_id: TokenId, receiver_id: AccountId, royalty: Option<HashMap<AccountId, u32>>) {
+        assert!(self.tokens.get(&token_id).is_none(), "Token already exists");
+        let royalty = royalty.unwrap_or_default();
+        assert!(
+            royalty.values().map(|bps| *bps as u128).sum::<u128>() < ROYALTY_DENOMINATOR,
+            "Royalties must add up to less than 100%"
+        );
+
+        let token = Token {
+            owner_id: receiver_id,
+            approved_account_ids: HashMap::new(),
+            next_approval_id: 0,
+            royalty,
+        };
+        self.tokens.insert(&token_id, &token);
+    }
+
+    pub fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
+        self.tokens.get(&token_id).map(|token| JsonToken {
+            token_id,
+            owner_id: token.owner_id,
+            approved_account_ids: token.approved_account_ids,
+        })
+    }
+
+    /// Approves `account_id` to transfer the token. If a `msg` is given, `nft_on_approve` is called on the
+    /// approved account with it.
+    #[payable]
+    pub fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>) {
+        let mut token = self.internal_token(&token_id);
+        assert_eq!(env::predecessor_account_id(), token.owner_id, "Only the owner can approve");
+
+        let approval_id = token.next_approval_id;
+        token.approved_account_ids.insert(account_id.clone(), approval_id);
+        token.next_approval_id += 1;
+        self.tokens.insert(&token_id, &token);
+
+        if let Some(msg) = msg {
+            ext_nft_approval_receiver::ext(account_id)
+                .with_static_gas(GAS_FOR_NFT_ON_APPROVE)
+                .nft_on_approve(token_id, token.owner_id, approval_id, msg)
+                .as_return();
+        }
+    }
+
+    #[payable]
+    pub fn nft_revoke(&mut self, token_id: TokenId, account_id: AccountId) {
+        assert_one_yocto();
+        let mut token = self.internal_token(&token_id);
+        assert_eq!(env::predecessor_account_id(), token.owner_id, "Only the owner can revoke");
+        token.approved_account_ids.remove(&account_id);
+        self.tokens.insert(&token_id, &token);
+    }
+
+    pub fn nft_is_approved(&self, token_id: TokenId, approved_account_id: AccountId, approval_id: Option<u64>) -> bool {
+        let token = self.internal_token(&token_id);
+        match (token.approved_account_ids.get(&approved_account_id), approval_id) {
+            (Some(actual), Some(expected)) => *actual == expected,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    #[payable]
+    pub fn nft_transfer(&mut self, receiver_id: AccountId, token_id: TokenId, approval_id: Option<u64>, memo: Option<String>) {
+        assert_one_yocto();
+        self.internal_transfer(&receiver_id, &token_id, approval_id, memo);
+    }
+
+    /// Transfers the token and returns how `balance` should be split between the previous owner and the
+    /// royalty holders. What's returned depends on the failure mode.
+    #[payable]
+    pub fn nft_transfer_payout(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Value {
+        assert_one_yocto();
+        let token = self.internal_transfer(&receiver_id, &token_id, approval_id, memo);
+        assert!(token.royalty.len() < max_len_payout as usize, "Too many payouts");
+
+        // Each royalty holder gets their share and the previous owner gets the rest
+        let mut payout = HashMap::new();
+        let mut remainder = balance.0;
+        for (account_id, bps) in token.royalty {
+            let amount = balance.0 * bps as u128 / ROYALTY_DENOMINATOR;
+            remainder -= amount;
+            payout.insert(account_id, U128(amount));
+        }
+        payout.insert(token.owner_id, U128(remainder));
+
+        match self.failure_mode {
+            FailureMode::OverPayout => {
+                let owner_id = env::current_account_id();
+                payout.insert(owner_id, U128(balance.0 + 1));
+                json!(Payout { payout })
+            }
+            FailureMode::GarbagePayout => json!("not a payout"),
+            _ => json!(Payout { payout }),
+        }
+    }
+}
+
+impl Contract {
+    fn internal_token(&self, token_id: &TokenId) -> Token {
+        self.tokens.get(token_id).unwrap_or_else(|| env::panic_str("Token not found"))
+    }
+
+    /// Moves the token to `receiver_id` if the caller is the owner or is approved with `approval_id`. Returns
+    /// the token as it was before the transfer.
+    fn internal_transfer(
+        &mut self,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) -> Token {
+        assert_ne!(self.failure_mode, FailureMode::FailTransfer, "Transfers are failing on purpose");
+
+        let token = self.internal_token(token_id);
+        let sender_id = env::predecessor_account_id();
+        if sender_id != token.owner_id {
+            let actual = token.approved_account_ids.get(&sender_id).unwrap_or_else(|| env::panic_str("Unauthorized"));
+            if let Some(expected) = approval_id {
+                assert_eq!(*actual, expected, "Wrong approval ID");
+            }
+        }
+        assert_ne!(&token.owner_id, receiver_id, "The token owner and the receiver should be different");
+
+        // Approvals are cleared on every transfer
+        let new_token = Token {
+            owner_id: receiver_id.clone(),
+            approved_account_ids: HashMap::new(),
+            next_approval_id: token.next_approval_id,
+            royalty: token.royalty.clone(),
+        };
+        self.tokens.insert(token_id, &new_token);
+
+        env::log_str(&format!(
+            "Transferred {} from {} to {}{}",
+            token_id,
+            token.owner_id,
+            receiver_id,
+            memo.map(|memo| format!(" with memo {}", memo)).unwrap_or_default()
+        ));
+        token
+    }
+}
@@ -0,0 +1,78 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::Deserialize;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Gas, NearToken};
+
+/// Gas for re-entering the FT contract with `ft_transfer`
+const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(5);
+/// Gas for the callback that returns the unused amount once the re-entrant transfer is done
+const GAS_FOR_RETURN_UNUSED: Gas = Gas::from_tgas(5);
+
+/// How the receiver should misbehave, passed in as the `msg` of `ft_transfer_call`. Examples:
+/// - `{"action": "over_refund", "unused": "1000"}` claims more tokens are unused than were transferred
+/// - `{"action": "garbage"}` returns a value that isn't a valid amount
+/// - `{"action": "burn_gas"}` runs out of gas
+/// - `{"action": "reenter", "receiver_id": "bob.near", "amount": "50", "unused": "100"}` moves 50 of the
+///   received tokens to `bob.near` with `ft_transfer` and then claims 100 are unused
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde", tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AdversarialMsg {
+    OverRefund { unused: NearToken },
+    Garbage,
+    BurnGas,
+    Reenter { receiver_id: AccountId, amount: NearToken, unused: NearToken },
+}
+
+/// A receiver that breaks the FT standard on purpose so that `ft_resolve_transfer`'s defensive paths can be
+/// tested. Never deploy this anywhere real tokens are held.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {}
+
+#[ext_contract(ext_ft)]
+trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: NearToken, memo: Option<String>);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Does whatever the `msg` asks for. The return value is written by hand so that it doesn't have to be a
+    /// valid amount.
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: NearToken, msg: String) {
+        let action: AdversarialMsg =
+            near_sdk::serde_json::from_str(&msg).unwrap_or_else(|_| env::panic_str("Invalid msg"));
+        env::log_str(&format!("Received {} tokens from {}", amount.as_yoctonear(), sender_id));
+
+        match action {
+            AdversarialMsg::OverRefund { unused } => return_amount(unused),
+            AdversarialMsg::Garbage => env::value_return(b"{\"not\": \"an amount\"}"),
+            AdversarialMsg::BurnGas => loop {
+                env::sha256(b"burn");
+            },
+            AdversarialMsg::Reenter { receiver_id, amount, unused } => {
+                // The FT contract is the one calling us
+                ext_ft::ext(env::predecessor_account_id())
+                    .with_attached_deposit(NearToken::from_yoctonear(1))
+                    .with_static_gas(GAS_FOR_FT_TRANSFER)
+                    .ft_transfer(receiver_id, amount, Some("Re-entrant transfer".to_string()))
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .with_static_gas(GAS_FOR_RETURN_UNUSED)
+                            .return_unused(unused),
+                    )
+                    .as_return();
+            }
+        }
+    }
+
+    /// Callback for the `reenter` action. Returns the unused amount once the re-entrant transfer has gone through.
+    #[private]
+    pub fn return_unused(&mut self, unused: NearToken) {
+        return_amount(unused);
+    }
+}
+
+/// Writes an amount as the call's return value, the same way the FT standard serializes it
+fn return_amount(amount: NearToken) {
+    env::value_return(&near_sdk::serde_json::to_vec(&amount).unwrap());
+}
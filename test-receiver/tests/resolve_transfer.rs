@@ -0,0 +1,170 @@
+use near_workspaces::network::Sandbox;
+use near_workspaces::result::ExecutionFinalResult;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract, Worker};
+use serde_json::json;
+
+const TOTAL_SUPPLY: u128 = 1_000_000;
+const AMOUNT: u128 = 100;
+
+struct Setup {
+    _worker: Worker<Sandbox>,
+    ft: Contract,
+    receiver: Contract,
+    sender: Account,
+    bystander: Account,
+}
+
+// Deploys the FT from 5.transfers and the adversarial receiver, and gives the sender some tokens
+async fn init() -> anyhow::Result<Setup> {
+    let worker = near_workspaces::sandbox().await?;
+    let ft = worker.dev_deploy(&near_workspaces::compile_project("../5.transfers").await?).await?;
+    let receiver = worker.dev_deploy(&near_workspaces::compile_project("./").await?).await?;
+
+    let root = worker.root_account()?;
+    let sender = root
+        .create_subaccount("sender")
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+    let bystander = root
+        .create_subaccount("bystander")
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    ft.call("new_default_meta")
+        .args_json(json!({ "owner_id": ft.id(), "total_supply": TOTAL_SUPPLY.to_string() }))
+        .transact()
+        .await?
+        .into_result()?;
+    for account_id in [receiver.id(), sender.id(), bystander.id()] {
+        ft.as_account()
+            .call(ft.id(), "storage_deposit")
+            .args_json(json!({ "account_id": account_id }))
+            .deposit(NearToken::from_millinear(100))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+    ft.call("ft_transfer")
+        .args_json(json!({ "receiver_id": sender.id(), "amount": AMOUNT.to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(Setup { _worker: worker, ft, receiver, sender, bystander })
+}
+
+async fn ft_transfer_call(setup: &Setup, msg: serde_json::Value) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(setup
+        .sender
+        .call(setup.ft.id(), "ft_transfer_call")
+        .args_json(json!({ "receiver_id": setup.receiver.id(), "amount": AMOUNT.to_string(), "msg": msg.to_string() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?)
+}
+
+async fn ft_balance_of(ft: &Contract, account_id: &near_workspaces::AccountId) -> anyhow::Result<u128> {
+    let balance: String = ft.view("ft_balance_of").args_json(json!({ "account_id": account_id })).await?.json()?;
+    Ok(balance.parse()?)
+}
+
+// The amount ft_transfer_call resolved to, which is what the sender ended up spending
+fn used_amount(result: ExecutionFinalResult) -> anyhow::Result<u128> {
+    let used: String = result.into_result()?.json()?;
+    Ok(used.parse()?)
+}
+
+// Moving tokens around must never create or destroy any
+async fn assert_supply_unchanged(setup: &Setup) -> anyhow::Result<()> {
+    let total_supply: String = setup.ft.view("ft_total_supply").await?.json()?;
+    assert_eq!(total_supply.parse::<u128>()?, TOTAL_SUPPLY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unused_amount_is_capped_at_the_transferred_amount() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    let result = ft_transfer_call(&setup, json!({ "action": "over_refund", "unused": (AMOUNT * 10).to_string() })).await?;
+
+    assert_eq!(used_amount(result)?, 0);
+    assert_eq!(ft_balance_of(&setup.ft, setup.sender.id()).await?, AMOUNT);
+    assert_eq!(ft_balance_of(&setup.ft, setup.receiver.id()).await?, 0);
+    assert_supply_unchanged(&setup).await
+}
+
+#[tokio::test]
+async fn garbage_return_value_refunds_everything() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    let result = ft_transfer_call(&setup, json!({ "action": "garbage" })).await?;
+
+    assert_eq!(used_amount(result)?, 0);
+    assert_eq!(ft_balance_of(&setup.ft, setup.sender.id()).await?, AMOUNT);
+    assert_eq!(ft_balance_of(&setup.ft, setup.receiver.id()).await?, 0);
+    assert_supply_unchanged(&setup).await
+}
+
+#[tokio::test]
+async fn receiver_out_of_gas_refunds_everything() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    let result = ft_transfer_call(&setup, json!({ "action": "burn_gas" })).await?;
+
+    assert_eq!(used_amount(result)?, 0);
+    assert_eq!(ft_balance_of(&setup.ft, setup.sender.id()).await?, AMOUNT);
+    assert_eq!(ft_balance_of(&setup.ft, setup.receiver.id()).await?, 0);
+    assert_supply_unchanged(&setup).await
+}
+
+#[tokio::test]
+async fn refund_is_capped_at_what_the_receiver_still_holds() -> anyhow::Result<()> {
+    let setup = init().await?;
+    let moved = 60;
+
+    // The receiver moves some of the tokens away before ft_resolve_transfer runs, then claims none were used
+    let result = ft_transfer_call(
+        &setup,
+        json!({
+            "action": "reenter",
+            "receiver_id": setup.bystander.id(),
+            "amount": moved.to_string(),
+            "unused": AMOUNT.to_string(),
+        }),
+    )
+    .await?;
+
+    assert_eq!(used_amount(result)?, moved);
+    assert_eq!(ft_balance_of(&setup.ft, setup.sender.id()).await?, AMOUNT - moved);
+    assert_eq!(ft_balance_of(&setup.ft, setup.receiver.id()).await?, 0);
+    assert_eq!(ft_balance_of(&setup.ft, setup.bystander.id()).await?, moved);
+    assert_supply_unchanged(&setup).await
+}
+
+#[tokio::test]
+async fn receiver_that_moved_everything_refunds_nothing() -> anyhow::Result<()> {
+    let setup = init().await?;
+
+    let result = ft_transfer_call(
+        &setup,
+        json!({
+            "action": "reenter",
+            "receiver_id": setup.bystander.id(),
+            "amount": AMOUNT.to_string(),
+            "unused": AMOUNT.to_string(),
+        }),
+    )
+    .await?;
+
+    assert_eq!(used_amount(result)?, AMOUNT);
+    assert_eq!(ft_balance_of(&setup.ft, setup.sender.id()).await?, 0);
+    assert_eq!(ft_balance_of(&setup.ft, setup.bystander.id()).await?, AMOUNT);
+    assert_supply_unchanged(&setup).await
+}
@@ -0,0 +1,123 @@
+//! Helpers shared by the unit tests in the tutorial crates. Add this crate as a dev-dependency and set up the
+//! blockchain context for each call with [`Context`]:
+//!
+//! ```ignore
+//! Context::new().predecessor(alice()).one_yocto().set();
+//! contract.ft_transfer(bob(), NearToken::from_yoctonear(10), None);
+//! ```
+
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{test_vm_config, testing_env, AccountId, Gas, NearToken, PromiseResult, RuntimeFeesConfig};
+
+/// The total supply the contracts are initialized with in the tests
+pub const TOTAL_SUPPLY: u128 = 1_000_000_000;
+
+/// Shorthand for an amount of the token in its smallest unit
+pub fn tokens(amount: u128) -> NearToken {
+    NearToken::from_yoctonear(amount)
+}
+
+/// The account the contract is deployed to
+pub fn contract() -> AccountId {
+    "ft.near".parse().unwrap()
+}
+
+/// The account the contract is initialized with as the owner
+pub fn owner() -> AccountId {
+    "owner.near".parse().unwrap()
+}
+
+pub fn alice() -> AccountId {
+    "alice.near".parse().unwrap()
+}
+
+pub fn bob() -> AccountId {
+    "bob.near".parse().unwrap()
+}
+
+pub fn charlie() -> AccountId {
+    "charlie.near".parse().unwrap()
+}
+
+/// Wipes the mocked contract storage. Contexts carry the storage over from one call to the next, so this is
+/// needed to start from scratch within the same test, e.g. between property test cases.
+pub fn clear_storage() {
+    near_sdk::mock::with_mocked_blockchain(|blockchain| {
+        blockchain.take_storage();
+    });
+}
+
+/// Builder for the context a contract call runs in. It starts out as a call from the owner with no deposit.
+pub struct Context {
+    builder: VMContextBuilder,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        let mut builder = VMContextBuilder::new();
+        builder.current_account_id(contract()).signer_account_id(owner()).predecessor_account_id(owner());
+        Self { builder }
+    }
+
+    /// Sets the account making the call. It's also used as the signer, as if the call was made directly.
+    pub fn predecessor(mut self, account_id: AccountId) -> Self {
+        self.builder.signer_account_id(account_id.clone()).predecessor_account_id(account_id);
+        self
+    }
+
+    /// Sets the account making the call without changing the signer, as if it was a cross-contract call.
+    pub fn cross_contract_caller(mut self, account_id: AccountId) -> Self {
+        self.builder.predecessor_account_id(account_id);
+        self
+    }
+
+    pub fn attached_deposit(mut self, deposit: NearToken) -> Self {
+        self.builder.attached_deposit(deposit);
+        self
+    }
+
+    /// Attaches exactly 1 yoctoNEAR, which most state-changing FT methods require
+    pub fn one_yocto(self) -> Self {
+        self.attached_deposit(NearToken::from_yoctonear(1))
+    }
+
+    pub fn prepaid_gas(mut self, gas: Gas) -> Self {
+        self.builder.prepaid_gas(gas);
+        self
+    }
+
+    /// Sets the block timestamp, in nanoseconds
+    pub fn block_timestamp(mut self, timestamp: u64) -> Self {
+        self.builder.block_timestamp(timestamp);
+        self
+    }
+
+    /// Makes this the context for the next contract calls
+    pub fn set(self) {
+        testing_env!(self.builder.build());
+    }
+
+    /// Makes this the context for the next contract calls, with `result` as the outcome of the promise being
+    /// resolved. Use it to test callbacks such as `ft_resolve_transfer`.
+    pub fn set_with_promise_result(self, result: PromiseResult) {
+        self.set_with_promise_results(vec![result]);
+    }
+
+    /// Makes this the context for the next contract calls, with `results` as the outcomes of the joint promises
+    /// being resolved, in order.
+    pub fn set_with_promise_results(self, results: Vec<PromiseResult>) {
+        testing_env!(
+            self.builder.build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            results
+        );
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}